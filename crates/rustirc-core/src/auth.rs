@@ -1,14 +1,32 @@
 //! SASL Authentication Implementation
 //!
 //! This module provides SASL authentication support for IRC connections,
-//! implementing PLAIN, EXTERNAL, and SCRAM-SHA-256 mechanisms as specified
-//! in Phase 2 requirements.
+//! implementing PLAIN, EXTERNAL, SCRAM-SHA-256 and SCRAM-SHA-512 mechanisms
+//! as specified in Phase 2 requirements.
 
 use anyhow::Result;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use rustirc_protocol::{Command, Message};
+use sha2::{Digest, Sha256, Sha512};
 use std::collections::HashMap;
 use tracing::{debug, info, warn};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Maximum length of a single AUTHENTICATE payload
+pub const AUTHENTICATE_CHUNK_SIZE: usize = 400;
+
+/// Largest reassembled AUTHENTICATE payload accepted from a server
+///
+/// Server challenges are a few hundred bytes; the limit stops a server from
+/// growing the buffer without bound by sending full-size chunks.
+pub const MAX_AUTHENTICATE_PAYLOAD: usize = 16 * 1024;
+
+/// Highest SCRAM iteration count accepted from a server
+///
+/// Servers use a few thousand iterations; a much larger count only makes
+/// the client burn CPU deriving the salted password.
+pub const MAX_SCRAM_ITERATIONS: u32 = 1_000_000;
 
 /// SASL authentication state
 #[derive(Debug, Clone, PartialEq)]
@@ -20,7 +38,50 @@ pub enum AuthState {
     /// Authentication completed successfully
     Success,
     /// Authentication failed
-    Failed(String),
+    Failed(AuthError),
+}
+
+/// Reasons a SASL exchange can fail
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("Unsupported SASL mechanism: {0}")]
+    UnsupportedMechanism(String),
+    #[error("Server does not support SASL")]
+    NotOffered,
+    #[error("Server does not support SASL mechanism {0}")]
+    MechanismNotOffered(String),
+    #[error("Malformed server message: {0}")]
+    MalformedMessage(String),
+    #[error("Server nonce does not extend the client nonce")]
+    NonceMismatch,
+    #[error("Invalid SCRAM iteration count: {0}")]
+    InvalidIterations(String),
+    #[error("Server signature verification failed")]
+    ServerSignatureMismatch,
+    #[error("Server reported success without proving its identity")]
+    ServerNotVerified,
+    #[error("Server reported SCRAM error: {0}")]
+    ServerError(String),
+    #[error("Unexpected server message in state {0}")]
+    UnexpectedMessage(String),
+    #[error("Nickname is locked: {0}")]
+    NickLocked(String),
+    #[error("Authentication rejected: {0}")]
+    Rejected(String),
+    #[error("SASL message too long")]
+    MessageTooLong,
+    #[error("SASL authentication aborted")]
+    Aborted,
+}
+
+impl AuthError {
+    /// Extract a typed error from a mechanism error
+    fn from_anyhow(error: &anyhow::Error) -> Self {
+        error
+            .downcast_ref::<AuthError>()
+            .cloned()
+            .unwrap_or_else(|| AuthError::MalformedMessage(error.to_string()))
+    }
 }
 
 /// SASL credentials with secure password storage
@@ -79,8 +140,16 @@ impl From<&str> for SecureString {
 /// SASL authentication mechanism trait
 pub trait SaslMechanism: Send + Sync {
     fn name(&self) -> &str;
-    fn initial_response(&self, credentials: &SaslCredentials) -> Result<Vec<u8>>;
+    fn initial_response(&mut self, credentials: &SaslCredentials) -> Result<Vec<u8>>;
     fn continue_auth(&mut self, challenge: Option<&[u8]>) -> Result<Vec<u8>>;
+
+    /// Whether the client side of the exchange has finished
+    ///
+    /// Mechanisms with mutual authentication return `false` until the server
+    /// has proven its identity, so that a bare success reply is not trusted.
+    fn is_complete(&self) -> bool {
+        true
+    }
 }
 
 /// PLAIN authentication mechanism
@@ -91,7 +160,7 @@ impl SaslMechanism for PlainMechanism {
         "PLAIN"
     }
 
-    fn initial_response(&self, credentials: &SaslCredentials) -> Result<Vec<u8>> {
+    fn initial_response(&mut self, credentials: &SaslCredentials) -> Result<Vec<u8>> {
         let authzid = credentials.authzid.as_deref().unwrap_or("");
         let authcid = &credentials.username;
         let password = credentials.password.as_str();
@@ -121,7 +190,7 @@ impl SaslMechanism for ExternalMechanism {
         "EXTERNAL"
     }
 
-    fn initial_response(&self, credentials: &SaslCredentials) -> Result<Vec<u8>> {
+    fn initial_response(&mut self, credentials: &SaslCredentials) -> Result<Vec<u8>> {
         // Send authzid or empty string
         Ok(credentials
             .authzid
@@ -137,6 +206,303 @@ impl SaslMechanism for ExternalMechanism {
     }
 }
 
+/// Hash function used by a SCRAM mechanism
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl ScramAlgorithm {
+    pub fn mechanism_name(&self) -> &'static str {
+        match self {
+            ScramAlgorithm::Sha256 => "SCRAM-SHA-256",
+            ScramAlgorithm::Sha512 => "SCRAM-SHA-512",
+        }
+    }
+
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            ScramAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    /// Constant-time check of an HMAC tag
+    fn verify_hmac(&self, key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+        match self {
+            ScramAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.verify_slice(tag).is_ok()
+            }
+            ScramAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.verify_slice(tag).is_ok()
+            }
+        }
+    }
+
+    fn salted_password(&self, password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha256 => {
+                pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password, salt, iterations).to_vec()
+            }
+            ScramAlgorithm::Sha512 => {
+                pbkdf2::pbkdf2_hmac_array::<Sha512, 64>(password, salt, iterations).to_vec()
+            }
+        }
+    }
+}
+
+/// Progress of a SCRAM exchange
+enum ScramState {
+    Initial,
+    ClientFirstSent {
+        client_nonce: String,
+        client_first_bare: String,
+        gs2_header: String,
+        password: SecureString,
+    },
+    ClientFinalSent {
+        server_key: Zeroizing<Vec<u8>>,
+        auth_message: String,
+    },
+    Complete,
+}
+
+impl ScramState {
+    fn name(&self) -> &'static str {
+        match self {
+            ScramState::Initial => "initial",
+            ScramState::ClientFirstSent { .. } => "client-first",
+            ScramState::ClientFinalSent { .. } => "client-final",
+            ScramState::Complete => "complete",
+        }
+    }
+}
+
+/// SCRAM-SHA-256 / SCRAM-SHA-512 authentication mechanism (RFC 5802, RFC 7677)
+///
+/// Channel binding is not supported; the GS2 header is always `n`.
+pub struct ScramMechanism {
+    algorithm: ScramAlgorithm,
+    state: ScramState,
+    nonce: Option<String>,
+}
+
+impl ScramMechanism {
+    pub fn new(algorithm: ScramAlgorithm) -> Self {
+        Self {
+            algorithm,
+            state: ScramState::Initial,
+            nonce: None,
+        }
+    }
+
+    pub fn sha256() -> Self {
+        Self::new(ScramAlgorithm::Sha256)
+    }
+
+    pub fn sha512() -> Self {
+        Self::new(ScramAlgorithm::Sha512)
+    }
+
+    /// Use a fixed client nonce instead of a random one
+    #[cfg(test)]
+    fn with_nonce(algorithm: ScramAlgorithm, nonce: &str) -> Self {
+        Self {
+            nonce: Some(nonce.to_string()),
+            ..Self::new(algorithm)
+        }
+    }
+
+    fn generate_nonce() -> String {
+        BASE64.encode(rand::random::<[u8; 24]>())
+    }
+
+    /// Escape `=` and `,` in a SCRAM username
+    fn escape_username(username: &str) -> String {
+        username.replace('=', "=3D").replace(',', "=2C")
+    }
+
+    /// Parse `key=value` attributes of a SCRAM message
+    fn parse_attributes(message: &str) -> Result<HashMap<char, &str>, AuthError> {
+        message
+            .split(',')
+            .map(|attr| match attr.split_once('=') {
+                Some((key, value)) if key.len() == 1 && key.is_ascii() => {
+                    Ok((char::from(key.as_bytes()[0]), value))
+                }
+                _ => Err(AuthError::MalformedMessage(message.to_string())),
+            })
+            .collect()
+    }
+
+    fn handle_server_first(&mut self, server_first: &str) -> Result<Vec<u8>, AuthError> {
+        let ScramState::ClientFirstSent {
+            client_nonce,
+            client_first_bare,
+            gs2_header,
+            password,
+        } = &self.state
+        else {
+            return Err(AuthError::UnexpectedMessage(self.state.name().to_string()));
+        };
+
+        let attrs = Self::parse_attributes(server_first)?;
+        if let Some(error) = attrs.get(&'e') {
+            return Err(AuthError::ServerError(error.to_string()));
+        }
+
+        let missing = |key: char| AuthError::MalformedMessage(format!("missing '{key}' attribute"));
+        let nonce = *attrs.get(&'r').ok_or_else(|| missing('r'))?;
+        let salt = attrs.get(&'s').ok_or_else(|| missing('s'))?;
+        let iterations = attrs.get(&'i').ok_or_else(|| missing('i'))?;
+
+        if !nonce.starts_with(client_nonce.as_str()) || nonce.len() == client_nonce.len() {
+            return Err(AuthError::NonceMismatch);
+        }
+
+        let salt = BASE64
+            .decode(salt)
+            .map_err(|e| AuthError::MalformedMessage(format!("invalid salt: {e}")))?;
+        let iterations = iterations
+            .parse::<u32>()
+            .ok()
+            .filter(|i| (1..=MAX_SCRAM_ITERATIONS).contains(i))
+            .ok_or_else(|| AuthError::InvalidIterations(iterations.to_string()))?;
+
+        let alg = self.algorithm;
+        let salted_password =
+            Zeroizing::new(alg.salted_password(password.as_bytes(), &salt, iterations));
+        let client_key = Zeroizing::new(alg.hmac(&salted_password, b"Client Key"));
+        let stored_key = alg.hash(&client_key);
+        let server_key = Zeroizing::new(alg.hmac(&salted_password, b"Server Key"));
+
+        let client_final_without_proof =
+            format!("c={},r={}", BASE64.encode(gs2_header.as_bytes()), nonce);
+        let auth_message =
+            format!("{client_first_bare},{server_first},{client_final_without_proof}");
+
+        let client_signature = alg.hmac(&stored_key, auth_message.as_bytes());
+        let client_proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(key, sig)| key ^ sig)
+            .collect();
+
+        let client_final = format!(
+            "{client_final_without_proof},p={}",
+            BASE64.encode(client_proof)
+        );
+
+        self.state = ScramState::ClientFinalSent {
+            server_key,
+            auth_message,
+        };
+
+        Ok(client_final.into_bytes())
+    }
+
+    fn handle_server_final(&mut self, server_final: &str) -> Result<Vec<u8>, AuthError> {
+        let ScramState::ClientFinalSent {
+            server_key,
+            auth_message,
+        } = &self.state
+        else {
+            return Err(AuthError::UnexpectedMessage(self.state.name().to_string()));
+        };
+
+        let attrs = Self::parse_attributes(server_final)?;
+        if let Some(error) = attrs.get(&'e') {
+            return Err(AuthError::ServerError(error.to_string()));
+        }
+
+        let signature = attrs
+            .get(&'v')
+            .and_then(|v| BASE64.decode(v).ok())
+            .ok_or_else(|| AuthError::MalformedMessage(server_final.to_string()))?;
+
+        if !self
+            .algorithm
+            .verify_hmac(server_key, auth_message.as_bytes(), &signature)
+        {
+            return Err(AuthError::ServerSignatureMismatch);
+        }
+
+        debug!("Verified SCRAM server signature");
+        self.state = ScramState::Complete;
+        Ok(Vec::new())
+    }
+}
+
+impl SaslMechanism for ScramMechanism {
+    fn name(&self) -> &str {
+        self.algorithm.mechanism_name()
+    }
+
+    fn initial_response(&mut self, credentials: &SaslCredentials) -> Result<Vec<u8>> {
+        let client_nonce = self.nonce.clone().unwrap_or_else(Self::generate_nonce);
+        let gs2_header = match &credentials.authzid {
+            Some(authzid) => format!("n,a={},", Self::escape_username(authzid)),
+            None => "n,,".to_string(),
+        };
+        let client_first_bare = format!(
+            "n={},r={}",
+            Self::escape_username(&credentials.username),
+            client_nonce
+        );
+        let client_first = format!("{gs2_header}{client_first_bare}");
+
+        self.state = ScramState::ClientFirstSent {
+            client_nonce,
+            client_first_bare,
+            gs2_header,
+            password: credentials.password.clone(),
+        };
+
+        Ok(client_first.into_bytes())
+    }
+
+    fn continue_auth(&mut self, challenge: Option<&[u8]>) -> Result<Vec<u8>> {
+        let challenge = challenge.unwrap_or_default();
+        let message = std::str::from_utf8(challenge)
+            .map_err(|_| AuthError::MalformedMessage("invalid UTF-8".to_string()))?;
+
+        let response = match self.state {
+            ScramState::ClientFirstSent { .. } => self.handle_server_first(message),
+            ScramState::ClientFinalSent { .. } => self.handle_server_final(message),
+            _ => Err(AuthError::UnexpectedMessage(self.state.name().to_string())),
+        };
+
+        response.map_err(|e| {
+            self.state = ScramState::Initial;
+            e.into()
+        })
+    }
+
+    fn is_complete(&self) -> bool {
+        matches!(self.state, ScramState::Complete)
+    }
+}
+
 /// SASL authenticator managing the authentication flow
 pub struct SaslAuthenticator {
     state: AuthState,
@@ -161,6 +527,8 @@ impl SaslAuthenticator {
         // Register built-in mechanisms
         auth.register_mechanism("PLAIN", Box::new(PlainMechanism));
         auth.register_mechanism("EXTERNAL", Box::new(ExternalMechanism));
+        auth.register_mechanism("SCRAM-SHA-256", Box::new(ScramMechanism::sha256()));
+        auth.register_mechanism("SCRAM-SHA-512", Box::new(ScramMechanism::sha512()));
 
         auth
     }
@@ -185,9 +553,9 @@ impl SaslAuthenticator {
         debug!("Starting SASL authentication with mechanism: {mechanism}");
 
         if !self.mechanisms.contains_key(mechanism) {
-            let error = format!("Unsupported SASL mechanism: {mechanism}");
+            let error = AuthError::UnsupportedMechanism(mechanism.to_string());
             self.state = AuthState::Failed(error.clone());
-            return Err(anyhow::anyhow!(error));
+            return Err(error.into());
         }
 
         self.current_mechanism = Some(mechanism.to_string());
@@ -195,7 +563,7 @@ impl SaslAuthenticator {
 
         let mechanism_impl = self
             .mechanisms
-            .get(mechanism)
+            .get_mut(mechanism)
             .ok_or_else(|| anyhow::anyhow!("Mechanism disappeared during authentication"))?;
         let response = mechanism_impl.initial_response(&credentials)?;
 
@@ -215,14 +583,23 @@ impl SaslAuthenticator {
         Err(anyhow::anyhow!("No authentication in progress"))
     }
 
+    /// Whether the current mechanism has finished its side of the exchange
+    ///
+    /// False when no exchange is running, e.g. after the client aborted it.
+    pub fn mechanism_complete(&self) -> bool {
+        self.current_mechanism
+            .as_ref()
+            .and_then(|name| self.mechanisms.get(name))
+            .is_some_and(|mechanism| mechanism.is_complete())
+    }
+
     pub fn handle_success(&mut self) {
         info!("SASL authentication successful");
         self.state = AuthState::Success;
         self.current_mechanism = None;
     }
 
-    pub fn handle_failure(&mut self, reason: Option<&str>) {
-        let error = reason.unwrap_or("Authentication failed").to_string();
+    pub fn handle_failure(&mut self, error: AuthError) {
         warn!("SASL authentication failed: {error}");
         self.state = AuthState::Failed(error);
         self.current_mechanism = None;
@@ -254,13 +631,309 @@ pub fn decode_authenticate_data(data: &str) -> Result<Vec<u8>> {
     }
 }
 
+/// Split data into AUTHENTICATE payloads
+///
+/// The base64-encoded data is sent in chunks of [`AUTHENTICATE_CHUNK_SIZE`]
+/// bytes. Empty data, or data whose final chunk is exactly
+/// [`AUTHENTICATE_CHUNK_SIZE`] bytes long, is terminated with `+`.
+pub fn chunk_authenticate_data(data: &[u8]) -> Vec<String> {
+    if data.is_empty() {
+        return vec!["+".to_string()];
+    }
+
+    let encoded = BASE64.encode(data);
+    let mut chunks: Vec<String> = encoded
+        .as_bytes()
+        .chunks(AUTHENTICATE_CHUNK_SIZE)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+
+    if encoded.len().is_multiple_of(AUTHENTICATE_CHUNK_SIZE) {
+        chunks.push("+".to_string());
+    }
+
+    chunks
+}
+
+/// Reassembles chunked AUTHENTICATE payloads received from the server
+#[derive(Debug, Default)]
+pub struct AuthenticateBuffer {
+    data: String,
+}
+
+impl AuthenticateBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a received payload, returning the decoded data once complete
+    ///
+    /// Fails with [`AuthError::MessageTooLong`] once the reassembled payload
+    /// exceeds [`MAX_AUTHENTICATE_PAYLOAD`].
+    pub fn push(&mut self, chunk: &str) -> Option<Result<Vec<u8>>> {
+        if chunk != "+" {
+            if self.data.len() + chunk.len() > MAX_AUTHENTICATE_PAYLOAD {
+                self.data.clear();
+                return Some(Err(AuthError::MessageTooLong.into()));
+            }
+            self.data.push_str(chunk);
+        }
+
+        if chunk.len() == AUTHENTICATE_CHUNK_SIZE {
+            return None;
+        }
+
+        let data = std::mem::take(&mut self.data);
+        if data.is_empty() {
+            Some(Ok(Vec::new()))
+        } else {
+            Some(decode_authenticate_data(&data))
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+}
+
+/// SASL exchange for a single connection registration
+///
+/// Drives the AUTHENTICATE command flow and maps the SASL numerics
+/// (900-908) onto [`AuthState`].
+pub struct SaslSession {
+    authenticator: SaslAuthenticator,
+    mechanism: String,
+    credentials: SaslCredentials,
+    required: bool,
+    buffer: AuthenticateBuffer,
+    initial_response: Option<Zeroizing<Vec<u8>>>,
+    account: Option<String>,
+    server_mechanisms: Vec<String>,
+}
+
+impl std::fmt::Debug for SaslSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaslSession")
+            .field("mechanism", &self.mechanism)
+            .field("state", self.authenticator.state())
+            .field("required", &self.required)
+            .field("account", &self.account)
+            .finish()
+    }
+}
+
+impl SaslSession {
+    pub fn new(mechanism: &str, credentials: SaslCredentials, required: bool) -> Self {
+        Self {
+            authenticator: SaslAuthenticator::new(),
+            mechanism: mechanism.to_string(),
+            credentials,
+            required,
+            buffer: AuthenticateBuffer::new(),
+            initial_response: None,
+            account: None,
+            server_mechanisms: Vec::new(),
+        }
+    }
+
+    /// Create a SASL EXTERNAL session for TLS client certificate (CertFP) login
+    pub fn external(required: bool) -> Self {
+        let credentials = SaslCredentials {
+            username: String::new(),
+            password: SecureString::new(String::new()),
+            authzid: None,
+        };
+        Self::new("EXTERNAL", credentials, required)
+    }
+
+    /// Create a session from a server's SASL configuration
+    pub fn from_config(config: &crate::config::SaslConfig) -> Self {
+        let credentials = SaslCredentials {
+            username: config.username.clone(),
+            password: SecureString::new(config.password.clone()),
+            authzid: None,
+        };
+        Self::new(config.mechanism.as_str(), credentials, config.required)
+    }
+
+    pub fn mechanism(&self) -> &str {
+        &self.mechanism
+    }
+
+    pub fn state(&self) -> &AuthState {
+        self.authenticator.state()
+    }
+
+    /// Whether registration must be aborted if authentication fails
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Account name reported by RPL_LOGGEDIN (900)
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    /// Mechanisms reported by the server in RPL_SASLMECHS (908)
+    pub fn server_mechanisms(&self) -> &[String] {
+        &self.server_mechanisms
+    }
+
+    /// Begin the exchange, returning the `AUTHENTICATE <mechanism>` command
+    pub async fn start(&mut self) -> Result<Command> {
+        self.authenticator.reset();
+        self.buffer.clear();
+        self.account = None;
+
+        let response = match self
+            .authenticator
+            .start_authentication(&self.mechanism, self.credentials.clone())
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                if !matches!(self.state(), AuthState::Failed(_)) {
+                    self.authenticator
+                        .handle_failure(AuthError::from_anyhow(&e));
+                }
+                return Err(e);
+            }
+        };
+        self.initial_response = Some(Zeroizing::new(response));
+
+        Ok(Command::Authenticate {
+            data: self.mechanism.clone(),
+        })
+    }
+
+    /// Mark the exchange as failed without contacting the server
+    pub fn fail(&mut self, error: AuthError) {
+        self.initial_response = None;
+        self.authenticator.handle_failure(error);
+    }
+
+    /// Handle an `AUTHENTICATE` payload from the server
+    ///
+    /// Returns the commands to send in reply. Client-side errors abort the
+    /// exchange with `AUTHENTICATE *`.
+    pub async fn handle_authenticate(&mut self, payload: &str) -> Vec<Command> {
+        if self.authenticator.state() != &AuthState::InProgress {
+            return Vec::new();
+        }
+
+        let challenge = match self.buffer.push(payload) {
+            None => return Vec::new(),
+            Some(Ok(challenge)) => challenge,
+            Some(Err(e)) => return self.abort(AuthError::from_anyhow(&e)),
+        };
+
+        let response = match self.initial_response.take() {
+            Some(initial) => Ok(initial.to_vec()),
+            None => {
+                let challenge = (!challenge.is_empty()).then_some(challenge.as_slice());
+                self.authenticator.continue_authentication(challenge).await
+            }
+        };
+
+        match response {
+            Ok(response) => {
+                let response = Zeroizing::new(response);
+                chunk_authenticate_data(&response)
+                    .into_iter()
+                    .map(|data| Command::Authenticate { data })
+                    .collect()
+            }
+            Err(e) => self.abort(AuthError::from_anyhow(&e)),
+        }
+    }
+
+    /// Handle a SASL numeric, returning the final state once the exchange ends
+    pub fn handle_numeric(&mut self, message: &Message) -> Option<AuthState> {
+        let text = message.params.last().map(String::as_str);
+
+        match message.command.as_str() {
+            // RPL_LOGGEDIN: <nick> <nick>!<ident>@<host> <account> :<text>
+            "900" => {
+                self.account = message.params.get(2).cloned();
+                if let Some(account) = &self.account {
+                    info!("Logged in as {account}");
+                }
+                None
+            }
+            // RPL_LOGGEDOUT
+            "901" => {
+                self.account = None;
+                None
+            }
+            // RPL_SASLSUCCESS, ERR_SASLALREADY
+            "903" | "907" => {
+                self.initial_response = None;
+                match self.state() {
+                    AuthState::InProgress if self.authenticator.mechanism_complete() => {
+                        self.authenticator.handle_success();
+                    }
+                    AuthState::InProgress => {
+                        // The server skipped the final step, e.g. the SCRAM server signature
+                        self.buffer.clear();
+                        self.authenticator
+                            .handle_failure(AuthError::ServerNotVerified);
+                    }
+                    // A success after we aborted must not override the failure
+                    AuthState::Failed(_) => {}
+                    // No exchange was started, so there is nothing to complete
+                    AuthState::Idle | AuthState::Success => return None,
+                }
+                Some(self.state().clone())
+            }
+            // ERR_NICKLOCKED, ERR_SASLFAIL, ERR_SASLTOOLONG, ERR_SASLABORTED
+            "902" | "904" | "905" | "906" => {
+                self.initial_response = None;
+                self.buffer.clear();
+
+                // Keep a client-side failure that caused us to abort
+                if !matches!(self.state(), AuthState::Failed(_)) {
+                    let text = text.unwrap_or_default().to_string();
+                    let error = match message.command.as_str() {
+                        "902" => AuthError::NickLocked(text),
+                        "905" => AuthError::MessageTooLong,
+                        "906" => AuthError::Aborted,
+                        _ => AuthError::Rejected(text),
+                    };
+                    self.authenticator.handle_failure(error);
+                }
+                Some(self.state().clone())
+            }
+            // RPL_SASLMECHS: <nick> <mechanisms> :are available SASL mechanisms
+            "908" => {
+                self.server_mechanisms = message
+                    .params
+                    .get(1)
+                    .map(|list| list.split(',').map(str::to_string).collect())
+                    .unwrap_or_default();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn abort(&mut self, error: AuthError) -> Vec<Command> {
+        warn!("Aborting SASL authentication: {error}");
+        self.initial_response = None;
+        self.buffer.clear();
+        self.authenticator.handle_failure(error);
+        vec![Command::Authenticate {
+            data: "*".to_string(),
+        }]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_plain_mechanism() {
-        let plain = PlainMechanism;
+        let mut plain = PlainMechanism;
         let creds = SaslCredentials {
             username: "user".to_string(),
             password: "pass".into(),
@@ -274,7 +947,7 @@ mod tests {
 
     #[test]
     fn test_plain_mechanism_with_authzid() {
-        let plain = PlainMechanism;
+        let mut plain = PlainMechanism;
         let creds = SaslCredentials {
             username: "user".to_string(),
             password: "pass".into(),
@@ -288,7 +961,7 @@ mod tests {
 
     #[test]
     fn test_external_mechanism() {
-        let external = ExternalMechanism;
+        let mut external = ExternalMechanism;
         let creds = SaslCredentials {
             username: "user".to_string(),
             password: "pass".into(),
@@ -421,4 +1094,412 @@ mod tests {
         fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
         assert_zeroize_on_drop::<SaslCredentials>();
     }
+
+    #[test]
+    fn test_chunk_authenticate_data() {
+        assert_eq!(chunk_authenticate_data(&[]), vec!["+"]);
+
+        let short = chunk_authenticate_data(b"\0user\0pass");
+        assert_eq!(short, vec![BASE64.encode(b"\0user\0pass")]);
+
+        // 300 bytes encode to exactly 400 base64 characters
+        let exact = chunk_authenticate_data(&[b'a'; 300]);
+        assert_eq!(exact.len(), 2);
+        assert_eq!(exact[0].len(), AUTHENTICATE_CHUNK_SIZE);
+        assert_eq!(exact[1], "+");
+
+        let long = chunk_authenticate_data(&[b'a'; 400]);
+        assert_eq!(long.len(), 2);
+        assert_eq!(long[0].len(), AUTHENTICATE_CHUNK_SIZE);
+        assert!(long[1].len() < AUTHENTICATE_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_authenticate_buffer_reassembly() {
+        let data = vec![b'x'; 300];
+        let mut buffer = AuthenticateBuffer::new();
+
+        let mut result = None;
+        for chunk in chunk_authenticate_data(&data) {
+            result = buffer.push(&chunk);
+        }
+        assert_eq!(result.unwrap().unwrap(), data);
+
+        assert_eq!(buffer.push("+").unwrap().unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_authenticate_buffer_limit() {
+        let chunk = "A".repeat(AUTHENTICATE_CHUNK_SIZE);
+        let mut buffer = AuthenticateBuffer::new();
+
+        let mut result = None;
+        for _ in 0..=MAX_AUTHENTICATE_PAYLOAD / AUTHENTICATE_CHUNK_SIZE {
+            result = buffer.push(&chunk);
+            if result.is_some() {
+                break;
+            }
+        }
+        let error = result.unwrap().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AuthError>(),
+            Some(&AuthError::MessageTooLong)
+        );
+
+        // The buffer starts over after the oversized payload
+        assert_eq!(buffer.push("+").unwrap().unwrap(), Vec::<u8>::new());
+    }
+
+    fn sasl_message(line: &str) -> Message {
+        rustirc_protocol::Parser::parse_message(line).unwrap()
+    }
+
+    fn test_session(required: bool) -> SaslSession {
+        let creds = SaslCredentials {
+            username: "user".to_string(),
+            password: "pass".into(),
+            authzid: None,
+        };
+        SaslSession::new("PLAIN", creds, required)
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_plain_success() {
+        let mut session = test_session(false);
+
+        let command = session.start().await.unwrap();
+        assert_eq!(command.to_message().to_string(), "AUTHENTICATE PLAIN");
+        assert_eq!(session.state(), &AuthState::InProgress);
+
+        let replies = session.handle_authenticate("+").await;
+        assert_eq!(
+            replies,
+            vec![Command::Authenticate {
+                data: BASE64.encode(b"\0user\0pass")
+            }]
+        );
+
+        let logged_in = sasl_message(":srv 900 nick nick!u@h user :You are now logged in as user");
+        assert_eq!(session.handle_numeric(&logged_in), None);
+        assert_eq!(session.account(), Some("user"));
+
+        let success = sasl_message(":srv 903 nick :SASL authentication successful");
+        assert_eq!(session.handle_numeric(&success), Some(AuthState::Success));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_failure_numerics() {
+        let mut session = test_session(true);
+        session.start().await.unwrap();
+        session.handle_authenticate("+").await;
+
+        let mechs =
+            sasl_message(":srv 908 nick EXTERNAL,SCRAM-SHA-256 :are available SASL mechanisms");
+        assert_eq!(session.handle_numeric(&mechs), None);
+        assert_eq!(session.server_mechanisms(), ["EXTERNAL", "SCRAM-SHA-256"]);
+
+        let fail = sasl_message(":srv 904 nick :SASL authentication failed");
+        assert_eq!(
+            session.handle_numeric(&fail),
+            Some(AuthState::Failed(AuthError::Rejected(
+                "SASL authentication failed".to_string()
+            )))
+        );
+        assert!(session.is_required());
+
+        // A fresh attempt resets the state
+        session.start().await.unwrap();
+        let aborted = sasl_message(":srv 906 nick :SASL authentication aborted");
+        assert_eq!(
+            session.handle_numeric(&aborted),
+            Some(AuthState::Failed(AuthError::Aborted))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_aborts_on_bad_payload() {
+        let mut session = test_session(false);
+        session.start().await.unwrap();
+        session.handle_authenticate("+").await;
+
+        let replies = session.handle_authenticate("not base64!").await;
+        assert_eq!(
+            replies,
+            vec![Command::Authenticate {
+                data: "*".to_string()
+            }]
+        );
+    }
+
+    fn scram_creds(username: &str, password: &str) -> SaslCredentials {
+        SaslCredentials {
+            username: username.to_string(),
+            password: password.into(),
+            authzid: None,
+        }
+    }
+
+    #[test]
+    fn test_scram_sha256_rfc7677_vector() {
+        let mut scram = ScramMechanism::with_nonce(ScramAlgorithm::Sha256, "rOprNGfwEbeRWgbNEkqO");
+
+        let client_first = scram
+            .initial_response(&scram_creds("user", "pencil"))
+            .unwrap();
+        assert_eq!(client_first, b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+
+        let server_first = b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096";
+        let client_final = scram.continue_auth(Some(server_first)).unwrap();
+        assert_eq!(
+            String::from_utf8(client_final).unwrap(),
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+        );
+
+        let server_final = b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=";
+        assert!(scram.continue_auth(Some(server_final)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scram_rejects_bad_server_signature() {
+        let mut scram = ScramMechanism::with_nonce(ScramAlgorithm::Sha256, "rOprNGfwEbeRWgbNEkqO");
+        scram
+            .initial_response(&scram_creds("user", "pencil"))
+            .unwrap();
+        scram
+            .continue_auth(Some(
+                b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+            ))
+            .unwrap();
+
+        let forged = format!("v={}", BASE64.encode([0u8; 32]));
+        let error = scram.continue_auth(Some(forged.as_bytes())).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AuthError>(),
+            Some(&AuthError::ServerSignatureMismatch)
+        );
+    }
+
+    #[test]
+    fn test_scram_rejects_bad_server_first() {
+        let creds = scram_creds("us,er=", "pencil");
+
+        let mut scram = ScramMechanism::with_nonce(ScramAlgorithm::Sha512, "abc");
+        let client_first = scram.initial_response(&creds).unwrap();
+        assert_eq!(client_first, b"n,,n=us=2Cer=3D,r=abc");
+
+        let error = scram
+            .continue_auth(Some(b"r=xyz123,s=c2FsdA==,i=4096"))
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AuthError>(),
+            Some(&AuthError::NonceMismatch)
+        );
+
+        scram.initial_response(&creds).unwrap();
+        let error = scram
+            .continue_auth(Some(b"r=abc123,s=c2FsdA==,i=0"))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AuthError>(),
+            Some(AuthError::InvalidIterations(_))
+        ));
+
+        scram.initial_response(&creds).unwrap();
+        let error = scram
+            .continue_auth(Some(b"r=abc123,s=c2FsdA==,i=4000000000"))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AuthError>(),
+            Some(AuthError::InvalidIterations(_))
+        ));
+
+        scram.initial_response(&creds).unwrap();
+        let error = scram
+            .continue_auth(Some("é=x,r=abc123".as_bytes()))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AuthError>(),
+            Some(AuthError::MalformedMessage(_))
+        ));
+
+        scram.initial_response(&creds).unwrap();
+        let error = scram.continue_auth(Some(b"e=unknown-user")).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AuthError>(),
+            Some(&AuthError::ServerError("unknown-user".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_scram_sha512() {
+        let mut session = SaslSession::new("SCRAM-SHA-512", scram_creds("user", "pencil"), true);
+        session.start().await.unwrap();
+
+        let client_first = session.handle_authenticate("+").await;
+        let Command::Authenticate { data } = &client_first[0] else {
+            panic!("Expected AUTHENTICATE");
+        };
+        let client_first = String::from_utf8(BASE64.decode(data).unwrap()).unwrap();
+        let client_first_bare = client_first.strip_prefix("n,,").unwrap();
+        let client_nonce = client_first_bare.strip_prefix("n=user,r=").unwrap();
+
+        // Play the server side of the exchange
+        let alg = ScramAlgorithm::Sha512;
+        let salt = b"saltysalt";
+        let server_first = format!("r={client_nonce}srv,s={},i=4096", BASE64.encode(salt));
+        let client_final = session
+            .handle_authenticate(&BASE64.encode(&server_first))
+            .await;
+        let Command::Authenticate { data } = &client_final[0] else {
+            panic!("Expected AUTHENTICATE");
+        };
+        let client_final = String::from_utf8(BASE64.decode(data).unwrap()).unwrap();
+        let (without_proof, proof) = client_final.split_once(",p=").unwrap();
+        assert_eq!(without_proof, format!("c=biws,r={client_nonce}srv"));
+
+        let salted = alg.salted_password(b"pencil", salt, 4096);
+        let client_key = alg.hmac(&salted, b"Client Key");
+        let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+        let signature = alg.hmac(&alg.hash(&client_key), auth_message.as_bytes());
+        let recovered: Vec<u8> = BASE64
+            .decode(proof)
+            .unwrap()
+            .iter()
+            .zip(signature.iter())
+            .map(|(p, s)| p ^ s)
+            .collect();
+        assert_eq!(alg.hash(&recovered), alg.hash(&client_key));
+
+        let server_signature = alg.hmac(&alg.hmac(&salted, b"Server Key"), auth_message.as_bytes());
+        let server_final = format!("v={}", BASE64.encode(server_signature));
+        let reply = session
+            .handle_authenticate(&BASE64.encode(server_final))
+            .await;
+        assert_eq!(
+            reply,
+            vec![Command::Authenticate {
+                data: "+".to_string()
+            }]
+        );
+
+        let success = sasl_message(":srv 903 nick :SASL authentication successful");
+        assert_eq!(session.handle_numeric(&success), Some(AuthState::Success));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_scram_requires_server_signature() {
+        let mut session = SaslSession::new("SCRAM-SHA-256", scram_creds("user", "pencil"), true);
+        session.start().await.unwrap();
+
+        let client_first = session.handle_authenticate("+").await;
+        let Command::Authenticate { data } = &client_first[0] else {
+            panic!("Expected AUTHENTICATE");
+        };
+        let client_first = String::from_utf8(BASE64.decode(data).unwrap()).unwrap();
+        let client_nonce = client_first.strip_prefix("n,,n=user,r=").unwrap();
+
+        let server_first = format!("r={client_nonce}srv,s=c2FsdA==,i=4096");
+        let client_final = session
+            .handle_authenticate(&BASE64.encode(server_first))
+            .await;
+        assert!(matches!(&client_final[0], Command::Authenticate { data } if data != "*"));
+
+        // The server claims success without sending its signature
+        let success = sasl_message(":srv 903 nick :SASL authentication successful");
+        assert_eq!(
+            session.handle_numeric(&success),
+            Some(AuthState::Failed(AuthError::ServerNotVerified))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_keeps_client_side_failure() {
+        let mut session = SaslSession::new("SCRAM-SHA-256", scram_creds("user", "pencil"), true);
+        session.start().await.unwrap();
+        session.handle_authenticate("+").await;
+
+        let reply = session
+            .handle_authenticate(&BASE64.encode("r=bogus,s=c2FsdA==,i=4096"))
+            .await;
+        assert_eq!(
+            reply,
+            vec![Command::Authenticate {
+                data: "*".to_string()
+            }]
+        );
+
+        let aborted = sasl_message(":srv 906 nick :SASL authentication aborted");
+        assert_eq!(
+            session.handle_numeric(&aborted),
+            Some(AuthState::Failed(AuthError::NonceMismatch))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_success_after_abort_stays_failed() {
+        let mut session = SaslSession::new("SCRAM-SHA-256", scram_creds("user", "pencil"), true);
+        session.start().await.unwrap();
+
+        let client_first = session.handle_authenticate("+").await;
+        let Command::Authenticate { data } = &client_first[0] else {
+            panic!("Expected AUTHENTICATE");
+        };
+        let client_first = String::from_utf8(BASE64.decode(data).unwrap()).unwrap();
+        let client_nonce = client_first.strip_prefix("n,,n=user,r=").unwrap();
+
+        let server_first = format!("r={client_nonce}srv,s=c2FsdA==,i=4096");
+        session
+            .handle_authenticate(&BASE64.encode(server_first))
+            .await;
+
+        // A forged server signature makes the client abort
+        let server_final = format!("v={}", BASE64.encode([0u8; 32]));
+        let reply = session
+            .handle_authenticate(&BASE64.encode(server_final))
+            .await;
+        assert_eq!(
+            reply,
+            vec![Command::Authenticate {
+                data: "*".to_string()
+            }]
+        );
+
+        let success = sasl_message(":srv 903 nick :SASL authentication successful");
+        assert_eq!(
+            session.handle_numeric(&success),
+            Some(AuthState::Failed(AuthError::ServerSignatureMismatch))
+        );
+        assert_eq!(
+            session.state(),
+            &AuthState::Failed(AuthError::ServerSignatureMismatch)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sasl_session_aborts_on_oversized_payload() {
+        let mut session = test_session(true);
+        session.start().await.unwrap();
+
+        let chunk = "A".repeat(AUTHENTICATE_CHUNK_SIZE);
+        let mut reply = Vec::new();
+        for _ in 0..=MAX_AUTHENTICATE_PAYLOAD / AUTHENTICATE_CHUNK_SIZE {
+            reply = session.handle_authenticate(&chunk).await;
+            if !reply.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(
+            reply,
+            vec![Command::Authenticate {
+                data: "*".to_string()
+            }]
+        );
+        assert_eq!(
+            session.state(),
+            &AuthState::Failed(AuthError::MessageTooLong)
+        );
+    }
 }
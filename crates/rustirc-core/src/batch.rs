@@ -5,9 +5,17 @@
 //!
 //! See: <https://ircv3.net/specs/extensions/batch>
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use rustirc_protocol::Message;
+use rustirc_protocol::split::{MultilineLimits, MULTILINE_BATCH_TYPE, MULTILINE_CONCAT_TAG};
+use rustirc_protocol::{Message, Tag};
+use tracing::{debug, warn};
+
+/// Limits for received `draft/multiline` batches when the server advertised none
+const FALLBACK_MULTILINE_LIMITS: MultilineLimits = MultilineLimits {
+    max_bytes: 65536,
+    max_lines: None,
+};
 
 /// The type of an IRCv3 batch.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -20,6 +28,8 @@ pub enum BatchType {
     ChatHistory,
     /// Labeled response grouping
     LabeledResponse,
+    /// One message split over several lines (`draft/multiline`)
+    Multiline,
     /// Custom or unknown batch type
     Custom(String),
 }
@@ -32,6 +42,7 @@ impl BatchType {
             "netsplit" => BatchType::Netsplit,
             "chathistory" => BatchType::ChatHistory,
             "labeled-response" => BatchType::LabeledResponse,
+            MULTILINE_BATCH_TYPE => BatchType::Multiline,
             other => BatchType::Custom(other.to_string()),
         }
     }
@@ -43,6 +54,7 @@ impl BatchType {
             BatchType::Netsplit => "netsplit",
             BatchType::ChatHistory => "chathistory",
             BatchType::LabeledResponse => "labeled-response",
+            BatchType::Multiline => MULTILINE_BATCH_TYPE,
             BatchType::Custom(s) => s,
         }
     }
@@ -61,6 +73,8 @@ pub struct Batch {
     pub parent_ref: Option<String>,
     /// Extra parameters from the BATCH + command (after the batch type).
     pub params: Vec<String>,
+    /// Tags of the BATCH + command, such as `msgid` and `time`.
+    pub tags: Vec<Tag>,
 }
 
 impl Batch {
@@ -77,8 +91,50 @@ impl Batch {
             messages: Vec::new(),
             parent_ref,
             params,
+            tags: Vec::new(),
         }
     }
+
+    /// Join the lines of a `draft/multiline` batch into one message.
+    ///
+    /// Lines are joined with `\n`, except lines tagged
+    /// `draft/multiline-concat`, which continue the previous line directly.
+    /// The message takes the tags of the BATCH + command. Returns `None` for
+    /// other batch types and for batches without a `PRIVMSG` or `NOTICE`.
+    pub fn assemble_multiline(&self) -> Option<Message> {
+        if self.batch_type != BatchType::Multiline {
+            return None;
+        }
+        let target = self.params.first()?;
+        let first = self.messages.first()?;
+        if !matches!(first.command.as_str(), "PRIVMSG" | "NOTICE") {
+            return None;
+        }
+
+        let mut text = String::new();
+        for (index, line) in self.messages.iter().enumerate() {
+            if line.command != first.command {
+                debug!(
+                    "Ignoring {} in multiline batch {}",
+                    line.command, self.ref_tag
+                );
+                continue;
+            }
+            if index > 0 && !line.has_tag(MULTILINE_CONCAT_TAG) {
+                text.push('\n');
+            }
+            text.push_str(line.params.get(1).map(String::as_str).unwrap_or(""));
+        }
+
+        let mut message = Message::new(first.command.clone())
+            .add_param(target.clone())
+            .add_param(text);
+        message.prefix = first.prefix.clone();
+        if !self.tags.is_empty() {
+            message.tags = Some(self.tags.clone());
+        }
+        Some(message)
+    }
 }
 
 /// Manages open IRCv3 batches on a connection.
@@ -91,6 +147,13 @@ pub struct BatchManager {
     open_batches: HashMap<String, Batch>,
     /// Completed batches awaiting consumption, keyed by reference tag.
     completed_batches: HashMap<String, Batch>,
+    /// Limits advertised with the `draft/multiline` capability.
+    multiline_limits: Option<MultilineLimits>,
+    /// Bytes of text collected so far, per open multiline batch.
+    multiline_bytes: HashMap<String, usize>,
+    /// Multiline batches dropped for exceeding the limits; their remaining
+    /// lines are discarded until the batch ends.
+    dropped: HashSet<String>,
 }
 
 impl BatchManager {
@@ -145,7 +208,8 @@ impl BatchManager {
                 .and_then(|t| t.value.clone())
         });
 
-        let batch = Batch::new(ref_tag.clone(), batch_type, parent_ref, extra_params);
+        let mut batch = Batch::new(ref_tag.clone(), batch_type, parent_ref, extra_params);
+        batch.tags = message.tags.clone().unwrap_or_default();
         self.open_batches.insert(ref_tag.clone(), batch);
 
         Ok(ref_tag)
@@ -227,6 +291,101 @@ impl BatchManager {
         self.completed_batches.len()
     }
 
+    /// Apply the limits advertised with the `draft/multiline` capability to
+    /// received batches.
+    pub fn set_multiline_limits(&mut self, limits: Option<MultilineLimits>) {
+        self.multiline_limits = limits;
+    }
+
+    /// Collect the lines of `draft/multiline` batches.
+    ///
+    /// Returns the message to deliver: the message itself when it is not part
+    /// of a multiline batch, nothing while a multiline batch is still open,
+    /// and the assembled message when one ends. Other batch types pass
+    /// through untouched.
+    ///
+    /// A batch that grows beyond the multiline limits is dropped along with
+    /// the rest of its lines.
+    pub fn collect_multiline(&mut self, message: Message) -> Option<Message> {
+        if message.command == "BATCH" {
+            let reference = message.params.first().map(String::as_str).unwrap_or("");
+            let batch_type = message.params.get(1).map(String::as_str);
+
+            if reference.starts_with('+') && batch_type == Some(MULTILINE_BATCH_TYPE) {
+                return match self.handle_batch_start(&message) {
+                    Ok(ref_tag) => {
+                        self.multiline_bytes.insert(ref_tag, 0);
+                        None
+                    }
+                    Err(e) => {
+                        debug!("Invalid multiline batch: {}", e);
+                        Some(message)
+                    }
+                };
+            }
+
+            if let Some(ref_tag) = reference.strip_prefix('-') {
+                if self.dropped.remove(ref_tag) {
+                    return None;
+                }
+                if self.is_multiline(ref_tag) {
+                    self.multiline_bytes.remove(ref_tag);
+                    let batch = self.handle_batch_end(&message).ok()?;
+                    self.completed_batches.remove(ref_tag);
+                    return batch.assemble_multiline();
+                }
+            }
+            return Some(message);
+        }
+
+        let Some(ref_tag) = message.get_tag("batch") else {
+            return Some(message);
+        };
+        if self.dropped.contains(&ref_tag) {
+            return None;
+        }
+        if !self.is_multiline(&ref_tag) {
+            return Some(message);
+        }
+
+        if self.exceeds_multiline_limits(&ref_tag, &message) {
+            warn!("Dropping multiline batch {} exceeding its limits", ref_tag);
+            self.open_batches.remove(&ref_tag);
+            self.multiline_bytes.remove(&ref_tag);
+            self.dropped.insert(ref_tag);
+            return None;
+        }
+        self.add_message(&message);
+        None
+    }
+
+    /// Count a line towards its multiline batch, checking the limits
+    fn exceeds_multiline_limits(&mut self, ref_tag: &str, message: &Message) -> bool {
+        let limits = self.multiline_limits.unwrap_or(FALLBACK_MULTILINE_LIMITS);
+        let lines = self
+            .open_batches
+            .get(ref_tag)
+            .map_or(0, |batch| batch.messages.len())
+            + 1;
+
+        // Line breaks count towards the byte limit
+        let text = message.params.get(1).map_or(0, String::len);
+        let line_break = lines > 1 && !message.has_tag(MULTILINE_CONCAT_TAG);
+        let bytes = self.multiline_bytes.entry(ref_tag.to_string()).or_default();
+        *bytes += text + usize::from(line_break);
+
+        // Every line but the first adds at least a byte, which bounds the
+        // number of lines when the server sets no line limit
+        let max_lines = limits.max_lines.unwrap_or(limits.max_bytes + 1);
+        *bytes > limits.max_bytes || lines > max_lines
+    }
+
+    fn is_multiline(&self, ref_tag: &str) -> bool {
+        self.open_batches
+            .get(ref_tag)
+            .is_some_and(|batch| batch.batch_type == BatchType::Multiline)
+    }
+
     /// Check if a message belongs to any open batch (has a `batch` tag
     /// matching an open ref_tag).
     pub fn message_is_batched(&self, message: &Message) -> bool {
@@ -247,6 +406,7 @@ impl BatchManager {
 mod tests {
     use super::*;
     use rustirc_protocol::message::Tag;
+    use rustirc_protocol::{Parser, Prefix};
 
     fn batch_start_msg(ref_tag: &str, batch_type: &str) -> Message {
         Message::new("BATCH").with_params(vec![format!("+{ref_tag}"), batch_type.to_string()])
@@ -341,9 +501,10 @@ mod tests {
             BatchType::parse("labeled-response"),
             BatchType::LabeledResponse
         );
+        assert_eq!(BatchType::parse("draft/multiline"), BatchType::Multiline);
         assert_eq!(
-            BatchType::parse("draft/multiline"),
-            BatchType::Custom("draft/multiline".to_string())
+            BatchType::parse("draft/example"),
+            BatchType::Custom("draft/example".to_string())
         );
 
         // Round-trip
@@ -404,4 +565,74 @@ mod tests {
         let wrong_ref = batched_privmsg("unknown", "#ch", "wrong ref");
         assert!(!mgr.message_is_batched(&wrong_ref));
     }
+
+    #[test]
+    fn test_collect_multiline() {
+        let mut mgr = BatchManager::new();
+        let lines = [
+            "@msgid=xyz :alice!a@host BATCH +ml draft/multiline #rust",
+            "@batch=ml :alice!a@host PRIVMSG #rust :hello",
+            "@batch=ml :alice!a@host PRIVMSG #rust :wor",
+            "@batch=ml;draft/multiline-concat :alice!a@host PRIVMSG #rust :ld",
+            "@batch=ml :alice!a@host PRIVMSG #rust :",
+            "@batch=ml :alice!a@host PRIVMSG #rust :bye",
+        ];
+        for line in lines {
+            let message = Parser::parse_message(line).unwrap();
+            assert!(mgr.collect_multiline(message).is_none(), "{line}");
+        }
+
+        let end = Parser::parse_message("BATCH -ml").unwrap();
+        let message = mgr.collect_multiline(end).unwrap();
+        assert_eq!(message.params, ["#rust", "hello\nworld\n\nbye"]);
+        assert_eq!(message.get_msgid().as_deref(), Some("xyz"));
+        assert!(matches!(
+            &message.prefix,
+            Some(Prefix::User { nick, .. }) if nick == "alice"
+        ));
+        assert_eq!(mgr.open_count() + mgr.completed_count(), 0);
+
+        // Other batches and their lines pass through
+        let other = batch_start_msg("hist", "chathistory");
+        assert!(mgr.collect_multiline(other).is_some());
+        let line = batched_privmsg("hist", "#rust", "old");
+        assert_eq!(mgr.collect_multiline(line.clone()), Some(line));
+    }
+
+    #[test]
+    fn test_collect_multiline_drops_oversized_batch() {
+        let mut mgr = BatchManager::new();
+        mgr.set_multiline_limits(MultilineLimits::parse("max-bytes=10,max-lines=3"));
+
+        let lines = [
+            ":alice!a@host BATCH +big draft/multiline #rust",
+            "@batch=big :alice!a@host PRIVMSG #rust :hello",
+            "@batch=big :alice!a@host PRIVMSG #rust :world",
+            "@batch=big :alice!a@host PRIVMSG #rust :more",
+            "BATCH -big",
+        ];
+        for line in lines {
+            let message = Parser::parse_message(line).unwrap();
+            assert!(mgr.collect_multiline(message).is_none(), "{line}");
+        }
+        assert_eq!(mgr.open_count() + mgr.completed_count(), 0);
+
+        // The line limit applies on its own
+        let lines = [
+            ":alice!a@host BATCH +many draft/multiline #rust",
+            "@batch=many :alice!a@host PRIVMSG #rust :a",
+            "@batch=many :alice!a@host PRIVMSG #rust :b",
+            "@batch=many :alice!a@host PRIVMSG #rust :c",
+            "@batch=many :alice!a@host PRIVMSG #rust :d",
+            "BATCH -many",
+        ];
+        for line in lines {
+            let message = Parser::parse_message(line).unwrap();
+            assert!(mgr.collect_multiline(message).is_none(), "{line}");
+        }
+        assert_eq!(mgr.open_count() + mgr.completed_count(), 0);
+
+        let line = batched_privmsg("many", "#rust", "after");
+        assert_eq!(mgr.collect_multiline(line.clone()), Some(line));
+    }
 }
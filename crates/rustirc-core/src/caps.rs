@@ -0,0 +1,464 @@
+//! IRCv3 capability negotiation
+//!
+//! Implements the client side of `CAP LS 302` negotiation as described in the
+//! IRCv3 capability negotiation specification, including multi-line `LS`
+//! replies, capability values, `ACK`/`NAK` handling and `cap-notify`
+//! `NEW`/`DEL` updates after registration.
+//!
+//! The negotiator is a pure state machine: it consumes `CAP` messages and
+//! returns the commands the connection should send in response.
+//!
+//! See: <https://ircv3.net/specs/extensions/capability-negotiation>
+
+use std::collections::HashSet;
+
+use rustirc_protocol::command::CapSubcommand;
+use rustirc_protocol::{Capability, CapabilitySet, Command, Message};
+use tracing::{debug, warn};
+
+/// Capability negotiation protocol version requested with `CAP LS`.
+pub const CAP_VERSION: &str = "302";
+
+/// Maximum length of the capability list in a single `CAP REQ` line.
+///
+/// Keeps the full `CAP REQ :...` line comfortably under the 512-byte limit.
+const MAX_REQ_LENGTH: usize = 400;
+
+/// Capabilities the client requests by default when the server offers them.
+pub fn default_capabilities() -> Vec<Capability> {
+    vec![
+        Capability::AccountNotify,
+        Capability::AccountTag,
+        Capability::AwayNotify,
+        Capability::Batch,
+        Capability::CapNotify,
+        Capability::ChatHistory,
+        Capability::ChgHost,
+        Capability::ExtendedJoin,
+        Capability::InviteNotify,
+        Capability::LabeledResponse,
+        Capability::MessageTags,
+        Capability::MultiPrefix,
+        Capability::Multiline,
+        Capability::ServerTime,
+        Capability::SetName,
+        Capability::UserhostInNames,
+        Capability::StandardReplies,
+    ]
+}
+
+/// Negotiation phase of a [`CapNegotiator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapPhase {
+    /// `CAP LS` has not been sent yet.
+    Idle,
+    /// Waiting for the (possibly multi-line) `CAP LS` reply.
+    Listing,
+    /// Waiting for `ACK`/`NAK` replies to our `CAP REQ` lines.
+    Requesting,
+    /// Initial negotiation finished; `CAP END` may be sent.
+    Ready,
+    /// `CAP END` has been sent; only `cap-notify` updates are processed.
+    Ended,
+}
+
+/// Result of feeding a `CAP` message to the negotiator.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapResponse {
+    /// Commands to send to the server in response.
+    pub commands: Vec<Command>,
+    /// Whether the set of enabled capabilities changed.
+    pub enabled_changed: bool,
+    /// Whether initial negotiation just became ready to be ended.
+    pub ready: bool,
+}
+
+/// Client-side IRCv3 capability negotiation state machine.
+#[derive(Debug, Clone)]
+pub struct CapNegotiator {
+    /// Capabilities the client wants to enable.
+    wanted: HashSet<Capability>,
+    /// Capabilities advertised by the server, with their values.
+    available: CapabilitySet,
+    /// Capabilities acknowledged by the server.
+    enabled: CapabilitySet,
+    /// Capability names requested but not yet acknowledged or rejected.
+    pending: HashSet<String>,
+    /// Current negotiation phase.
+    phase: CapPhase,
+}
+
+impl Default for CapNegotiator {
+    fn default() -> Self {
+        Self::new(default_capabilities())
+    }
+}
+
+impl CapNegotiator {
+    /// Create a negotiator that requests the given capabilities when offered.
+    pub fn new(wanted: impl IntoIterator<Item = Capability>) -> Self {
+        Self {
+            wanted: wanted.into_iter().collect(),
+            available: CapabilitySet::new(),
+            enabled: CapabilitySet::new(),
+            pending: HashSet::new(),
+            phase: CapPhase::Idle,
+        }
+    }
+
+    /// Add a capability to the wanted set (e.g. `sasl` when credentials exist).
+    pub fn want(&mut self, cap: Capability) {
+        self.wanted.insert(cap);
+    }
+
+    /// Begin negotiation, returning the `CAP LS 302` command to send.
+    pub fn start(&mut self) -> Command {
+        self.available.clear();
+        self.enabled.clear();
+        self.pending.clear();
+        self.phase = CapPhase::Listing;
+
+        Command::Cap {
+            subcommand: CapSubcommand::Ls {
+                version: Some(CAP_VERSION.to_string()),
+            },
+        }
+    }
+
+    /// Current negotiation phase.
+    pub fn phase(&self) -> CapPhase {
+        self.phase
+    }
+
+    /// Whether initial negotiation is finished and `CAP END` may be sent.
+    pub fn is_ready(&self) -> bool {
+        self.phase == CapPhase::Ready
+    }
+
+    /// Capabilities advertised by the server.
+    pub fn available(&self) -> &CapabilitySet {
+        &self.available
+    }
+
+    /// Capabilities currently enabled on the connection.
+    pub fn enabled(&self) -> &CapabilitySet {
+        &self.enabled
+    }
+
+    /// Check whether a capability is enabled.
+    pub fn is_enabled(&self, cap: &Capability) -> bool {
+        self.enabled.contains(cap)
+    }
+
+    /// Finish initial negotiation, returning the `CAP END` command.
+    ///
+    /// Returns `None` if negotiation has already been ended.
+    pub fn end(&mut self) -> Option<Command> {
+        if self.phase == CapPhase::Ended {
+            return None;
+        }
+        self.phase = CapPhase::Ended;
+        Some(Command::Cap {
+            subcommand: CapSubcommand::End,
+        })
+    }
+
+    /// Abandon negotiation because the server does not support `CAP`.
+    ///
+    /// Called when the server answers `CAP LS` with `421 ERR_UNKNOWNCOMMAND`
+    /// or completes registration without ever replying.
+    pub fn abort(&mut self) {
+        if self.phase != CapPhase::Ended {
+            debug!("Capability negotiation not supported by server");
+            self.pending.clear();
+            self.phase = CapPhase::Ended;
+        }
+    }
+
+    /// Process a `CAP` message from the server.
+    pub fn handle_message(&mut self, message: &Message) -> CapResponse {
+        // CAP <target> <subcommand> [*] :<capabilities>
+        let Some(subcommand) = message.params.get(1) else {
+            warn!("Malformed CAP message: {}", message);
+            return CapResponse::default();
+        };
+
+        let (more, list) = match message.params.get(2).map(String::as_str) {
+            Some("*") => (true, message.params.get(3).map(String::as_str)),
+            other => (false, other),
+        };
+        let list = list.unwrap_or("");
+
+        match subcommand.to_ascii_uppercase().as_str() {
+            "LS" => self.handle_ls(list, more),
+            "ACK" => self.handle_ack(list),
+            "NAK" => self.handle_nak(list),
+            "NEW" => self.handle_new(list),
+            "DEL" => self.handle_del(list),
+            "LIST" => CapResponse::default(),
+            other => {
+                debug!("Ignoring unknown CAP subcommand: {}", other);
+                CapResponse::default()
+            }
+        }
+    }
+
+    fn handle_ls(&mut self, list: &str, more: bool) -> CapResponse {
+        self.available.extend_from_list(list);
+
+        if more || self.phase != CapPhase::Listing {
+            return CapResponse::default();
+        }
+
+        let to_request: Vec<Capability> = self
+            .available
+            .iter()
+            .filter(|cap| self.wanted.contains(cap) && !self.enabled.contains(cap))
+            .cloned()
+            .collect();
+
+        let commands = self.request(to_request);
+        self.phase = CapPhase::Requesting;
+        self.check_ready(commands, false)
+    }
+
+    fn handle_ack(&mut self, list: &str) -> CapResponse {
+        let mut changed = false;
+        for token in list.split_whitespace() {
+            self.pending.remove(token.trim_start_matches('-'));
+
+            if let Some(name) = token.strip_prefix('-') {
+                if let Ok(cap) = name.parse::<Capability>() {
+                    changed |= self.enabled.remove(&cap);
+                }
+            } else if let Ok(cap) = token.parse::<Capability>() {
+                let value = self.available.value(&cap).map(str::to_string);
+                self.enabled.add_with_value(cap, value);
+                changed = true;
+            }
+        }
+        self.check_ready(Vec::new(), changed)
+    }
+
+    fn handle_nak(&mut self, list: &str) -> CapResponse {
+        for token in list.split_whitespace() {
+            warn!("Server rejected capability request: {}", token);
+            self.pending.remove(token);
+        }
+        self.check_ready(Vec::new(), false)
+    }
+
+    fn handle_new(&mut self, list: &str) -> CapResponse {
+        let new_caps = CapabilitySet::parse(list);
+        let mut to_request = Vec::new();
+        for cap in new_caps.iter() {
+            let value = new_caps.value(cap).map(str::to_string);
+            self.available.add_with_value(cap.clone(), value);
+            if self.wanted.contains(cap) && !self.enabled.contains(cap) {
+                to_request.push(cap.clone());
+            }
+        }
+
+        CapResponse {
+            commands: self.request(to_request),
+            ..CapResponse::default()
+        }
+    }
+
+    fn handle_del(&mut self, list: &str) -> CapResponse {
+        let mut changed = false;
+        for cap in CapabilitySet::parse(list).iter() {
+            self.available.remove(cap);
+            changed |= self.enabled.remove(cap);
+        }
+        CapResponse {
+            enabled_changed: changed,
+            ..CapResponse::default()
+        }
+    }
+
+    /// Build `CAP REQ` lines for the given capabilities, recording them as pending.
+    fn request(&mut self, caps: Vec<Capability>) -> Vec<Command> {
+        let mut names: Vec<String> = caps
+            .into_iter()
+            .map(|cap| cap.as_str().to_string())
+            .filter(|name| !self.pending.contains(name))
+            .collect();
+        names.sort();
+
+        let mut commands = Vec::new();
+        let mut batch: Vec<String> = Vec::new();
+        let mut batch_len = 0;
+
+        for name in names {
+            if !batch.is_empty() && batch_len + name.len() + 1 > MAX_REQ_LENGTH {
+                commands.push(Self::req_command(std::mem::take(&mut batch)));
+                batch_len = 0;
+            }
+            batch_len += name.len() + 1;
+            self.pending.insert(name.clone());
+            batch.push(name);
+        }
+
+        if !batch.is_empty() {
+            commands.push(Self::req_command(batch));
+        }
+
+        commands
+    }
+
+    fn req_command(capabilities: Vec<String>) -> Command {
+        Command::Cap {
+            subcommand: CapSubcommand::Req { capabilities },
+        }
+    }
+
+    fn check_ready(&mut self, commands: Vec<Command>, enabled_changed: bool) -> CapResponse {
+        let ready = self.phase == CapPhase::Requesting && self.pending.is_empty();
+        if ready {
+            self.phase = CapPhase::Ready;
+        }
+
+        CapResponse {
+            commands,
+            enabled_changed,
+            ready,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    fn cap(line: &str) -> Message {
+        Parser::parse_message(line).unwrap()
+    }
+
+    fn req_caps(command: &Command) -> Vec<String> {
+        match command {
+            Command::Cap {
+                subcommand: CapSubcommand::Req { capabilities },
+            } => capabilities.clone(),
+            other => panic!("Expected CAP REQ, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_start_sends_ls_302() {
+        let mut negotiator = CapNegotiator::default();
+        let command = negotiator.start();
+        assert_eq!(command.to_message().to_string(), "CAP LS 302");
+        assert_eq!(negotiator.phase(), CapPhase::Listing);
+    }
+
+    #[test]
+    fn test_multiline_ls_with_values() {
+        let mut negotiator = CapNegotiator::new(vec![Capability::MultiPrefix, Capability::Sasl]);
+        negotiator.start();
+
+        let response = negotiator.handle_message(&cap(
+            ":irc.example.com CAP * LS * :multi-prefix extended-join",
+        ));
+        assert!(response.commands.is_empty());
+        assert_eq!(negotiator.phase(), CapPhase::Listing);
+
+        let response = negotiator.handle_message(&cap(
+            ":irc.example.com CAP * LS :sasl=PLAIN,EXTERNAL server-time",
+        ));
+        assert_eq!(response.commands.len(), 1);
+        assert_eq!(
+            req_caps(&response.commands[0]),
+            vec!["multi-prefix", "sasl"]
+        );
+        assert_eq!(
+            negotiator.available().value(&Capability::Sasl),
+            Some("PLAIN,EXTERNAL")
+        );
+        assert_eq!(negotiator.phase(), CapPhase::Requesting);
+    }
+
+    #[test]
+    fn test_ack_and_nak_complete_negotiation() {
+        let mut negotiator =
+            CapNegotiator::new(vec![Capability::MultiPrefix, Capability::ServerTime]);
+        negotiator.start();
+        negotiator.handle_message(&cap(":srv CAP * LS :multi-prefix server-time"));
+
+        let response = negotiator.handle_message(&cap(":srv CAP * NAK :multi-prefix server-time"));
+        assert!(response.ready);
+        assert!(negotiator.enabled().is_empty());
+        assert!(negotiator.is_ready());
+
+        let mut negotiator = CapNegotiator::new(vec![Capability::ServerTime]);
+        negotiator.start();
+        negotiator.handle_message(&cap(":srv CAP * LS :server-time"));
+        let response = negotiator.handle_message(&cap(":srv CAP nick ACK :server-time"));
+        assert!(response.ready);
+        assert!(response.enabled_changed);
+        assert!(negotiator.is_enabled(&Capability::ServerTime));
+
+        let end = negotiator.end().unwrap();
+        assert_eq!(end.to_message().to_string(), "CAP END");
+        assert!(negotiator.end().is_none());
+    }
+
+    #[test]
+    fn test_nothing_to_request_is_immediately_ready() {
+        let mut negotiator = CapNegotiator::new(vec![Capability::Batch]);
+        negotiator.start();
+        let response = negotiator.handle_message(&cap(":srv CAP * LS :away-notify"));
+        assert!(response.commands.is_empty());
+        assert!(response.ready);
+    }
+
+    #[test]
+    fn test_cap_notify_new_and_del() {
+        let mut negotiator =
+            CapNegotiator::new(vec![Capability::AwayNotify, Capability::AccountNotify]);
+        negotiator.start();
+        negotiator.handle_message(&cap(":srv CAP * LS :away-notify"));
+        negotiator.handle_message(&cap(":srv CAP * ACK :away-notify"));
+        negotiator.end();
+
+        let response = negotiator.handle_message(&cap(":srv CAP nick NEW :account-notify batch"));
+        assert_eq!(req_caps(&response.commands[0]), vec!["account-notify"]);
+        assert!(!response.ready);
+
+        let response = negotiator.handle_message(&cap(":srv CAP nick ACK :account-notify"));
+        assert!(response.enabled_changed);
+        assert!(negotiator.is_enabled(&Capability::AccountNotify));
+        assert_eq!(negotiator.phase(), CapPhase::Ended);
+
+        let response = negotiator.handle_message(&cap(":srv CAP nick DEL :away-notify"));
+        assert!(response.enabled_changed);
+        assert!(!negotiator.is_enabled(&Capability::AwayNotify));
+        assert!(!negotiator.available().contains(&Capability::AwayNotify));
+    }
+
+    #[test]
+    fn test_long_request_is_split() {
+        let wanted: Vec<Capability> = (0..60)
+            .map(|i| Capability::Custom(format!("vendor.example/capability-{i}")))
+            .collect();
+        let list = wanted
+            .iter()
+            .map(|c| c.as_str().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut negotiator = CapNegotiator::new(wanted);
+        negotiator.start();
+        let response = negotiator.handle_message(&Message::new("CAP").with_params(vec![
+            "*".to_string(),
+            "LS".to_string(),
+            list,
+        ]));
+
+        assert!(response.commands.len() > 1);
+        for command in &response.commands {
+            assert!(command.to_message().to_string().len() < 512);
+        }
+    }
+}
@@ -3,8 +3,10 @@
 //! Full-featured command-line interface with all GUI features for testing IRC functionality.
 //! Includes themes, settings, multiple servers, tab management, and comprehensive IRC support.
 
+use crate::auth::SaslSession;
 use crate::{AuthState, Config, IrcClient, SaslCredentials};
 use anyhow::Result;
+use rustirc_protocol::Message;
 use std::collections::HashMap;
 use std::io::{self, Write};
 use tokio::time::{timeout, Duration};
@@ -408,6 +410,18 @@ impl CliClient {
                 Ok(())
             }
 
+            Some(&"/certfp") => {
+                if let Some(path) = parts.get(1) {
+                    match crate::tls::certificate_fingerprint_from_file(*path) {
+                        Ok(fingerprint) => println!("SHA-256 fingerprint: {fingerprint}"),
+                        Err(e) => println!("Failed to read certificate: {e}"),
+                    }
+                } else {
+                    println!("Usage: /certfp <certificate.pem>");
+                }
+                Ok(())
+            }
+
             // Status and information
             Some(&"/status") => {
                 self.show_status();
@@ -631,22 +645,34 @@ impl CliClient {
             password: "testpass".to_string().into(),
             authzid: None,
         };
+        let username = credentials.username.clone();
 
-        // Test authentication state transitions
-        let mut auth_state = AuthState::Idle;
-        println!("Initial auth state: {auth_state:?}");
+        // Drive a PLAIN exchange against canned server replies
+        let mut session = SaslSession::new("PLAIN", credentials, false);
+        println!("Initial auth state: {:?}", session.state());
 
-        auth_state = AuthState::InProgress;
-        println!("Auth state during authentication: {auth_state:?}");
+        let start = session.start().await?;
+        println!("Sent: {}", start.to_message());
+        println!("Auth state during authentication: {:?}", session.state());
 
-        auth_state = AuthState::Success;
-        println!("Auth state after success: {auth_state:?}");
+        for reply in session.handle_authenticate("+").await {
+            println!(
+                "Sent: AUTHENTICATE <{} bytes>",
+                reply.to_message().to_string().len()
+            );
+        }
+
+        let success = Message::new("903")
+            .add_param(username.clone())
+            .add_param("SASL authentication successful");
+        let final_state = session.handle_numeric(&success);
+        println!("Auth state after success: {final_state:?}");
+
+        if final_state != Some(AuthState::Success) {
+            anyhow::bail!("SASL self-test did not succeed");
+        }
 
-        // In a real implementation, this would use the credentials with a SaslAuthenticator
-        println!(
-            "SASL credentials validated for user: {}",
-            credentials.username
-        );
+        println!("SASL credentials validated for user: {username}");
         println!("SASL authentication test completed successfully");
 
         Ok(())
@@ -685,6 +711,7 @@ impl CliClient {
         println!();
         println!("Information:");
         println!("  /status               - Show connection status");
+        println!("  /certfp <cert.pem>    - Show a client certificate's SHA-256 fingerprint");
         println!("  /help                 - Show this help");
         println!("  /quit                 - Exit the client");
         println!();
@@ -22,9 +22,12 @@ use crate::config::Config;
 use crate::connection::{ConnectionConfig, ConnectionManager};
 use crate::error::{Error, Result};
 use crate::events::EventBus;
+use crate::recovery::RecoveryStats;
 use crate::state::ClientState;
 use rustirc_protocol::{Command, Message};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 /// The main IRC client
@@ -96,11 +99,31 @@ impl IrcClient {
                 server: srv_config.address.clone(),
                 port: srv_config.port,
                 use_tls: srv_config.use_tls,
-                verify_tls: true,
+                tls_verification: srv_config.tls_verification,
+                ca_bundle: srv_config.ca_bundle.clone(),
                 nickname: self.config.user.nickname.clone(),
+                alternative_nicknames: self.config.user.alternative_nicknames.clone(),
                 username: self.config.user.username.clone(),
                 realname: self.config.user.realname.clone(),
                 password: srv_config.password.clone(),
+                sasl: srv_config.sasl.clone(),
+                proxy: srv_config
+                    .proxy
+                    .clone()
+                    .or_else(|| self.config.proxy.clone()),
+                client_cert: srv_config.client_cert.clone(),
+                client_key: srv_config.client_key.clone(),
+                encoding: srv_config.encoding.clone(),
+                channel_encodings: srv_config.channel_encodings.clone(),
+                transport: srv_config.transport.clone(),
+                fallback_endpoints: srv_config.fallback_endpoints.clone(),
+                rotation: srv_config.rotation,
+                address_family: srv_config.address_family,
+                bind_address: srv_config.bind_address,
+                connection_attempt_delay: Duration::from_millis(
+                    srv_config.connection_attempt_delay_ms,
+                ),
+                flood: self.config.flood.clone(),
                 ..Default::default()
             }
         } else {
@@ -110,6 +133,8 @@ impl IrcClient {
                 nickname: "RustIRC".to_string(),
                 username: "rustirc".to_string(),
                 realname: "RustIRC Client".to_string(),
+                proxy: self.config.proxy.clone(),
+                flood: self.config.flood.clone(),
                 ..Default::default()
             }
         };
@@ -217,6 +242,14 @@ impl IrcClient {
         self.connection_manager.clone()
     }
 
+    /// Connection attempts and failures per connection and endpoint
+    pub async fn recovery_stats(&self) -> HashMap<String, RecoveryStats> {
+        self.connection_manager
+            .recovery()
+            .get_recovery_stats()
+            .await
+    }
+
     /// Connect to a specific server with custom configuration
     pub async fn connect_with_config(&self, connection_config: ConnectionConfig) -> Result<String> {
         let connection_id = format!("{}:{}", connection_config.server, connection_config.port);
@@ -0,0 +1,347 @@
+//! Line framing and character encoding for IRC streams
+//!
+//! [`IrcCodec`] splits the byte stream into lines for `FramedRead` and writes
+//! [`Message`]s for `FramedWrite`. Lines may end in `\r\n` or a bare `\n`,
+//! stray `\r` bytes are dropped, and lines longer than the limit are
+//! discarded instead of growing the buffer without bound.
+//!
+//! Lines that are not valid UTF-8 are decoded with a fallback [`Charset`]
+//! rather than failing the read, which older networks still need.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use bytes::{BufMut, BytesMut};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use rustirc_protocol::Message;
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+
+/// Longest accepted line: 8191 bytes of tags plus a 512 byte message
+pub const MAX_LINE_LENGTH: usize = 8191 + 512;
+
+/// Channel prefixes used to find a line's target channel
+const CHANNEL_PREFIXES: &[u8] = b"#&+!";
+
+/// Look up an encoding by its WHATWG label, e.g. `latin1` or `iso-8859-15`
+///
+/// Note that `latin1` and `iso-8859-1` are aliases for `windows-1252`.
+pub fn lookup_encoding(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| Error::Config(format!("Unknown encoding: {label}")))
+}
+
+/// Character encodings used on a connection
+///
+/// Incoming lines are UTF-8 when they are valid UTF-8, and are otherwise
+/// decoded with the channel's override or the server fallback. Outgoing
+/// lines use the configured encoding unless the server advertises
+/// `UTF8ONLY`, and are sent as UTF-8 when the text can't be represented.
+#[derive(Debug, Clone)]
+pub struct Charset {
+    fallback: &'static Encoding,
+    configured: Option<&'static Encoding>,
+    channels: HashMap<String, &'static Encoding>,
+    utf8_only: bool,
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Self {
+            fallback: WINDOWS_1252,
+            configured: None,
+            channels: HashMap::new(),
+            utf8_only: false,
+        }
+    }
+}
+
+impl Charset {
+    /// Build a charset from configured encoding labels
+    ///
+    /// Without a server encoding, outgoing text is UTF-8 and invalid incoming
+    /// lines are decoded as `windows-1252`. Unknown labels are logged and
+    /// ignored.
+    pub fn new(encoding: Option<&str>, channel_encodings: &HashMap<String, String>) -> Self {
+        let mut charset = Self::default();
+        if let Some(label) = encoding {
+            match lookup_encoding(label) {
+                Ok(encoding) => {
+                    charset.configured = Some(encoding);
+                    if encoding != UTF_8 {
+                        charset.fallback = encoding;
+                    }
+                }
+                Err(e) => warn!("{}", e),
+            }
+        }
+        for (channel, label) in channel_encodings {
+            if let Err(e) = charset.set_channel_encoding(channel, label) {
+                warn!("{} for {}", e, channel);
+            }
+        }
+        charset
+    }
+
+    /// Override the encoding used for one channel
+    pub fn set_channel_encoding(&mut self, channel: &str, label: &str) -> Result<()> {
+        let encoding = lookup_encoding(label)?;
+        self.channels.insert(channel.to_ascii_lowercase(), encoding);
+        Ok(())
+    }
+
+    /// Remove a channel's encoding override
+    pub fn clear_channel_encoding(&mut self, channel: &str) {
+        self.channels.remove(&channel.to_ascii_lowercase());
+    }
+
+    /// Record whether the server advertised `UTF8ONLY`
+    pub fn set_utf8_only(&mut self, utf8_only: bool) {
+        self.utf8_only = utf8_only;
+    }
+
+    fn channel_encoding(&self, line: &[u8]) -> Option<&'static Encoding> {
+        if self.channels.is_empty() {
+            return None;
+        }
+        let channel = target_channel(line)?;
+        self.channels
+            .get(&String::from_utf8_lossy(channel).to_ascii_lowercase())
+            .copied()
+    }
+
+    /// Decode a received line
+    pub fn decode(&self, line: &[u8]) -> String {
+        if let Ok(text) = std::str::from_utf8(line) {
+            return text.to_string();
+        }
+
+        let encoding = self.channel_encoding(line).unwrap_or(self.fallback);
+        let (text, _) = encoding.decode_without_bom_handling(line);
+        text.into_owned()
+    }
+
+    /// Encode a line for sending
+    pub fn encode(&self, line: &str) -> Vec<u8> {
+        if self.utf8_only {
+            return line.as_bytes().to_vec();
+        }
+        let encoding = match self.channel_encoding(line.as_bytes()).or(self.configured) {
+            Some(encoding) if encoding != UTF_8 => encoding,
+            _ => return line.as_bytes().to_vec(),
+        };
+
+        let (bytes, _, unmappable) = encoding.encode(line);
+        if unmappable {
+            debug!(
+                "Line not representable in {}, sending UTF-8",
+                encoding.name()
+            );
+            return line.as_bytes().to_vec();
+        }
+        bytes.into_owned()
+    }
+}
+
+/// The first channel among a raw line's middle parameters
+fn target_channel(line: &[u8]) -> Option<&[u8]> {
+    let mut words = line.split(|&b| b == b' ').filter(|word| !word.is_empty());
+    let mut word = words.next()?;
+    if word.starts_with(b"@") {
+        word = words.next()?;
+    }
+    if word.starts_with(b":") {
+        words.next()?;
+    }
+
+    words
+        .take_while(|word| !word.starts_with(b":"))
+        .find(|word| CHANNEL_PREFIXES.contains(&word[0]))
+}
+
+/// Framing codec for IRC lines
+///
+/// Decodes into line text without the terminator and encodes [`Message`]s
+/// with a trailing `\r\n`. The [`Charset`] is shared so the connection can
+/// update it, e.g. when `UTF8ONLY` is advertised.
+#[derive(Debug)]
+pub struct IrcCodec {
+    charset: Arc<RwLock<Charset>>,
+    max_line_length: usize,
+    /// Bytes of the buffer already searched for a line feed
+    next_index: usize,
+    /// Skipping the rest of an over-long line
+    discarding: bool,
+}
+
+impl IrcCodec {
+    /// Create a codec with the default line length limit
+    pub fn new(charset: Arc<RwLock<Charset>>) -> Self {
+        Self::with_max_line_length(charset, MAX_LINE_LENGTH)
+    }
+
+    /// Create a codec that discards lines longer than `max_line_length` bytes
+    pub fn with_max_line_length(charset: Arc<RwLock<Charset>>, max_line_length: usize) -> Self {
+        Self {
+            charset,
+            max_line_length,
+            next_index: 0,
+            discarding: false,
+        }
+    }
+
+    fn charset(&self) -> std::sync::RwLockReadGuard<'_, Charset> {
+        self.charset.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Decode a complete line, or `None` if it should be skipped
+    fn decode_line(&mut self, line: &[u8]) -> Option<String> {
+        if std::mem::take(&mut self.discarding) {
+            return None;
+        }
+
+        let line: Vec<u8> = line.iter().copied().filter(|&b| b != b'\r').collect();
+        if line.len() > self.max_line_length {
+            warn!("Discarding {} byte line over the length limit", line.len());
+            return None;
+        }
+        if line.is_empty() {
+            return None;
+        }
+        Some(self.charset().decode(&line))
+    }
+}
+
+impl Decoder for IrcCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<String>> {
+        loop {
+            let Some(offset) = buf[self.next_index..].iter().position(|&b| b == b'\n') else {
+                if buf.len() > self.max_line_length {
+                    if !self.discarding {
+                        warn!("Discarding line over {} bytes", self.max_line_length);
+                        self.discarding = true;
+                    }
+                    buf.clear();
+                }
+                self.next_index = buf.len();
+                return Ok(None);
+            };
+
+            let line = buf.split_to(self.next_index + offset + 1);
+            self.next_index = 0;
+            if let Some(line) = self.decode_line(&line[..line.len() - 1]) {
+                return Ok(Some(line));
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> io::Result<Option<String>> {
+        if let Some(line) = self.decode(buf)? {
+            return Ok(Some(line));
+        }
+
+        // A final line without a terminator
+        self.next_index = 0;
+        let line = buf.split();
+        Ok(self.decode_line(&line))
+    }
+}
+
+impl Encoder<Message> for IrcCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, message: Message, dst: &mut BytesMut) -> io::Result<()> {
+        let line = self.charset().encode(&message.to_string());
+        dst.reserve(line.len() + 2);
+        dst.put_slice(&line);
+        dst.put_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codec(charset: Charset) -> IrcCodec {
+        IrcCodec::with_max_line_length(Arc::new(RwLock::new(charset)), 32)
+    }
+
+    fn decode_all(codec: &mut IrcCodec, input: &[u8]) -> Vec<String> {
+        let mut buf = BytesMut::from(input);
+        let mut lines = Vec::new();
+        while let Some(line) = codec.decode(&mut buf).unwrap() {
+            lines.push(line);
+        }
+        lines.extend(codec.decode_eof(&mut buf).unwrap());
+        lines
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let mut codec = codec(Charset::default());
+        let lines = decode_all(&mut codec, b"PING a\r\nPING b\n\r\nPING\r c\r\nPING d");
+        assert_eq!(lines, ["PING a", "PING b", "PING c", "PING d"]);
+    }
+
+    #[test]
+    fn test_over_long_lines_are_discarded() {
+        let mut codec = codec(Charset::default());
+        let mut buf = BytesMut::from(&[b'x'; 40][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"tail\r\nPING ok\r\n");
+        assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("PING ok"));
+    }
+
+    #[test]
+    fn test_fallback_decoding() {
+        let mut channels = HashMap::new();
+        channels.insert("#Euro".to_string(), "iso-8859-15".to_string());
+        let mut codec = codec(Charset::new(Some("latin1"), &channels));
+
+        let lines = decode_all(
+            &mut codec,
+            b"PRIVMSG #a :caf\xe9 \xa4\r\nPRIVMSG #euro :\xa4\r\nPRIVMSG #a :\xc3\xa9\r\n",
+        );
+        assert_eq!(
+            lines,
+            [
+                "PRIVMSG #a :caf\u{e9} \u{a4}",
+                "PRIVMSG #euro :\u{20ac}",
+                "PRIVMSG #a :\u{e9}"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut charset = Charset::new(Some("iso-8859-15"), &HashMap::new());
+        let mut codec = codec(charset.clone());
+        let message =
+            Message::new("PRIVMSG").with_params(vec!["#a".into(), "\u{20ac}5 each".into()]);
+
+        let mut buf = BytesMut::new();
+        codec.encode(message.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"PRIVMSG #a :\xa45 each\r\n");
+
+        // Text the charset can't represent is sent as UTF-8
+        assert_eq!(
+            charset.encode("PRIVMSG #a :\u{263a}"),
+            "PRIVMSG #a :\u{263a}".as_bytes()
+        );
+
+        charset.set_utf8_only(true);
+        assert_eq!(
+            charset.encode("PRIVMSG #a :\u{20ac}"),
+            "PRIVMSG #a :\u{20ac}".as_bytes()
+        );
+    }
+}
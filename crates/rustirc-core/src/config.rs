@@ -19,6 +19,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
 /// Main configuration structure for the IRC client
@@ -58,6 +59,10 @@ pub struct UserConfig {
 }
 
 /// IRC server connection configuration
+///
+/// Describes a network: `address`, `port`, `use_tls` and `password` give its
+/// first server and `fallback_endpoints` lists further servers tried in turn
+/// when a connection fails.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServerConfig {
@@ -66,10 +71,116 @@ pub struct ServerConfig {
     pub port: u16,
     pub use_tls: bool,
     pub password: Option<String>,
+    /// Other servers of the same network, in order
+    pub fallback_endpoints: Vec<ServerEndpoint>,
+    /// How the next endpoint is picked after a failed connection
+    pub rotation: EndpointRotation,
     pub auto_connect: bool,
     pub channels: Vec<ChannelConfig>,
     pub sasl: Option<SaslConfig>,
     pub proxy: Option<ProxyConfig>,
+    /// PEM client certificate for CertFP (SASL EXTERNAL)
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key for `client_cert`; read from the certificate file if unset
+    pub client_key: Option<PathBuf>,
+    /// PEM bundle of additional CA certificates trusted for this server
+    pub ca_bundle: Option<PathBuf>,
+    /// How the server certificate is verified
+    pub tls_verification: TlsVerification,
+    /// Charset for servers that don't use UTF-8, e.g. `latin1`, `windows-1252`
+    /// or `iso-8859-15`; lines that aren't valid UTF-8 are decoded with it
+    pub encoding: Option<String>,
+    /// Per-channel charset overrides, keyed by channel name
+    pub channel_encodings: HashMap<String, String>,
+    /// Carry IRC over a raw TCP stream or a WebSocket
+    pub transport: Transport,
+    /// Which IP versions to connect over and which to try first
+    pub address_family: AddressFamily,
+    /// Local source address (e.g. a vhost IP) for direct connections
+    pub bind_address: Option<IpAddr>,
+    /// Delay before racing the next resolved address, in milliseconds
+    pub connection_attempt_delay_ms: u64,
+}
+
+/// IP version preference for connecting to a server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    /// Try IPv6 first, falling back to IPv4 (RFC 8305)
+    #[default]
+    Any,
+    PreferIpv6,
+    PreferIpv4,
+    Ipv6Only,
+    Ipv4Only,
+}
+
+/// One server of a network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerEndpoint {
+    pub address: String,
+    pub port: u16,
+    pub use_tls: bool,
+    pub password: Option<String>,
+}
+
+impl Default for ServerEndpoint {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            port: 6697,
+            use_tls: true,
+            password: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ServerEndpoint {
+    /// `host:port`, with the conventional `+` before TLS ports
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tls = if self.use_tls { "+" } else { "" };
+        write!(f, "{}:{tls}{}", self.address, self.port)
+    }
+}
+
+/// Order in which a network's endpoints are tried
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointRotation {
+    /// The next endpoint in the list, wrapping around
+    #[default]
+    RoundRobin,
+    /// A random endpoint other than the one that failed
+    Random,
+}
+
+/// How IRC lines are carried to the server
+///
+/// WebSockets use `wss://` when `use_tls` is set and connect through the
+/// configured proxy like plain connections.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Tcp,
+    /// IRCv3 WebSocket at `path` on the server, e.g. `/webirc`
+    #[serde(rename = "websocket")]
+    WebSocket { path: String },
+}
+
+/// Server certificate verification policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsVerification {
+    /// Require a certificate chain to a trusted root
+    #[default]
+    Verify,
+    /// Pin each server's certificate in the known-hosts store on first use;
+    /// later certificates must match the pin or validate against the roots
+    TrustOnFirstUse,
+    /// Accept any certificate; the connection is flagged as unverified
+    AcceptInvalid,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +196,9 @@ pub struct SaslConfig {
     pub mechanism: SaslMechanism,
     pub username: String,
     pub password: String,
+    /// Abort registration instead of continuing unauthenticated if SASL fails
+    #[serde(default)]
+    pub required: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +206,19 @@ pub enum SaslMechanism {
     Plain,
     External,
     ScramSha256,
+    ScramSha512,
+}
+
+impl SaslMechanism {
+    /// IANA-registered mechanism name sent with AUTHENTICATE
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::External => "EXTERNAL",
+            SaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+            SaslMechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -169,6 +296,13 @@ pub struct ProxyConfig {
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Let the proxy resolve server hostnames instead of resolving them locally
+    #[serde(default = "default_remote_dns")]
+    pub remote_dns: bool,
+}
+
+fn default_remote_dns() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,10 +356,22 @@ impl Default for ServerConfig {
             port: 6697,
             use_tls: true,
             password: None,
+            fallback_endpoints: Vec::new(),
+            rotation: EndpointRotation::default(),
             auto_connect: false,
             channels: vec![],
             sasl: None,
             proxy: None,
+            client_cert: None,
+            client_key: None,
+            ca_bundle: None,
+            tls_verification: TlsVerification::default(),
+            encoding: None,
+            channel_encodings: HashMap::new(),
+            transport: Transport::default(),
+            address_family: AddressFamily::default(),
+            bind_address: None,
+            connection_attempt_delay_ms: 250,
         }
     }
 }
@@ -496,4 +642,55 @@ nickname = "TestNick"
         assert_eq!(config.ui.theme, "dark"); // default
         assert!(config.flood.enabled); // default
     }
+
+    #[test]
+    fn test_server_tls_verification() {
+        let config: Config = toml::from_str(
+            r#"
+[[servers]]
+name = "Internal"
+address = "irc.internal"
+ca_bundle = "/etc/rustirc/internal-ca.pem"
+tls_verification = "trust_on_first_use"
+"#,
+        )
+        .unwrap();
+        let server = &config.servers[0];
+        assert_eq!(server.tls_verification, TlsVerification::TrustOnFirstUse);
+        assert_eq!(
+            server.ca_bundle.as_deref(),
+            Some(Path::new("/etc/rustirc/internal-ca.pem"))
+        );
+
+        assert_eq!(
+            ServerConfig::default().tls_verification,
+            TlsVerification::Verify
+        );
+    }
+
+    #[test]
+    fn test_server_fallback_endpoints() {
+        let config: Config = toml::from_str(
+            r#"
+[[servers]]
+name = "Libera"
+address = "irc.libera.chat"
+rotation = "random"
+
+[[servers.fallback_endpoints]]
+address = "irc.eu.libera.chat"
+port = 6667
+use_tls = false
+password = "secret"
+"#,
+        )
+        .unwrap();
+        let server = &config.servers[0];
+        assert_eq!(server.rotation, EndpointRotation::Random);
+
+        assert_eq!(server.fallback_endpoints.len(), 1);
+        let fallback = &server.fallback_endpoints[0];
+        assert_eq!(fallback.to_string(), "irc.eu.libera.chat:6667");
+        assert_eq!(fallback.password.as_deref(), Some("secret"));
+    }
 }
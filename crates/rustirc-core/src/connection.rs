@@ -7,19 +7,49 @@
 //! - Connection state tracking
 //! - Heartbeat/keepalive management
 
+use crate::auth::{AuthError, AuthState, SaslSession};
+use crate::batch::BatchManager;
+use crate::caps::CapNegotiator;
+use crate::codec::{Charset, IrcCodec};
+use crate::config::{
+    AddressFamily, EndpointRotation, FloodConfig, ProxyConfig, SaslConfig, ServerEndpoint,
+    TlsVerification, Transport,
+};
 use crate::error::{Error, Result};
 use crate::events::{Event, EventBus};
-use rustirc_protocol::{Command, Message, Parser, MAX_MESSAGE_LENGTH};
-use rustls::{ClientConfig as TlsConfig, RootCertStore};
+use crate::flood::{is_priority, SendQueue};
+use crate::nick::{NickManager, NICK_REGAIN_INTERVAL};
+use crate::recovery::{EndpointList, RecoveryManager};
+use crate::request::{next_label, PendingRequests, ReplyMatcher};
+use crate::session::Session;
+use crate::state::StateManager;
+use crate::sts::{StsPolicies, StsPolicy};
+use crate::tls::{
+    CertificateStatus, ClientIdentity, KnownHosts, ServerCertificate, ServerCertificateVerifier,
+};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use rustirc_protocol::isupport::isupport_tokens;
+use rustirc_protocol::split::{
+    multiline_batch, split_command, text_budget, MultilineLimits, DEFAULT_HOSTLEN,
+};
+use rustirc_protocol::{
+    Capability, CapabilitySet, Command, Isupport, Message, MessageRef, Parser, PrefixRef, Tag,
+    MAX_MESSAGE_LENGTH,
+};
+use rustls::ClientConfig as TlsConfig;
 use rustls_pki_types::ServerName;
-use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufStream, BufWriter, Lines};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::net::TcpStream;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, Notify, RwLock};
 use tokio::time::{interval, sleep, timeout};
 use tokio_rustls::{client::TlsStream, TlsConnector};
+use tokio_util::codec::{FramedRead, FramedWrite};
 use tracing::{debug, error, info, warn};
 
 /// Connection state for IRC connections
@@ -93,8 +123,10 @@ pub struct ConnectionConfig {
     pub server: String,
     pub port: u16,
     pub use_tls: bool,
-    pub verify_tls: bool,
+    pub tls_verification: TlsVerification,
     pub nickname: String,
+    /// Nicknames tried in order when `nickname` is unavailable
+    pub alternative_nicknames: Vec<String>,
     pub username: String,
     pub realname: String,
     pub password: Option<String>,
@@ -102,16 +134,55 @@ pub struct ConnectionConfig {
     pub reconnect_delay: Duration,
     pub ping_timeout: Duration,
     pub message_timeout: Duration,
+    pub sasl: Option<SaslConfig>,
+    pub proxy: Option<ProxyConfig>,
+    /// PEM client certificate presented during the TLS handshake (CertFP)
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key for `client_cert`; read from the certificate file if unset
+    pub client_key: Option<PathBuf>,
+    /// PEM bundle of additional CA certificates trusted for this server
+    pub ca_bundle: Option<PathBuf>,
+    /// Known-hosts file used for trust-on-first-use; defaults to
+    /// [`KnownHosts::default_path`]
+    pub known_hosts: Option<PathBuf>,
+    /// STS policy store; defaults to [`StsPolicies::default_path`]
+    pub sts_policies: Option<PathBuf>,
+    /// Charset of a server that doesn't use UTF-8, e.g. `latin1` or
+    /// `iso-8859-15`; see [`Charset`]
+    pub encoding: Option<String>,
+    /// Per-channel charset overrides, keyed by channel name
+    pub channel_encodings: HashMap<String, String>,
+    /// Raw TCP or WebSocket; applies on top of TLS and the proxy
+    pub transport: Transport,
+    /// IP versions used for direct connections and their order
+    pub address_family: AddressFamily,
+    /// Local address to connect from, e.g. a vhost IP
+    pub bind_address: Option<IpAddr>,
+    /// Delay before racing the next resolved address (RFC 8305)
+    pub connection_attempt_delay: Duration,
+    /// Other servers of the network tried after `server` fails
+    pub fallback_endpoints: Vec<ServerEndpoint>,
+    /// How the next endpoint is picked after a failed connection
+    pub rotation: EndpointRotation,
+    /// Rate limit for outgoing messages; see [`SendQueue`]
+    pub flood: FloodConfig,
+    /// Capacity of the channel between senders and the writer task; senders
+    /// wait while it is full
+    pub command_queue_size: usize,
 }
 
+/// Default capacity of a connection's command channel
+pub const DEFAULT_COMMAND_QUEUE_SIZE: usize = 64;
+
 impl Default for ConnectionConfig {
     fn default() -> Self {
         Self {
             server: "irc.libera.chat".to_string(),
             port: 6697,
             use_tls: true,
-            verify_tls: true,
+            tls_verification: TlsVerification::Verify,
             nickname: "RustIRC".to_string(),
+            alternative_nicknames: Vec::new(),
             username: "rustirc".to_string(),
             realname: "RustIRC Client".to_string(),
             password: None,
@@ -119,10 +190,52 @@ impl Default for ConnectionConfig {
             reconnect_delay: Duration::from_secs(5),
             ping_timeout: Duration::from_secs(300), // 5 minutes
             message_timeout: Duration::from_secs(30),
+            sasl: None,
+            proxy: None,
+            client_cert: None,
+            client_key: None,
+            ca_bundle: None,
+            known_hosts: None,
+            sts_policies: None,
+            encoding: None,
+            channel_encodings: HashMap::new(),
+            transport: Transport::Tcp,
+            address_family: AddressFamily::Any,
+            bind_address: None,
+            connection_attempt_delay: crate::net::DEFAULT_ATTEMPT_DELAY,
+            fallback_endpoints: Vec::new(),
+            rotation: EndpointRotation::RoundRobin,
+            flood: FloodConfig::default(),
+            command_queue_size: DEFAULT_COMMAND_QUEUE_SIZE,
         }
     }
 }
 
+impl ConnectionConfig {
+    /// All endpoints of the network, starting with `server`
+    pub fn endpoints(&self) -> Vec<ServerEndpoint> {
+        let primary = ServerEndpoint {
+            address: self.server.clone(),
+            port: self.port,
+            use_tls: self.use_tls,
+            password: self.password.clone(),
+        };
+        std::iter::once(primary)
+            .chain(self.fallback_endpoints.iter().cloned())
+            .collect()
+    }
+}
+
+/// Counter for the references of outgoing `draft/multiline` batches
+static NEXT_BATCH_REFERENCE: AtomicU64 = AtomicU64::new(1);
+
+/// Our `user@host` as the server shows it to others
+#[derive(Debug, Clone, Default)]
+struct OwnHostmask {
+    user: Option<String>,
+    host: Option<String>,
+}
+
 /// IRC connection handle with async networking and event support
 ///
 /// Manages a single IRC server connection with automatic reconnection,
@@ -157,10 +270,41 @@ pub struct IrcConnection {
     config: ConnectionConfig,
     state: Arc<RwLock<ConnectionState>>,
     event_bus: Arc<EventBus>,
-    tx_commands: Arc<RwLock<Option<mpsc::UnboundedSender<Command>>>>,
+    tx_commands: Arc<RwLock<Option<mpsc::Sender<Message>>>>,
     last_ping: Arc<RwLock<Option<Instant>>>,
     connection_id: String,
     state_broadcast: broadcast::Sender<ConnectionState>,
+    caps: Arc<RwLock<CapNegotiator>>,
+    sasl: Arc<RwLock<Option<SaslSession>>>,
+    nicks: Arc<RwLock<NickManager>>,
+    isupport: Arc<RwLock<Isupport>>,
+    hostmask: Arc<RwLock<OwnHostmask>>,
+    /// Shared with the reader and writer codecs
+    charset: Arc<std::sync::RwLock<Charset>>,
+    requests: Arc<RwLock<PendingRequests>>,
+    endpoints: Arc<RwLock<EndpointList>>,
+    /// Endpoint of the current connection attempt; differs from the
+    /// configured one when STS upgrades it to TLS
+    endpoint: Arc<RwLock<ServerEndpoint>>,
+    /// TLS ports required by STS, keyed by lowercase hostname
+    sts_ports: Arc<RwLock<HashMap<String, u16>>>,
+    /// Signalled to drop the current connection, e.g. for an STS upgrade
+    close: Arc<Notify>,
+    /// Outgoing messages held back by flood protection
+    send_queue: Arc<std::sync::Mutex<SendQueue>>,
+    /// Signalled when a priority message is added to `send_queue` directly
+    wake_writer: Arc<Notify>,
+    /// Channels, modes and the like restored after reconnecting
+    session: Arc<RwLock<Session>>,
+    /// Decides how long to wait before reconnecting, and when to give up
+    recovery: Arc<RecoveryManager>,
+    /// Set once a QUIT is queued; the link it closes is not reconnected
+    quitting: Arc<AtomicBool>,
+    /// Reason of the server's `ERROR` on the current link, if it sent one
+    server_error: Arc<RwLock<Option<String>>>,
+    /// Whether the current link's certificate chain validated; STS policies
+    /// are only persisted from such links
+    tls_verified: Arc<AtomicBool>,
 }
 
 impl IrcConnection {
@@ -188,9 +332,42 @@ impl IrcConnection {
     /// assert_eq!(connection.id(), "irc.libera.chat:6697");
     /// ```
     pub fn new(config: ConnectionConfig, event_bus: Arc<EventBus>) -> Self {
+        let (recovery, _) = RecoveryManager::new(Arc::new(StateManager::new()), event_bus.clone());
+        Self::with_recovery(config, event_bus, Arc::new(recovery))
+    }
+
+    /// Create a connection whose reconnection is tracked by a shared
+    /// [`RecoveryManager`]
+    ///
+    /// The manager's backoff and attempt limit decide when a dropped
+    /// connection is reconnected, and its statistics cover this connection.
+    pub fn with_recovery(
+        config: ConnectionConfig,
+        event_bus: Arc<EventBus>,
+        recovery: Arc<RecoveryManager>,
+    ) -> Self {
         let connection_id = format!("{}:{}", config.server, config.port);
         let (state_broadcast, _) = broadcast::channel(100);
 
+        let mut caps = CapNegotiator::default();
+        // A client certificate implies SASL EXTERNAL unless SASL is configured explicitly
+        let sasl = match (&config.sasl, &config.client_cert) {
+            (Some(sasl_config), _) => Some(SaslSession::from_config(sasl_config)),
+            (None, Some(_)) => Some(SaslSession::external(false)),
+            (None, None) => None,
+        };
+        if sasl.is_some() {
+            caps.want(Capability::Sasl);
+        }
+        let nicks = NickManager::new(
+            config.nickname.clone(),
+            config.alternative_nicknames.clone(),
+        );
+        let charset = Charset::new(config.encoding.as_deref(), &config.channel_encodings);
+        let endpoints = EndpointList::new(config.endpoints(), config.rotation);
+        let endpoint = endpoints.current().clone();
+        let send_queue = SendQueue::new(&config.flood);
+
         Self {
             config,
             state: Arc::new(RwLock::new(ConnectionState::Disconnected)),
@@ -199,6 +376,24 @@ impl IrcConnection {
             last_ping: Arc::new(RwLock::new(None)),
             connection_id,
             state_broadcast,
+            caps: Arc::new(RwLock::new(caps)),
+            sasl: Arc::new(RwLock::new(sasl)),
+            nicks: Arc::new(RwLock::new(nicks)),
+            isupport: Arc::new(RwLock::new(Isupport::default())),
+            hostmask: Arc::new(RwLock::new(OwnHostmask::default())),
+            charset: Arc::new(std::sync::RwLock::new(charset)),
+            requests: Arc::new(RwLock::new(PendingRequests::new())),
+            endpoints: Arc::new(RwLock::new(endpoints)),
+            endpoint: Arc::new(RwLock::new(endpoint)),
+            sts_ports: Arc::new(RwLock::new(HashMap::new())),
+            close: Arc::new(Notify::new()),
+            send_queue: Arc::new(std::sync::Mutex::new(send_queue)),
+            wake_writer: Arc::new(Notify::new()),
+            session: Arc::new(RwLock::new(Session::new())),
+            recovery,
+            quitting: Arc::new(AtomicBool::new(false)),
+            server_error: Arc::new(RwLock::new(None)),
+            tls_verified: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -236,6 +431,84 @@ impl IrcConnection {
         self.state_broadcast.subscribe()
     }
 
+    /// Get the capabilities currently enabled on this connection
+    pub async fn capabilities(&self) -> CapabilitySet {
+        self.caps.read().await.enabled().clone()
+    }
+
+    /// Check whether a capability has been negotiated with the server
+    pub async fn has_capability(&self, cap: &Capability) -> bool {
+        self.caps.read().await.is_enabled(cap)
+    }
+
+    /// Get the nickname currently in use (or being attempted during registration)
+    pub async fn nickname(&self) -> String {
+        self.nicks.read().await.current().to_string()
+    }
+
+    /// Get the endpoint of the current or last connection attempt
+    pub async fn endpoint(&self) -> ServerEndpoint {
+        self.endpoint.read().await.clone()
+    }
+
+    /// Number of outgoing lines waiting for flood protection
+    pub fn queued_messages(&self) -> usize {
+        self.lock_send_queue().len()
+    }
+
+    /// Drop the outgoing lines waiting for flood protection
+    ///
+    /// Returns how many lines were cancelled. `PONG` and `QUIT` are never
+    /// held back, so they are not affected.
+    pub async fn cancel_queued_messages(&self) -> usize {
+        let cancelled = self.lock_send_queue().clear();
+        if cancelled > 0 {
+            debug!(
+                "Cancelled {} queued lines on {}",
+                cancelled, self.connection_id
+            );
+            self.event_bus
+                .emit(Event::SendQueueChanged {
+                    connection_id: self.connection_id.clone(),
+                    pending: 0,
+                })
+                .await;
+        }
+        cancelled
+    }
+
+    fn lock_send_queue(&self) -> std::sync::MutexGuard<'_, SendQueue> {
+        self.send_queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the features advertised by the server in RPL_ISUPPORT
+    pub async fn isupport(&self) -> Isupport {
+        self.isupport.read().await.clone()
+    }
+
+    /// Use a different charset for one channel, or the server's with `None`
+    ///
+    /// Affects lines that aren't valid UTF-8 from the channel and text sent to it.
+    pub fn set_channel_encoding(&self, channel: &str, encoding: Option<&str>) -> Result<()> {
+        let mut charset = self.charset.write().unwrap_or_else(|e| e.into_inner());
+        match encoding {
+            Some(label) => charset.set_channel_encoding(channel, label),
+            None => {
+                charset.clear_channel_encoding(channel);
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the SASL authentication state, if SASL is configured
+    pub async fn auth_state(&self) -> Option<AuthState> {
+        self.sasl
+            .read()
+            .await
+            .as_ref()
+            .map(|session| session.state().clone())
+    }
+
     /// Connect to the IRC server with automatic reconnection
     ///
     /// Attempts to establish a connection to the configured IRC server.
@@ -244,8 +517,12 @@ impl IrcConnection {
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` when successfully connected and registered, or an error
-    /// if all connection attempts fail.
+    /// Runs until the connection ends. A registered connection that drops is
+    /// reconnected with the [`RecoveryManager`]'s backoff, and its [`Session`]
+    /// is restored once it registers again. A link closed after a QUIT or by
+    /// a server `ERROR`, e.g. a ban, is not reconnected. Returns `Ok(())` when
+    /// the connection ends without being reconnected, or an error if all
+    /// connection attempts fail.
     ///
     /// # Examples
     ///
@@ -271,30 +548,93 @@ impl IrcConnection {
     /// # Errors
     ///
     /// Returns `Error::ConnectionFailed` if unable to connect after all retry attempts.
+    ///
+    /// Each failure moves on to the network's next endpoint; the retry delay
+    /// only applies once every endpoint has been tried.
     pub async fn connect(&self) -> Result<()> {
+        self.quitting.store(false, Ordering::SeqCst);
+        self.recovery
+            .track_connection(&self.connection_id, &self.config)
+            .await;
+
+        loop {
+            self.connect_with_retries().await?;
+
+            if self.quitting.load(Ordering::SeqCst) {
+                // Keep the reason when we quit because registration failed
+                if !matches!(*self.state.read().await, ConnectionState::Failed(_)) {
+                    self.set_state(ConnectionState::Disconnected).await;
+                }
+                return Ok(());
+            }
+            if let Some(reason) = self.server_error.write().await.take() {
+                let reason = format!("Closed by server: {reason}");
+                warn!("Not reconnecting to {}: {}", self.connection_id, reason);
+                self.set_state(ConnectionState::Failed(reason)).await;
+                return Ok(());
+            }
+            // Only a dropped link gets here still registered
+            if *self.state.read().await != ConnectionState::Registered {
+                return Ok(());
+            }
+
+            let Some(delay) = self.recovery.reconnect_delay(&self.connection_id).await else {
+                let error_msg = "Lost connection and gave up reconnecting".to_string();
+                self.set_state(ConnectionState::Failed(error_msg.clone()))
+                    .await;
+                return Err(Error::ConnectionFailed(error_msg));
+            };
+            warn!(
+                "Lost connection to {}, reconnecting in {:?}",
+                self.endpoint.read().await,
+                delay
+            );
+            self.set_state(ConnectionState::Reconnecting).await;
+            sleep(delay).await;
+        }
+    }
+
+    /// Connect, failing over between endpoints, and run the connection
+    /// until it ends
+    async fn connect_with_retries(&self) -> Result<()> {
         self.set_state(ConnectionState::Connecting).await;
 
+        let endpoint_count = self.endpoints.read().await.len() as u32;
         let mut attempt = 0;
         loop {
             match self.try_connect().await {
                 Ok(()) => {
-                    info!("Successfully connected to {}", self.config.server);
+                    info!("Successfully connected to {}", self.endpoint.read().await);
                     break;
                 }
                 Err(e) => {
                     attempt += 1;
                     error!("Connection attempt {} failed: {}", attempt, e);
-
-                    if attempt >= self.config.reconnect_attempts {
+                    self.event_bus
+                        .emit(Event::Error {
+                            connection_id: Some(self.connection_id.clone()),
+                            error: format!(
+                                "Connection to {} failed: {e}",
+                                self.endpoint.read().await
+                            ),
+                        })
+                        .await;
+
+                    let Some(delay) = self.recovery.reconnect_delay(&self.connection_id).await
+                    else {
                         let error_msg = format!("Failed to connect after {attempt} attempts");
                         self.set_state(ConnectionState::Failed(error_msg.clone()))
                             .await;
                         return Err(Error::ConnectionFailed(error_msg));
-                    }
+                    };
 
-                    let delay = self.config.reconnect_delay * attempt;
-                    warn!("Retrying connection in {} seconds", delay.as_secs());
-                    sleep(delay).await;
+                    let next = self.endpoints.write().await.advance().clone();
+                    if attempt % endpoint_count == 0 {
+                        warn!("Retrying connection via {} in {:?}", next, delay);
+                        sleep(delay).await;
+                    } else {
+                        warn!("Failing over to {}", next);
+                    }
                 }
             }
         }
@@ -302,19 +642,21 @@ impl IrcConnection {
         Ok(())
     }
 
-    /// Resolve server address to SocketAddr for proper network handling
-    async fn resolve_server_address(&self) -> Result<SocketAddr> {
-        let addr_string = format!("{}:{}", self.config.server, self.config.port);
+    /// Resolve the server to its addresses in connection order
+    async fn resolve_server_addresses(&self, endpoint: &ServerEndpoint) -> Result<Vec<SocketAddr>> {
+        let addr_string = format!("{}:{}", endpoint.address, endpoint.port);
 
         // Use tokio's DNS resolution for async operation
         match tokio::net::lookup_host(addr_string.clone()).await {
-            Ok(mut addrs) => {
-                if let Some(addr) = addrs.next() {
-                    Ok(addr)
-                } else {
+            Ok(addrs) => {
+                let addrs = crate::net::order_addresses(addrs, self.config.address_family);
+                if addrs.is_empty() {
                     Err(Error::ConnectionFailed(format!(
-                        "No addresses found for {addr_string}"
+                        "No {:?} addresses found for {addr_string}",
+                        self.config.address_family
                     )))
+                } else {
+                    Ok(addrs)
                 }
             }
             Err(e) => Err(Error::ConnectionFailed(format!(
@@ -323,27 +665,128 @@ impl IrcConnection {
         }
     }
 
-    /// Attempt single connection
-    async fn try_connect(&self) -> Result<()> {
+    /// Open the TCP stream to the server, directly or through the configured proxy
+    async fn open_stream(&self, endpoint: &ServerEndpoint) -> Result<TcpStream> {
+        if let Some(proxy_config) = &self.config.proxy {
+            debug!(
+                "Connecting to {}:{} via {:?} proxy {}:{}",
+                endpoint.address,
+                endpoint.port,
+                proxy_config.proxy_type,
+                proxy_config.address,
+                proxy_config.port
+            );
+
+            let proxy = crate::proxy::from_config(proxy_config);
+            return timeout(
+                self.config.message_timeout,
+                proxy.connect(&endpoint.address, endpoint.port),
+            )
+            .await
+            .map_err(|_| Error::ConnectionTimeout)?;
+        }
+
         // Resolve server address with proper SocketAddr usage
-        let socket_addr = self.resolve_server_address().await?;
-        debug!("Resolved server address: {}", socket_addr);
+        let addrs = self.resolve_server_addresses(endpoint).await?;
+        debug!("Resolved server addresses: {:?}", addrs);
 
-        let stream = timeout(self.config.message_timeout, TcpStream::connect(socket_addr))
+        let connect = crate::net::connect(
+            &addrs,
+            self.config.connection_attempt_delay,
+            self.config.bind_address,
+        );
+        timeout(self.config.message_timeout, connect)
             .await
             .map_err(|_| Error::ConnectionTimeout)?
-            .map_err(|e| Error::ConnectionFailed(e.to_string()))?;
+            .map_err(|e| Error::ConnectionFailed(e.to_string()))
+    }
+
+    /// Pick the current endpoint, applying any STS policy for its host
+    async fn resolve_endpoint(&self) -> ServerEndpoint {
+        let mut endpoint = self.endpoints.read().await.current().clone();
+        if endpoint.use_tls {
+            return endpoint;
+        }
+
+        let host = endpoint.address.to_ascii_lowercase();
+        let mut sts_ports = self.sts_ports.write().await;
+        if !sts_ports.contains_key(&host) {
+            let stored = self
+                .sts_policies()
+                .and_then(|policies| policies.get(&host, SystemTime::now()));
+            if let Some(entry) = stored {
+                sts_ports.insert(host.clone(), entry.port);
+            }
+        }
+        if let Some(&port) = sts_ports.get(&host) {
+            info!(
+                "STS policy for {} requires TLS, using port {}",
+                endpoint.address, port
+            );
+            endpoint.port = port;
+            endpoint.use_tls = true;
+        }
+        endpoint
+    }
+
+    /// Load the STS policy store, or `None` if it cannot be read
+    ///
+    /// A broken store only costs STS persistence; it never stops a connection.
+    fn sts_policies(&self) -> Option<StsPolicies> {
+        let path = self
+            .config
+            .sts_policies
+            .clone()
+            .unwrap_or_else(StsPolicies::default_path);
+        StsPolicies::open(path)
+            .map_err(|e| warn!("Failed to load STS policies: {}", e))
+            .ok()
+    }
+
+    /// Attempt single connection
+    ///
+    /// A plaintext connection that learns of an STS policy is closed and
+    /// immediately replaced by a TLS connection.
+    async fn try_connect(&self) -> Result<()> {
+        loop {
+            let endpoint = self.resolve_endpoint().await;
+            if *self.endpoint.read().await != endpoint {
+                info!("Connecting via {}", endpoint);
+            }
+            *self.endpoint.write().await = endpoint.clone();
+            self.event_bus
+                .emit(Event::EndpointChanged {
+                    connection_id: self.connection_id.clone(),
+                    endpoint: endpoint.to_string(),
+                })
+                .await;
+            self.connect_endpoint(&endpoint).await?;
+
+            let host = endpoint.address.to_ascii_lowercase();
+            if endpoint.use_tls || !self.sts_ports.read().await.contains_key(&host) {
+                return Ok(());
+            }
+            info!("Reconnecting to {} with TLS for STS", endpoint.address);
+        }
+    }
+
+    /// Connect to one endpoint and run the connection until it ends
+    async fn connect_endpoint(&self, endpoint: &ServerEndpoint) -> Result<()> {
+        let stream = self.open_stream(endpoint).await?;
 
         self.set_state(ConnectionState::Connected).await;
+        self.session.write().await.start_connection();
+        *self.server_error.write().await = None;
+        self.tls_verified.store(false, Ordering::SeqCst);
 
         // Create command channel
-        let (tx_commands, rx_commands) = mpsc::unbounded_channel();
+        let (tx_commands, rx_commands) = mpsc::channel(self.config.command_queue_size.max(1));
         *self.tx_commands.write().await = Some(tx_commands.clone());
 
         // Handle TLS vs plain connections
-        if self.config.use_tls {
-            let connector = self.create_tls_connector()?;
-            let server_string = self.config.server.clone();
+        if endpoint.use_tls {
+            let (connector, verifier) = self.create_tls_connector(endpoint)?;
+            let server_string = endpoint.address.clone();
             let server_string_for_error = server_string.clone();
 
             // Convert to owned ServerName with proper validation
@@ -355,10 +798,11 @@ impl IrcConnection {
             // Validate the server name is properly formatted for TLS
             debug!("Establishing TLS connection to: {:?}", server_name);
 
-            let tls_stream = connector
-                .connect(server_name, stream)
-                .await
-                .map_err(|e| Error::TlsError(e.to_string()))?;
+            let handshake = connector.connect(server_name, stream).await;
+            if let Some(certificate) = verifier.take_outcome() {
+                self.report_certificate(certificate, endpoint).await?;
+            }
+            let tls_stream = handshake.map_err(|e| Error::TlsError(e.to_string()))?;
 
             self.handle_connection_tls(tls_stream, rx_commands).await?;
         } else {
@@ -369,22 +813,107 @@ impl IrcConnection {
     }
 
     /// Create TLS connector
-    fn create_tls_connector(&self) -> Result<TlsConnector> {
-        let mut root_store = RootCertStore::empty();
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    ///
+    /// Returns the server certificate verifier alongside the connector so the
+    /// verification outcome can be reported after the handshake.
+    fn create_tls_connector(
+        &self,
+        endpoint: &ServerEndpoint,
+    ) -> Result<(TlsConnector, Arc<ServerCertificateVerifier>)> {
+        let roots = crate::tls::root_store(self.config.ca_bundle.as_deref())?;
+        let known_hosts = match self.config.tls_verification {
+            TlsVerification::TrustOnFirstUse => KnownHosts::open(
+                self.config
+                    .known_hosts
+                    .clone()
+                    .unwrap_or_else(KnownHosts::default_path),
+            )?,
+            _ => KnownHosts::default(),
+        };
+        let verifier = Arc::new(ServerCertificateVerifier::new(
+            self.config.tls_verification,
+            roots,
+            &endpoint.address,
+            endpoint.port,
+            known_hosts,
+        )?);
+
+        let builder = TlsConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone());
+
+        let config = match self.client_identity()? {
+            Some(identity) => {
+                info!(
+                    "Presenting client certificate with SHA-256 fingerprint {}",
+                    identity.fingerprint()
+                );
+                builder.with_client_auth_cert(identity.certificates, identity.key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok((TlsConnector::from(Arc::new(config)), verifier))
+    }
+
+    /// Emit the server certificate outcome, refusing a changed fingerprint
+    async fn report_certificate(
+        &self,
+        certificate: ServerCertificate,
+        endpoint: &ServerEndpoint,
+    ) -> Result<()> {
+        let host = format!("{}:{}", endpoint.address, endpoint.port);
+        match &certificate.status {
+            CertificateStatus::Changed { expected } => error!(
+                "Certificate for {} changed: expected {}, got {}",
+                host, expected, certificate.fingerprint
+            ),
+            CertificateStatus::Unverified => warn!(
+                "Certificate verification disabled for {} ({})",
+                host, certificate.fingerprint
+            ),
+            status => debug!(
+                "Certificate for {} accepted ({:?}): {}",
+                host, status, certificate.fingerprint
+            ),
+        }
+        self.tls_verified.store(
+            certificate.status == CertificateStatus::Verified,
+            Ordering::SeqCst,
+        );
 
-        let config = TlsConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+        self.event_bus
+            .emit(Event::ServerCertificate {
+                connection_id: self.connection_id.clone(),
+                host: host.clone(),
+                certificate: certificate.clone(),
+            })
+            .await;
+
+        match certificate.status {
+            CertificateStatus::Changed { expected } => Err(Error::CertificateChanged {
+                host,
+                expected,
+                actual: certificate.fingerprint,
+            }),
+            _ => Ok(()),
+        }
+    }
 
-        Ok(TlsConnector::from(Arc::new(config)))
+    /// Load the configured TLS client certificate, if any
+    pub fn client_identity(&self) -> Result<Option<ClientIdentity>> {
+        self.config
+            .client_cert
+            .as_deref()
+            .map(|cert| ClientIdentity::load(cert, self.config.client_key.as_deref()))
+            .transpose()
     }
 
     /// Handle TLS connection with enhanced features
     async fn handle_connection_tls(
         &self,
         tls_stream: TlsStream<TcpStream>,
-        rx_commands: mpsc::UnboundedReceiver<Command>,
+        rx_commands: mpsc::Receiver<Message>,
     ) -> Result<()> {
         // Get TLS connection info for debugging
         let (_, session) = tls_stream.get_ref();
@@ -393,41 +922,63 @@ impl IrcConnection {
             session.protocol_version()
         );
 
-        // Create buffered stream for better performance
-        let buffered_stream = BufStream::new(tls_stream);
-        let (reader, writer) = tokio::io::split(buffered_stream);
-        let reader = BufReader::new(reader);
-        let writer = BufWriter::new(writer);
-
-        self.run_connection_tasks(reader, writer, rx_commands).await
+        self.run_transport(tls_stream, rx_commands).await
     }
 
     /// Handle plain connection
     async fn handle_connection_plain(
         &self,
         stream: TcpStream,
-        rx_commands: mpsc::UnboundedReceiver<Command>,
+        rx_commands: mpsc::Receiver<Message>,
     ) -> Result<()> {
-        let (reader, writer) = stream.into_split();
-        let reader = BufReader::new(reader);
-        let writer = BufWriter::new(writer);
+        self.run_transport(stream, rx_commands).await
+    }
 
-        self.run_connection_tasks(reader, writer, rx_commands).await
+    /// Frame the stream as IRC lines, directly or inside a WebSocket
+    async fn run_transport<S>(&self, stream: S, rx_commands: mpsc::Receiver<Message>) -> Result<()>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        match &self.config.transport {
+            Transport::Tcp => {
+                let (reader, writer) = tokio::io::split(stream);
+                let reader = FramedRead::new(reader, IrcCodec::new(self.charset.clone()));
+                let writer = FramedWrite::new(writer, IrcCodec::new(self.charset.clone()));
+                self.run_connection_tasks(reader, writer, rx_commands).await
+            }
+            Transport::WebSocket { path } => {
+                let endpoint = self.endpoint.read().await.clone();
+                let url = crate::websocket::websocket_url(
+                    &endpoint.address,
+                    endpoint.port,
+                    endpoint.use_tls,
+                    path,
+                );
+                let (socket, protocol) = timeout(
+                    self.config.message_timeout,
+                    crate::websocket::handshake(stream, &url),
+                )
+                .await
+                .map_err(|_| Error::ConnectionTimeout)??;
+                let (lines, sink) = crate::websocket::split(socket, protocol, self.charset.clone());
+                self.run_connection_tasks(lines, sink, rx_commands).await
+            }
+        }
     }
 
     /// Run connection tasks (reader, writer, ping)
-    async fn run_connection_tasks<R, W>(
+    async fn run_connection_tasks<L, W>(
         &self,
-        reader: BufReader<R>,
-        writer: BufWriter<W>,
-        rx_commands: mpsc::UnboundedReceiver<Command>,
+        lines: L,
+        writer: W,
+        rx_commands: mpsc::Receiver<Message>,
     ) -> Result<()>
     where
-        R: tokio::io::AsyncRead + Unpin + Send + 'static,
-        W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+        L: Stream<Item = std::io::Result<String>> + Unpin + Send + 'static,
+        W: Sink<Message, Error = std::io::Error> + Unpin + Send + 'static,
     {
         // Start reader task
-        let reader_task = self.start_reader_task_generic(reader);
+        let reader_task = self.start_reader_task_generic(lines);
 
         // Start writer task
         let writer_task = self.start_writer_task_generic(writer, rx_commands);
@@ -435,6 +986,9 @@ impl IrcConnection {
         // Start ping task
         let ping_task = self.start_ping_task();
 
+        // Start task that periodically tries to regain the primary nickname
+        let regain_task = self.start_nick_regain_task();
+
         // Perform IRC registration now that connection tasks are running
         tokio::spawn({
             let connection_self = self.clone();
@@ -448,10 +1002,13 @@ impl IrcConnection {
         });
 
         // Wait for tasks to complete (they run until disconnection)
+        let mut tasks = [reader_task, writer_task, ping_task, regain_task];
         tokio::select! {
-            _ = reader_task => {},
-            _ = writer_task => {},
-            _ = ping_task => {},
+            _ = futures_util::future::select_all(tasks.iter_mut()) => {},
+            _ = self.close.notified() => debug!("Closing connection to {}", self.endpoint.read().await),
+        }
+        for task in &tasks {
+            task.abort();
         }
 
         Ok(())
@@ -461,19 +1018,27 @@ impl IrcConnection {
     async fn register(&self) -> Result<()> {
         self.set_state(ConnectionState::Authenticating).await;
 
-        // Send PASS if password provided
-        if let Some(password) = &self.config.password {
-            self.send_command_internal(Command::Pass {
-                password: password.clone(),
-            })
-            .await?;
+        *self.isupport.write().await = Isupport::default();
+        self.charset
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_utf8_only(false);
+        *self.hostmask.write().await = OwnHostmask::default();
+
+        // Start capability negotiation; the server holds registration until CAP END
+        let cap_ls = self.caps.write().await.start();
+        self.send_command_internal(cap_ls).await?;
+
+        // Send PASS if the endpoint has a password
+        let password = self.endpoint.read().await.password.clone();
+        if let Some(password) = password {
+            self.send_command_internal(Command::Pass { password })
+                .await?;
         }
 
-        // Send NICK
-        self.send_command_internal(Command::Nick {
-            nickname: self.config.nickname.clone(),
-        })
-        .await?;
+        // Send NICK; collisions are resolved as 433/432/437 replies arrive
+        let nick = self.nicks.write().await.start();
+        self.send_command_internal(nick).await?;
 
         // Send USER
         self.send_command_internal(Command::User {
@@ -500,9 +1065,11 @@ impl IrcConnection {
                 let current_state = state_arc.read().await.clone();
                 if current_state == ConnectionState::Registered {
                     // Registration completed successfully
-                    drop(event_bus.publish(Event::Connected {
-                        connection_id: connection_id.clone(),
-                    }));
+                    event_bus
+                        .publish(Event::Connected {
+                            connection_id: connection_id.clone(),
+                        })
+                        .await;
                     return;
                 }
 
@@ -521,56 +1088,473 @@ impl IrcConnection {
             *state_guard = ConnectionState::Failed("Registration timeout".to_string());
         });
 
-        // Set initial registered state (will be updated by message handler)
-        self.set_state(ConnectionState::Registered).await;
+        Ok(())
+    }
+
+    /// Handle protocol-level messages that drive connection state
+    ///
+    /// Processes capability negotiation replies and registration numerics
+    /// before the message is published to the event bus. Works on the
+    /// borrowed line; the few handlers that keep an owned message copy it.
+    async fn handle_protocol_message(&self, message: &MessageRef<'_>) -> Result<()> {
+        if let Some(PrefixRef::User {
+            nick,
+            user,
+            host: Some(host),
+        }) = message.prefix
+        {
+            if self.nicks.read().await.is_current(nick) {
+                *self.hostmask.write().await = OwnHostmask {
+                    user: user.map(str::to_string),
+                    host: Some(host.to_string()),
+                };
+            }
+        }
+
+        let nickname = self.nicks.read().await.current().to_string();
+        self.session
+            .write()
+            .await
+            .handle_incoming(message, &nickname);
+
+        match message.command {
+            "CAP" => {
+                let response = self
+                    .caps
+                    .write()
+                    .await
+                    .handle_message(&message.to_message());
+                // Nothing more is sent over a link that is being upgraded
+                if matches!(message.params.get(1).copied(), Some("LS" | "NEW"))
+                    && self.handle_sts().await
+                {
+                    return Ok(());
+                }
+
+                for command in response.commands {
+                    self.queue_reply(command);
+                }
+
+                if response.enabled_changed {
+                    self.emit_capabilities().await;
+                }
+
+                if response.ready {
+                    self.start_sasl_or_finish().await?;
+                }
+            }
+            // The server is closing the link, e.g. for a ban
+            "ERROR" => {
+                let reason = message.params.first().copied().unwrap_or_default();
+                *self.server_error.write().await = Some(reason.to_string());
+            }
+            "AUTHENTICATE" => {
+                let payload = message.params.first().copied().unwrap_or("+");
+                let commands = match self.sasl.write().await.as_mut() {
+                    Some(session) => session.handle_authenticate(payload).await,
+                    None => Vec::new(),
+                };
+
+                for command in commands {
+                    self.queue_reply(command);
+                }
+            }
+            "900" | "901" | "902" | "903" | "904" | "905" | "906" | "907" | "908" => {
+                self.handle_sasl_numeric(&message.to_message()).await?;
+            }
+            // 421 ERR_UNKNOWNCOMMAND for CAP: server predates capability negotiation
+            "421" if message.params.get(1).copied() == Some("CAP") => {
+                self.caps.write().await.abort();
+            }
+            // Nickname rejected: try the next candidate during registration
+            "432" | "433" | "437" => {
+                let next = self
+                    .nicks
+                    .write()
+                    .await
+                    .handle_rejection(&message.to_message());
+                if let Some(command) = next {
+                    self.queue_reply(command);
+                }
+            }
+            // 001 RPL_WELCOME: registration complete
+            "001" => {
+                self.caps.write().await.abort();
+
+                let nickname = {
+                    let mut nicks = self.nicks.write().await;
+                    if let Some(nick) = message.params.first() {
+                        nicks.handle_registered(nick);
+                    }
+                    nicks.current().to_string()
+                };
+
+                self.set_state(ConnectionState::Registered).await;
+                self.event_bus
+                    .emit(Event::Registered {
+                        connection_id: self.connection_id.clone(),
+                        nickname,
+                    })
+                    .await;
+            }
+            // 005 RPL_ISUPPORT: track UTF8ONLY, and watch the primary nickname if MONITOR is available
+            "005" => {
+                let (monitor, casemapping, utf8only, nicklen) = {
+                    let mut isupport = self.isupport.write().await;
+                    isupport.apply_tokens(isupport_tokens(&message.params).iter().copied());
+                    (
+                        isupport.monitor,
+                        isupport.casemapping,
+                        isupport.utf8only,
+                        isupport.nicklen,
+                    )
+                };
+                self.charset
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .set_utf8_only(utf8only);
+                {
+                    let mut nicks = self.nicks.write().await;
+                    nicks.set_casemapping(casemapping);
+                    nicks.set_nicklen(nicklen);
+                }
+                self.session.write().await.set_casemapping(casemapping);
+                self.requests.write().await.set_casemapping(casemapping);
+                if monitor {
+                    let command = self.nicks.write().await.enable_monitor();
+                    if let Some(command) = command {
+                        self.queue_reply(command);
+                    }
+                }
+            }
+            // 376 RPL_ENDOFMOTD / 422 ERR_NOMOTD: registration is complete
+            "376" | "422" => {
+                // Sent from a separate task so that a full command queue
+                // doesn't hold up the reader
+                let connection = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = connection.restore_session().await {
+                        warn!("Failed to restore session: {}", e);
+                    }
+                });
+            }
+            // 731 RPL_MONOFFLINE: a monitored nickname became available
+            "731" => {
+                let targets = message.params.get(1).copied().unwrap_or("");
+                let command = self.nicks.write().await.handle_monitor_offline(targets);
+                if let Some(command) = command {
+                    self.queue_reply(command);
+                }
+            }
+            // 396 RPL_VISIBLEHOST: our host is now shown as a cloak
+            "396" => {
+                if let Some(host) = message.params.get(1) {
+                    self.hostmask.write().await.host = Some(host.to_string());
+                }
+            }
+            "CHGHOST" => {
+                let (Some(nick), [user, host, ..]) = (message.nick(), message.params.as_slice())
+                else {
+                    return Ok(());
+                };
+                if self.nicks.read().await.is_current(nick) {
+                    *self.hostmask.write().await = OwnHostmask {
+                        user: Some(user.to_string()),
+                        host: Some(host.to_string()),
+                    };
+                }
+            }
+            "NICK" => {
+                let (Some(nick), Some(new)) = (message.nick(), message.params.first()) else {
+                    return Ok(());
+                };
+
+                let commands = {
+                    let mut nicks = self.nicks.write().await;
+                    if !nicks.is_current(nick) {
+                        return Ok(());
+                    }
+                    nicks.handle_nick_change(new)
+                };
+                for command in commands {
+                    self.queue_reply(command);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
 
+    /// Rejoin channels and restore the rest of the session after reconnecting
+    ///
+    /// History gaps are announced before the commands are sent, so that
+    /// fetched history lands after the marker.
+    async fn restore_session(&self) -> Result<()> {
+        let nickname = self.nicks.read().await.current().to_string();
+        let isupport = self.isupport.read().await.clone();
+        let chathistory = self.caps.read().await.is_enabled(&Capability::ChatHistory)
+            || isupport.tokens.contains_key("CHATHISTORY");
+        let Some(restoration) =
+            self.session
+                .write()
+                .await
+                .restore(&nickname, &isupport, chathistory)
+        else {
+            return Ok(());
+        };
+
+        if !restoration.commands.is_empty() {
+            info!("Restoring session on {}", self.connection_id);
+        }
+        for gap in restoration.gaps {
+            self.event_bus
+                .emit(Event::HistoryGap {
+                    connection_id: self.connection_id.clone(),
+                    target: gap.target,
+                    since: gap.since,
+                })
+                .await;
+        }
+        for command in restoration.commands {
+            self.send_command_internal(command).await?;
+        }
         Ok(())
     }
 
-    /// Start message reader task with Lines iterator for efficient reading
-    fn start_reader_task_generic<R>(&self, reader: BufReader<R>) -> tokio::task::JoinHandle<()>
+    /// Start SASL authentication if configured, otherwise end negotiation
+    ///
+    /// CAP END is deferred until the SASL exchange completes.
+    async fn start_sasl_or_finish(&self) -> Result<()> {
+        let failure = {
+            let mut sasl = self.sasl.write().await;
+            let Some(session) = sasl.as_mut() else {
+                drop(sasl);
+                return self.finish_cap_negotiation().await;
+            };
+
+            let caps = self.caps.read().await;
+            let offered = caps.is_enabled(&Capability::Sasl);
+            // CAP 302 servers may advertise their mechanisms as the cap value
+            let mechanism_supported = caps
+                .available()
+                .value(&Capability::Sasl)
+                .is_none_or(|mechs| mechs.split(',').any(|m| m == session.mechanism()));
+            drop(caps);
+
+            if !offered {
+                session.fail(AuthError::NotOffered);
+            } else if !mechanism_supported {
+                let mechanism = session.mechanism().to_string();
+                session.fail(AuthError::MechanismNotOffered(mechanism));
+            } else {
+                match session.start().await {
+                    Ok(command) => {
+                        drop(sasl);
+                        self.queue_reply(command);
+                        return Ok(());
+                    }
+                    // The authenticator records the typed failure itself
+                    Err(e) => debug!("Unable to start SASL: {}", e),
+                }
+            }
+
+            Self::sasl_failure(session)
+        };
+
+        self.complete_sasl(failure).await
+    }
+
+    /// Handle SASL numerics (900-908)
+    async fn handle_sasl_numeric(&self, message: &Message) -> Result<()> {
+        let failure = {
+            let mut sasl = self.sasl.write().await;
+            let Some(session) = sasl.as_mut() else {
+                return Ok(());
+            };
+
+            match session.handle_numeric(message) {
+                Some(_) => Self::sasl_failure(session),
+                None => return Ok(()),
+            }
+        };
+
+        self.complete_sasl(failure).await
+    }
+
+    /// Registration-blocking failure reason for a finished SASL session
+    fn sasl_failure(session: &SaslSession) -> Option<String> {
+        match session.state() {
+            AuthState::Failed(reason) if session.is_required() => Some(reason.to_string()),
+            AuthState::Failed(reason) => {
+                warn!("SASL authentication failed, continuing registration: {reason}");
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Finish negotiation after SASL, or abort registration if it was required
+    async fn complete_sasl(&self, failure: Option<String>) -> Result<()> {
+        match failure {
+            None => self.finish_cap_negotiation().await,
+            Some(reason) => {
+                let reason = format!("SASL authentication required: {reason}");
+                error!(
+                    "Aborting registration on {}: {}",
+                    self.connection_id, reason
+                );
+
+                self.event_bus
+                    .emit(Event::Error {
+                        connection_id: Some(self.connection_id.clone()),
+                        error: reason.clone(),
+                    })
+                    .await;
+                self.set_state(ConnectionState::Failed(reason.clone()))
+                    .await;
+                self.queue_reply(Command::Quit {
+                    message: Some("SASL authentication failed".to_string()),
+                });
+
+                Err(Error::AuthenticationFailed(reason))
+            }
+        }
+    }
+
+    /// Apply an advertised STS policy
+    ///
+    /// Over plaintext the connection is closed and retried with TLS on the
+    /// policy's port; over a verified TLS link the policy is persisted with
+    /// its duration. Returns whether the connection is closing for the upgrade.
+    async fn handle_sts(&self) -> bool {
+        let policy = match self.caps.read().await.available().value(&Capability::Sts) {
+            Some(value) => StsPolicy::parse(value),
+            None => return false,
+        };
+        let endpoint = self.endpoint.read().await.clone();
+
+        if !endpoint.use_tls {
+            if let Some(port) = policy.port {
+                info!(
+                    "{} advertises STS, upgrading to TLS on port {}",
+                    endpoint.address, port
+                );
+                self.sts_ports
+                    .write()
+                    .await
+                    .insert(endpoint.address.to_ascii_lowercase(), port);
+                self.close.notify_one();
+                return true;
+            }
+        } else if let Some(duration) = policy.duration {
+            if !self.tls_verified.load(Ordering::SeqCst) {
+                warn!(
+                    "Not storing STS policy for {}: certificate was not verified",
+                    endpoint.address
+                );
+                return false;
+            }
+            let Some(mut policies) = self.sts_policies() else {
+                return false;
+            };
+            policies.update(
+                &endpoint.address,
+                endpoint.port,
+                duration,
+                SystemTime::now(),
+            );
+            match policies.save() {
+                Ok(()) => debug!(
+                    "Stored STS policy for {} for {}s",
+                    endpoint.address,
+                    duration.as_secs()
+                ),
+                Err(e) => warn!("Failed to store STS policy for {}: {}", endpoint.address, e),
+            }
+        }
+        false
+    }
+
+    /// Send CAP END to release the server's registration hold
+    async fn finish_cap_negotiation(&self) -> Result<()> {
+        let end = self.caps.write().await.end();
+        if let Some(command) = end {
+            debug!("Capability negotiation complete for {}", self.connection_id);
+            self.queue_reply(command);
+        }
+        Ok(())
+    }
+
+    /// Publish the currently enabled capabilities
+    async fn emit_capabilities(&self) {
+        let capabilities = self
+            .caps
+            .read()
+            .await
+            .enabled()
+            .iter()
+            .map(|cap| cap.as_str().to_string())
+            .collect();
+
+        self.event_bus
+            .emit(Event::CapabilitiesChanged {
+                connection_id: self.connection_id.clone(),
+                capabilities,
+            })
+            .await;
+    }
+
+    /// Start message reader task over the framed line stream
+    fn start_reader_task_generic<L>(&self, mut lines: L) -> tokio::task::JoinHandle<()>
     where
-        R: tokio::io::AsyncRead + Unpin + Send + 'static,
+        L: Stream<Item = std::io::Result<String>> + Unpin + Send + 'static,
     {
         let event_bus = self.event_bus.clone();
         let connection_id = self.connection_id.clone();
         let last_ping = self.last_ping.clone();
+        let connection = self.clone();
 
         tokio::spawn(async move {
-            // Use Lines iterator for more efficient line reading
-            let mut lines: Lines<BufReader<R>> = reader.lines();
+            let parser = Parser::new();
+            // Lines of draft/multiline batches are delivered as one message
+            let mut batches = BatchManager::new();
 
             loop {
-                match lines.next_line().await {
-                    Ok(Some(message_text)) => {
-                        // Message_text is already trimmed by lines.next_line()
-                        // No need to trim again
+                match lines.next().await {
+                    Some(Ok(message_text)) => {
+                        // The transport strips the line terminator
 
                         debug!("Received: {}", message_text);
 
-                        // Parse IRC message
-                        match Parser::parse_message(&message_text) {
+                        // Parse borrowed and copy once, when the message is emitted
+                        match parser.parse_ref(&message_text) {
                             Ok(message) => {
                                 // Handle PING specially
                                 if message.command == "PING" {
-                                    if let Some(server) = message.params.first() {
+                                    if let Some(&server) = message.params.first() {
                                         // Send PONG response via command channel
                                         debug!("Responding to PING from {}", server);
 
                                         let pong_command = Command::Pong {
-                                            server1: server.clone(),
+                                            server1: server.to_string(),
                                             server2: None,
                                         };
 
-                                        // Actually use the pong_command by converting it to a message
-                                        // and creating an appropriate response event
+                                        // PONG skips the flood protection queue
                                         let pong_message = pong_command.to_message();
                                         debug!("Sending PONG response: {}", pong_message);
+                                        if let Err(e) = connection
+                                            .send_message_internal(pong_message.clone())
+                                            .await
+                                        {
+                                            warn!("Failed to send PONG: {}", e);
+                                        }
 
                                         // Emit both a PongRequired event and a MessageSent event
                                         let pong_event = Event::PongRequired {
                                             connection_id: connection_id.clone(),
-                                            server: server.clone(),
+                                            server: server.to_string(),
                                         };
                                         event_bus.emit(pong_event).await;
 
@@ -587,6 +1571,20 @@ impl IrcConnection {
                                     *last_ping.write().await = Some(Instant::now());
                                 }
 
+                                if let Err(e) = connection.handle_protocol_message(&message).await {
+                                    warn!("Failed to handle {}: {}", message.command, e);
+                                }
+                                connection.requests.write().await.handle(&message);
+
+                                if message.command == "BATCH" {
+                                    batches
+                                        .set_multiline_limits(connection.multiline_limits().await);
+                                }
+                                let Some(message) = batches.collect_multiline(message.to_message())
+                                else {
+                                    continue;
+                                };
+
                                 // Emit message event
                                 let event = Event::MessageReceived {
                                     connection_id: connection_id.clone(),
@@ -599,17 +1597,20 @@ impl IrcConnection {
                             }
                         }
                     }
-                    Ok(None) => {
+                    None => {
                         // Connection closed
                         break;
                     }
-                    Err(e) => {
+                    Some(Err(e)) => {
                         error!("Read error: {}", e);
                         break;
                     }
                 }
             }
 
+            // Requests still waiting will never be answered
+            connection.requests.write().await.clear();
+
             // Emit disconnection event
             let event = Event::Disconnected {
                 connection_id,
@@ -620,29 +1621,62 @@ impl IrcConnection {
     }
 
     /// Start message writer task (generic version)
+    ///
+    /// Messages go through the flood protection queue; the task sleeps
+    /// until the next queued message can be sent, and stops taking commands
+    /// while the queue is full so that senders wait.
     fn start_writer_task_generic<W>(
         &self,
-        mut writer: BufWriter<W>,
-        mut rx_commands: mpsc::UnboundedReceiver<Command>,
+        mut writer: W,
+        mut rx_commands: mpsc::Receiver<Message>,
     ) -> tokio::task::JoinHandle<()>
     where
-        W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+        W: Sink<Message, Error = std::io::Error> + Unpin + Send + 'static,
     {
+        let connection = self.clone();
+
         tokio::spawn(async move {
-            while let Some(command) = rx_commands.recv().await {
-                let message = command.to_message();
-                let message_text = format!("{message}\r\n");
+            // Lines still queued are left over from the last connection
+            connection.cancel_queued_messages().await;
+            let mut pending = 0;
 
-                debug!("Sending: {}", message_text.trim());
+            loop {
+                let (wake, full) = {
+                    let mut queue = connection.lock_send_queue();
+                    (queue.next_send_time(), queue.is_full())
+                };
+                tokio::select! {
+                    // Senders wait on the channel while the queue is full
+                    message = rx_commands.recv(), if !full => {
+                        let Some(message) = message else { break };
+                        connection.lock_send_queue().push(message);
+                    }
+                    _ = tokio::time::sleep_until(wake.unwrap_or_else(Instant::now).into()),
+                        if wake.is_some() => {}
+                    _ = connection.wake_writer.notified() => {}
+                }
 
-                if let Err(e) = writer.write_all(message_text.as_bytes()).await {
-                    error!("Write error: {}", e);
-                    break;
+                let ready = connection.lock_send_queue().drain_ready();
+                for message in ready {
+                    debug!("Sending: {}", message);
+
+                    // Encodes and flushes the line
+                    if let Err(e) = writer.send(message).await {
+                        error!("Write error: {}", e);
+                        return;
+                    }
                 }
 
-                if let Err(e) = writer.flush().await {
-                    error!("Flush error: {}", e);
-                    break;
+                let queued = connection.queued_messages();
+                if queued != pending {
+                    pending = queued;
+                    connection
+                        .event_bus
+                        .emit(Event::SendQueueChanged {
+                            connection_id: connection.connection_id.clone(),
+                            pending,
+                        })
+                        .await;
                 }
             }
         })
@@ -677,9 +1711,9 @@ impl IrcConnection {
                     };
 
                     // Get the sender from the Arc<RwLock<Option<_>>>
-                    let tx_opt = tx_commands.read().await;
-                    if let Some(ref tx) = *tx_opt {
-                        if tx.send(ping_cmd).is_err() {
+                    let tx_opt = tx_commands.read().await.clone();
+                    if let Some(tx) = tx_opt {
+                        if tx.send(ping_cmd.to_message()).await.is_err() {
                             // Channel closed, exit task
                             break;
                         }
@@ -692,11 +1726,45 @@ impl IrcConnection {
         })
     }
 
+    /// Start task that periodically tries to regain the primary nickname
+    ///
+    /// Only sends `NICK` while registered under a fallback nickname on a server
+    /// without `MONITOR`; otherwise each tick is a no-op.
+    fn start_nick_regain_task(&self) -> tokio::task::JoinHandle<()> {
+        let tx_commands = self.tx_commands.clone();
+        let nicks = self.nicks.clone();
+
+        tokio::spawn(async move {
+            let mut regain_interval = interval(NICK_REGAIN_INTERVAL);
+            // The first tick completes immediately; skip it
+            regain_interval.tick().await;
+
+            loop {
+                regain_interval.tick().await;
+
+                let Some(command) = nicks.write().await.regain() else {
+                    continue;
+                };
+
+                let tx_opt = tx_commands.read().await.clone();
+                match tx_opt {
+                    Some(tx) if tx.send(command.to_message()).await.is_ok() => {}
+                    // Channel closed or no sender available, exit task
+                    _ => break,
+                }
+            }
+        })
+    }
+
     /// Send an IRC command through the connection
     ///
     /// Validates the command and sends it to the IRC server. The command is
     /// converted to a properly formatted IRC message before transmission.
     ///
+    /// A `PRIVMSG` or `NOTICE` too long to fit in one line once the server adds
+    /// our `nick!user@host` is split into several messages, or sent as a
+    /// single `draft/multiline` batch when that capability is enabled.
+    ///
     /// # Arguments
     ///
     /// * `command` - IRC command to send (JOIN, PRIVMSG, etc.)
@@ -736,82 +1804,275 @@ impl IrcConnection {
     /// Returns an error if:
     /// - Connection is not established (`Error::ConnectionClosed`)
     /// - Command generates a message that's too long (`Error::Protocol`)
+    ///
+    /// Waits while the connection's command queue is full, so a fast
+    /// producer is slowed down to the rate the server accepts.
     pub async fn send_command(&self, command: Command) -> Result<()> {
-        // Validate message length before sending
-        let message = command.to_message();
-        let message_text = format!("{message}\r\n");
+        self.session.write().await.handle_outgoing(&command);
+        let messages = self.split_outgoing(command).await;
+        for message in &messages {
+            Self::check_length(message)?;
+        }
 
-        if message_text.len() > MAX_MESSAGE_LENGTH {
-            return Err(Error::Protocol(format!(
-                "Message too long: {} bytes (max: {})",
-                message_text.len(),
-                MAX_MESSAGE_LENGTH
-            )));
+        let tx = self.command_sender().await?;
+        for message in messages {
+            self.queue_message(&tx, message).await?;
         }
+        Ok(())
+    }
 
-        let tx_opt = self.tx_commands.read().await;
-        if let Some(ref tx) = *tx_opt {
-            tx.send(command).map_err(|_| Error::ConnectionClosed)?;
-        } else {
-            return Err(Error::ConnectionClosed);
+    /// Send an IRC command without waiting for room in the command queue
+    ///
+    /// Like [`send_command`](Self::send_command), but fails with
+    /// [`Error::QueueFull`] when the queue cannot take all of the command's
+    /// messages, in which case none of them are sent.
+    pub async fn try_send_command(&self, command: Command) -> Result<()> {
+        let outgoing = command.clone();
+        let messages = self.split_outgoing(command).await;
+        for message in &messages {
+            Self::check_length(message)?;
+        }
+
+        let tx = self.command_sender().await?;
+        let count = messages.iter().filter(|m| !is_priority(m)).count();
+        let mut permits = match tx.try_reserve_many(count) {
+            Ok(permits) => permits,
+            Err(TrySendError::Full(())) => {
+                info!(
+                    "Command queue of {} is full, rejecting {} messages",
+                    self.connection_id, count
+                );
+                return Err(Error::QueueFull);
+            }
+            Err(TrySendError::Closed(())) => return Err(Error::ConnectionClosed),
+        };
+        for message in messages {
+            if is_priority(&message) {
+                self.queue_priority(message);
+            } else if let Some(permit) = permits.next() {
+                permit.send(message);
+            }
         }
+        self.session.write().await.handle_outgoing(&outgoing);
         Ok(())
     }
 
+    /// Send a command and wait for the server's reply to it
+    ///
+    /// With `labeled-response` the command carries a unique `label` tag, and
+    /// the reply is the labeled message, the messages of the labeled batch, or
+    /// the server's `ACK` when it has nothing to say. Without the capability,
+    /// replies are recognized by the numerics the command is known to produce
+    /// (see [`ReplyMatcher`]), and commands without a known reply are rejected.
+    ///
+    /// The command is not split, and its replies are still delivered as
+    /// [`Event::MessageReceived`] events. Fails with [`Error::RequestTimeout`]
+    /// after the configured `message_timeout`.
+    pub async fn request(&self, command: Command) -> Result<Vec<Message>> {
+        let mut message = command.to_message();
+        let name = message.command.clone();
+        let (key, reply) = if self.has_capability(&Capability::LabeledResponse).await {
+            let label = next_label();
+            message
+                .tags
+                .get_or_insert_with(Vec::new)
+                .push(Tag::new("label", Some(label.clone())));
+            let reply = self.requests.write().await.expect_labeled(label.clone());
+            (label, reply)
+        } else {
+            let matcher = ReplyMatcher::for_command(&command).ok_or_else(|| {
+                Error::Protocol(format!(
+                    "Replies to {name} can't be recognized without labeled-response"
+                ))
+            })?;
+            self.requests.write().await.expect_unlabeled(matcher)
+        };
+
+        let sent = match Self::check_length(&message) {
+            Ok(()) => self.send_message_internal(message).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            self.requests.write().await.cancel(&key);
+            return Err(e);
+        }
+
+        match timeout(self.config.message_timeout, reply).await {
+            Ok(Ok(messages)) => Ok(messages),
+            Ok(Err(_)) => Err(Error::ConnectionClosed),
+            Err(_) => {
+                self.requests.write().await.cancel(&key);
+                Err(Error::RequestTimeout(name))
+            }
+        }
+    }
+
     /// Send raw message directly (advanced usage)
+    ///
+    /// The message is sent as is, without splitting.
     pub async fn send_raw_message(&self, message: Message) -> Result<()> {
-        // Convert message to command for proper handling
-        let message_text = format!("{message}\r\n");
+        Self::check_length(&message)?;
+        self.send_message_internal(message).await
+    }
+
+    /// Reject messages longer than the IRC line limit
+    ///
+    /// Tags have their own budget and are not counted.
+    fn check_length(message: &Message) -> Result<()> {
+        let length = match message.tags {
+            Some(_) => {
+                let untagged = Message {
+                    tags: None,
+                    ..message.clone()
+                };
+                format!("{untagged}\r\n").len()
+            }
+            None => format!("{message}\r\n").len(),
+        };
 
-        if message_text.len() > MAX_MESSAGE_LENGTH {
+        if length > MAX_MESSAGE_LENGTH {
             return Err(Error::Protocol(format!(
                 "Message too long: {} bytes (max: {})",
-                message_text.len(),
-                MAX_MESSAGE_LENGTH
+                length, MAX_MESSAGE_LENGTH
             )));
         }
+        Ok(())
+    }
+
+    /// Limits of the negotiated `draft/multiline` capability
+    async fn multiline_limits(&self) -> Option<MultilineLimits> {
+        let caps = self.caps.read().await;
+        caps.is_enabled(&Capability::Multiline)
+            .then(|| caps.available().value(&Capability::Multiline))
+            .flatten()
+            .and_then(MultilineLimits::parse)
+    }
+
+    /// Split a `PRIVMSG` or `NOTICE` into messages that fit the line length
+    async fn split_outgoing(&self, command: Command) -> Vec<Message> {
+        let (name, target, text) = match &command {
+            Command::PrivMsg { target, text } => ("PRIVMSG", target, text),
+            Command::Notice { target, text } => ("NOTICE", target, text),
+            _ => return vec![command.to_message()],
+        };
 
-        // For raw messages, we need to convert back to Command
-        // This demonstrates proper Message type usage
-        let command = match message.command.as_str() {
-            "PRIVMSG" => {
-                if let (Some(target), Some(text)) = (message.params.first(), message.params.get(1))
+        let source_len = self.source_len().await;
+        let linelen = self.isupport.read().await.linelen;
+        let budget = text_budget(linelen, source_len, name, target);
+        let needs_split = text.len() > budget || text.contains('\n');
+
+        // CTCP, including ACTION, cannot span a batch
+        if needs_split && !text.starts_with('\x01') {
+            if let Some(limits) = self.multiline_limits().await {
+                let reference = format!(
+                    "rustirc{}",
+                    NEXT_BATCH_REFERENCE.fetch_add(1, Ordering::Relaxed)
+                );
+                if let Some(batch) =
+                    multiline_batch(name, target, text, &reference, budget, &limits)
                 {
-                    Command::PrivMsg {
-                        target: target.clone(),
-                        text: text.clone(),
-                    }
-                } else {
-                    return Err(Error::Protocol("Invalid PRIVMSG format".to_string()));
-                }
-            }
-            "JOIN" => {
-                let channels = message.params.first().unwrap_or(&String::new()).clone();
-                Command::Join {
-                    channels: vec![channels],
-                    keys: vec![],
+                    return batch;
                 }
+                debug!("Message exceeds draft/multiline limits, splitting instead");
             }
-            _ => {
-                return Err(Error::Protocol(format!(
-                    "Unsupported raw message command: {}",
-                    message.command
-                )))
-            }
+        }
+
+        split_command(&command, source_len, linelen)
+            .iter()
+            .map(Command::to_message)
+            .collect()
+    }
+
+    /// Length of our `nick!user@host` as the server relays it
+    ///
+    /// Until the server has shown us our hostmask, assume the longest host it
+    /// allows and an ident-less `~user`.
+    async fn source_len(&self) -> usize {
+        let nick_len = self.nicks.read().await.current().len();
+        let hostmask = self.hostmask.read().await.clone();
+
+        let user_len = hostmask
+            .user
+            .map_or(1 + self.config.username.len(), |user| user.len());
+        let host_len = match hostmask.host {
+            Some(host) => host.len(),
+            None => self
+                .isupport
+                .read()
+                .await
+                .get("HOSTLEN")
+                .flatten()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_HOSTLEN),
         };
 
-        self.send_command(command).await
+        nick_len + 1 + user_len + 1 + host_len
     }
 
     /// Internal command sending (for registration)
     async fn send_command_internal(&self, command: Command) -> Result<()> {
-        let tx_opt = self.tx_commands.read().await;
-        if let Some(ref tx) = *tx_opt {
-            tx.send(command).map_err(|_| Error::ConnectionClosed)?;
-        } else {
-            return Err(Error::ConnectionClosed);
+        self.send_message_internal(command.to_message()).await
+    }
+
+    async fn send_message_internal(&self, message: Message) -> Result<()> {
+        let tx = self.command_sender().await?;
+        self.queue_message(&tx, message).await
+    }
+
+    /// Sender of the current connection's command channel
+    async fn command_sender(&self) -> Result<mpsc::Sender<Message>> {
+        match &*self.tx_commands.read().await {
+            Some(tx) if !tx.is_closed() => Ok(tx.clone()),
+            _ => Err(Error::ConnectionClosed),
         }
-        Ok(())
+    }
+
+    /// Hand a message to the writer task, waiting while the channel is full
+    async fn queue_message(&self, tx: &mpsc::Sender<Message>, message: Message) -> Result<()> {
+        if is_priority(&message) {
+            self.queue_priority(message);
+            return Ok(());
+        }
+
+        match tx.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(message)) => {
+                debug!(
+                    "Command queue of {} is full, waiting to send {}",
+                    self.connection_id, message.command
+                );
+                tx.send(message).await.map_err(|_| Error::ConnectionClosed)
+            }
+            Err(TrySendError::Closed(_)) => Err(Error::ConnectionClosed),
+        }
+    }
+
+    /// Queue a reply generated while reading from the server
+    ///
+    /// Replies skip the command channel and go straight to the writer's
+    /// queue, still rate limited, so that the reader never waits behind
+    /// user traffic filling the channel.
+    fn queue_reply(&self, command: Command) {
+        let message = command.to_message();
+        if is_priority(&message) {
+            self.queue_priority(message);
+            return;
+        }
+        self.lock_send_queue().push(message);
+        self.wake_writer.notify_one();
+    }
+
+    /// Add a `PONG` or `QUIT` to the writer's priority lane, skipping the
+    /// command channel so that it is not held up behind a full queue
+    ///
+    /// Every `QUIT` passes through here, however it was sent, and marks the
+    /// connection as quitting so that it is not reconnected.
+    fn queue_priority(&self, message: Message) {
+        if message.command.eq_ignore_ascii_case("QUIT") {
+            self.quitting.store(true, Ordering::SeqCst);
+        }
+        self.lock_send_queue().push(message);
+        self.wake_writer.notify_one();
     }
 
     /// Set connection state and emit event
@@ -888,6 +2149,8 @@ impl IrcConnection {
 pub struct ConnectionManager {
     connections: Arc<RwLock<std::collections::HashMap<String, Arc<IrcConnection>>>>,
     event_bus: Arc<EventBus>,
+    /// Shared by the managed connections for backoff and statistics
+    recovery: Arc<RecoveryManager>,
 }
 
 impl ConnectionManager {
@@ -908,19 +2171,31 @@ impl ConnectionManager {
     /// let manager = ConnectionManager::new(event_bus);
     /// ```
     pub fn new(event_bus: Arc<EventBus>) -> Self {
+        let (recovery, _) = RecoveryManager::new(Arc::new(StateManager::new()), event_bus.clone());
         Self {
             connections: Arc::new(RwLock::new(std::collections::HashMap::new())),
             event_bus,
+            recovery: Arc::new(recovery),
         }
     }
 
+    /// Recovery manager tracking the attempts and failures of every
+    /// managed connection
+    pub fn recovery(&self) -> Arc<RecoveryManager> {
+        self.recovery.clone()
+    }
+
     /// Add a new connection
     pub async fn add_connection(
         &self,
         id: String,
         config: ConnectionConfig,
     ) -> Result<Arc<IrcConnection>> {
-        let connection = Arc::new(IrcConnection::new(config, self.event_bus.clone()));
+        let connection = Arc::new(IrcConnection::with_recovery(
+            config,
+            self.event_bus.clone(),
+            self.recovery.clone(),
+        ));
         self.connections
             .write()
             .await
@@ -1000,3 +2275,343 @@ impl ConnectionManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockIrcServer, MockServerConfig};
+    use rustirc_protocol::command::MonitorSubcommand;
+
+    #[tokio::test]
+    async fn test_writer_paces_lines() {
+        let config = ConnectionConfig {
+            flood: FloodConfig {
+                enabled: true,
+                messages_per_second: 20.0,
+                burst_limit: 1,
+                queue_size: 2,
+            },
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+
+        let (written, mut lines) = mpsc::unbounded_channel();
+        let sink = futures_util::sink::unfold(written, |written, message: Message| async move {
+            let _ = written.send((Instant::now(), message));
+            Ok::<_, std::io::Error>(written)
+        });
+        let (tx_commands, rx_commands) = mpsc::channel(8);
+        let writer = connection.start_writer_task_generic(Box::pin(sink), rx_commands);
+
+        // More lines than the send queue holds
+        for i in 0..5 {
+            let line = Message::new("PRIVMSG")
+                .add_param("#rust")
+                .add_param(i.to_string());
+            tx_commands.send(line).await.unwrap();
+        }
+
+        let mut sent = Vec::new();
+        while sent.len() < 5 {
+            let line = timeout(Duration::from_secs(5), lines.recv()).await.unwrap();
+            sent.push(line.unwrap());
+        }
+        writer.abort();
+
+        let texts: Vec<_> = sent.iter().map(|(_, m)| m.params[1].as_str()).collect();
+        assert_eq!(texts, ["0", "1", "2", "3", "4"]);
+        // One token every 50ms after the burst
+        for pair in sent.windows(2) {
+            let gap = pair[1].0 - pair[0].0;
+            assert!(gap >= Duration::from_millis(40), "{gap:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_backpressure() {
+        let connection = IrcConnection::new(ConnectionConfig::default(), Arc::new(EventBus::new()));
+        // A channel the writer never drains
+        let (tx, mut rx) = mpsc::channel(1);
+        *connection.tx_commands.write().await = Some(tx);
+        let privmsg = |text: &str| Command::PrivMsg {
+            target: "#rust".to_string(),
+            text: text.to_string(),
+        };
+
+        connection.send_command(privmsg("first")).await.unwrap();
+        assert!(matches!(
+            connection.try_send_command(privmsg("rejected")).await,
+            Err(Error::QueueFull)
+        ));
+        let blocked = timeout(
+            Duration::from_millis(100),
+            connection.send_command(privmsg("blocked")),
+        )
+        .await;
+        assert!(blocked.is_err(), "send_command returned on a full channel");
+
+        // PONG and QUIT skip the full channel
+        connection
+            .send_command(Command::Pong {
+                server1: "irc.test".to_string(),
+                server2: None,
+            })
+            .await
+            .unwrap();
+        connection
+            .try_send_command(Command::Quit { message: None })
+            .await
+            .unwrap();
+        let priority: Vec<_> = connection
+            .lock_send_queue()
+            .drain_ready()
+            .into_iter()
+            .map(|message| message.command)
+            .collect();
+        assert_eq!(priority, ["PONG", "QUIT"]);
+
+        // Only the first line reached the channel
+        assert_eq!(rx.recv().await.unwrap().params[1], "first");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replies_skip_full_queue() {
+        let config = ConnectionConfig {
+            nickname: "taken".to_string(),
+            alternative_nicknames: vec!["free".to_string()],
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        // User traffic has filled a channel the writer never drains
+        let (tx, mut rx) = mpsc::channel(1);
+        tx.try_send(
+            Message::new("PRIVMSG")
+                .add_param("#rust")
+                .add_param("queued"),
+        )
+        .unwrap();
+        *connection.tx_commands.write().await = Some(tx);
+        connection.caps.write().await.start();
+        connection.nicks.write().await.start();
+
+        for line in [
+            ":irc.test CAP * LS :multi-prefix",
+            ":irc.test 433 * taken :Nickname is already in use",
+            ":irc.test 396 free cloak.test :is now your displayed host",
+        ] {
+            let message = Parser::parse_message(line).unwrap();
+            timeout(
+                Duration::from_secs(1),
+                connection.handle_protocol_message(&MessageRef::from(&message)),
+            )
+            .await
+            .expect("reader blocked on a full queue")
+            .unwrap();
+        }
+        assert_eq!(
+            connection.hostmask.read().await.host.as_deref(),
+            Some("cloak.test")
+        );
+
+        let replies: Vec<_> = connection
+            .lock_send_queue()
+            .drain_ready()
+            .into_iter()
+            .map(|message| message.to_string())
+            .collect();
+        assert_eq!(replies, ["CAP REQ multi-prefix", "NICK free"]);
+        assert_eq!(rx.recv().await.unwrap().params[1], "queued");
+    }
+
+    /// Wait for the next time the connection registers
+    async fn registered(states: &mut broadcast::Receiver<ConnectionState>) {
+        timeout(Duration::from_secs(10), async {
+            while states.recv().await.unwrap() != ConnectionState::Registered {}
+        })
+        .await
+        .expect("connection did not register");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_restores_session() {
+        let mut server = MockIrcServer::new(MockServerConfig::default());
+        server.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port: server.local_addr().unwrap().port(),
+            use_tls: false,
+            nickname: "restorer".to_string(),
+            reconnect_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        let mut states = connection.subscribe_state_changes();
+        tokio::spawn({
+            let connection = connection.clone();
+            async move { connection.connect().await }
+        });
+        registered(&mut states).await;
+
+        let session = [
+            Command::Join {
+                channels: vec!["#rust".to_string()],
+                keys: Vec::new(),
+            },
+            Command::Away {
+                message: Some("back after lunch".to_string()),
+            },
+            Command::Monitor {
+                subcommand: MonitorSubcommand::Add {
+                    targets: vec!["alice".to_string()],
+                },
+            },
+        ];
+        for command in &session {
+            connection.send_command(command.clone()).await.unwrap();
+        }
+        // The channel is only remembered once the server confirms the JOIN
+        timeout(Duration::from_secs(10), async {
+            while connection.session.read().await.channels().is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let before = server.received_lines().await.len();
+        server.drop_clients();
+        registered(&mut states).await;
+
+        let expected: Vec<_> = session.iter().map(Command::to_message).collect();
+        let restored = |lines: &[String]| {
+            let received: Vec<_> = lines
+                .iter()
+                .filter_map(|line| Parser::parse_message(line).ok())
+                .collect();
+            expected.iter().all(|message| received.contains(message))
+        };
+        let mut lines = Vec::new();
+        for _ in 0..500 {
+            lines = server.received_lines().await.split_off(before);
+            if restored(&lines) {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(restored(&lines), "{lines:?}");
+        // The session is restored on the new connection, after registering
+        let user = lines.iter().position(|line| line.starts_with("USER"));
+        let join = lines.iter().position(|line| line.starts_with("JOIN"));
+        assert!(user.is_some() && user < join, "{lines:?}");
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_quit_is_not_reconnected() {
+        let mut server = MockIrcServer::new(MockServerConfig::default());
+        server.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port: server.local_addr().unwrap().port(),
+            use_tls: false,
+            nickname: "leaver".to_string(),
+            reconnect_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        let mut states = connection.subscribe_state_changes();
+        let task = tokio::spawn({
+            let connection = connection.clone();
+            async move { connection.connect().await }
+        });
+        registered(&mut states).await;
+
+        connection
+            .send_command(Command::Quit {
+                message: Some("bye".to_string()),
+            })
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(10), async {
+            while !server
+                .received_lines()
+                .await
+                .iter()
+                .any(|line| line.starts_with("QUIT"))
+            {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        server.drop_clients();
+
+        let result = timeout(Duration::from_secs(5), task).await;
+        assert!(matches!(result, Ok(Ok(Ok(())))), "{result:?}");
+        assert_eq!(connection.state().await, ConnectionState::Disconnected);
+        sleep(Duration::from_millis(300)).await;
+        let registrations = server
+            .received_lines()
+            .await
+            .iter()
+            .filter(|line| line.starts_with("USER"))
+            .count();
+        assert_eq!(registrations, 1);
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_error_is_final() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                if line.starts_with("USER") {
+                    writer
+                        .write_all(
+                            b":irc.test 001 banned :Welcome\r\n\
+                              ERROR :Closing Link: banned (K-Lined)\r\n",
+                        )
+                        .await
+                        .unwrap();
+                    break;
+                }
+            }
+            drop((lines, writer));
+
+            // A reconnect would show up as another connection
+            timeout(Duration::from_millis(500), listener.accept())
+                .await
+                .is_err()
+        });
+
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port,
+            use_tls: false,
+            nickname: "banned".to_string(),
+            reconnect_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        timeout(Duration::from_secs(10), connection.connect())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(
+            matches!(connection.state().await, ConnectionState::Failed(reason) if reason.contains("K-Lined"))
+        );
+        assert!(server.await.unwrap(), "connection was reconnected");
+    }
+}
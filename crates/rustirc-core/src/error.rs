@@ -16,12 +16,24 @@ pub enum Error {
     #[error("Connection closed")]
     ConnectionClosed,
 
+    #[error("Request timed out: {0}")]
+    RequestTimeout(String),
+
     #[error("Invalid address: {0}")]
     InvalidAddress(String),
 
     #[error("Invalid TLS name: {0}")]
     InvalidTlsName(String),
 
+    #[error("Proxy authentication required: {0}")]
+    ProxyAuthRequired(String),
+
+    #[error("Proxy authentication failed: {0}")]
+    ProxyAuthFailed(String),
+
+    #[error("Authentication failed: {0}")]
+    AuthenticationFailed(String),
+
     #[error("Protocol error: {0}")]
     Protocol(String),
 
@@ -37,12 +49,22 @@ pub enum Error {
     #[error("TLS error: {0}")]
     TlsError(String),
 
+    #[error("Certificate for {host} changed: expected {expected}, got {actual}")]
+    CertificateChanged {
+        host: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("TLS config error: {0}")]
     TlsConfig(#[from] rustls::Error),
 
     #[error("Channel send error")]
     ChannelSend,
 
+    #[error("Command queue is full")]
+    QueueFull,
+
     #[error("Scripting error: {0}")]
     Scripting(String),
 
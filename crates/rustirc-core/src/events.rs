@@ -71,6 +71,29 @@ pub enum Event {
         connection_id: String,
         state: crate::connection::ConnectionState,
     },
+    /// A connection attempt started on another of the network's endpoints,
+    /// shown as `host:port` with `+` before TLS ports
+    EndpointChanged {
+        connection_id: String,
+        endpoint: String,
+    },
+    /// The number of outgoing lines held back by flood protection changed
+    SendQueueChanged {
+        connection_id: String,
+        pending: usize,
+    },
+    /// A buffer may be missing messages from while disconnected; `since` is
+    /// the server time of the last message seen before the gap, if known
+    HistoryGap {
+        connection_id: String,
+        target: String,
+        since: Option<String>,
+    },
+    /// Registration completed (001 RPL_WELCOME) under the given nickname
+    Registered {
+        connection_id: String,
+        nickname: String,
+    },
 
     // Message events
     MessageReceived {
@@ -125,6 +148,21 @@ pub enum Event {
         connection_id: String,
         server: String,
     },
+    CapabilitiesChanged {
+        connection_id: String,
+        capabilities: Vec<String>,
+    },
+
+    // TLS events
+    /// Server certificate checked during the TLS handshake; a
+    /// [`CertificateStatus::Changed`](crate::tls::CertificateStatus::Changed)
+    /// status means the connection was refused and the user must decide
+    /// whether to trust the new certificate
+    ServerCertificate {
+        connection_id: String,
+        host: String,
+        certificate: crate::tls::ServerCertificate,
+    },
 }
 
 /// Trait for handling IRC events asynchronously
@@ -3,12 +3,61 @@
 //! Prevents the client from being disconnected due to excess flood by
 //! rate-limiting outgoing messages using a token bucket. Messages that
 //! cannot be sent immediately are queued for later delivery.
+//!
+//! Commands that make the server do more work cost more than one token
+//! (see [`message_cost`]), and [`SendQueue`] lets `PONG` and `QUIT` skip
+//! the queue.
 
 use std::collections::VecDeque;
 use std::time::Instant;
 
+use rustirc_protocol::Message;
+
 use crate::config::FloodConfig;
 
+/// Token cost of sending a message
+///
+/// Messages and `JOIN`/`PART` cost one token per target, `WHO` and `WHOIS`
+/// two (per nickname for `WHOIS`), and listing every channel's names or the
+/// whole channel list three. Everything else costs one token.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_core::flood::message_cost;
+/// use rustirc_protocol::Message;
+///
+/// let message = Message::new("PRIVMSG").add_param("#a,#b,bob").add_param("hi");
+/// assert_eq!(message_cost(&message), 3.0);
+/// assert_eq!(message_cost(&Message::new("WHO").add_param("#a")), 2.0);
+/// assert_eq!(message_cost(&Message::new("NICK").add_param("bob")), 1.0);
+/// ```
+pub fn message_cost(message: &Message) -> f64 {
+    let targets = |index: usize| {
+        message.params.get(index).map_or(1, |param| {
+            param.split(',').filter(|t| !t.is_empty()).count().max(1)
+        }) as f64
+    };
+
+    match message.command.to_ascii_uppercase().as_str() {
+        "PRIVMSG" | "NOTICE" | "TAGMSG" | "JOIN" | "PART" => targets(0),
+        "WHO" | "WHOWAS" => 2.0,
+        "WHOIS" => 2.0 * targets(message.params.len().saturating_sub(1)),
+        "LIST" => 3.0,
+        "NAMES" if message.params.is_empty() => 3.0,
+        "NAMES" => targets(0),
+        _ => 1.0,
+    }
+}
+
+/// Whether a message bypasses the queue
+///
+/// A late `PONG` gets the connection dropped, and a `QUIT` should not wait
+/// behind lines the user is leaving anyway.
+pub fn is_priority(message: &Message) -> bool {
+    message.command.eq_ignore_ascii_case("PONG") || message.command.eq_ignore_ascii_case("QUIT")
+}
+
 /// A token-bucket based flood protector for outgoing IRC messages.
 ///
 /// The token bucket starts full (at `max_tokens`) and drains by one token
@@ -85,20 +134,47 @@ impl FloodProtector {
     /// Otherwise, refills tokens and checks if at least one is available.
     /// Consumes a token if the send is permitted.
     pub fn try_send(&mut self) -> bool {
+        self.try_send_cost(1.0)
+    }
+
+    /// Check whether a message costing `cost` tokens can be sent immediately.
+    ///
+    /// Costs above the burst capacity are capped at it, so that expensive
+    /// messages still go out once the bucket is full. Consumes the tokens
+    /// if the send is permitted.
+    pub fn try_send_cost(&mut self, cost: f64) -> bool {
         if !self.enabled {
             return true;
         }
 
         self.refill();
 
-        if self.tokens >= 1.0 {
-            self.tokens -= 1.0;
+        let cost = self.capped(cost);
+        if self.tokens >= cost {
+            self.tokens -= cost;
             true
         } else {
             false
         }
     }
 
+    /// Consume tokens for a message that was sent regardless of the limit.
+    ///
+    /// The bucket does not go below empty, so priority traffic delays
+    /// later messages without starving them.
+    pub fn charge(&mut self, cost: f64) {
+        if !self.enabled {
+            return;
+        }
+
+        self.refill();
+        self.tokens = (self.tokens - self.capped(cost)).max(0.0);
+    }
+
+    fn capped(&self, cost: f64) -> f64 {
+        cost.min(self.max_tokens as f64)
+    }
+
     /// Enqueue a message for later sending.
     ///
     /// Returns `true` if the message was enqueued, `false` if the queue
@@ -117,16 +193,25 @@ impl FloodProtector {
     /// is disabled. Otherwise returns the `Instant` when the next token
     /// will be available.
     pub fn next_send_time(&mut self) -> Option<Instant> {
+        self.next_send_time_cost(1.0)
+    }
+
+    /// Calculate when a message costing `cost` tokens can be sent.
+    ///
+    /// Like [`next_send_time`](Self::next_send_time), returns `None` if it
+    /// can be sent right now.
+    pub fn next_send_time_cost(&mut self, cost: f64) -> Option<Instant> {
         if !self.enabled {
             return None;
         }
 
         self.refill();
 
-        if self.tokens >= 1.0 {
+        let cost = self.capped(cost);
+        if self.tokens >= cost {
             None
         } else {
-            let deficit = 1.0 - self.tokens;
+            let deficit = cost - self.tokens;
             let wait_secs = deficit / self.refill_rate;
             Some(self.last_refill + std::time::Duration::from_secs_f64(wait_secs))
         }
@@ -177,6 +262,102 @@ impl FloodProtector {
     }
 }
 
+/// Outgoing messages of a connection, paced by a [`FloodProtector`]
+///
+/// Messages are sent in order as tokens for their [`message_cost`] become
+/// available. [Priority](is_priority) messages skip the queue and are only
+/// charged afterwards.
+#[derive(Debug)]
+pub struct SendQueue {
+    protector: FloodProtector,
+    priority: VecDeque<Message>,
+    queue: VecDeque<(Message, f64)>,
+    max_len: usize,
+}
+
+impl SendQueue {
+    /// Create a queue with the rate limits and size of `config`
+    pub fn new(config: &FloodConfig) -> Self {
+        Self {
+            protector: FloodProtector::from_config(config),
+            priority: VecDeque::new(),
+            queue: VecDeque::new(),
+            // A queue that can't hold a line would never send one
+            max_len: config.queue_size.max(1),
+        }
+    }
+
+    /// Add a message to send
+    ///
+    /// Messages are never dropped: callers stop adding them once the queue
+    /// [`is_full`](Self::is_full), so senders wait instead. Priority messages
+    /// don't count towards the limit, and replies to the server may be added
+    /// past it so that reading never waits on the queue.
+    pub fn push(&mut self, message: Message) {
+        if is_priority(&message) {
+            self.priority.push_back(message);
+        } else {
+            let cost = message_cost(&message);
+            self.queue.push_back((message, cost));
+        }
+    }
+
+    /// Take the messages that can be sent now, priority messages first
+    pub fn drain_ready(&mut self) -> Vec<Message> {
+        let mut ready = Vec::new();
+        for message in self.priority.drain(..) {
+            self.protector.charge(message_cost(&message));
+            ready.push(message);
+        }
+        while let Some((_, cost)) = self.queue.front() {
+            if !self.protector.try_send_cost(*cost) {
+                break;
+            }
+            if let Some((message, _)) = self.queue.pop_front() {
+                ready.push(message);
+            }
+        }
+        ready
+    }
+
+    /// When the next queued message can be sent, if any is waiting
+    pub fn next_send_time(&mut self) -> Option<Instant> {
+        if !self.priority.is_empty() {
+            return Some(Instant::now());
+        }
+        let (_, cost) = self.queue.front()?;
+        Some(
+            self.protector
+                .next_send_time_cost(*cost)
+                .unwrap_or_else(Instant::now),
+        )
+    }
+
+    /// Number of messages waiting for tokens
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether no messages are waiting
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Whether the queue has reached its size limit
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.max_len
+    }
+
+    /// Drop the waiting messages, returning how many there were
+    ///
+    /// Priority messages are kept.
+    pub fn clear(&mut self) -> usize {
+        let cancelled = self.queue.len();
+        self.queue.clear();
+        cancelled
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,6 +501,49 @@ mod tests {
         assert!(!fp.try_send());
     }
 
+    #[test]
+    fn test_cost_above_burst_is_capped() {
+        let mut fp = FloodProtector::new(3, 1.0, 10);
+
+        // A 5-token message still goes out with a full bucket
+        assert!(fp.try_send_cost(5.0));
+        assert!(!fp.try_send());
+
+        // Charging never takes the bucket below empty
+        fp.charge(2.0);
+        assert!(fp.tokens >= 0.0);
+    }
+
+    #[test]
+    fn test_send_queue_costs_and_priority() {
+        let config = FloodConfig {
+            enabled: true,
+            messages_per_second: 1.0,
+            burst_limit: 4,
+            queue_size: 2,
+        };
+        let mut queue = SendQueue::new(&config);
+
+        queue.push(Message::new("JOIN").add_param("#a,#b,#c"));
+        assert!(!queue.is_full());
+        queue.push(Message::new("PRIVMSG").add_param("#a").add_param("1"));
+        assert!(queue.is_full());
+        // The queue is full, but PONG skips it
+        queue.push(Message::new("PONG").add_param("server"));
+        assert_eq!(queue.len(), 2);
+
+        // PONG first; then the 3-token JOIN leaves too little for PRIVMSG
+        let ready = queue.drain_ready();
+        let commands: Vec<_> = ready.iter().map(|m| m.command.as_str()).collect();
+        assert_eq!(commands, ["PONG", "JOIN"]);
+        assert_eq!(queue.len(), 1);
+        assert!(queue.next_send_time().unwrap() > Instant::now());
+
+        assert_eq!(queue.clear(), 1);
+        assert!(queue.is_empty());
+        assert!(queue.next_send_time().is_none());
+    }
+
     #[test]
     fn test_next_send_time() {
         let mut fp = FloodProtector::new(1, 2.0, 10);
@@ -12,9 +12,11 @@ use tokio::sync::RwLock;
 
 pub mod auth;
 pub mod batch;
+pub mod caps;
 pub mod chathistory;
 pub mod cli;
 pub mod client;
+pub mod codec;
 pub mod config;
 pub mod connection;
 pub mod dcc;
@@ -22,16 +24,24 @@ pub mod error;
 pub mod events;
 pub mod flood;
 pub mod mock_server;
+pub mod net;
+pub mod nick;
 pub mod proxy;
 pub mod recovery;
+pub mod request;
 pub mod router;
+pub mod session;
 pub mod state;
+pub mod sts;
+pub mod tls;
 pub mod ui;
+pub mod websocket;
 
 pub use auth::{
-    AuthState, ExternalMechanism, PlainMechanism, SaslAuthenticator, SaslCredentials,
-    SaslMechanism, SecureString,
+    AuthError, AuthState, ExternalMechanism, PlainMechanism, SaslAuthenticator, SaslCredentials,
+    SaslMechanism, SaslSession, ScramAlgorithm, ScramMechanism, SecureString,
 };
+pub use caps::{CapNegotiator, CapPhase};
 pub use cli::{run_cli_prototype, CliClient};
 pub use client::IrcClient;
 pub use config::Config;
@@ -39,11 +49,13 @@ pub use connection::{ConnectionConfig, ConnectionManager, ConnectionState, IrcCo
 pub use error::{Error, Result};
 pub use events::{Event, EventHandler};
 pub use mock_server::{MockClient, MockIrcServer, MockServerConfig};
+pub use nick::NickManager;
 pub use recovery::{ReconnectConfig, RecoveryManager, RecoveryStats};
 pub use router::{CommandProcessor, MessageContext, MessageHandler, MessageRouter};
 pub use state::{
     ChannelState, ChannelUser, ClientState, ServerState, StateManager, TopicInfo, User,
 };
+pub use tls::{CertificateStatus, KnownHosts, ServerCertificate};
 pub use ui::{StateChange, UiEvent, UserInterface, View, ViewId, ViewManager, ViewType};
 
 /// Global client instance manager
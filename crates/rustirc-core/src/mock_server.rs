@@ -20,7 +20,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::{timeout, Duration};
 use tracing::{debug, error, info, warn};
 
@@ -96,7 +96,12 @@ pub struct MockIrcServer {
     clients: Arc<RwLock<HashMap<SocketAddr, MockClient>>>,
     channels: Arc<RwLock<HashMap<String, Vec<SocketAddr>>>>,
     listener: Option<TcpListener>,
+    local_addr: Option<SocketAddr>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    /// Every line received from any client, in order
+    received: Arc<RwLock<Vec<String>>>,
+    /// Tells the connected clients' handlers to close their links
+    drop_links: broadcast::Sender<()>,
 }
 
 impl MockIrcServer {
@@ -106,7 +111,10 @@ impl MockIrcServer {
             clients: Arc::new(RwLock::new(HashMap::new())),
             channels: Arc::new(RwLock::new(HashMap::new())),
             listener: None,
+            local_addr: None,
             shutdown_tx: None,
+            received: Arc::new(RwLock::new(Vec::new())),
+            drop_links: broadcast::channel(1).0,
         }
     }
 
@@ -115,6 +123,7 @@ impl MockIrcServer {
         let listener = TcpListener::bind(addr).await?;
         let local_addr = listener.local_addr()?;
         info!("Mock IRC server listening on {}", local_addr);
+        self.local_addr = Some(local_addr);
 
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         self.shutdown_tx = Some(shutdown_tx);
@@ -122,6 +131,8 @@ impl MockIrcServer {
         let clients = Arc::clone(&self.clients);
         let channels = Arc::clone(&self.channels);
         let config = self.config.clone();
+        let received = Arc::clone(&self.received);
+        let drop_links = self.drop_links.clone();
 
         tokio::spawn(async move {
             loop {
@@ -137,6 +148,8 @@ impl MockIrcServer {
                                     config: config.clone(),
                                     clients: Arc::clone(&clients),
                                     channels: Arc::clone(&channels),
+                                    received: Arc::clone(&received),
+                                    drop_link: drop_links.subscribe(),
                                 };
 
                                 tokio::spawn(async move {
@@ -162,6 +175,11 @@ impl MockIrcServer {
         Ok(())
     }
 
+    /// The address the server is listening on, e.g. after binding port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
     /// Stop the mock IRC server
     pub async fn stop(&mut self) -> Result<()> {
         if let Some(shutdown_tx) = self.shutdown_tx.take() {
@@ -170,6 +188,16 @@ impl MockIrcServer {
         Ok(())
     }
 
+    /// Lines received from clients so far
+    pub async fn received_lines(&self) -> Vec<String> {
+        self.received.read().await.clone()
+    }
+
+    /// Close the links of all connected clients, as a server restart would
+    pub fn drop_clients(&self) {
+        let _ = self.drop_links.send(());
+    }
+
     /// Get current client count
     pub async fn client_count(&self) -> usize {
         self.clients.read().await.len()
@@ -199,6 +227,8 @@ struct ClientHandler {
     config: MockServerConfig,
     clients: Arc<RwLock<HashMap<SocketAddr, MockClient>>>,
     channels: Arc<RwLock<HashMap<String, Vec<SocketAddr>>>>,
+    received: Arc<RwLock<Vec<String>>>,
+    drop_link: broadcast::Receiver<()>,
 }
 
 impl ClientHandler {
@@ -207,6 +237,8 @@ impl ClientHandler {
         let config = self.config;
         let clients = self.clients;
         let channels = self.channels;
+        let received = self.received;
+        let mut drop_link = self.drop_link;
 
         let (reader, mut writer) = self.stream.into_split();
         let mut reader = BufReader::new(reader);
@@ -227,7 +259,14 @@ impl ClientHandler {
         loop {
             line.clear();
 
-            match timeout(config.ping_interval * 5, reader.read_line(&mut line)).await {
+            let read = tokio::select! {
+                read = timeout(config.ping_interval * 5, reader.read_line(&mut line)) => read,
+                _ = drop_link.recv() => {
+                    debug!("Dropping link to {}", addr);
+                    break;
+                }
+            };
+            match read {
                 Ok(Ok(0)) => {
                     debug!("Client {} disconnected", addr);
                     break;
@@ -239,6 +278,7 @@ impl ClientHandler {
                     }
 
                     debug!("Received from {}: {}", addr, line);
+                    received.write().await.push(line.to_string());
 
                     if let Err(e) =
                         Self::process_message_static(&mut writer, line, addr, &clients, &channels)
@@ -332,6 +372,22 @@ impl ClientHandler {
         Ok(())
     }
 
+    // Static helper completing registration: welcome, ISUPPORT and the end of the MOTD
+    async fn send_welcome_static(
+        writer: &mut tokio::net::tcp::OwnedWriteHalf,
+        nick: &str,
+    ) -> Result<()> {
+        Self::send_numeric_static(writer, "001", &[nick, "Welcome to Mock IRC Server"]).await?;
+        Self::send_numeric_static(
+            writer,
+            "005",
+            &[nick, "MONITOR=100", ":are supported by this server"],
+        )
+        .await?;
+        Self::send_numeric_static(writer, "422", &[nick, ":MOTD File is missing"]).await?;
+        Ok(())
+    }
+
     // Static handlers
     async fn handle_nick_static(
         writer: &mut tokio::net::tcp::OwnedWriteHalf,
@@ -372,12 +428,7 @@ impl ClientHandler {
                 // Check if both nick and user are set for registration
                 if client.nickname.is_some() && client.username.is_some() && !client.is_registered {
                     client.is_registered = true;
-                    Self::send_numeric_static(
-                        writer,
-                        "001",
-                        &[new_nick, "Welcome to Mock IRC Server"],
-                    )
-                    .await?;
+                    Self::send_welcome_static(writer, new_nick).await?;
                 }
             }
         }
@@ -407,12 +458,7 @@ impl ClientHandler {
                 if client.nickname.is_some() && client.username.is_some() && !client.is_registered {
                     client.is_registered = true;
                     if let Some(nick) = &client.nickname {
-                        Self::send_numeric_static(
-                            writer,
-                            "001",
-                            &[nick, "Welcome to Mock IRC Server"],
-                        )
-                        .await?;
+                        Self::send_welcome_static(writer, nick).await?;
                     }
                 }
             }
@@ -0,0 +1,215 @@
+//! Outgoing TCP connections
+//!
+//! Resolved addresses are ordered by family preference and raced in the
+//! style of RFC 8305 (Happy Eyeballs v2): a new attempt starts every
+//! attempt delay, or as soon as the previous one fails, and the first
+//! connection to succeed wins. This avoids hanging on an unreachable IPv6
+//! route or a dead round-robin entry.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::time::sleep;
+use tracing::debug;
+
+use crate::config::AddressFamily;
+
+/// Delay between connection attempts recommended by RFC 8305
+pub const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Order addresses for connecting, interleaving IPv6 and IPv4
+///
+/// The preferred family goes first (IPv6 unless IPv4 is preferred) and the
+/// `*Only` settings drop the other family. Duplicates are removed.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_core::config::AddressFamily;
+/// use rustirc_core::net::order_addresses;
+///
+/// let addrs = ["192.0.2.1:6667", "192.0.2.2:6667", "[2001:db8::1]:6667"]
+///     .map(|addr| addr.parse().unwrap());
+/// let ordered = order_addresses(addrs, AddressFamily::PreferIpv4);
+/// assert_eq!(ordered, [addrs[0], addrs[2], addrs[1]]);
+/// ```
+pub fn order_addresses(
+    addrs: impl IntoIterator<Item = SocketAddr>,
+    family: AddressFamily,
+) -> Vec<SocketAddr> {
+    let mut ipv6 = Vec::new();
+    let mut ipv4 = Vec::new();
+    for addr in addrs {
+        let list = if addr.is_ipv6() { &mut ipv6 } else { &mut ipv4 };
+        if !list.contains(&addr) {
+            list.push(addr);
+        }
+    }
+
+    let (first, second) = match family {
+        AddressFamily::Any | AddressFamily::PreferIpv6 => (ipv6, ipv4),
+        AddressFamily::PreferIpv4 => (ipv4, ipv6),
+        AddressFamily::Ipv6Only => (ipv6, Vec::new()),
+        AddressFamily::Ipv4Only => (ipv4, Vec::new()),
+    };
+
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
+/// Race connections to `addrs` in order, starting one every `attempt_delay`
+///
+/// With `bind` set, connections use it as the local address and addresses of
+/// the other family are skipped. Returns the last error if every attempt
+/// fails.
+pub async fn connect(
+    addrs: &[SocketAddr],
+    attempt_delay: Duration,
+    bind: Option<IpAddr>,
+) -> io::Result<TcpStream> {
+    let mut remaining = addrs
+        .iter()
+        .copied()
+        .filter(|addr| bind.is_none_or(|ip| ip.is_ipv6() == addr.is_ipv6()))
+        .collect::<Vec<_>>()
+        .into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match remaining.next() {
+                Some(addr) => attempts.push(connect_one(addr, bind)),
+                None => break,
+            }
+        }
+
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(stream) => {
+                    debug!("Connected to {}", addr);
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    debug!("Connection to {} failed: {}", addr, e);
+                    last_error = Some(e);
+                    // Start the next attempt without waiting out the delay
+                    if let Some(addr) = remaining.next() {
+                        attempts.push(connect_one(addr, bind));
+                    }
+                }
+            },
+            _ = sleep(attempt_delay), if remaining.len() > 0 => {
+                if let Some(addr) = remaining.next() {
+                    attempts.push(connect_one(addr, bind));
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        let message = match bind {
+            Some(ip) => format!("No addresses of the same family as bind address {ip}"),
+            None => "No addresses to connect to".to_string(),
+        };
+        io::Error::new(io::ErrorKind::AddrNotAvailable, message)
+    }))
+}
+
+async fn connect_one(
+    addr: SocketAddr,
+    bind: Option<IpAddr>,
+) -> (SocketAddr, io::Result<TcpStream>) {
+    let result = async {
+        let socket = if addr.is_ipv6() {
+            TcpSocket::new_v6()?
+        } else {
+            TcpSocket::new_v4()?
+        };
+        if let Some(ip) = bind {
+            socket.bind(SocketAddr::new(ip, 0))?;
+        }
+        socket.connect(addr).await
+    };
+    (addr, result.await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_order_addresses() {
+        let resolved = addrs(&["1.1.1.1:1", "[::1]:1", "1.1.1.2:1", "1.1.1.1:1", "[::2]:1"]);
+
+        assert_eq!(
+            order_addresses(resolved.clone(), AddressFamily::Any),
+            addrs(&["[::1]:1", "1.1.1.1:1", "[::2]:1", "1.1.1.2:1"])
+        );
+        assert_eq!(
+            order_addresses(resolved.clone(), AddressFamily::PreferIpv4),
+            addrs(&["1.1.1.1:1", "[::1]:1", "1.1.1.2:1", "[::2]:1"])
+        );
+        assert_eq!(
+            order_addresses(resolved, AddressFamily::Ipv4Only),
+            addrs(&["1.1.1.1:1", "1.1.1.2:1"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_skips_failed_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        // Bound but not listening, so connecting is refused
+        let refused = {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            socket.local_addr().unwrap()
+        };
+
+        let stream = connect(&[refused, good], Duration::from_secs(30), None)
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+
+        let error = connect(&[refused], Duration::from_secs(30), None)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_bind_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        let ipv6 = SocketAddr::new("::1".parse().unwrap(), good.port());
+        let bind = "127.0.0.1".parse().unwrap();
+
+        let stream = connect(&[ipv6, good], DEFAULT_ATTEMPT_DELAY, Some(bind))
+            .await
+            .unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), bind);
+
+        let error = connect(&[ipv6], DEFAULT_ATTEMPT_DELAY, Some(bind))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrNotAvailable);
+    }
+}
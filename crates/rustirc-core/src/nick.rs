@@ -0,0 +1,424 @@
+//! Nickname selection and recovery
+//!
+//! Picks a usable nickname during registration when the server rejects the
+//! configured one (433 ERR_NICKNAMEINUSE, 432 ERR_ERRONEUSNICKNAME or
+//! 437 ERR_UNAVAILRESOURCE), trying each alternative nickname in order and then
+//! numbered variants of the primary nickname.
+//!
+//! Once registered under a fallback nickname, the manager tries to regain the
+//! primary one: through `MONITOR` when the server supports it, or by periodic
+//! `NICK` attempts otherwise.
+//!
+//! Like [`CapNegotiator`](crate::caps::CapNegotiator), the manager is a pure
+//! state machine that returns the commands the connection should send.
+
+use std::time::Duration;
+
+use rustirc_protocol::command::MonitorSubcommand;
+use rustirc_protocol::{CaseMapping, Command, Message};
+use tracing::{debug, info, warn};
+
+/// Interval between `NICK` attempts to regain the primary nickname when the
+/// server does not support `MONITOR`.
+pub const NICK_REGAIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of numbered variants of the primary nickname tried after the
+/// alternatives are exhausted.
+const MAX_NUMBERED_VARIANTS: usize = 20;
+
+/// Tracks the nickname in use and drives collision recovery.
+#[derive(Debug, Clone)]
+pub struct NickManager {
+    /// Nickname the user wants.
+    primary: String,
+    /// Fallback nicknames tried in order during registration.
+    alternatives: Vec<String>,
+    /// Nickname currently in use, or being attempted during registration.
+    current: String,
+    /// Index of `current` in the candidate sequence.
+    attempt: usize,
+    /// Whether registration has completed.
+    registered: bool,
+    /// Whether the primary nickname is watched with `MONITOR`.
+    monitoring: bool,
+    /// A `NICK` sent by the manager that the server has not answered yet.
+    pending: Option<String>,
+    /// Server CASEMAPPING used to compare nicknames.
+    casemapping: CaseMapping,
+    /// Server NICKLEN, kept across registrations as 005 arrives after 001.
+    nicklen: Option<usize>,
+}
+
+impl NickManager {
+    /// Create a manager for the given primary and alternative nicknames.
+    pub fn new(primary: impl Into<String>, alternatives: Vec<String>) -> Self {
+        let primary = primary.into();
+        Self {
+            current: primary.clone(),
+            primary,
+            alternatives,
+            attempt: 0,
+            registered: false,
+            monitoring: false,
+            pending: None,
+            casemapping: CaseMapping::default(),
+            nicklen: None,
+        }
+    }
+
+    /// The nickname the user wants.
+    pub fn primary(&self) -> &str {
+        &self.primary
+    }
+
+    /// The nickname currently in use (or being attempted during registration).
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Whether registration has completed.
+    pub fn is_registered(&self) -> bool {
+        self.registered
+    }
+
+    /// Whether the primary nickname is in use.
+    pub fn has_primary(&self) -> bool {
+        self.casemapping.equals(&self.current, &self.primary)
+    }
+
+    /// Check whether a nickname refers to us.
+    pub fn is_current(&self, nick: &str) -> bool {
+        self.casemapping.equals(&self.current, nick)
+    }
+
+    /// Compare nicknames with the server's CASEMAPPING from now on.
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+    }
+
+    /// Shorten numbered variants to the server's NICKLEN from now on.
+    pub fn set_nicklen(&mut self, nicklen: Option<usize>) {
+        if nicklen.is_some() {
+            self.nicklen = nicklen;
+        }
+    }
+
+    /// Begin registration, returning the `NICK` command for the primary nickname.
+    pub fn start(&mut self) -> Command {
+        self.current = self.primary.clone();
+        self.attempt = 0;
+        self.registered = false;
+        self.monitoring = false;
+        self.pending = None;
+        self.casemapping = CaseMapping::default();
+        Self::nick_command(&self.primary)
+    }
+
+    /// Nickname candidate for the given registration attempt.
+    ///
+    /// Attempt 0 is the primary nickname, followed by the alternatives and then
+    /// numbered variants of the primary (`nick1`, `nick2`, ...). The primary is
+    /// truncated so that a variant fits within NICKLEN.
+    fn candidate(&self, attempt: usize) -> Option<String> {
+        if attempt == 0 {
+            return Some(self.primary.clone());
+        }
+        if let Some(alternative) = self.alternatives.get(attempt - 1) {
+            return Some(alternative.clone());
+        }
+
+        let number = attempt - self.alternatives.len();
+        if number > MAX_NUMBERED_VARIANTS {
+            return None;
+        }
+
+        let suffix = number.to_string();
+        let base: String = match self.nicklen {
+            Some(nicklen) => self
+                .primary
+                .chars()
+                .take(nicklen.saturating_sub(suffix.len()))
+                .collect(),
+            None => self.primary.clone(),
+        };
+        Some(format!("{base}{suffix}"))
+    }
+
+    /// Handle a nickname rejection numeric (432, 433 or 437).
+    ///
+    /// During registration this returns the `NICK` command for the next
+    /// candidate. After registration the rejection only ends a regain attempt.
+    pub fn handle_rejection(&mut self, message: &Message) -> Option<Command> {
+        // <numeric> <client> <nick> :<reason>
+        let rejected = message.params.get(1)?;
+
+        // 437 is also used for channels, so only react to our own nicknames
+        let ours = self.is_current(rejected)
+            || self
+                .pending
+                .as_deref()
+                .is_some_and(|pending| self.casemapping.equals(pending, rejected));
+        if !ours {
+            debug!("Ignoring {} for {}", message.command, rejected);
+            return None;
+        }
+
+        if self.registered {
+            debug!("Nickname {} still unavailable", rejected);
+            self.pending = None;
+            return None;
+        }
+
+        // A numbered variant rejected as erroneous is most likely too long;
+        // without a known NICKLEN, assume the primary is at the limit
+        let numbered = self.attempt > self.alternatives.len();
+        if message.command == "432" && numbered && self.nicklen.is_none() {
+            let nicklen = self.primary.chars().count();
+            debug!(
+                "Assuming NICKLEN {} after {} was rejected",
+                nicklen, rejected
+            );
+            self.nicklen = Some(nicklen);
+        }
+
+        let Some(next) = self.candidate(self.attempt + 1) else {
+            warn!("Ran out of nicknames to try after {}", rejected);
+            return None;
+        };
+
+        info!("Nickname {} unavailable, trying {}", rejected, next);
+        self.attempt += 1;
+        self.current = next;
+        Some(Self::nick_command(&self.current))
+    }
+
+    /// Handle 001 RPL_WELCOME, which names the nickname we registered with.
+    pub fn handle_registered(&mut self, nick: &str) {
+        self.current = nick.to_string();
+        self.registered = true;
+        self.pending = None;
+
+        if !self.has_primary() {
+            info!(
+                "Registered as {}; will try to regain {}",
+                self.current, self.primary
+            );
+        }
+    }
+
+    /// Start watching the primary nickname once the server advertises `MONITOR`.
+    pub fn enable_monitor(&mut self) -> Option<Command> {
+        if self.monitoring || !self.registered || self.has_primary() {
+            return None;
+        }
+
+        self.monitoring = true;
+        Some(Command::Monitor {
+            subcommand: MonitorSubcommand::Add {
+                targets: vec![self.primary.clone()],
+            },
+        })
+    }
+
+    /// Handle one of our own nickname changes (a `NICK` message from us).
+    pub fn handle_nick_change(&mut self, new: &str) -> Vec<Command> {
+        self.pending = None;
+        self.current = new.to_string();
+
+        if self.has_primary() {
+            info!("Using primary nickname {}", self.primary);
+        } else {
+            // Changed by the user: keep the new nickname instead of regaining
+            debug!(
+                "Nickname changed to {}; no longer regaining {}",
+                new, self.primary
+            );
+        }
+
+        let mut commands = Vec::new();
+        if self.monitoring {
+            self.monitoring = false;
+            commands.push(Command::Monitor {
+                subcommand: MonitorSubcommand::Remove {
+                    targets: vec![self.primary.clone()],
+                },
+            });
+        }
+        self.primary = self.current.clone();
+        commands
+    }
+
+    /// Handle 731 RPL_MONOFFLINE: try to take the primary nickname when it frees up.
+    pub fn handle_monitor_offline(&mut self, targets: &str) -> Option<Command> {
+        let freed = targets
+            .split(',')
+            .map(|target| target.split('!').next().unwrap_or(target))
+            .any(|nick| self.casemapping.equals(nick, &self.primary));
+
+        if freed {
+            self.regain_command()
+        } else {
+            None
+        }
+    }
+
+    /// Periodic regain attempt for servers without `MONITOR`.
+    pub fn regain(&mut self) -> Option<Command> {
+        if self.monitoring {
+            return None;
+        }
+        self.regain_command()
+    }
+
+    fn regain_command(&mut self) -> Option<Command> {
+        if !self.registered || self.has_primary() {
+            return None;
+        }
+
+        self.pending = Some(self.primary.clone());
+        Some(Self::nick_command(&self.primary))
+    }
+
+    fn nick_command(nick: &str) -> Command {
+        Command::Nick {
+            nickname: nick.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    fn msg(line: &str) -> Message {
+        Parser::parse_message(line).unwrap()
+    }
+
+    fn nick_of(command: Option<Command>) -> String {
+        match command {
+            Some(Command::Nick { nickname }) => nickname,
+            other => panic!("Expected NICK, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_registration_tries_alternatives_then_numbered() {
+        let mut nicks = NickManager::new("rusty", vec!["rusty_".to_string()]);
+        assert_eq!(nick_of(Some(nicks.start())), "rusty");
+
+        let next = nicks.handle_rejection(&msg(":srv 433 * rusty :Nickname is already in use"));
+        assert_eq!(nick_of(next), "rusty_");
+
+        let next = nicks.handle_rejection(&msg(":srv 432 * rusty_ :Erroneous nickname"));
+        assert_eq!(nick_of(next), "rusty1");
+
+        let next =
+            nicks.handle_rejection(&msg(":srv 437 * rusty1 :Nick is temporarily unavailable"));
+        assert_eq!(nick_of(next), "rusty2");
+        assert_eq!(nicks.current(), "rusty2");
+    }
+
+    #[test]
+    fn test_gives_up_after_numbered_variants() {
+        let mut nicks = NickManager::new("n", Vec::new());
+        nicks.start();
+        for _ in 0..MAX_NUMBERED_VARIANTS {
+            let line = format!(":srv 433 * {} :in use", nicks.current());
+            assert!(nicks.handle_rejection(&msg(&line)).is_some());
+        }
+        let line = format!(":srv 433 * {} :in use", nicks.current());
+        assert!(nicks.handle_rejection(&msg(&line)).is_none());
+    }
+
+    #[test]
+    fn test_channel_unavailable_is_ignored() {
+        let mut nicks = NickManager::new("rusty", Vec::new());
+        nicks.start();
+        assert!(nicks
+            .handle_rejection(&msg(":srv 437 * #rust :Channel is temporarily unavailable"))
+            .is_none());
+        assert_eq!(nicks.current(), "rusty");
+    }
+
+    #[test]
+    fn test_periodic_regain() {
+        let mut nicks = NickManager::new("rusty", vec!["rusty_".to_string()]);
+        nicks.start();
+        nicks.handle_rejection(&msg(":srv 433 * rusty :in use"));
+        nicks.handle_registered("rusty_");
+        assert!(!nicks.has_primary());
+
+        assert_eq!(nick_of(nicks.regain()), "rusty");
+        // Still taken: keep the fallback nickname
+        assert!(nicks
+            .handle_rejection(&msg(":srv 433 rusty_ rusty :in use"))
+            .is_none());
+        assert_eq!(nicks.current(), "rusty_");
+
+        assert_eq!(nick_of(nicks.regain()), "rusty");
+        assert!(nicks.handle_nick_change("rusty").is_empty());
+        assert!(nicks.has_primary());
+        assert!(nicks.regain().is_none());
+    }
+
+    #[test]
+    fn test_monitor_regain() {
+        let mut nicks = NickManager::new("rusty", vec!["rusty_".to_string()]);
+        nicks.start();
+        nicks.handle_registered("rusty_");
+
+        let monitor = nicks.enable_monitor().unwrap();
+        assert_eq!(monitor.to_message().to_string(), "MONITOR + rusty");
+        assert!(nicks.regain().is_none());
+
+        assert!(nicks.handle_monitor_offline("someone").is_none());
+        assert_eq!(nick_of(nicks.handle_monitor_offline("Rusty")), "rusty");
+
+        let commands = nicks.handle_nick_change("rusty");
+        assert_eq!(commands[0].to_message().to_string(), "MONITOR - rusty");
+    }
+
+    #[test]
+    fn test_user_nick_change_stops_regain() {
+        let mut nicks = NickManager::new("rusty", Vec::new());
+        nicks.start();
+        nicks.handle_registered("rusty1");
+
+        nicks.handle_nick_change("crab");
+        assert_eq!(nicks.primary(), "crab");
+        assert!(nicks.has_primary());
+        assert!(nicks.regain().is_none());
+    }
+
+    #[test]
+    fn test_numbered_variants_fit_nicklen() {
+        let mut nicks = NickManager::new("ninechars", Vec::new());
+        nicks.set_nicklen(Some(9));
+        nicks.start();
+
+        let next = nicks.handle_rejection(&msg(":srv 433 * ninechars :Nickname is already in use"));
+        assert_eq!(nick_of(next), "ninechar1");
+
+        // Without a known NICKLEN, a 432 for a variant shortens the next one
+        let mut nicks = NickManager::new("ninechars", Vec::new());
+        nicks.start();
+        let next = nicks.handle_rejection(&msg(":srv 433 * ninechars :Nickname is already in use"));
+        assert_eq!(nick_of(next), "ninechars1");
+        let next = nicks.handle_rejection(&msg(":srv 432 * ninechars1 :Erroneous nickname"));
+        assert_eq!(nick_of(next), "ninechar2");
+    }
+
+    #[test]
+    fn test_casemapping_comparisons() {
+        let mut nicks = NickManager::new("rusty[m]", Vec::new());
+        nicks.start();
+        nicks.handle_registered("RUSTY{M}");
+        assert!(nicks.has_primary());
+
+        nicks.set_casemapping(CaseMapping::Ascii);
+        assert!(!nicks.has_primary());
+        assert!(nicks.is_current("rusty{m}"));
+        assert!(!nicks.is_current("rusty[m]"));
+    }
+}
@@ -27,6 +27,8 @@ pub struct HttpProxy {
     username: Option<String>,
     /// Optional password for Basic auth.
     password: Option<String>,
+    /// Whether the proxy resolves the target hostname.
+    remote_dns: bool,
 }
 
 impl HttpProxy {
@@ -42,9 +44,16 @@ impl HttpProxy {
             proxy_port,
             username,
             password,
+            remote_dns: true,
         }
     }
 
+    /// Set whether the target hostname is resolved by the proxy.
+    pub fn with_remote_dns(mut self, remote_dns: bool) -> Self {
+        self.remote_dns = remote_dns;
+        self
+    }
+
     /// Return the proxy address in `host:port` format.
     pub fn proxy_address(&self) -> String {
         format!("{}:{}", self.proxy_addr, self.proxy_port)
@@ -52,9 +61,15 @@ impl HttpProxy {
 
     /// Build the HTTP CONNECT request for the target.
     fn build_connect_request(&self, target_addr: &str, target_port: u16) -> String {
+        // IPv6 literals must be bracketed to separate them from the port
+        let target = if target_addr.contains(':') && !target_addr.starts_with('[') {
+            format!("[{target_addr}]:{target_port}")
+        } else {
+            format!("{target_addr}:{target_port}")
+        };
         let mut request = format!(
-            "CONNECT {target_addr}:{target_port} HTTP/1.1\r\n\
-             Host: {target_addr}:{target_port}\r\n"
+            "CONNECT {target} HTTP/1.1\r\n\
+             Host: {target}\r\n"
         );
 
         // Add Basic auth header if credentials are provided
@@ -86,8 +101,16 @@ impl ProxyConnector for HttpProxy {
             ))
         })?;
 
-        // Send the CONNECT request
-        let connect_request = self.build_connect_request(target_addr, target_port);
+        // Send the CONNECT request, resolving the target ourselves if requested
+        let target_host = if self.remote_dns {
+            target_addr.to_string()
+        } else {
+            super::resolve_locally(target_addr, target_port)
+                .await?
+                .ip()
+                .to_string()
+        };
+        let connect_request = self.build_connect_request(&target_host, target_port);
         let (reader, mut writer) = tokio::io::split(stream);
         writer
             .write_all(connect_request.as_bytes())
@@ -114,6 +137,19 @@ impl ProxyConnector for HttpProxy {
             ))
         })?;
 
+        if status_code == 407 {
+            let message = format!(
+                "HTTP CONNECT to {target_addr}:{target_port} via {proxy_addr} \
+                 rejected: {}",
+                status_line.trim()
+            );
+            return Err(if self.username.is_some() && self.password.is_some() {
+                Error::ProxyAuthFailed(message)
+            } else {
+                Error::ProxyAuthRequired(message)
+            });
+        }
+
         if status_code != 200 {
             return Err(Error::Connection(format!(
                 "HTTP CONNECT to {target_addr}:{target_port} via {proxy_addr} \
@@ -181,6 +217,15 @@ mod tests {
         assert!(request.ends_with("\r\n\r\n"));
     }
 
+    #[test]
+    fn test_connect_request_ipv6_target() {
+        let proxy = HttpProxy::new("proxy.local".to_string(), 8080, None, None);
+        let request = proxy.build_connect_request("2001:db8::1", 6697);
+
+        assert!(request.starts_with("CONNECT [2001:db8::1]:6697 HTTP/1.1\r\n"));
+        assert!(request.contains("Host: [2001:db8::1]:6697\r\n"));
+    }
+
     #[test]
     fn test_connect_request_with_auth() {
         let proxy = HttpProxy::new(
@@ -235,4 +280,62 @@ mod tests {
             "Error should mention HTTP proxy: {msg}"
         );
     }
+
+    /// Spawn a fake proxy that answers every CONNECT with the given response.
+    async fn fake_proxy(response: &'static str) -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                request.push_str(&line);
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            writer.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy_auth_errors() {
+        const REJECTED: &str = "HTTP/1.1 407 Proxy Authentication Required\r\n\r\n";
+
+        let (port, _) = fake_proxy(REJECTED).await;
+        let proxy = HttpProxy::new("127.0.0.1".to_string(), port, None, None);
+        let err = proxy.connect("irc.example.com", 6667).await.unwrap_err();
+        assert!(matches!(err, Error::ProxyAuthRequired(_)), "{err}");
+
+        let (port, _) = fake_proxy(REJECTED).await;
+        let proxy = HttpProxy::new(
+            "127.0.0.1".to_string(),
+            port,
+            Some("alice".to_string()),
+            Some("wrong".to_string()),
+        );
+        let err = proxy.connect("irc.example.com", 6667).await.unwrap_err();
+        assert!(matches!(err, Error::ProxyAuthFailed(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy_local_dns() {
+        let (port, request) = fake_proxy("HTTP/1.1 200 Connection established\r\n\r\n").await;
+        let proxy =
+            HttpProxy::new("127.0.0.1".to_string(), port, None, None).with_remote_dns(false);
+        proxy.connect("localhost", 6667).await.unwrap();
+
+        let request = request.await.unwrap();
+        assert!(
+            request.starts_with("CONNECT 127.0.0.1:6667 ")
+                || request.starts_with("CONNECT [::1]:6667 "),
+            "{request}"
+        );
+    }
 }
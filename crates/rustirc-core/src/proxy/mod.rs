@@ -7,10 +7,11 @@ pub mod http;
 pub mod socks5;
 
 use async_trait::async_trait;
+use std::net::SocketAddr;
 use tokio::net::TcpStream;
 
 use crate::config::ProxyConfig;
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 pub use http::HttpProxy;
 pub use socks5::Socks5Proxy;
@@ -29,17 +30,36 @@ pub trait ProxyConnector: Send + Sync {
 /// Create a `ProxyConnector` from a `ProxyConfig`.
 pub fn from_config(config: &ProxyConfig) -> Box<dyn ProxyConnector> {
     match config.proxy_type {
-        crate::config::ProxyType::Socks5 => Box::new(Socks5Proxy::new(
-            config.address.clone(),
-            config.port,
-            config.username.clone(),
-            config.password.clone(),
-        )),
-        crate::config::ProxyType::HttpConnect => Box::new(HttpProxy::new(
-            config.address.clone(),
-            config.port,
-            config.username.clone(),
-            config.password.clone(),
-        )),
+        crate::config::ProxyType::Socks5 => Box::new(
+            Socks5Proxy::new(
+                config.address.clone(),
+                config.port,
+                config.username.clone(),
+                config.password.clone(),
+            )
+            .with_remote_dns(config.remote_dns),
+        ),
+        crate::config::ProxyType::HttpConnect => Box::new(
+            HttpProxy::new(
+                config.address.clone(),
+                config.port,
+                config.username.clone(),
+                config.password.clone(),
+            )
+            .with_remote_dns(config.remote_dns),
+        ),
     }
 }
+
+/// Resolve the target host locally, for proxies configured without remote DNS.
+async fn resolve_locally(target_addr: &str, target_port: u16) -> Result<SocketAddr> {
+    tokio::net::lookup_host((target_addr, target_port))
+        .await
+        .map_err(|e| Error::ConnectionFailed(format!("DNS resolution failed: {e}")))?
+        .next()
+        .ok_or_else(|| {
+            Error::ConnectionFailed(format!(
+                "No addresses found for {target_addr}:{target_port}"
+            ))
+        })
+}
@@ -9,6 +9,7 @@
 use async_trait::async_trait;
 use tokio::net::TcpStream;
 use tokio_socks::tcp::Socks5Stream;
+use tokio_socks::TargetAddr;
 
 use super::ProxyConnector;
 use crate::error::{Error, Result};
@@ -27,6 +28,8 @@ pub struct Socks5Proxy {
     username: Option<String>,
     /// Optional password for proxy authentication.
     password: Option<String>,
+    /// Whether the proxy resolves target hostnames (SOCKS5 domain addressing).
+    remote_dns: bool,
 }
 
 impl Socks5Proxy {
@@ -42,9 +45,16 @@ impl Socks5Proxy {
             proxy_port,
             username,
             password,
+            remote_dns: true,
         }
     }
 
+    /// Set whether target hostnames are resolved by the proxy.
+    pub fn with_remote_dns(mut self, remote_dns: bool) -> Self {
+        self.remote_dns = remote_dns;
+        self
+    }
+
     /// Return the proxy address in `host:port` format.
     pub fn proxy_address(&self) -> String {
         format!("{}:{}", self.proxy_addr, self.proxy_port)
@@ -59,7 +69,11 @@ impl ProxyConnector for Socks5Proxy {
     /// SOCKS5. Otherwise, uses unauthenticated mode.
     async fn connect(&self, target_addr: &str, target_port: u16) -> Result<TcpStream> {
         let proxy_addr = self.proxy_address();
-        let target = (target_addr, target_port);
+        let target = if self.remote_dns {
+            TargetAddr::Domain(target_addr.into(), target_port)
+        } else {
+            TargetAddr::Ip(super::resolve_locally(target_addr, target_port).await?)
+        };
 
         let stream = match (&self.username, &self.password) {
             (Some(user), Some(pass)) => Socks5Stream::connect_with_password(
@@ -70,18 +84,24 @@ impl ProxyConnector for Socks5Proxy {
             )
             .await
             .map_err(|e| {
-                Error::Connection(format!(
-                    "SOCKS5 authenticated connection to {target_addr}:{target_port} \
-                         via {proxy_addr} failed: {e}"
-                ))
+                map_socks_error(
+                    e,
+                    format!(
+                        "SOCKS5 authenticated connection to {target_addr}:{target_port} \
+                         via {proxy_addr} failed"
+                    ),
+                )
             })?,
             _ => Socks5Stream::connect(proxy_addr.as_str(), target)
                 .await
                 .map_err(|e| {
-                    Error::Connection(format!(
-                        "SOCKS5 connection to {target_addr}:{target_port} \
-                             via {proxy_addr} failed: {e}"
-                    ))
+                    map_socks_error(
+                        e,
+                        format!(
+                            "SOCKS5 connection to {target_addr}:{target_port} \
+                             via {proxy_addr} failed"
+                        ),
+                    )
                 })?,
         };
 
@@ -89,6 +109,19 @@ impl ProxyConnector for Socks5Proxy {
     }
 }
 
+/// Map a `tokio-socks` error onto our error type, separating auth failures.
+fn map_socks_error(error: tokio_socks::Error, context: String) -> Error {
+    match error {
+        tokio_socks::Error::PasswordAuthFailure(_) | tokio_socks::Error::InvalidAuthValues(_) => {
+            Error::ProxyAuthFailed(format!("{context}: {error}"))
+        }
+        tokio_socks::Error::NoAcceptableAuthMethods | tokio_socks::Error::AuthorizationRequired => {
+            Error::ProxyAuthRequired(format!("{context}: {error}"))
+        }
+        _ => Error::Connection(format!("{context}: {error}")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +164,64 @@ mod tests {
             "Error should mention SOCKS5: {msg}"
         );
     }
+
+    /// Spawn a fake SOCKS5 server that replies with the given bytes after
+    /// reading each client message, returning everything the client sent.
+    async fn fake_socks5(replies: Vec<&'static [u8]>) -> (u16, tokio::task::JoinHandle<Vec<u8>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            for reply in replies {
+                let mut buf = [0u8; 512];
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                stream.write_all(reply).await.unwrap();
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn test_socks5_auth_errors() {
+        // No acceptable authentication methods
+        let (port, _) = fake_socks5(vec![&[0x05, 0xff]]).await;
+        let proxy = Socks5Proxy::new("127.0.0.1".to_string(), port, None, None);
+        let err = proxy.connect("irc.example.com", 6667).await.unwrap_err();
+        assert!(matches!(err, Error::ProxyAuthRequired(_)), "{err}");
+
+        // Username/password rejected
+        let (port, _) = fake_socks5(vec![&[0x05, 0x02], &[0x01, 0x01]]).await;
+        let proxy = Socks5Proxy::new(
+            "127.0.0.1".to_string(),
+            port,
+            Some("user".to_string()),
+            Some("wrong".to_string()),
+        );
+        let err = proxy.connect("irc.example.com", 6667).await.unwrap_err();
+        assert!(matches!(err, Error::ProxyAuthFailed(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_socks5_remote_dns_sends_domain() {
+        // Greeting accepted, then connection refused (0x05) so the test ends early
+        let (port, received) = fake_socks5(vec![
+            &[0x05, 0x00],
+            &[0x05, 0x05, 0x00, 0x01, 0, 0, 0, 0, 0, 0],
+        ])
+        .await;
+        let proxy = Socks5Proxy::new("127.0.0.1".to_string(), port, None, None);
+        let err = proxy.connect("irc.example.com", 6667).await.unwrap_err();
+        assert!(matches!(err, Error::Connection(_)), "{err}");
+
+        let received = received.await.unwrap();
+        let domain = b"irc.example.com";
+        assert!(received
+            .windows(domain.len() + 2)
+            .any(|w| w[0] == 0x03 && w[1] as usize == domain.len() && &w[2..] == domain));
+    }
 }
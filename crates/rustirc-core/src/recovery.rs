@@ -3,16 +3,19 @@
 //! This module provides comprehensive error recovery capabilities:
 //! - Automatic reconnection with exponential backoff
 //! - Connection health monitoring
-//! - State recovery after reconnection
 //! - Error classification and handling strategies
 //! - Circuit breaker pattern for failing connections
+//! - Failover between a network's endpoints
 
+use crate::config::{EndpointRotation, ServerEndpoint};
 use crate::connection::{ConnectionConfig, ConnectionState, IrcConnection};
 use crate::error::{Error, Result};
-use crate::events::{Event, EventBus};
+use crate::events::{Event, EventBus, EventHandler};
 use crate::state::{ServerState, StateManager};
+use async_trait::async_trait;
 use rustirc_protocol::Command;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
@@ -78,8 +81,10 @@ impl ErrorType {
             Error::ConnectionTimeout => ErrorType::Timeout,
             Error::InvalidAddress(_) => ErrorType::DnsError,
             Error::InvalidTlsName(_) => ErrorType::TlsError,
-            Error::TlsError(_) => ErrorType::TlsError,
+            Error::TlsError(_) | Error::CertificateChanged { .. } => ErrorType::TlsError,
             Error::Protocol(_) => ErrorType::ProtocolError,
+            Error::AuthenticationFailed(_) => ErrorType::AuthError,
+            Error::ProxyAuthRequired(_) | Error::ProxyAuthFailed(_) => ErrorType::AuthError,
             _ => ErrorType::Unknown,
         }
     }
@@ -109,6 +114,18 @@ impl Default for ReconnectConfig {
     }
 }
 
+impl ReconnectConfig {
+    /// Reconnection settings of a connection: at most `reconnect_attempts`
+    /// attempts, backing off from `reconnect_delay`
+    pub fn for_connection(config: &ConnectionConfig) -> Self {
+        Self {
+            max_attempts: config.reconnect_attempts,
+            initial_delay: config.reconnect_delay,
+            ..Default::default()
+        }
+    }
+}
+
 /// Circuit breaker states
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CircuitState {
@@ -204,6 +221,81 @@ impl CircuitBreaker {
     }
 }
 
+/// A network's endpoints and the one currently in use
+///
+/// After a failed connection [`advance`](Self::advance) moves to the next
+/// endpoint according to the [`EndpointRotation`].
+#[derive(Debug, Clone)]
+pub struct EndpointList {
+    endpoints: Vec<ServerEndpoint>,
+    rotation: EndpointRotation,
+    current: usize,
+}
+
+impl EndpointList {
+    /// Create a list starting at the first endpoint
+    ///
+    /// # Panics
+    ///
+    /// Panics if `endpoints` is empty.
+    pub fn new(endpoints: Vec<ServerEndpoint>, rotation: EndpointRotation) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "a network needs at least one endpoint"
+        );
+        Self {
+            endpoints,
+            rotation,
+            current: 0,
+        }
+    }
+
+    /// The endpoint in use
+    pub fn current(&self) -> &ServerEndpoint {
+        &self.endpoints[self.current]
+    }
+
+    /// Switch to the next endpoint and return it
+    pub fn advance(&mut self) -> &ServerEndpoint {
+        let len = self.endpoints.len();
+        if len > 1 {
+            self.current = match self.rotation {
+                EndpointRotation::RoundRobin => (self.current + 1) % len,
+                EndpointRotation::Random => {
+                    // Pick among the other endpoints
+                    let next = rand::random_range(0..len - 1);
+                    if next >= self.current {
+                        next + 1
+                    } else {
+                        next
+                    }
+                }
+            };
+        }
+        self.current()
+    }
+
+    /// Number of endpoints
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Always false; a list has at least one endpoint
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+}
+
+/// Connection outcomes recorded for one endpoint
+#[derive(Debug, Clone, Default)]
+pub struct EndpointStats {
+    pub attempt_count: u32,
+    pub success_count: u32,
+    pub failure_count: u32,
+    pub last_attempt: Option<Instant>,
+    pub last_error: Option<String>,
+}
+
 /// Recovery manager for a single connection
 #[derive(Debug)]
 pub struct ConnectionRecovery {
@@ -212,7 +304,15 @@ pub struct ConnectionRecovery {
     circuit_breaker: CircuitBreaker,
     current_attempt: u32,
     last_attempt: Option<Instant>,
+    /// Whether the current attempt has already been recorded as failed
+    attempt_failed: bool,
     state_before_disconnect: Option<ServerState>,
+    /// Endpoint the next outcomes are recorded against
+    endpoint: Option<String>,
+    endpoint_stats: HashMap<String, EndpointStats>,
+    /// The network's endpoints, carried over to a new connection
+    endpoints: Vec<ServerEndpoint>,
+    rotation: EndpointRotation,
 }
 
 impl ConnectionRecovery {
@@ -223,10 +323,31 @@ impl ConnectionRecovery {
             circuit_breaker: CircuitBreaker::new(5, Duration::from_secs(60), 2),
             current_attempt: 0,
             last_attempt: None,
+            attempt_failed: false,
             state_before_disconnect: None,
+            endpoint: None,
+            endpoint_stats: HashMap::new(),
+            endpoints: Vec::new(),
+            rotation: EndpointRotation::default(),
         }
     }
 
+    /// Remember the network's endpoints for reconnecting
+    pub fn set_endpoints(&mut self, endpoints: Vec<ServerEndpoint>, rotation: EndpointRotation) {
+        self.endpoints = endpoints;
+        self.rotation = rotation;
+    }
+
+    /// Record that the connection switched to another endpoint
+    pub fn set_endpoint(&mut self, endpoint: impl Into<String>) {
+        self.endpoint = Some(endpoint.into());
+    }
+
+    fn current_endpoint_stats(&mut self) -> Option<&mut EndpointStats> {
+        let endpoint = self.endpoint.clone()?;
+        Some(self.endpoint_stats.entry(endpoint).or_default())
+    }
+
     /// Check if we should attempt reconnection
     pub fn should_reconnect(&mut self) -> bool {
         if !self.config.enabled {
@@ -252,6 +373,18 @@ impl ConnectionRecovery {
         true
     }
 
+    /// Delay before the next attempt, or `None` once the connection should
+    /// give up (reconnection disabled, attempts used up or circuit open)
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if !self.config.enabled
+            || self.current_attempt >= self.config.max_attempts
+            || !self.circuit_breaker.should_allow_request()
+        {
+            return None;
+        }
+        Some(self.calculate_delay())
+    }
+
     /// Calculate delay for next reconnection attempt
     fn calculate_delay(&self) -> Duration {
         let mut delay = self.config.initial_delay.as_millis() as f64
@@ -275,22 +408,47 @@ impl ConnectionRecovery {
     pub fn record_attempt(&mut self) {
         self.current_attempt += 1;
         self.last_attempt = Some(Instant::now());
+        self.attempt_failed = false;
+        if let Some(stats) = self.current_endpoint_stats() {
+            stats.attempt_count += 1;
+            stats.last_attempt = Some(Instant::now());
+        }
     }
 
     /// Record successful connection
     pub fn record_success(&mut self) {
         self.current_attempt = 0;
+        self.attempt_failed = false;
         self.circuit_breaker.record_success();
+        if let Some(stats) = self.current_endpoint_stats() {
+            stats.success_count += 1;
+        }
     }
 
     /// Record failed connection
+    ///
+    /// Counts once per attempt, or once per connection after it succeeded,
+    /// however many errors it reports.
     pub fn record_failure(&mut self, error: &Error) {
+        if self.attempt_failed {
+            return;
+        }
+        self.attempt_failed = true;
+
         let error_type = ErrorType::from_error(error);
         self.circuit_breaker.record_failure();
+        if let Some(stats) = self.current_endpoint_stats() {
+            stats.failure_count += 1;
+            stats.last_error = Some(error.to_string());
+        }
 
         warn!(
-            "Connection {} failed (attempt {}): {} (type: {:?})",
-            self.connection_id, self.current_attempt, error, error_type
+            "Connection {} failed (attempt {}, endpoint {}): {} (type: {:?})",
+            self.connection_id,
+            self.current_attempt,
+            self.endpoint.as_deref().unwrap_or("unknown"),
+            error,
+            error_type
         );
     }
 
@@ -311,13 +469,14 @@ pub struct RecoveryManager {
     state_manager: Arc<StateManager>,
     event_bus: Arc<EventBus>,
     recovery_tx: mpsc::UnboundedSender<RecoveryTask>,
+    /// Set once the event handler is registered
+    tracking: AtomicBool,
 }
 
 /// Recovery task
 #[derive(Debug)]
 pub enum RecoveryTask {
     ScheduleReconnect { connection_id: String },
-    RestoreState { connection_id: String },
     HealthCheck { connection_id: String },
 }
 
@@ -333,6 +492,7 @@ impl RecoveryManager {
             state_manager,
             event_bus,
             recovery_tx,
+            tracking: AtomicBool::new(false),
         };
 
         (manager, recovery_rx)
@@ -347,24 +507,99 @@ impl RecoveryManager {
             .insert(connection_id, recovery);
     }
 
+    /// Record the endpoints of a registered connection
+    pub async fn set_endpoints(
+        &self,
+        connection_id: &str,
+        endpoints: Vec<ServerEndpoint>,
+        rotation: EndpointRotation,
+    ) {
+        if let Some(recovery) = self.connections.write().await.get_mut(connection_id) {
+            recovery.set_endpoints(endpoints, rotation);
+        }
+    }
+
+    /// Register a connection with its configured endpoints and reconnection
+    /// settings, unless it is registered already, and track its events
+    pub async fn track_connection(&self, connection_id: &str, config: &ConnectionConfig) {
+        self.connections
+            .write()
+            .await
+            .entry(connection_id.to_string())
+            .or_insert_with(|| {
+                let mut recovery = ConnectionRecovery::new(
+                    connection_id.to_string(),
+                    ReconnectConfig::for_connection(config),
+                );
+                recovery.set_endpoints(config.endpoints(), config.rotation);
+                recovery
+            });
+        self.start_event_tracking().await;
+    }
+
+    /// Delay before reconnecting a connection, or `None` when it should give up
+    pub async fn reconnect_delay(&self, connection_id: &str) -> Option<Duration> {
+        self.connections
+            .write()
+            .await
+            .get_mut(connection_id)?
+            .next_delay()
+    }
+
+    /// Track connection attempts and endpoint changes from the event bus
+    ///
+    /// Only the first call registers the event handler.
+    pub async fn start_event_tracking(&self) {
+        if self.tracking.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.event_bus
+            .register(RecoveryEvents {
+                connections: self.connections.clone(),
+            })
+            .await;
+    }
+
     /// Create connection config from recovery data
+    ///
+    /// The new connection starts at the endpoint that was in use and keeps
+    /// the network's other endpoints to fail over to.
     pub async fn create_connection_config(&self, connection_id: &str) -> Option<ConnectionConfig> {
         let connections = self.connections.read().await;
         if let Some(recovery) = connections.get(connection_id) {
             if let Some(saved_state) = recovery.get_saved_state() {
+                let primary = recovery
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| Some(endpoint.to_string()) == recovery.endpoint)
+                    .cloned()
+                    .unwrap_or_else(|| ServerEndpoint {
+                        address: saved_state.address.clone(),
+                        port: saved_state.port,
+                        use_tls: saved_state.use_tls,
+                        password: None,
+                    });
+                let fallback_endpoints = recovery
+                    .endpoints
+                    .iter()
+                    .filter(|endpoint| **endpoint != primary)
+                    .cloned()
+                    .collect();
                 return Some(ConnectionConfig {
-                    server: saved_state.address.clone(),
-                    port: saved_state.port,
-                    use_tls: saved_state.use_tls,
-                    verify_tls: true,
+                    server: primary.address,
+                    port: primary.port,
+                    use_tls: primary.use_tls,
                     nickname: saved_state.nickname.clone(),
                     username: saved_state.username.clone(),
                     realname: saved_state.realname.clone(),
-                    password: None,
+                    password: primary.password,
+                    fallback_endpoints,
+                    rotation: recovery.rotation,
                     reconnect_attempts: recovery.config.max_attempts,
                     reconnect_delay: recovery.config.initial_delay,
                     ping_timeout: std::time::Duration::from_secs(300),
                     message_timeout: std::time::Duration::from_secs(30),
+                    ..Default::default()
                 });
             }
         }
@@ -436,6 +671,10 @@ impl RecoveryManager {
     }
 
     /// Create new IRC connection from recovery data
+    ///
+    /// The new connection starts with an empty session. A connection that
+    /// drops after registering reconnects by itself and keeps its session;
+    /// see [`IrcConnection::connect`].
     pub async fn create_irc_connection(
         &self,
         connection_id: &str,
@@ -477,59 +716,13 @@ impl RecoveryManager {
     }
 
     /// Handle successful connection
+    ///
+    /// The connection restores its own session once it registers; see
+    /// [`Session`](crate::session::Session).
     pub async fn handle_connection_success(&self, connection_id: String) -> Result<()> {
         let mut connections = self.connections.write().await;
         if let Some(recovery) = connections.get_mut(&connection_id) {
             recovery.record_success();
-
-            // Schedule state restoration
-            self.recovery_tx
-                .send(RecoveryTask::RestoreState { connection_id })
-                .map_err(|_| Error::ChannelSend)?;
-        }
-
-        Ok(())
-    }
-
-    /// Restore connection state after reconnection
-    pub async fn restore_connection_state(&self, connection_id: String) -> Result<()> {
-        let connections = self.connections.read().await;
-        if let Some(recovery) = connections.get(&connection_id) {
-            if let Some(saved_state) = recovery.get_saved_state() {
-                info!("Restoring state for connection {}", connection_id);
-
-                // Rejoin channels
-                for (channel_name, channel_state) in &saved_state.channels {
-                    if channel_state.joined {
-                        let join_cmd = Command::Join {
-                            channels: vec![channel_name.clone()],
-                            keys: vec![],
-                        };
-
-                        // Send through event system
-                        let event = Event::MessageSent {
-                            connection_id: connection_id.clone(),
-                            message: join_cmd.to_message(),
-                        };
-                        self.event_bus.emit(event).await;
-                    }
-                }
-
-                // Restore nickname if different
-                if !saved_state.nickname.is_empty() {
-                    let nick_cmd = Command::Nick {
-                        nickname: saved_state.nickname.clone(),
-                    };
-
-                    let event = Event::MessageSent {
-                        connection_id: connection_id.clone(),
-                        message: nick_cmd.to_message(),
-                    };
-                    self.event_bus.emit(event).await;
-                }
-
-                info!("State restoration completed for {}", connection_id);
-            }
         }
 
         Ok(())
@@ -634,6 +827,8 @@ impl RecoveryManager {
                     last_attempt: recovery.last_attempt,
                     circuit_state: recovery.circuit_breaker.state.clone(),
                     has_saved_state: recovery.state_before_disconnect.is_some(),
+                    current_endpoint: recovery.endpoint.clone(),
+                    endpoints: recovery.endpoint_stats.clone(),
                 },
             );
         }
@@ -642,6 +837,49 @@ impl RecoveryManager {
     }
 }
 
+/// Records connection attempts and their outcomes against the endpoint
+/// each connection is using
+struct RecoveryEvents {
+    connections: Arc<RwLock<HashMap<String, ConnectionRecovery>>>,
+}
+
+#[async_trait]
+impl EventHandler for RecoveryEvents {
+    async fn handle(&self, event: &Event) {
+        let mut connections = self.connections.write().await;
+        match event {
+            // Each endpoint a connection tries is one attempt
+            Event::EndpointChanged {
+                connection_id,
+                endpoint,
+            } => {
+                if let Some(recovery) = connections.get_mut(connection_id) {
+                    recovery.set_endpoint(endpoint.clone());
+                    recovery.record_attempt();
+                }
+            }
+            Event::Connected { connection_id } => {
+                if let Some(recovery) = connections.get_mut(connection_id) {
+                    recovery.record_success();
+                }
+            }
+            Event::Disconnected {
+                connection_id,
+                reason,
+            }
+            | Event::Error {
+                connection_id: Some(connection_id),
+                error: reason,
+            } => {
+                if let Some(recovery) = connections.get_mut(connection_id) {
+                    recovery.record_failure(&Error::ConnectionFailed(reason.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// Recovery statistics
 #[derive(Debug, Clone)]
 pub struct RecoveryStats {
@@ -649,6 +887,10 @@ pub struct RecoveryStats {
     pub last_attempt: Option<Instant>,
     pub circuit_state: CircuitState,
     pub has_saved_state: bool,
+    /// Endpoint in use, shown as `host:port`
+    pub current_endpoint: Option<String>,
+    /// Outcomes keyed by endpoint
+    pub endpoints: HashMap<String, EndpointStats>,
 }
 
 /// Recovery task processor
@@ -683,14 +925,6 @@ pub async fn process_recovery_tasks(
                     });
                 }
             }
-            RecoveryTask::RestoreState { connection_id } => {
-                if let Err(e) = recovery_manager
-                    .restore_connection_state(connection_id)
-                    .await
-                {
-                    error!("Failed to restore connection state: {}", e);
-                }
-            }
             RecoveryTask::HealthCheck { connection_id } => {
                 if let Err(e) = recovery_manager.health_check(connection_id).await {
                     error!("Health check failed: {}", e);
@@ -699,3 +933,225 @@ pub async fn process_recovery_tasks(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockIrcServer, MockServerConfig};
+
+    fn endpoints(count: u16) -> Vec<ServerEndpoint> {
+        (0..count)
+            .map(|i| ServerEndpoint {
+                address: format!("irc{i}.example.com"),
+                port: 6697 + i,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_endpoint_rotation() {
+        let mut list = EndpointList::new(endpoints(3), EndpointRotation::RoundRobin);
+        assert_eq!(list.current().port, 6697);
+        assert_eq!(list.advance().port, 6698);
+        assert_eq!(list.advance().port, 6699);
+        assert_eq!(list.advance().port, 6697);
+
+        let mut list = EndpointList::new(endpoints(3), EndpointRotation::Random);
+        for _ in 0..20 {
+            let previous = list.current().port;
+            assert_ne!(list.advance().port, previous);
+        }
+
+        let mut single = EndpointList::new(endpoints(1), EndpointRotation::Random);
+        assert_eq!(single.advance().port, 6697);
+    }
+
+    #[test]
+    fn test_stats_per_endpoint() {
+        let mut recovery = ConnectionRecovery::new("net".to_string(), ReconnectConfig::default());
+        recovery.set_endpoint("a:+6697");
+        recovery.record_attempt();
+        recovery.record_failure(&Error::ConnectionTimeout);
+        recovery.set_endpoint("b:6667");
+        recovery.record_attempt();
+        recovery.record_success();
+
+        let a = &recovery.endpoint_stats["a:+6697"];
+        assert_eq!(
+            (a.attempt_count, a.failure_count, a.success_count),
+            (1, 1, 0)
+        );
+        assert!(a.last_error.is_some());
+        let b = &recovery.endpoint_stats["b:6667"];
+        assert_eq!(
+            (b.attempt_count, b.failure_count, b.success_count),
+            (1, 0, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_carried_through_recovery() {
+        let event_bus = Arc::new(EventBus::new());
+        let (manager, _tasks) =
+            RecoveryManager::new(Arc::new(StateManager::new()), event_bus.clone());
+        manager
+            .register_connection("net".to_string(), ReconnectConfig::default())
+            .await;
+        manager
+            .set_endpoints("net", endpoints(3), EndpointRotation::Random)
+            .await;
+        manager.start_event_tracking().await;
+
+        for endpoint in ["irc0.example.com:+6697", "irc1.example.com:+6698"] {
+            event_bus
+                .emit(Event::EndpointChanged {
+                    connection_id: "net".to_string(),
+                    endpoint: endpoint.to_string(),
+                })
+                .await;
+        }
+        let stats = &manager.get_recovery_stats().await["net"];
+        assert_eq!(stats.attempt_count, 2);
+        assert_eq!(
+            stats.current_endpoint.as_deref(),
+            Some("irc1.example.com:+6698")
+        );
+        assert_eq!(stats.endpoints["irc0.example.com:+6697"].attempt_count, 1);
+
+        let saved = ServerState::new(
+            "net".to_string(),
+            "irc1.example.com".to_string(),
+            6698,
+            true,
+        );
+        manager
+            .connections
+            .write()
+            .await
+            .get_mut("net")
+            .unwrap()
+            .save_state(saved);
+        let config = manager.create_connection_config("net").await.unwrap();
+        assert_eq!(
+            (config.server.as_str(), config.port),
+            ("irc1.example.com", 6698)
+        );
+        assert_eq!(config.rotation, EndpointRotation::Random);
+        let fallbacks: Vec<_> = config.fallback_endpoints.iter().map(|e| e.port).collect();
+        assert_eq!(fallbacks, [6697, 6699]);
+
+        event_bus
+            .emit(Event::Connected {
+                connection_id: "net".to_string(),
+            })
+            .await;
+        assert_eq!(manager.get_recovery_stats().await["net"].attempt_count, 0);
+    }
+
+    /// Wait for the next time the connection registers
+    async fn registered(states: &mut tokio::sync::broadcast::Receiver<ConnectionState>) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while states.recv().await.unwrap() != ConnectionState::Registered {}
+        })
+        .await
+        .expect("connection did not register");
+    }
+
+    /// Poll a connection's statistics until `done` holds
+    async fn wait_for_stats(
+        manager: &RecoveryManager,
+        connection_id: &str,
+        done: impl Fn(&RecoveryStats) -> bool,
+    ) -> RecoveryStats {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let stats = manager.get_recovery_stats().await[connection_id].clone();
+                if done(&stats) {
+                    return stats;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("statistics were not recorded")
+    }
+
+    #[tokio::test]
+    async fn test_connection_outcomes_recorded_per_endpoint() {
+        let mut server = MockIrcServer::new(MockServerConfig::default());
+        server.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        // Nothing listens on a port whose listener was dropped
+        let refused = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let event_bus = Arc::new(EventBus::new());
+        let (manager, _tasks) =
+            RecoveryManager::new(Arc::new(StateManager::new()), event_bus.clone());
+        let manager = Arc::new(manager);
+        let mock = ServerEndpoint {
+            address: "127.0.0.1".to_string(),
+            port: server.local_addr().unwrap().port(),
+            use_tls: false,
+            password: None,
+        };
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port: refused,
+            use_tls: false,
+            fallback_endpoints: vec![mock.clone()],
+            nickname: "tracked".to_string(),
+            reconnect_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let connection = IrcConnection::with_recovery(config, event_bus, manager.clone());
+        let connection_id = connection.id().to_string();
+        let mut states = connection.subscribe_state_changes();
+        tokio::spawn({
+            let connection = connection.clone();
+            async move { connection.connect().await }
+        });
+
+        registered(&mut states).await;
+        // Connected is published shortly after the state changes
+        let stats = wait_for_stats(&manager, &connection_id, |stats| {
+            stats.endpoints[&mock.to_string()].success_count > 0
+        })
+        .await;
+        let failed = &stats.endpoints[&format!("127.0.0.1:{refused}")];
+        assert_eq!((failed.attempt_count, failed.failure_count), (1, 1));
+        assert!(failed.last_error.is_some());
+        let working = &stats.endpoints[&mock.to_string()];
+        assert_eq!(
+            (
+                working.attempt_count,
+                working.failure_count,
+                working.success_count
+            ),
+            (1, 0, 1)
+        );
+
+        // A dropped link is a failure of the endpoint it was using
+        server.drop_clients();
+        registered(&mut states).await;
+        let stats = wait_for_stats(&manager, &connection_id, |stats| {
+            stats.endpoints[&mock.to_string()].success_count > 1
+        })
+        .await;
+        let working = &stats.endpoints[&mock.to_string()];
+        assert_eq!(
+            (
+                working.attempt_count,
+                working.failure_count,
+                working.success_count
+            ),
+            (2, 1, 2)
+        );
+
+        server.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,625 @@
+//! Correlating commands with the server's replies
+//!
+//! With the IRCv3 `labeled-response` capability a command carries a unique
+//! `label` tag, and the server answers with a single labeled message, a
+//! labeled `BATCH`, or an `ACK` when it has nothing to say. Without the
+//! capability, replies are matched heuristically by the numerics each query
+//! is known to produce and the names they mention, so concurrent queries
+//! about different names each get their own replies.
+//!
+//! See: <https://ircv3.net/specs/extensions/labeled-response>
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rustirc_protocol::command::{ChatHistorySubcommand, MonitorSubcommand};
+use rustirc_protocol::{CaseMapping, Command, Message, MessageRef};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// Counter for unique `label` tags
+static NEXT_LABEL: AtomicU64 = AtomicU64::new(1);
+
+/// A label not used by any other request in this process
+pub fn next_label() -> String {
+    format!("rirc{}", NEXT_LABEL.fetch_add(1, Ordering::Relaxed))
+}
+
+/// What ends a reply that has no label
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expected {
+    /// `replies` are collected until one of `ends`
+    Numerics {
+        replies: &'static [&'static str],
+        ends: &'static [&'static str],
+    },
+    /// A `BATCH` of this type, collected until it closes
+    Batch(&'static str),
+}
+
+/// Recognizes the reply to a command sent without a label
+///
+/// Heuristics only exist for queries whose replies end in a known numeric,
+/// such as WHOIS, WHO and NAMES, or arrive in a batch, such as CHATHISTORY.
+/// A `FAIL` for the command, or `ERR_UNKNOWNCOMMAND`/`ERR_NEEDMOREPARAMS`
+/// naming it, ends any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyMatcher {
+    command: String,
+    expected: Expected,
+    /// Nickname, mask or channel the final reply must mention
+    subject: Option<String>,
+    /// Names the other replies must mention one of, if any
+    mentions: Vec<String>,
+    /// A reply that ends the request unless one of the ends directly follows
+    closing: Option<&'static str>,
+    /// Whether the `closing` reply has arrived
+    closed: bool,
+}
+
+/// How a message relates to a pending request
+enum Matched {
+    Reply,
+    End,
+    BatchStart(String),
+}
+
+impl ReplyMatcher {
+    fn numerics(
+        command: &str,
+        replies: &'static [&'static str],
+        ends: &'static [&'static str],
+        subject: Option<&String>,
+    ) -> Self {
+        Self {
+            command: command.to_string(),
+            expected: Expected::Numerics { replies, ends },
+            subject: subject.cloned(),
+            mentions: Vec::new(),
+            closing: None,
+            closed: false,
+        }
+    }
+
+    /// Only take replies that mention one of `names`
+    fn mentioning(mut self, names: &[String]) -> Self {
+        self.mentions = names.to_vec();
+        self
+    }
+
+    /// End the request after `reply` when no end follows it
+    fn closed_by(mut self, reply: &'static str) -> Self {
+        self.closing = Some(reply);
+        self
+    }
+
+    /// The matcher for a command's replies, if they can be recognized
+    pub fn for_command(command: &Command) -> Option<Self> {
+        let matcher = match command {
+            Command::Whois { targets } => Self::numerics(
+                "WHOIS",
+                &[
+                    "276", "301", "307", "311", "312", "313", "317", "319", "320", "330", "338",
+                    "378", "379", "671",
+                ],
+                &["318", "401", "402", "431"],
+                targets.last(),
+            )
+            .mentioning(targets),
+            Command::Whowas { nicknames, .. } => Self::numerics(
+                "WHOWAS",
+                &["312", "314", "338"],
+                &["369", "406", "431"],
+                nicknames.first(),
+            )
+            .mentioning(nicknames),
+            Command::Who { mask } => Self::numerics("WHO", &["352", "354"], &["315"], Some(mask)),
+            // Several channels end with several 366s; wait for the last one
+            Command::Names { channels } => {
+                Self::numerics("NAMES", &["353", "366"], &["366", "403"], channels.last())
+                    .mentioning(channels)
+            }
+            Command::List { .. } => Self::numerics("LIST", &["321", "322"], &["323"], None),
+            // 333 RPL_TOPICWHOTIME is optional after the topic
+            Command::Topic {
+                channel,
+                topic: None,
+            } => Self::numerics(
+                "TOPIC",
+                &["332"],
+                &["331", "333", "403", "442"],
+                Some(channel),
+            )
+            .mentioning(std::slice::from_ref(channel))
+            .closed_by("332"),
+            Command::Motd { .. } => Self::numerics("MOTD", &["375", "372"], &["376", "422"], None),
+            Command::Ison { .. } => Self::numerics("ISON", &[], &["303"], None),
+            Command::Userhost { .. } => Self::numerics("USERHOST", &[], &["302"], None),
+            Command::Time { .. } => Self::numerics("TIME", &[], &["391", "402"], None),
+            Command::Info { .. } => Self::numerics("INFO", &["371"], &["374", "402"], None),
+            Command::Admin { .. } => Self::numerics(
+                "ADMIN",
+                &["256", "257", "258"],
+                &["259", "402", "423"],
+                None,
+            ),
+            Command::Monitor {
+                subcommand: MonitorSubcommand::List,
+            } => Self::numerics("MONITOR", &["732"], &["733"], None),
+            Command::ChatHistory { subcommand } => {
+                let (batch_type, target) = match subcommand {
+                    ChatHistorySubcommand::Targets { .. } => ("draft/chathistory-targets", None),
+                    ChatHistorySubcommand::Latest { target, .. }
+                    | ChatHistorySubcommand::Before { target, .. }
+                    | ChatHistorySubcommand::After { target, .. }
+                    | ChatHistorySubcommand::Around { target, .. }
+                    | ChatHistorySubcommand::Between { target, .. } => {
+                        ("chathistory", Some(target))
+                    }
+                };
+                Self {
+                    command: "CHATHISTORY".to_string(),
+                    expected: Expected::Batch(batch_type),
+                    subject: target.cloned(),
+                    mentions: Vec::new(),
+                    closing: None,
+                    closed: false,
+                }
+            }
+            _ => return None,
+        };
+        Some(matcher)
+    }
+
+    /// Whether any param after the client's nickname is one of `names`
+    fn mentions(params: &[&str], names: &[String], casemapping: CaseMapping) -> bool {
+        params
+            .iter()
+            .skip(1)
+            .any(|param| names.iter().any(|name| casemapping.equals(param, name)))
+    }
+
+    fn mentions_subject(&self, params: &[&str], casemapping: CaseMapping) -> bool {
+        self.subject.as_ref().is_none_or(|subject| {
+            Self::mentions(params, std::slice::from_ref(subject), casemapping)
+        })
+    }
+
+    fn matches(&self, message: &MessageRef<'_>, casemapping: CaseMapping) -> Option<Matched> {
+        let command = message.command;
+        let params = &message.params;
+
+        // Errors naming the command end the request
+        let names_command = |index: usize| {
+            params
+                .get(index)
+                .is_some_and(|name| name.eq_ignore_ascii_case(&self.command))
+        };
+        if command == "FAIL" && names_command(0)
+            || matches!(command, "421" | "461") && names_command(1)
+        {
+            return Some(Matched::End);
+        }
+
+        match &self.expected {
+            Expected::Numerics { replies, ends } => {
+                if ends.contains(&command) && self.mentions_subject(params, casemapping) {
+                    Some(Matched::End)
+                } else if replies.contains(&command)
+                    && (self.mentions.is_empty()
+                        || Self::mentions(params, &self.mentions, casemapping))
+                {
+                    Some(Matched::Reply)
+                } else {
+                    None
+                }
+            }
+            Expected::Batch(batch_type) => {
+                let reference = params.first()?.strip_prefix('+')?;
+                let starts = command == "BATCH"
+                    && params.get(1) == Some(batch_type)
+                    && self.subject.as_ref().is_none_or(|subject| {
+                        params
+                            .get(2)
+                            .is_some_and(|t| casemapping.equals(t, subject))
+                    });
+                starts.then(|| Matched::BatchStart(reference.to_string()))
+            }
+        }
+    }
+}
+
+/// A request waiting for its reply
+#[derive(Debug)]
+struct Pending {
+    reply: oneshot::Sender<Vec<Message>>,
+    messages: Vec<Message>,
+    /// Reference tag of the batch carrying the reply
+    batch: Option<String>,
+}
+
+impl Pending {
+    fn new() -> (Self, oneshot::Receiver<Vec<Message>>) {
+        let (reply, receiver) = oneshot::channel();
+        let pending = Self {
+            reply,
+            messages: Vec::new(),
+            batch: None,
+        };
+        (pending, receiver)
+    }
+}
+
+/// Requests waiting for replies on one connection
+///
+/// Every received message is passed to [`handle`](Self::handle), which
+/// completes a request once its reply is whole. Messages are borrowed and
+/// only copied when they belong to a reply, so they are still delivered as
+/// normal events.
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    labeled: HashMap<String, Pending>,
+    /// Unlabeled requests, oldest first, keyed by a label that is never sent
+    unlabeled: VecDeque<(String, ReplyMatcher, Pending)>,
+    /// Batch reference tags, including nested batches, to request keys
+    batches: HashMap<String, String>,
+    /// Casemapping for comparing the names replies mention
+    casemapping: CaseMapping,
+}
+
+impl PendingRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare names in replies using the server's `CASEMAPPING`
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+    }
+
+    /// Wait for the reply to a command sent with `label`
+    pub fn expect_labeled(&mut self, label: String) -> oneshot::Receiver<Vec<Message>> {
+        let (pending, receiver) = Pending::new();
+        self.labeled.insert(label, pending);
+        receiver
+    }
+
+    /// Wait for a reply recognized by `matcher`
+    ///
+    /// Returns the key for [`cancel`](Self::cancel) and the reply receiver.
+    pub fn expect_unlabeled(
+        &mut self,
+        matcher: ReplyMatcher,
+    ) -> (String, oneshot::Receiver<Vec<Message>>) {
+        let key = next_label();
+        let (pending, receiver) = Pending::new();
+        self.unlabeled.push_back((key.clone(), matcher, pending));
+        (key, receiver)
+    }
+
+    /// Stop waiting for a request, e.g. after it timed out
+    pub fn cancel(&mut self, key: &str) {
+        self.labeled.remove(key);
+        self.unlabeled.retain(|(k, _, _)| k != key);
+        self.batches.retain(|_, k| k != key);
+    }
+
+    /// Drop all pending requests; their receivers see the connection close
+    pub fn clear(&mut self) {
+        self.labeled.clear();
+        self.unlabeled.clear();
+        self.batches.clear();
+    }
+
+    /// Number of requests still waiting
+    pub fn len(&self) -> usize {
+        self.labeled.len() + self.unlabeled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn pending_mut(&mut self, key: &str) -> Option<&mut Pending> {
+        match self.labeled.get_mut(key) {
+            Some(pending) => Some(pending),
+            None => self
+                .unlabeled
+                .iter_mut()
+                .find(|(k, _, _)| k == key)
+                .map(|(_, _, pending)| pending),
+        }
+    }
+
+    fn complete(&mut self, key: &str) {
+        let pending = match self.labeled.remove(key) {
+            Some(pending) => pending,
+            None => {
+                let Some(index) = self.unlabeled.iter().position(|(k, _, _)| k == key) else {
+                    return;
+                };
+                let Some((_, _, pending)) = self.unlabeled.remove(index) else {
+                    return;
+                };
+                pending
+            }
+        };
+
+        self.batches.retain(|_, k| k != key);
+        if pending.reply.send(pending.messages).is_err() {
+            debug!("Reply to request {} arrived after it was abandoned", key);
+        }
+    }
+
+    /// Complete the requests whose closing reply is not followed by an end
+    fn complete_closed(&mut self, message: &MessageRef<'_>) {
+        let casemapping = self.casemapping;
+        let closed: Vec<String> = self
+            .unlabeled
+            .iter()
+            .filter(|(_, matcher, _)| {
+                matcher.closed
+                    && !matches!(matcher.matches(message, casemapping), Some(Matched::End))
+            })
+            .map(|(key, _, _)| key.clone())
+            .collect();
+        for key in closed {
+            self.complete(&key);
+        }
+    }
+
+    /// Track a received message
+    pub fn handle(&mut self, message: &MessageRef<'_>) {
+        self.complete_closed(message);
+
+        let is_batch = message.command == "BATCH";
+        let reference = message.params.first().copied().unwrap_or("");
+
+        // Lines of a batch carrying a reply, including nested batches
+        if let Some(key) = message
+            .get_tag("batch")
+            .and_then(|batch| self.batches.get(batch.as_ref()).cloned())
+        {
+            match reference.strip_prefix('+') {
+                Some(nested) if is_batch => {
+                    self.batches.insert(nested.to_string(), key);
+                    return;
+                }
+                _ if is_batch => {}
+                _ => {
+                    if let Some(pending) = self.pending_mut(&key) {
+                        pending.messages.push(message.to_message());
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Some(ended) = reference.strip_prefix('-').filter(|_| is_batch) {
+            if let Some(key) = self.batches.remove(ended) {
+                let outermost = self
+                    .pending_mut(&key)
+                    .is_some_and(|pending| pending.batch.as_deref() == Some(ended));
+                if outermost {
+                    self.complete(&key);
+                }
+            }
+            return;
+        }
+
+        if let Some(label) = message
+            .get_tag("label")
+            .map(String::from)
+            .filter(|l| self.labeled.contains_key(l))
+        {
+            match reference.strip_prefix('+').filter(|_| is_batch) {
+                Some(batch) => {
+                    self.batches.insert(batch.to_string(), label.clone());
+                    if let Some(pending) = self.labeled.get_mut(&label) {
+                        pending.batch = Some(batch.to_string());
+                    }
+                }
+                None => {
+                    if let Some(pending) = self.labeled.get_mut(&label) {
+                        pending.messages.push(message.to_message());
+                    }
+                    self.complete(&label);
+                }
+            }
+            return;
+        }
+
+        let casemapping = self.casemapping;
+        let matched = self
+            .unlabeled
+            .iter_mut()
+            .find_map(|(key, matcher, pending)| {
+                let matched = matcher.matches(message, casemapping)?;
+                if !matches!(matched, Matched::BatchStart(_)) {
+                    pending.messages.push(message.to_message());
+                }
+                if matches!(matched, Matched::Reply) && matcher.closing == Some(message.command) {
+                    matcher.closed = true;
+                }
+                Some((key.clone(), matched))
+            });
+        match matched {
+            Some((key, Matched::End)) => self.complete(&key),
+            Some((key, Matched::BatchStart(batch))) => {
+                if let Some(pending) = self.pending_mut(&key) {
+                    pending.batch = Some(batch.clone());
+                }
+                self.batches.insert(batch, key);
+            }
+            Some((_, Matched::Reply)) | None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    fn feed(requests: &mut PendingRequests, lines: &[&str]) {
+        let parser = Parser::new();
+        for line in lines {
+            requests.handle(&parser.parse_ref(line).unwrap());
+        }
+    }
+
+    fn commands(reply: Vec<Message>) -> Vec<String> {
+        reply.into_iter().map(|m| m.command).collect()
+    }
+
+    #[test]
+    fn test_labeled_replies() {
+        let mut requests = PendingRequests::new();
+        let mut single = requests.expect_labeled("a".into());
+        let mut ack = requests.expect_labeled("b".into());
+        let mut batch = requests.expect_labeled("c".into());
+
+        feed(
+            &mut requests,
+            &[
+                "@label=c :irc.test BATCH +r1 labeled-response",
+                "@batch=r1 :irc.test 311 me bob b host * :Bob",
+                "@batch=r1 :irc.test BATCH +r2 netsplit a b",
+                "@batch=r2 :bob QUIT :a b",
+                ":irc.test BATCH -r2",
+                "@label=a :irc.test 303 me :bob",
+                "@label=b :irc.test ACK",
+                ":other PRIVMSG me :unrelated",
+                "@batch=r1 :irc.test 318 me bob :End of WHOIS",
+            ],
+        );
+        assert_eq!(commands(single.try_recv().unwrap()), ["303"]);
+        assert_eq!(commands(ack.try_recv().unwrap()), ["ACK"]);
+        assert!(batch.try_recv().is_err());
+
+        feed(&mut requests, &[":irc.test BATCH -r1"]);
+        assert_eq!(commands(batch.try_recv().unwrap()), ["311", "QUIT", "318"]);
+        assert!(requests.is_empty());
+    }
+
+    #[test]
+    fn test_numeric_heuristics() {
+        let mut requests = PendingRequests::new();
+        let whois = Command::Whois {
+            targets: vec!["bob".into()],
+        };
+        let (_, mut reply) = requests.expect_unlabeled(ReplyMatcher::for_command(&whois).unwrap());
+        let names = Command::Names {
+            channels: vec!["#rust".into()],
+        };
+        let (_, mut names) = requests.expect_unlabeled(ReplyMatcher::for_command(&names).unwrap());
+
+        feed(
+            &mut requests,
+            &[
+                ":irc.test 311 me bob b host * :Bob",
+                ":irc.test 401 me alice :No such nick",
+                ":irc.test 319 me bob :#rust",
+                ":irc.test 318 me Bob :End of WHOIS",
+                ":irc.test 353 me = #rust :me bob",
+            ],
+        );
+        assert_eq!(commands(reply.try_recv().unwrap()), ["311", "319", "318"]);
+        assert!(names.try_recv().is_err());
+
+        feed(&mut requests, &[":irc.test 421 me NAMES :Unknown command"]);
+        assert_eq!(commands(names.try_recv().unwrap()), ["353", "421"]);
+        assert!(ReplyMatcher::for_command(&Command::Lusers).is_none());
+    }
+
+    #[test]
+    fn test_topic_without_whotime() {
+        let mut requests = PendingRequests::new();
+        let topic = |channel: &str| Command::Topic {
+            channel: channel.into(),
+            topic: None,
+        };
+        let (_, mut rust) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&topic("#rust")).unwrap());
+        let (_, mut irc) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&topic("#irc")).unwrap());
+
+        feed(
+            &mut requests,
+            &[
+                ":irc.test 332 me #rust :Rust talk",
+                ":irc.test 333 me #rust alice 1700000000",
+                ":irc.test 332 me #irc :IRC talk",
+            ],
+        );
+        assert_eq!(commands(rust.try_recv().unwrap()), ["332", "333"]);
+        assert!(irc.try_recv().is_err());
+
+        // Servers that don't send RPL_TOPICWHOTIME go straight to other lines
+        feed(&mut requests, &[":irc.test PING :irc.test"]);
+        assert_eq!(commands(irc.try_recv().unwrap()), ["332"]);
+        assert!(requests.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_whois_by_subject() {
+        let mut requests = PendingRequests::new();
+        requests.set_casemapping(CaseMapping::Rfc1459);
+        let whois = |nick: &str| Command::Whois {
+            targets: vec![nick.into()],
+        };
+        let (_, mut first) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&whois("alice")).unwrap());
+        let (_, mut second) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&whois("bob[m]")).unwrap());
+
+        // The server answers the second query first
+        feed(
+            &mut requests,
+            &[
+                ":irc.test 311 me Bob{M} b host * :Bob",
+                ":irc.test 319 me Bob{M} :#rust",
+                ":irc.test 318 me Bob{M} :End of WHOIS",
+                ":irc.test 311 me alice a host * :Alice",
+                ":irc.test 318 me alice :End of WHOIS",
+            ],
+        );
+        let second = second.try_recv().unwrap();
+        assert_eq!(commands(second.clone()), ["311", "319", "318"]);
+        assert!(second.iter().all(|m| m.params[1] == "Bob{M}"));
+        assert_eq!(commands(first.try_recv().unwrap()), ["311", "318"]);
+        assert!(requests.is_empty());
+    }
+
+    #[test]
+    fn test_chathistory_batch_heuristic() {
+        let mut requests = PendingRequests::new();
+        let command = Command::ChatHistory {
+            subcommand: ChatHistorySubcommand::Latest {
+                target: "#rust".into(),
+                reference: rustirc_protocol::command::HistoryReference::Any,
+                limit: 10,
+            },
+        };
+        let (key, mut reply) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&command).unwrap());
+
+        feed(
+            &mut requests,
+            &[
+                ":irc.test BATCH +h1 chathistory #other",
+                ":irc.test BATCH +h2 chathistory #rust",
+                "@batch=h2 :bob PRIVMSG #rust :old",
+                ":irc.test BATCH -h2",
+            ],
+        );
+        assert_eq!(commands(reply.try_recv().unwrap()), ["PRIVMSG"]);
+
+        // A cancelled request is forgotten
+        let (key2, _reply) =
+            requests.expect_unlabeled(ReplyMatcher::for_command(&command).unwrap());
+        assert_ne!(key, key2);
+        requests.cancel(&key2);
+        assert!(requests.is_empty());
+    }
+}
@@ -10,10 +10,15 @@
 use crate::error::{Error, Result};
 use crate::events::{Event, EventBus};
 use crate::state::{StateManager, User};
-use rustirc_protocol::{Command, Message, Numeric, Prefix};
+use rustirc_protocol::command::MonitorSubcommand;
+use rustirc_protocol::validation::IrcValidator;
+use rustirc_protocol::{
+    CaseMapping, Command, Isupport, Message, MessageRef, Numeric, Prefix, Reply,
+};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info, warn};
 
@@ -32,7 +37,9 @@ pub trait MessageHandler: Send + Sync {
     fn message_types(&self) -> Vec<String>;
 
     /// Check if this handler should process the message
-    fn should_handle(&self, message: &Message) -> bool {
+    ///
+    /// Called with the borrowed message, before any handler runs.
+    fn should_handle(&self, message: &MessageRef<'_>) -> bool {
         self.message_types()
             .contains(&message.command.to_uppercase())
     }
@@ -46,6 +53,10 @@ pub struct MessageContext {
     pub is_own_message: bool,
     pub target_channel: Option<String>,
     pub source_user: Option<User>,
+    /// Server CASEMAPPING for comparing nicknames and channel names
+    pub casemapping: CaseMapping,
+    /// Server CHANTYPES, the prefixes that start a channel name
+    pub chantypes: String,
 }
 
 impl MessageContext {
@@ -56,9 +67,19 @@ impl MessageContext {
             is_own_message: false,
             target_channel: None,
             source_user: None,
+            casemapping: CaseMapping::default(),
+            chantypes: Isupport::default().chantypes,
         }
     }
 
+    /// Whether `target` is a channel name on this server
+    pub fn is_channel(&self, target: &str) -> bool {
+        target
+            .chars()
+            .next()
+            .is_some_and(|first| self.chantypes.contains(first))
+    }
+
     pub fn with_channel(mut self, channel: String) -> Self {
         self.target_channel = Some(channel);
         self
@@ -191,14 +212,14 @@ pub struct MessageRouter {
     event_bus: Arc<EventBus>,
     rate_limits: Arc<RwLock<HashMap<String, RateLimitState>>>,
     rate_limit_config: RateLimitConfig,
-    command_queue: mpsc::UnboundedSender<(String, Command)>, // connection_id, command
+    command_queue: mpsc::Sender<(String, Command)>, // connection_id, command
 }
 
 impl MessageRouter {
     pub fn new(
         state_manager: Arc<StateManager>,
         event_bus: Arc<EventBus>,
-        command_queue: mpsc::UnboundedSender<(String, Command)>,
+        command_queue: mpsc::Sender<(String, Command)>,
     ) -> Self {
         let mut router = Self {
             handlers: Arc::new(RwLock::new(Vec::new())),
@@ -224,55 +245,94 @@ impl MessageRouter {
 
     /// Process an incoming IRC message
     pub async fn route_message(&self, connection_id: String, message: Message) -> Result<()> {
+        let context = self
+            .message_context(connection_id, &MessageRef::from(&message))
+            .await;
+        if let Some(context) = context {
+            self.dispatch(context, message).await;
+        }
+        Ok(())
+    }
+
+    /// Process an incoming IRC message borrowed from the read buffer
+    ///
+    /// Rate limiting and context extraction work on the borrowed message. It
+    /// is copied once, for the handlers and the `MessageReceived` event, and
+    /// not at all when it is dropped by the rate limiter.
+    pub async fn route_message_ref(
+        &self,
+        connection_id: String,
+        message: &MessageRef<'_>,
+    ) -> Result<()> {
+        let context = self.message_context(connection_id, message).await;
+        if let Some(context) = context {
+            self.dispatch(context, message.to_message()).await;
+        }
+        Ok(())
+    }
+
+    /// Build the handler context, or `None` if the message is rate limited
+    async fn message_context(
+        &self,
+        connection_id: String,
+        message: &MessageRef<'_>,
+    ) -> Option<MessageContext> {
         // Check rate limits
         if !self.check_rate_limit(&connection_id).await {
             debug!("Message dropped due to rate limiting: {}", connection_id);
-            return Ok(());
+            return None;
         }
 
+        debug!(
+            "Routing message: {} from {}",
+            message.command, connection_id
+        );
+
         // Create message context
-        let mut context = MessageContext::new(connection_id.clone());
+        let mut context = MessageContext::new(connection_id);
+        context.casemapping = self.state_manager.casemapping(&context.connection_id).await;
+        context.chantypes = self.state_manager.chantypes(&context.connection_id).await;
 
         // Extract user information from prefix
         if let Some(prefix) = &message.prefix {
-            context.source_user = Some(User::from_prefix(prefix));
+            context.source_user = Some(User::from_prefix(&prefix.to_prefix()));
         }
 
         // Determine target channel for channel messages
-        match message.command.as_str() {
+        match message.command {
             "PRIVMSG" | "NOTICE" | "JOIN" | "PART" | "TOPIC" | "MODE" => {
                 if let Some(target) = message.params.first() {
-                    if target.starts_with('#') || target.starts_with('&') {
-                        context.target_channel = Some(target.clone());
+                    if context.is_channel(target) {
+                        context.target_channel = Some(target.to_string());
                     }
                 }
             }
             _ => {}
         }
 
-        debug!(
-            "Routing message: {} from {}",
-            message.command, connection_id
-        );
+        Some(context)
+    }
 
-        // Route to handlers
-        let handlers = self.handlers.read().await;
-        for handler in handlers.iter() {
-            if handler.should_handle(&message) {
-                if let Err(e) = handler.handle_message(&context, &message).await {
-                    error!("Handler error for {}: {}", message.command, e);
+    /// Run the handlers for a message and publish it
+    async fn dispatch(&self, context: MessageContext, message: Message) {
+        {
+            let borrowed = MessageRef::from(&message);
+            let handlers = self.handlers.read().await;
+            for handler in handlers.iter() {
+                if handler.should_handle(&borrowed) {
+                    if let Err(e) = handler.handle_message(&context, &message).await {
+                        error!("Handler error for {}: {}", message.command, e);
+                    }
                 }
             }
         }
 
         // Update state through event system
         let event = Event::MessageReceived {
-            connection_id: connection_id.clone(),
-            message: message.clone(),
+            connection_id: context.connection_id,
+            message,
         };
         self.event_bus.emit(event).await;
-
-        Ok(())
     }
 
     /// Handle numeric responses from IRC server
@@ -336,20 +396,62 @@ impl MessageRouter {
     }
 
     /// Send a command to a specific connection
+    ///
+    /// Waits while the command queue is full.
     pub async fn send_command(&self, connection_id: String, command: Command) -> Result<()> {
-        self.command_queue
-            .send((connection_id.clone(), command.clone()))
-            .map_err(|_| Error::ConnectionClosed)?;
+        let message = command.to_message();
+        match self
+            .command_queue
+            .try_send((connection_id.clone(), command))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(entry)) => {
+                debug!(
+                    "Command queue is full, waiting to send {} to {}",
+                    message.command, connection_id
+                );
+                self.command_queue
+                    .send(entry)
+                    .await
+                    .map_err(|_| Error::ConnectionClosed)?;
+            }
+            Err(TrySendError::Closed(_)) => return Err(Error::ConnectionClosed),
+        }
 
-        // Emit event
+        self.emit_sent(connection_id, message).await;
+        Ok(())
+    }
+
+    /// Send a command to a specific connection without waiting
+    ///
+    /// Fails with [`Error::QueueFull`] if the command queue is full.
+    pub async fn try_send_command(&self, connection_id: String, command: Command) -> Result<()> {
         let message = command.to_message();
+        match self
+            .command_queue
+            .try_send((connection_id.clone(), command))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                info!(
+                    "Command queue is full, rejecting {} to {}",
+                    message.command, connection_id
+                );
+                return Err(Error::QueueFull);
+            }
+            Err(TrySendError::Closed(_)) => return Err(Error::ConnectionClosed),
+        }
+
+        self.emit_sent(connection_id, message).await;
+        Ok(())
+    }
+
+    async fn emit_sent(&self, connection_id: String, message: Message) {
         let event = Event::MessageSent {
             connection_id,
             message,
         };
         self.event_bus.emit(event).await;
-
-        Ok(())
     }
 
     /// Check rate limits for a connection
@@ -434,23 +536,16 @@ impl MessageHandler for NumericHandler {
                     info!("Registration successful for {}", context.connection_id);
                     // Emit registration event
                 }
-                353 => {
-                    // RPL_NAMREPLY - Names list
-                    if message.params.len() >= 3 {
-                        let channel = &message.params[2];
-                        let default_names = String::new();
-                        let names = message.params.last().unwrap_or(&default_names);
-                        debug!("Names for {}: {}", channel, names);
-                        // Process user list
+                353 | 366 => match Reply::parse(message) {
+                    Ok(Reply::NameReply { channel, names, .. }) => {
+                        debug!("Names for {}: {}", channel, names.join(" "));
                     }
-                }
-                366 => {
-                    // RPL_ENDOFNAMES - End of names list
-                    if message.params.len() >= 2 {
-                        let channel = &message.params[1];
+                    Ok(Reply::EndOfNames { channel }) => {
                         debug!("End of names for {}", channel);
                     }
-                }
+                    Ok(_) => {}
+                    Err(e) => warn!("Malformed reply on {}: {}", context.connection_id, e),
+                },
                 432 | 433 => {
                     // ERR_ERRONEUSNICKNAME or ERR_NICKNAMEINUSE
                     warn!("Nickname error for {}: {}", context.connection_id, numeric);
@@ -473,7 +568,7 @@ impl MessageHandler for NumericHandler {
         (1..=999).map(|n| format!("{n:03}")).collect()
     }
 
-    fn should_handle(&self, message: &Message) -> bool {
+    fn should_handle(&self, message: &MessageRef<'_>) -> bool {
         message.command.parse::<u16>().is_ok()
     }
 }
@@ -599,13 +694,13 @@ impl MessageHandler_ {
         Self { highlight_patterns }
     }
 
-    fn is_highlighted(&self, message: &str) -> bool {
-        for pattern in &self.highlight_patterns {
-            if message.to_lowercase().contains(&pattern.to_lowercase()) {
-                return true;
-            }
-        }
-        false
+    /// Match highlight patterns (usually nicknames) with the server's CASEMAPPING,
+    /// so that `nick[away]` highlights on `Nick{away}` under rfc1459
+    fn is_highlighted(&self, message: &str, casemapping: CaseMapping) -> bool {
+        let message = casemapping.fold(message);
+        self.highlight_patterns
+            .iter()
+            .any(|pattern| message.contains(&casemapping.fold(pattern)))
     }
 }
 
@@ -616,8 +711,8 @@ impl MessageHandler for MessageHandler_ {
             let target = &message.params[0];
             let text = &message.params[1];
 
-            let is_private = !target.starts_with('#') && !target.starts_with('&');
-            let is_highlighted = self.is_highlighted(text);
+            let is_private = !context.is_channel(target);
+            let is_highlighted = self.is_highlighted(text, context.casemapping);
 
             match message.command.as_str() {
                 "PRIVMSG" => {
@@ -806,16 +901,95 @@ impl CommandProcessor {
                     return Err(Error::Protocol("WHOIS requires a nickname".to_string()));
                 }
             }
+            "away" => Some(Command::Away {
+                message: (!args.is_empty()).then(|| args.join(" ")),
+            }),
+            "back" => Some(Command::Away { message: None }),
+            "invite" => {
+                if args.len() >= 2 {
+                    Some(Command::Invite {
+                        nickname: args[0].to_string(),
+                        channel: args[1].to_string(),
+                    })
+                } else {
+                    return Err(Error::Protocol(
+                        "INVITE requires a nickname and channel".to_string(),
+                    ));
+                }
+            }
+            "knock" => {
+                if !args.is_empty() {
+                    Some(Command::Knock {
+                        channel: args[0].to_string(),
+                        message: (args.len() > 1).then(|| args[1..].join(" ")),
+                    })
+                } else {
+                    return Err(Error::Protocol("KNOCK requires a channel name".to_string()));
+                }
+            }
+            "setname" => {
+                if !args.is_empty() {
+                    Some(Command::SetName {
+                        realname: args.join(" "),
+                    })
+                } else {
+                    return Err(Error::Protocol("SETNAME requires a real name".to_string()));
+                }
+            }
+            "monitor" => {
+                let targets = || {
+                    args.get(1)
+                        .map(|targets| targets.split(',').map(str::to_string).collect())
+                        .unwrap_or_default()
+                };
+                let subcommand = match args.first().copied() {
+                    Some("+") => MonitorSubcommand::Add { targets: targets() },
+                    Some("-") => MonitorSubcommand::Remove { targets: targets() },
+                    Some("C" | "c") => MonitorSubcommand::Clear,
+                    Some("S" | "s") => MonitorSubcommand::Status,
+                    Some("L" | "l") | None => MonitorSubcommand::List,
+                    Some(other) => {
+                        return Err(Error::Protocol(format!(
+                            "Unknown MONITOR subcommand: {other}"
+                        )))
+                    }
+                };
+                Some(Command::Monitor { subcommand })
+            }
+            "wallops" => Some(Command::Wallops {
+                text: args.join(" "),
+            }),
+            "kill" => {
+                if args.len() >= 2 {
+                    Some(Command::Kill {
+                        nickname: args[0].to_string(),
+                        comment: args[1..].join(" "),
+                    })
+                } else {
+                    return Err(Error::Protocol(
+                        "KILL requires a nickname and comment".to_string(),
+                    ));
+                }
+            }
             _ => {
-                // Unknown command, send as raw
-                Some(Command::Raw {
-                    command: cmd_name.to_uppercase(),
-                    params: args.iter().map(|s| s.to_string()).collect(),
-                })
+                // Unknown command, send typed if we know it or as raw otherwise
+                let mut message = Message::new(cmd_name.to_uppercase());
+                message.params = args.iter().map(|s| s.to_string()).collect();
+                Some(Command::from_message(&message).map_err(|e| Error::Protocol(e.to_string()))?)
             }
         };
 
         if let Some(command) = command {
+            let validator = self
+                .router
+                .get_server_state(&connection_id)
+                .await
+                .map(|server| IrcValidator::from_isupport(&server.isupport))
+                .unwrap_or_default();
+            command
+                .validate(&validator)
+                .map_err(|e| Error::Protocol(e.to_string()))?;
+
             self.router.send_command(connection_id, command).await?;
         }
 
@@ -837,3 +1011,54 @@ impl CommandProcessor {
         self.aliases.insert(alias, command);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    #[tokio::test]
+    async fn test_channel_targets_follow_chantypes() {
+        let state_manager = Arc::new(StateManager::new());
+        state_manager
+            .apply_event(&Event::Connected {
+                connection_id: "test".to_string(),
+            })
+            .await
+            .unwrap();
+        let (command_queue, _commands) = mpsc::channel(1);
+        let router = MessageRouter::new(
+            state_manager.clone(),
+            Arc::new(EventBus::new()),
+            command_queue,
+        );
+
+        let parser = Parser::new();
+        let target = |line: &'static str| {
+            let router = &router;
+            let message = parser.parse_ref(line).unwrap();
+            async move {
+                router
+                    .message_context("test".to_string(), &message)
+                    .await
+                    .unwrap()
+                    .target_channel
+            }
+        };
+        assert_eq!(
+            target(":bob PRIVMSG #rust :hi").await.as_deref(),
+            Some("#rust")
+        );
+        assert_eq!(target(":bob PRIVMSG !rust :hi").await, None);
+
+        let isupport = parser
+            .parse_ref(":irc.test 005 me CHANTYPES=! :are supported by this server")
+            .unwrap();
+        state_manager.apply_message_ref("test", &isupport).await;
+        assert_eq!(
+            target(":bob PRIVMSG !rust :hi").await.as_deref(),
+            Some("!rust")
+        );
+        assert_eq!(target(":bob PRIVMSG #rust :hi").await, None);
+    }
+}
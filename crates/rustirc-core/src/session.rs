@@ -0,0 +1,543 @@
+//! Session state restored after a reconnection
+//!
+//! A connection remembers what the user set up on it: the joined channels
+//! and their keys, away status, user modes and `MONITOR` list, and where
+//! each buffer's history was last seen. Once a new connection has
+//! registered, [`Session::restore`] turns that back into commands, along
+//! with the gap in each buffer's history, which is fetched with
+//! `CHATHISTORY` when the server supports it.
+
+use std::collections::{BTreeSet, HashMap};
+
+use rustirc_protocol::command::{ChatHistorySubcommand, HistoryReference, MonitorSubcommand};
+use rustirc_protocol::{CaseMapping, Command, Isupport, MessageRef};
+
+/// User modes only the server sets, which are not restored
+const SERVER_USER_MODES: &str = "oOrz";
+
+/// Messages fetched per buffer, unless the server's `CHATHISTORY` limit is lower
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// A buffer whose history may be missing messages from while disconnected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryGap {
+    /// Channel or nickname of the buffer
+    pub target: String,
+    /// Server time of the last message seen before the gap, if known
+    pub since: Option<String>,
+}
+
+/// Commands and history gaps for a newly registered connection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Restoration {
+    pub commands: Vec<Command>,
+    pub gaps: Vec<HistoryGap>,
+}
+
+/// Last message seen in a buffer
+#[derive(Debug, Clone)]
+struct LastSeen {
+    target: String,
+    time: Option<String>,
+    msgid: Option<String>,
+}
+
+/// What a connection's user set up, kept across reconnections
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    casemapping: CaseMapping,
+    /// Joined channels in the order they were joined
+    channels: Vec<String>,
+    /// Keys given when joining, keyed by folded channel name
+    keys: HashMap<String, String>,
+    away: Option<String>,
+    user_modes: BTreeSet<char>,
+    monitor: Vec<String>,
+    /// Keyed by folded buffer name
+    last_seen: HashMap<String, LastSeen>,
+    /// Whether a connection has registered since the session started
+    registered: bool,
+    /// Whether the current connection still needs restoring
+    restore_pending: bool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the server's CASEMAPPING to match channel names and nicknames
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+    }
+
+    /// Channels to rejoin, in the order they were joined
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    /// Away message to restore, if away
+    pub fn away(&self) -> Option<&str> {
+        self.away.as_deref()
+    }
+
+    /// Nicknames on the user's `MONITOR` list
+    pub fn monitor_list(&self) -> &[String] {
+        &self.monitor
+    }
+
+    /// Note that a new connection started; it is restored once it registers
+    pub fn start_connection(&mut self) {
+        self.restore_pending = true;
+    }
+
+    /// Track a command the user sent
+    pub fn handle_outgoing(&mut self, command: &Command) {
+        match command {
+            Command::Join { channels, keys } => {
+                for (channel, key) in channels.iter().zip(keys) {
+                    if !key.is_empty() {
+                        self.keys
+                            .insert(self.casemapping.fold(channel), key.clone());
+                    }
+                }
+            }
+            Command::Away { message } => {
+                self.away = message.clone().filter(|message| !message.is_empty());
+            }
+            Command::Monitor { subcommand } => match subcommand {
+                MonitorSubcommand::Add { targets } => {
+                    for target in targets {
+                        if !self
+                            .monitor
+                            .iter()
+                            .any(|n| self.casemapping.equals(n, target))
+                        {
+                            self.monitor.push(target.clone());
+                        }
+                    }
+                }
+                MonitorSubcommand::Remove { targets } => {
+                    let casemapping = self.casemapping;
+                    self.monitor
+                        .retain(|n| !targets.iter().any(|t| casemapping.equals(n, t)));
+                }
+                MonitorSubcommand::Clear => self.monitor.clear(),
+                MonitorSubcommand::List | MonitorSubcommand::Status => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Track a message from the server; `nickname` is our current nickname
+    pub fn handle_incoming(&mut self, message: &MessageRef<'_>, nickname: &str) {
+        let source = message.nick();
+        let from_us = source.is_some_and(|nick| self.casemapping.equals(nick, nickname));
+
+        match message.command {
+            "JOIN" if from_us => {
+                if let Some(channel) = message.params.first() {
+                    if !self.is_joined(channel) {
+                        self.channels.push(channel.to_string());
+                    }
+                }
+            }
+            "PART" if from_us => {
+                if let Some(channel) = message.params.first() {
+                    self.forget_channel(channel);
+                }
+            }
+            "KICK" => {
+                if let [channel, kicked, ..] = message.params.as_slice() {
+                    if self.casemapping.equals(kicked, nickname) {
+                        self.forget_channel(channel);
+                    }
+                }
+            }
+            "MODE" => {
+                if let [target, modes, ..] = message.params.as_slice() {
+                    if self.casemapping.equals(target, nickname) {
+                        self.apply_user_modes(modes);
+                    }
+                }
+            }
+            // 221 RPL_UMODEIS: all of our user modes
+            "221" => {
+                if let Some(modes) = message.params.get(1) {
+                    self.user_modes.clear();
+                    self.apply_user_modes(modes);
+                }
+            }
+            // 305 RPL_UNAWAY
+            "305" => self.away = None,
+            "PRIVMSG" | "NOTICE" => {
+                let (Some(source), Some(target)) = (source, message.params.first()) else {
+                    return;
+                };
+                // Private messages to us belong in the sender's buffer
+                let buffer = if self.casemapping.equals(target, nickname) {
+                    source
+                } else {
+                    target
+                };
+                let (time, msgid) = (
+                    message.get_tag("time").map(String::from),
+                    message.get_tag("msgid").map(String::from),
+                );
+                if time.is_some() || msgid.is_some() {
+                    self.last_seen.insert(
+                        self.casemapping.fold(buffer),
+                        LastSeen {
+                            target: buffer.to_string(),
+                            time,
+                            msgid,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Restore the session once a new connection has registered
+    ///
+    /// JOINs are batched by the `JOIN` TARGMAX and line length, with keyed
+    /// channels first, followed by away status, user modes and the `MONITOR`
+    /// list. With `chathistory`, the latest messages of every buffer with a
+    /// known last message are requested. Returns `None` if the connection was
+    /// already restored, e.g. when the MOTD is requested again.
+    pub fn restore(
+        &mut self,
+        nickname: &str,
+        isupport: &Isupport,
+        chathistory: bool,
+    ) -> Option<Restoration> {
+        if !std::mem::take(&mut self.restore_pending) {
+            return None;
+        }
+        // The first connection has nothing to restore
+        if !std::mem::replace(&mut self.registered, true) {
+            return Some(Restoration::default());
+        }
+
+        let mut commands = self.join_commands(isupport);
+
+        if let Some(message) = &self.away {
+            commands.push(Command::Away {
+                message: Some(message.clone()),
+            });
+        }
+
+        let modes: String = self
+            .user_modes
+            .iter()
+            .filter(|mode| !SERVER_USER_MODES.contains(**mode))
+            .collect();
+        if !modes.is_empty() {
+            commands.push(Command::Mode {
+                target: nickname.to_string(),
+                modes: Some(format!("+{modes}")),
+                params: Vec::new(),
+            });
+        }
+
+        if isupport.monitor {
+            let limit = isupport.monitor_limit.unwrap_or(usize::MAX);
+            let nicknames = self.monitor.iter().take(limit).cloned().collect::<Vec<_>>();
+            let budget = isupport.linelen.saturating_sub("MONITOR + \r\n".len());
+            for targets in batch_targets(nicknames, budget) {
+                commands.push(Command::Monitor {
+                    subcommand: MonitorSubcommand::Add { targets },
+                });
+            }
+        }
+
+        let mut gaps = Vec::new();
+        let mut buffers: Vec<_> = self
+            .channels
+            .iter()
+            .map(|channel| {
+                let last = self.last_seen.get(&self.casemapping.fold(channel));
+                (channel.clone(), last)
+            })
+            .collect();
+        // Then private conversations, by name
+        let mut queries: Vec<_> = self
+            .last_seen
+            .values()
+            .filter(|last| !last.target.starts_with(|c| isupport.chantypes.contains(c)))
+            .collect();
+        queries.sort_by(|a, b| a.target.cmp(&b.target));
+        buffers.extend(
+            queries
+                .into_iter()
+                .map(|last| (last.target.clone(), Some(last))),
+        );
+        let limit = history_limit(isupport);
+        for (target, last) in buffers {
+            let reference = last.and_then(|last| match (&last.time, &last.msgid) {
+                (Some(time), _) => Some(HistoryReference::Timestamp(time.clone())),
+                (None, Some(msgid)) => Some(HistoryReference::MsgId(msgid.clone())),
+                (None, None) => None,
+            });
+            if let Some(reference) = reference.filter(|_| chathistory) {
+                commands.push(Command::ChatHistory {
+                    subcommand: ChatHistorySubcommand::Latest {
+                        target: target.clone(),
+                        reference,
+                        limit,
+                    },
+                });
+            }
+            gaps.push(HistoryGap {
+                target,
+                since: last.and_then(|last| last.time.clone()),
+            });
+        }
+
+        Some(Restoration { commands, gaps })
+    }
+
+    /// JOINs for the saved channels
+    fn join_commands(&self, isupport: &Isupport) -> Vec<Command> {
+        let channels: Vec<_> = self
+            .channels
+            .iter()
+            .map(|channel| {
+                let key = self.keys.get(&self.casemapping.fold(channel)).cloned();
+                (channel.clone(), key)
+            })
+            .collect();
+        join_commands(&channels, isupport)
+    }
+
+    fn is_joined(&self, channel: &str) -> bool {
+        self.channels
+            .iter()
+            .any(|joined| self.casemapping.equals(joined, channel))
+    }
+
+    fn forget_channel(&mut self, channel: &str) {
+        let casemapping = self.casemapping;
+        self.channels
+            .retain(|joined| !casemapping.equals(joined, channel));
+        let folded = casemapping.fold(channel);
+        self.keys.remove(&folded);
+        self.last_seen.remove(&folded);
+    }
+
+    fn apply_user_modes(&mut self, modes: &str) {
+        let mut adding = true;
+        for mode in modes.chars() {
+            match mode {
+                '+' => adding = true,
+                '-' => adding = false,
+                mode if adding => {
+                    self.user_modes.insert(mode);
+                }
+                mode => {
+                    self.user_modes.remove(&mode);
+                }
+            }
+        }
+    }
+}
+
+/// Batch JOINs for `(channel, key)` pairs by the `JOIN` TARGMAX and line length
+///
+/// Keyed channels go first, since keys apply to channels in order.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_core::session::join_commands;
+/// use rustirc_protocol::Isupport;
+///
+/// let channels = [("#a".to_string(), None), ("#b".to_string(), Some("key".to_string()))];
+/// let commands = join_commands(&channels, &Isupport::default());
+/// assert_eq!(commands[0].to_message().to_string(), "JOIN #b,#a key");
+/// ```
+pub fn join_commands(channels: &[(String, Option<String>)], isupport: &Isupport) -> Vec<Command> {
+    let (keyed, unkeyed): (Vec<_>, Vec<_>) = channels.iter().partition(|(_, key)| key.is_some());
+    let max_targets = isupport.max_targets("JOIN");
+    let budget = isupport.linelen.saturating_sub("JOIN  \r\n".len());
+
+    let mut commands = Vec::new();
+    let mut batch = Vec::new();
+    let mut keys = Vec::new();
+    let mut length = 0;
+    for (channel, key) in keyed.into_iter().chain(unkeyed) {
+        let added = channel.len() + 1 + key.as_ref().map_or(0, |key| key.len() + 1);
+        let full = max_targets.is_some_and(|max| batch.len() >= max);
+        if !batch.is_empty() && (full || length + added > budget) {
+            commands.push(Command::Join {
+                channels: std::mem::take(&mut batch),
+                keys: std::mem::take(&mut keys),
+            });
+            length = 0;
+        }
+        batch.push(channel.clone());
+        keys.extend(key.clone());
+        length += added;
+    }
+    if !batch.is_empty() {
+        commands.push(Command::Join {
+            channels: batch,
+            keys,
+        });
+    }
+    commands
+}
+
+/// The server's `CHATHISTORY` limit, capped at [`DEFAULT_HISTORY_LIMIT`]
+fn history_limit(isupport: &Isupport) -> u32 {
+    let advertised = isupport
+        .tokens
+        .get("CHATHISTORY")
+        .and_then(|value| value.as_deref()?.parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_HISTORY_LIMIT);
+    advertised.min(DEFAULT_HISTORY_LIMIT) as u32
+}
+
+/// Group targets into comma-separated lists that fit in `budget` bytes
+fn batch_targets(targets: Vec<String>, budget: usize) -> Vec<Vec<String>> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<String> = Vec::new();
+    let mut length = 0;
+    for target in targets {
+        if !batch.is_empty() && length + target.len() + 1 > budget {
+            batches.push(std::mem::take(&mut batch));
+            length = 0;
+        }
+        length += target.len() + 1;
+        batch.push(target);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    fn parse(line: &str) -> MessageRef<'_> {
+        Parser::new().parse_ref(line).unwrap()
+    }
+
+    fn lines(restoration: &Restoration) -> Vec<String> {
+        restoration
+            .commands
+            .iter()
+            .map(|command| command.to_message().to_string())
+            .collect()
+    }
+
+    /// A session that registered once and has reconnected
+    fn reconnected(session: &mut Session) {
+        session.start_connection();
+        session.restore("me", &Isupport::default(), false);
+        session.start_connection();
+    }
+
+    #[test]
+    fn test_rejoin_with_keys_in_batches() {
+        let mut session = Session::new();
+        session.handle_outgoing(&Command::Join {
+            channels: vec!["#secret".to_string()],
+            keys: vec!["hunter2".to_string()],
+        });
+        for channel in ["#a", "#secret", "#b", "#c"] {
+            session.handle_incoming(&parse(&format!(":me!u@h JOIN {channel}")), "me");
+        }
+        session.handle_incoming(&parse(":me!u@h PART #c"), "me");
+
+        let mut isupport = Isupport::default();
+        isupport.targmax.insert("JOIN".to_string(), Some(2));
+        reconnected(&mut session);
+        let restoration = session.restore("me", &isupport, false).unwrap();
+
+        assert_eq!(lines(&restoration), ["JOIN #secret,#a hunter2", "JOIN #b"]);
+        // Restored once per connection
+        assert!(session.restore("me", &isupport, false).is_none());
+    }
+
+    #[test]
+    fn test_restore_away_modes_and_monitor() {
+        let mut session = Session::new();
+        session.handle_outgoing(&Command::Away {
+            message: Some("lunch".to_string()),
+        });
+        session.handle_outgoing(&Command::Monitor {
+            subcommand: MonitorSubcommand::Add {
+                targets: vec!["alice".to_string(), "bob".to_string()],
+            },
+        });
+        session.handle_outgoing(&Command::Monitor {
+            subcommand: MonitorSubcommand::Remove {
+                targets: vec!["Bob".to_string()],
+            },
+        });
+        session.handle_incoming(&parse(":server 221 me +iwo"), "me");
+        session.handle_incoming(&parse(":me MODE me :-w+x"), "me");
+
+        let isupport = Isupport {
+            monitor: true,
+            ..Default::default()
+        };
+        reconnected(&mut session);
+        let restoration = session.restore("me", &isupport, false).unwrap();
+
+        assert_eq!(
+            lines(&restoration),
+            ["AWAY lunch", "MODE me +ix", "MONITOR + alice"]
+        );
+    }
+
+    #[test]
+    fn test_history_gaps() {
+        let mut session = Session::new();
+        session.handle_incoming(&parse(":me!u@h JOIN #a"), "me");
+        session.handle_incoming(&parse(":me!u@h JOIN #b"), "me");
+        session.handle_incoming(
+            &parse("@time=2024-01-01T12:00:00.000Z :bob!u@h PRIVMSG #a :hi"),
+            "me",
+        );
+        session.handle_incoming(&parse("@msgid=abc :bob!u@h PRIVMSG me :psst"), "me");
+
+        reconnected(&mut session);
+        let restoration = session.restore("me", &Isupport::default(), true).unwrap();
+
+        let history: Vec<_> = lines(&restoration)
+            .into_iter()
+            .filter(|line| line.starts_with("CHATHISTORY"))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                "CHATHISTORY LATEST #a timestamp=2024-01-01T12:00:00.000Z 100",
+                "CHATHISTORY LATEST bob msgid=abc 100",
+            ]
+        );
+        assert_eq!(
+            restoration.gaps,
+            [
+                HistoryGap {
+                    target: "#a".to_string(),
+                    since: Some("2024-01-01T12:00:00.000Z".to_string()),
+                },
+                HistoryGap {
+                    target: "#b".to_string(),
+                    since: None,
+                },
+                HistoryGap {
+                    target: "bob".to_string(),
+                    since: None,
+                },
+            ]
+        );
+    }
+}
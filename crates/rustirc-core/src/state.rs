@@ -5,9 +5,14 @@
 
 use crate::error::{Error, Result};
 use crate::events::Event;
-use rustirc_protocol::{Message, Prefix};
+use rustirc_protocol::isupport::isupport_tokens;
+use rustirc_protocol::mode::{parse_channel_modes, parse_user_modes};
+use rustirc_protocol::{
+    CaseMapping, IrcString, Isupport, Message, MessageRef, ModeChange, ModeKind, Prefix, PrefixRef,
+    Reply,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
@@ -60,10 +65,11 @@ pub struct ServerState {
     pub nickname: String,
     pub username: String,
     pub realname: String,
-    pub channels: HashMap<String, ChannelState>,
-    pub users: HashMap<String, User>, // Global user cache
+    pub channels: HashMap<IrcString, ChannelState>, // Keyed by the server's CASEMAPPING
+    pub users: HashMap<IrcString, User>,            // Global user cache
     pub capabilities: Vec<String>,
-    pub isupport: HashMap<String, String>,
+    pub isupport: Isupport,
+    pub user_modes: BTreeSet<char>, // Our own user modes
     pub server_info: ServerInfo,
     pub last_ping: Option<u64>, // Unix timestamp
     pub message_history: VecDeque<HistoryEntry>,
@@ -84,12 +90,238 @@ impl ServerState {
             channels: HashMap::new(),
             users: HashMap::new(),
             capabilities: Vec::new(),
-            isupport: HashMap::new(),
+            isupport: Isupport::default(),
+            user_modes: BTreeSet::new(),
             server_info: ServerInfo::default(),
             last_ping: None,
             message_history: VecDeque::with_capacity(1000),
         }
     }
+
+    /// Build a map key for a nickname or channel name under the server's CASEMAPPING
+    pub fn key(&self, name: &str) -> IrcString {
+        IrcString::new(name, self.isupport.casemapping)
+    }
+
+    /// Look up a channel by name, ignoring case as the server does
+    pub fn channel(&self, name: &str) -> Option<&ChannelState> {
+        self.channels.get(&self.key(name))
+    }
+
+    /// Look up a channel by name for modification
+    pub fn channel_mut(&mut self, name: &str) -> Option<&mut ChannelState> {
+        let key = self.key(name);
+        self.channels.get_mut(&key)
+    }
+
+    /// Look up a cached user by nickname
+    pub fn user(&self, nick: &str) -> Option<&User> {
+        self.users.get(&self.key(nick))
+    }
+
+    /// Check whether a nickname is ours
+    pub fn is_own_nick(&self, nick: &str) -> bool {
+        self.isupport.casemapping.equals(&self.nickname, nick)
+    }
+
+    /// Apply parsed mode changes to a channel and its members
+    pub fn apply_channel_modes(&mut self, channel: &str, changes: &[ModeChange]) {
+        let key = self.key(channel);
+        if let Some(channel_state) = self.channels.get_mut(&key) {
+            channel_state.apply_mode_changes(changes, &self.isupport);
+        }
+    }
+
+    /// Apply a change to our own user modes
+    pub fn apply_user_modes(&mut self, changes: &[ModeChange]) {
+        for change in changes {
+            if change.adding {
+                self.user_modes.insert(change.mode);
+            } else {
+                self.user_modes.remove(&change.mode);
+            }
+        }
+    }
+
+    /// Track channel membership and modes from an incoming message
+    fn apply_message(&mut self, message: &MessageRef<'_>) {
+        let source = message.nick();
+
+        match message.command {
+            "JOIN" => {
+                let (Some(prefix @ PrefixRef::User { nick, .. }), Some(&channel)) =
+                    (message.prefix, message.params.first())
+                else {
+                    return;
+                };
+                if self.is_own_nick(nick) {
+                    let casemapping = self.isupport.casemapping;
+                    let channel_state =
+                        self.channels.entry(self.key(channel)).or_insert_with(|| {
+                            ChannelState::with_casemapping(channel.to_string(), casemapping)
+                        });
+                    channel_state.joined = true;
+                    channel_state.users.clear();
+                }
+                if let Some(channel_state) = self.channel_mut(channel) {
+                    channel_state
+                        .add_user(nick.to_string(), User::from_prefix(&prefix.to_prefix()));
+                }
+            }
+            "PART" | "KICK" => {
+                // PART <channel>, KICK <channel> <nick>
+                let nick = if message.command == "KICK" {
+                    message.params.get(1).copied()
+                } else {
+                    source
+                };
+                let (Some(nick), Some(channel)) = (nick, message.params.first()) else {
+                    return;
+                };
+                let own = self.is_own_nick(nick);
+                if let Some(channel_state) = self.channel_mut(channel) {
+                    if own {
+                        channel_state.joined = false;
+                        channel_state.users.clear();
+                    } else {
+                        channel_state.remove_user(nick);
+                    }
+                }
+            }
+            "QUIT" => {
+                if let Some(nick) = source {
+                    for channel_state in self.channels.values_mut() {
+                        if channel_state.user(nick).is_some() {
+                            channel_state.remove_user(nick);
+                        }
+                    }
+                }
+            }
+            "353" => {
+                let Ok(Reply::NameReply { channel, names, .. }) = Reply::parse_ref(message) else {
+                    debug!("Ignoring malformed RPL_NAMREPLY: {}", message);
+                    return;
+                };
+                let key = self.key(&channel);
+                let Some(channel_state) = self.channels.get_mut(&key) else {
+                    return;
+                };
+                for entry in &names {
+                    let (modes, nick) = self.isupport.split_prefixes(entry);
+                    // userhost-in-names sends nick!user@host
+                    let nick = nick.split('!').next().unwrap_or(nick);
+                    channel_state.add_user_with_modes(nick.to_string(), modes);
+                }
+            }
+            "MODE" => {
+                let (Some(target), Some(modestring)) =
+                    (message.params.first(), message.params.get(1))
+                else {
+                    return;
+                };
+                if self.isupport.is_channel(target) {
+                    let changes =
+                        parse_channel_modes(modestring, &message.params[2..], &self.isupport);
+                    self.apply_channel_modes(target, &changes);
+                } else if self.is_own_nick(target) {
+                    self.apply_user_modes(&parse_user_modes(modestring));
+                }
+            }
+            // RPL_UMODEIS: <client> <user modes>
+            "221" => {
+                if let Some(modestring) = message.params.get(1) {
+                    self.user_modes.clear();
+                    self.apply_user_modes(&parse_user_modes(modestring));
+                }
+            }
+            // RPL_CHANNELMODEIS: <client> <channel> <modestring> <mode arguments>...
+            "324" => {
+                let (Some(channel), Some(modestring)) =
+                    (message.params.get(1), message.params.get(2))
+                else {
+                    return;
+                };
+                let changes = parse_channel_modes(modestring, &message.params[3..], &self.isupport);
+                if let Some(channel_state) = self.channel_mut(channel) {
+                    channel_state.modes.clear_settings();
+                    channel_state.user_limit = None;
+                }
+                self.apply_channel_modes(channel, &changes);
+            }
+            "332" | "333" => match Reply::parse_ref(message) {
+                Ok(Reply::Topic { channel, topic }) => {
+                    if let Some(channel_state) = self.channel_mut(&channel) {
+                        let info = channel_state.topic.get_or_insert_with(TopicInfo::default);
+                        info.text = topic;
+                    }
+                }
+                Ok(Reply::TopicWhoTime {
+                    channel,
+                    setter,
+                    set_at,
+                }) => {
+                    if let Some(channel_state) = self.channel_mut(&channel) {
+                        let info = channel_state.topic.get_or_insert_with(TopicInfo::default);
+                        info.set_by = setter;
+                        info.set_time = set_at;
+                    }
+                }
+                _ => debug!("Ignoring malformed topic reply: {}", message),
+            },
+            _ => {}
+        }
+    }
+
+    /// Apply `005` tokens, membership and modes from an incoming message
+    fn apply_incoming(&mut self, message: &MessageRef<'_>) {
+        if message.command == "005" {
+            let casemapping = self.isupport.casemapping;
+            let tokens = isupport_tokens(&message.params);
+            self.isupport.apply_tokens(tokens.iter().copied());
+            if self.isupport.casemapping != casemapping {
+                self.set_casemapping(self.isupport.casemapping);
+            }
+        }
+
+        self.apply_message(message);
+    }
+
+    /// Add a message to its channel's history, or the server history otherwise
+    fn record_history(&mut self, target: &str, entry: HistoryEntry) {
+        let history = if self.isupport.is_channel(target) {
+            match self.channel_mut(target) {
+                Some(channel_state) => &mut channel_state.message_history,
+                None => return,
+            }
+        } else {
+            // Private message or server message
+            &mut self.message_history
+        };
+
+        history.push_back(entry);
+        // Keep only last 1000 messages
+        if history.len() > 1000 {
+            history.pop_front();
+        }
+    }
+
+    /// Re-key channels and users after the server announces a new CASEMAPPING
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.isupport.casemapping = casemapping;
+        self.channels = self
+            .channels
+            .drain()
+            .map(|(name, mut channel)| {
+                channel.set_casemapping(casemapping);
+                (name.with_casemapping(casemapping), channel)
+            })
+            .collect();
+        self.users = self
+            .users
+            .drain()
+            .map(|(nick, user)| (nick.with_casemapping(casemapping), user))
+            .collect();
+    }
 }
 
 /// Information about the IRC server
@@ -107,7 +339,8 @@ pub struct ServerInfo {
 pub struct ChannelState {
     pub name: String,
     pub topic: Option<TopicInfo>,
-    pub users: HashMap<String, ChannelUser>, // Nick -> User info
+    pub users: HashMap<IrcString, ChannelUser>, // Nick -> User info
+    pub casemapping: CaseMapping,
     pub modes: ChannelModes,
     pub joined: bool,
     pub message_history: VecDeque<HistoryEntry>,
@@ -121,6 +354,7 @@ impl ChannelState {
             name,
             topic: None,
             users: HashMap::new(),
+            casemapping: CaseMapping::default(),
             modes: ChannelModes::default(),
             joined: false,
             message_history: VecDeque::with_capacity(1000),
@@ -129,6 +363,73 @@ impl ChannelState {
         }
     }
 
+    /// Create a channel whose users are keyed with the given mapping
+    pub fn with_casemapping(name: String, casemapping: CaseMapping) -> Self {
+        Self {
+            casemapping,
+            ..Self::new(name)
+        }
+    }
+
+    /// Re-key the channel's users with a different mapping
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+        self.users = self
+            .users
+            .drain()
+            .map(|(nick, user)| (nick.with_casemapping(casemapping), user))
+            .collect();
+    }
+
+    /// Look up a user in the channel, ignoring case as the server does
+    pub fn user(&self, nick: &str) -> Option<&ChannelUser> {
+        self.users.get(&IrcString::new(nick, self.casemapping))
+    }
+
+    /// Rename a user after a `NICK` change, keeping their channel modes
+    pub fn rename_user(&mut self, old_nick: &str, new_nick: &str) -> bool {
+        let Some(mut user) = self.remove_user_quiet(old_nick) else {
+            return false;
+        };
+        user.nick = new_nick.to_string();
+        self.users
+            .insert(IrcString::new(new_nick, self.casemapping), user);
+        true
+    }
+
+    /// Add a user with membership prefix modes, e.g. from `NAMES`
+    pub fn add_user_with_modes(&mut self, nick: String, modes: Vec<char>) {
+        let key = IrcString::new(nick.clone(), self.casemapping);
+        let channel_user = self.users.entry(key).or_insert_with(|| ChannelUser {
+            nick: nick.clone(),
+            modes: Vec::new(),
+            join_time: current_timestamp(),
+        });
+        channel_user.nick = nick;
+        channel_user.modes = modes;
+    }
+
+    /// Apply parsed mode changes to the channel settings and member prefixes
+    pub fn apply_mode_changes(&mut self, changes: &[ModeChange], isupport: &Isupport) {
+        for change in changes {
+            if change.kind == ModeKind::Prefix {
+                let Some(nick) = &change.param else {
+                    continue;
+                };
+                match self
+                    .users
+                    .get_mut(&IrcString::new(nick.as_str(), self.casemapping))
+                {
+                    Some(user) => user.apply_prefix_mode(change, isupport),
+                    None => debug!("Mode {} for unknown member {}", change, nick),
+                }
+            } else {
+                self.modes.apply(change);
+            }
+        }
+        self.user_limit = self.modes.limit;
+    }
+
     /// Add user to channel
     pub fn add_user(&mut self, nick: String, user: User) {
         let channel_user = ChannelUser {
@@ -136,7 +437,8 @@ impl ChannelState {
             modes: Vec::new(),
             join_time: current_timestamp(),
         };
-        self.users.insert(nick.clone(), channel_user);
+        self.users
+            .insert(IrcString::new(nick.clone(), self.casemapping), channel_user);
 
         // Also add to global user cache if provided
         // This would typically be done at the server level, but we store it here for now
@@ -148,7 +450,7 @@ impl ChannelState {
 
     /// Remove user from channel
     pub fn remove_user(&mut self, nick: &str) -> Option<ChannelUser> {
-        if let Some(user) = self.users.remove(nick) {
+        if let Some(user) = self.remove_user_quiet(nick) {
             debug!("Removed user {} from channel", nick);
             Some(user)
         } else {
@@ -160,6 +462,10 @@ impl ChannelState {
         }
     }
 
+    fn remove_user_quiet(&mut self, nick: &str) -> Option<ChannelUser> {
+        self.users.remove(&IrcString::new(nick, self.casemapping))
+    }
+
     /// Get user count
     pub fn user_count(&self) -> usize {
         self.users.len()
@@ -170,14 +476,34 @@ impl ChannelState {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelUser {
     pub nick: String,
-    pub modes: Vec<char>, // Channel-specific modes (o, v, etc.)
+    pub modes: Vec<char>, // Channel-specific modes (o, v, etc.), highest first
     pub join_time: u64,
 }
 
+impl ChannelUser {
+    /// Add or remove a `PREFIX` mode, keeping the modes ordered by rank
+    pub fn apply_prefix_mode(&mut self, change: &ModeChange, isupport: &Isupport) {
+        if !change.adding {
+            self.modes.retain(|mode| *mode != change.mode);
+        } else if !self.modes.contains(&change.mode) {
+            self.modes.push(change.mode);
+            self.modes
+                .sort_by_key(|mode| isupport.prefix_rank(*mode).unwrap_or(usize::MAX));
+        }
+    }
+
+    /// Highest membership prefix symbol, e.g. `@` for an operator
+    pub fn prefix(&self, isupport: &Isupport) -> Option<char> {
+        self.modes
+            .first()
+            .and_then(|mode| isupport.prefix_for_mode(*mode))
+    }
+}
+
 /// Channel modes
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChannelModes {
-    pub modes: String, // Raw mode string
+    pub modes: String, // Set modes without parameters, e.g. "+kntl"
     pub secret: bool,
     pub private: bool,
     pub invite_only: bool,
@@ -186,10 +512,71 @@ pub struct ChannelModes {
     pub moderated: bool,
     pub key: Option<String>,
     pub limit: Option<u32>,
+    pub settings: BTreeMap<char, Option<String>>, // Every set non-list mode and its parameter
+    pub lists: HashMap<char, Vec<String>>,        // List modes (bans, exceptions, ...) -> masks
+}
+
+impl ChannelModes {
+    /// Apply a non-prefix mode change
+    pub fn apply(&mut self, change: &ModeChange) {
+        if change.kind == ModeKind::List {
+            // A list query (`MODE #chan +b`) carries no mask
+            let Some(mask) = &change.param else {
+                return;
+            };
+            let list = self.lists.entry(change.mode).or_default();
+            if !change.adding {
+                list.retain(|entry| entry != mask);
+            } else if !list.contains(mask) {
+                list.push(mask.clone());
+            }
+            return;
+        }
+
+        if change.adding {
+            self.settings.insert(change.mode, change.param.clone());
+        } else {
+            self.settings.remove(&change.mode);
+        }
+        self.sync();
+    }
+
+    /// Forget all settings before a full listing (324 RPL_CHANNELMODEIS)
+    pub fn clear_settings(&mut self) {
+        self.settings.clear();
+        self.sync();
+    }
+
+    /// Check whether a mode is set
+    pub fn is_set(&self, mode: char) -> bool {
+        self.settings.contains_key(&mode)
+    }
+
+    /// Update the convenience fields from `settings`
+    fn sync(&mut self) {
+        self.secret = self.is_set('s');
+        self.private = self.is_set('p');
+        self.invite_only = self.is_set('i');
+        self.topic_protected = self.is_set('t');
+        self.no_external_messages = self.is_set('n');
+        self.moderated = self.is_set('m');
+        self.key = self.settings.get(&'k').cloned().flatten();
+        self.limit = self
+            .settings
+            .get(&'l')
+            .and_then(|limit| limit.as_deref()?.parse().ok());
+        self.modes = if self.settings.is_empty() {
+            String::new()
+        } else {
+            std::iter::once('+')
+                .chain(self.settings.keys().copied())
+                .collect()
+        };
+    }
 }
 
 /// Topic information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TopicInfo {
     pub text: String,
     pub set_by: String,
@@ -279,7 +666,9 @@ pub enum StateEventType {
     ServerDisconnected {
         reason: String,
     },
-    ServerRegistered,
+    ServerRegistered {
+        nickname: String,
+    },
 
     // User events
     NickChanged {
@@ -318,7 +707,7 @@ pub enum StateEventType {
     },
     ChannelModeChanged {
         channel: String,
-        modes: String,
+        changes: Vec<ModeChange>,
     },
 
     // Message events
@@ -370,6 +759,25 @@ impl StateManager {
         state.servers.get(connection_id).cloned()
     }
 
+    /// Get the CASEMAPPING of a connection, falling back to `rfc1459`
+    pub async fn casemapping(&self, connection_id: &str) -> CaseMapping {
+        let state = self.state.read().await;
+        state
+            .servers
+            .get(connection_id)
+            .map(|server| server.isupport.casemapping)
+            .unwrap_or_default()
+    }
+
+    /// Get the CHANTYPES of a connection, falling back to `#&`
+    pub async fn chantypes(&self, connection_id: &str) -> String {
+        let state = self.state.read().await;
+        state.servers.get(connection_id).map_or_else(
+            || Isupport::default().chantypes,
+            |server| server.isupport.chantypes.clone(),
+        )
+    }
+
     /// Apply an IRC event to the state
     pub async fn apply_event(&self, event: &Event) -> Result<()> {
         let mut state = self.state.write().await;
@@ -393,6 +801,34 @@ impl StateManager {
         Ok(())
     }
 
+    /// Apply a borrowed message directly, without recording a state event
+    ///
+    /// Meant for bulk input such as bouncer playback and NAMES bursts:
+    /// `005` tokens, membership, modes and topics are updated in place, and
+    /// the message is only copied when a `PRIVMSG` or `NOTICE` is kept in
+    /// history. Unlike [`StateManager::apply_event`], nothing is added to the
+    /// event log, so these changes are not reproduced by
+    /// [`StateManager::replay_events`].
+    pub async fn apply_message_ref(&self, connection_id: &str, message: &MessageRef<'_>) {
+        let mut state = self.state.write().await;
+        let Some(server_state) = state.servers.get_mut(connection_id) else {
+            debug!(
+                "Ignoring {} for unknown connection {}",
+                message.command, connection_id
+            );
+            return;
+        };
+
+        server_state.apply_incoming(message);
+        if matches!(message.command, "PRIVMSG" | "NOTICE") {
+            if let Some(target) = message.params.first() {
+                server_state.record_history(target, HistoryEntry::new(message.to_message()));
+            }
+        }
+
+        state.version += 1;
+    }
+
     /// Create state event from IRC event
     async fn create_state_event(&self, event: &Event, id: u64) -> Result<StateEvent> {
         let event_type = match event {
@@ -426,6 +862,18 @@ impl StateManager {
                     message: message.clone(),
                 }
             }
+            Event::Registered {
+                connection_id,
+                nickname,
+            } => {
+                debug!(
+                    "Creating registration state event for connection: {} - nick: {}",
+                    connection_id, nickname
+                );
+                StateEventType::ServerRegistered {
+                    nickname: nickname.clone(),
+                }
+            }
             Event::ChannelJoined {
                 connection_id,
                 channel,
@@ -465,6 +913,18 @@ impl StateManager {
                     new_nick: new.clone(),
                 }
             }
+            Event::CapabilitiesChanged {
+                connection_id,
+                capabilities,
+            } => {
+                debug!(
+                    "Creating capabilities state event for connection: {} - {:?}",
+                    connection_id, capabilities
+                );
+                StateEventType::CapabilitiesReceived {
+                    capabilities: capabilities.clone(),
+                }
+            }
             _ => {
                 return Err(Error::State("Unsupported event type".to_string()));
             }
@@ -473,10 +933,12 @@ impl StateManager {
         let connection_id = match event {
             Event::Connected { connection_id }
             | Event::Disconnected { connection_id, .. }
+            | Event::Registered { connection_id, .. }
             | Event::MessageReceived { connection_id, .. }
             | Event::ChannelJoined { connection_id, .. }
             | Event::ChannelLeft { connection_id, .. }
-            | Event::NickChanged { connection_id, .. } => connection_id.clone(),
+            | Event::NickChanged { connection_id, .. }
+            | Event::CapabilitiesChanged { connection_id, .. } => connection_id.clone(),
             _ => String::new(),
         };
 
@@ -515,59 +977,57 @@ impl StateManager {
                     channel.joined = false;
                 }
             }
-            StateEventType::ServerRegistered => {
+            StateEventType::ServerRegistered { nickname } => {
                 server_state.registered = true;
+                server_state.nickname = nickname.clone();
             }
             StateEventType::ChannelJoined { channel } => {
+                let casemapping = server_state.isupport.casemapping;
                 let channel_state = server_state
                     .channels
-                    .entry(channel.clone())
-                    .or_insert_with(|| ChannelState::new(channel.clone()));
+                    .entry(IrcString::new(channel.clone(), casemapping))
+                    .or_insert_with(|| {
+                        ChannelState::with_casemapping(channel.clone(), casemapping)
+                    });
                 channel_state.joined = true;
             }
             StateEventType::ChannelLeft { channel, reason } => {
                 debug!("Channel left: {} with reason: {:?}", channel, reason);
-                if let Some(channel_state) = server_state.channels.get_mut(channel) {
+                if let Some(channel_state) = server_state.channel_mut(channel) {
                     channel_state.joined = false;
                 }
             }
             StateEventType::NickChanged { old_nick, new_nick } => {
-                server_state.nickname = new_nick.clone();
+                if server_state.is_own_nick(old_nick) {
+                    server_state.nickname = new_nick.clone();
+                }
                 // Update nick in all channels
                 for channel_state in server_state.channels.values_mut() {
-                    if let Some(user) = channel_state.users.remove(old_nick) {
-                        let mut new_user = user;
-                        new_user.nick = new_nick.clone();
-                        channel_state.users.insert(new_nick.clone(), new_user);
-                    }
+                    channel_state.rename_user(old_nick, new_nick);
+                }
+                let old_key = server_state.key(old_nick);
+                if let Some(mut user) = server_state.users.remove(&old_key) {
+                    user.nickname = new_nick.clone();
+                    let new_key = server_state.key(new_nick);
+                    server_state.users.insert(new_key, user);
                 }
             }
             StateEventType::MessageReceived { target, message } => {
+                server_state.apply_incoming(&MessageRef::from(message));
                 // Add to appropriate message history
-                let history_entry = HistoryEntry::new(message.clone());
-
-                if target.starts_with('#') || target.starts_with('&') {
-                    // Channel message
-                    if let Some(channel_state) = server_state.channels.get_mut(target) {
-                        channel_state.message_history.push_back(history_entry);
-                        // Keep only last 1000 messages
-                        if channel_state.message_history.len() > 1000 {
-                            channel_state.message_history.pop_front();
-                        }
-                    }
-                } else {
-                    // Private message or server message
-                    server_state.message_history.push_back(history_entry);
-                    if server_state.message_history.len() > 1000 {
-                        server_state.message_history.pop_front();
-                    }
-                }
+                server_state.record_history(target, HistoryEntry::new(message.clone()));
             }
             StateEventType::TopicChanged { channel, topic } => {
-                if let Some(channel_state) = server_state.channels.get_mut(channel) {
+                if let Some(channel_state) = server_state.channel_mut(channel) {
                     channel_state.topic = Some(topic.clone());
                 }
             }
+            StateEventType::ChannelModeChanged { channel, changes } => {
+                server_state.apply_channel_modes(channel, changes);
+            }
+            StateEventType::CapabilitiesReceived { capabilities } => {
+                server_state.capabilities = capabilities.clone();
+            }
             _ => {
                 // Handle other event types
                 debug!("Unhandled state event type: {:?}", event.event_type);
@@ -630,3 +1090,115 @@ fn current_timestamp() -> u64 {
         .unwrap_or_default()
         .as_secs()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustirc_protocol::Parser;
+
+    async fn receive(manager: &StateManager, line: &str) {
+        let event = Event::MessageReceived {
+            connection_id: "test".to_string(),
+            message: Parser::parse_message(line).unwrap(),
+        };
+        manager.apply_event(&event).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_membership_and_mode_changes() {
+        let manager = StateManager::new();
+        receive(&manager, ":srv 001 me :Welcome").await;
+        manager
+            .apply_event(&Event::Registered {
+                connection_id: "test".to_string(),
+                nickname: "me".to_string(),
+            })
+            .await
+            .unwrap();
+        receive(
+            &manager,
+            ":srv 005 me PREFIX=(ov)@+ CHANMODES=b,k,l,imnst :are supported",
+        )
+        .await;
+        receive(&manager, ":me!u@h JOIN #Rust").await;
+        receive(&manager, ":srv 353 me = #rust :@me +Alice bob").await;
+        receive(&manager, ":op!u@h MODE #RUST +ok-v bob secret Alice").await;
+        receive(&manager, ":op!u@h MODE #rust +lb 25 *!*@spam").await;
+
+        let server = manager.get_server_state("test").await.unwrap();
+        let channel = server.channel("#rust").unwrap();
+        assert_eq!(channel.name, "#Rust");
+        assert_eq!(channel.user("BOB").unwrap().modes, vec!['o']);
+        assert!(channel.user("alice").unwrap().modes.is_empty());
+        assert_eq!(
+            channel.user("me").unwrap().prefix(&server.isupport),
+            Some('@')
+        );
+        assert_eq!(channel.modes.key.as_deref(), Some("secret"));
+        assert_eq!(channel.user_limit, Some(25));
+        assert_eq!(channel.modes.lists[&'b'], vec!["*!*@spam".to_string()]);
+
+        receive(&manager, ":srv 324 me #rust +nt").await;
+        let server = manager.get_server_state("test").await.unwrap();
+        let channel = server.channel("#rust").unwrap();
+        assert_eq!(channel.modes.modes, "+nt");
+        assert!(channel.modes.topic_protected && channel.modes.key.is_none());
+        assert_eq!(channel.user_limit, None);
+    }
+
+    #[tokio::test]
+    async fn test_borrowed_messages_skip_event_log() {
+        let manager = StateManager::new();
+        receive(&manager, ":srv 001 me :Welcome").await;
+        manager
+            .apply_event(&Event::Registered {
+                connection_id: "test".to_string(),
+                nickname: "me".to_string(),
+            })
+            .await
+            .unwrap();
+        receive(&manager, ":me!u@h JOIN #rust").await;
+        let logged = manager.get_events().await.len();
+
+        let parser = Parser::new();
+        for line in [
+            ":srv 353 me = #rust :@alice bob",
+            ":alice!a@h PRIVMSG #rust :hello",
+            ":bob!b@h PART #rust",
+        ] {
+            let message = parser.parse_ref(line).unwrap();
+            manager.apply_message_ref("test", &message).await;
+        }
+
+        let server = manager.get_server_state("test").await.unwrap();
+        let channel = server.channel("#rust").unwrap();
+        assert_eq!(channel.user("alice").unwrap().modes, vec!['o']);
+        assert!(channel.user("bob").is_none());
+        assert_eq!(channel.message_history.len(), 1);
+        assert_eq!(manager.get_events().await.len(), logged);
+    }
+
+    #[tokio::test]
+    async fn test_topic_replies() {
+        let manager = StateManager::new();
+        receive(&manager, ":srv 001 me :Welcome").await;
+        manager
+            .apply_event(&Event::Registered {
+                connection_id: "test".to_string(),
+                nickname: "me".to_string(),
+            })
+            .await
+            .unwrap();
+        receive(&manager, ":me!u@h JOIN #rust").await;
+        receive(&manager, ":srv 332 me #rust :Welcome to #rust").await;
+        receive(&manager, ":srv 333 me #rust alice 1700000000").await;
+        // Malformed replies leave the topic alone
+        receive(&manager, ":srv 333 me #rust bob later").await;
+
+        let server = manager.get_server_state("test").await.unwrap();
+        let topic = server.channel("#rust").unwrap().topic.clone().unwrap();
+        assert_eq!(topic.text, "Welcome to #rust");
+        assert_eq!(topic.set_by, "alice");
+        assert_eq!(topic.set_time, 1700000000);
+    }
+}
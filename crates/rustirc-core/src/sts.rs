@@ -0,0 +1,405 @@
+//! IRCv3 Strict Transport Security
+//!
+//! A server advertising the `sts` capability on a plaintext connection tells
+//! the client which port to reconnect to with TLS. Over TLS it advertises a
+//! `duration`, and the policy is stored per hostname so that later plaintext
+//! connections are upgraded until it expires.
+//!
+//! See: <https://ircv3.net/specs/extensions/sts>
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+/// An `sts` capability value, e.g. `port=6697,duration=2592000`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StsPolicy {
+    /// TLS port to reconnect to; only meaningful on plaintext connections
+    pub port: Option<u16>,
+    /// How long the policy stays in effect; only meaningful over TLS
+    pub duration: Option<Duration>,
+    /// Whether the server allows its policy to be preloaded
+    pub preload: bool,
+}
+
+impl StsPolicy {
+    /// Parse a capability value, ignoring unknown and malformed keys
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_core::sts::StsPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = StsPolicy::parse("duration=300,port=6697,preload");
+    /// assert_eq!(policy.port, Some(6697));
+    /// assert_eq!(policy.duration, Some(Duration::from_secs(300)));
+    /// assert!(policy.preload);
+    /// ```
+    pub fn parse(value: &str) -> Self {
+        let mut policy = Self::default();
+        for token in value.split(',') {
+            let (key, value) = token.split_once('=').unwrap_or((token, ""));
+            match key {
+                "port" => policy.port = value.parse().ok(),
+                "duration" => policy.duration = value.parse().ok().map(Duration::from_secs),
+                "preload" => policy.preload = true,
+                _ => {}
+            }
+        }
+        policy
+    }
+}
+
+/// A stored policy: the TLS port and when the policy expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StsEntry {
+    pub port: u16,
+    pub expires: SystemTime,
+}
+
+/// Persistent store of STS policies keyed by hostname
+///
+/// Each line holds a hostname, the TLS port and the expiry as seconds since
+/// the Unix epoch. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct StsPolicies {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, StsEntry>,
+}
+
+impl StsPolicies {
+    /// Default location of the policy file
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("rustirc")
+            .join("sts_policies")
+    }
+
+    /// Open a policy file, starting empty if it does not exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut policies = match std::fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                return Err(Error::Config(format!(
+                    "Failed to read {}: {e}",
+                    path.display()
+                )))
+            }
+        };
+        policies.path = Some(path);
+        Ok(policies)
+    }
+
+    /// Parse policy entries without an associated file
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let host = fields.next()?;
+                let port = fields.next().and_then(|port| port.parse().ok());
+                let expires = fields.next().and_then(|secs| secs.parse().ok());
+                match (port, expires) {
+                    (Some(port), Some(expires)) => Some((
+                        host.to_ascii_lowercase(),
+                        StsEntry {
+                            port,
+                            expires: UNIX_EPOCH + Duration::from_secs(expires),
+                        },
+                    )),
+                    _ => {
+                        warn!("Ignoring malformed STS policy line: {}", line);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            path: None,
+            entries,
+        }
+    }
+
+    /// The policy for a host if it has not expired at `now`
+    pub fn get(&self, host: &str, now: SystemTime) -> Option<StsEntry> {
+        self.entries
+            .get(&host.to_ascii_lowercase())
+            .filter(|entry| entry.expires > now)
+            .copied()
+    }
+
+    /// Record a policy advertised over TLS on `port`
+    ///
+    /// A zero duration removes the host's policy, as the spec requires.
+    pub fn update(&mut self, host: &str, port: u16, duration: Duration, now: SystemTime) {
+        let host = host.to_ascii_lowercase();
+        if duration.is_zero() {
+            self.entries.remove(&host);
+        } else {
+            let expires = now + duration;
+            self.entries.insert(host, StsEntry { port, expires });
+        }
+    }
+
+    /// Forget a host's policy
+    pub fn remove(&mut self, host: &str) -> Option<StsEntry> {
+        self.entries.remove(&host.to_ascii_lowercase())
+    }
+
+    /// Number of stored policies, including expired ones
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no policies are stored
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the store back to the file it was opened from, dropping
+    /// policies that have expired
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let now = SystemTime::now();
+        let mut contents = String::from("# RustIRC STS policies: <host> <port> <expiry>\n");
+        for (host, entry) in self.entries.iter().filter(|(_, e)| e.expires > now) {
+            let expires = entry
+                .expires
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            contents.push_str(&format!("{host} {} {expires}\n", entry.port));
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TlsVerification;
+    use crate::connection::{ConnectionConfig, IrcConnection};
+    use crate::events::EventBus;
+    use crate::tls::tests::{TEST_CERT, TEST_KEY};
+    use crate::tls::ClientIdentity;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = StsPolicy::parse("port=6697");
+        assert_eq!(policy.port, Some(6697));
+        assert_eq!(policy.duration, None);
+        assert!(!policy.preload);
+
+        let policy = StsPolicy::parse("port=abc,duration=60,foo=bar");
+        assert_eq!(policy.port, None);
+        assert_eq!(policy.duration, Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_policies_expire_and_persist() {
+        let path = std::env::temp_dir().join(format!("rustirc-sts-{}", std::process::id()));
+        let now = SystemTime::now();
+
+        let mut policies = StsPolicies::open(&path).unwrap();
+        assert!(policies.is_empty());
+        policies.update("IRC.Example.com", 6697, Duration::from_secs(3600), now);
+        policies.update("old.example.com", 6697, Duration::from_secs(1), now);
+        assert_eq!(policies.get("irc.example.com", now).unwrap().port, 6697);
+        assert!(policies
+            .get("old.example.com", now + Duration::from_secs(2))
+            .is_none());
+        policies.save().unwrap();
+
+        let mut reopened = StsPolicies::open(&path).unwrap();
+        let entry = reopened.get("irc.example.com", now).unwrap();
+        assert_eq!(entry.port, 6697);
+        assert!(entry.expires > now + Duration::from_secs(3500));
+
+        // A zero duration clears the policy
+        reopened.update("irc.example.com", 6697, Duration::ZERO, now);
+        assert!(reopened.get("irc.example.com", now).is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_upgrades_to_tls() {
+        let plain = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let secure = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let plain_port = plain.local_addr().unwrap().port();
+        let secure_port = secure.local_addr().unwrap().port();
+
+        // Advertise STS and record everything the client sends afterwards
+        let plain_server = tokio::spawn(async move {
+            let (stream, _) = plain.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut received = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.starts_with("CAP LS") {
+                    let ls = format!(":irc.test CAP * LS :sts=port={secure_port} multi-prefix\r\n");
+                    writer.write_all(ls.as_bytes()).await.unwrap();
+                }
+                received.push(line);
+            }
+            received
+        });
+        let secure_server = tokio::spawn(async move {
+            let (mut stream, _) = secure.accept().await.unwrap();
+            stream.read_u8().await.unwrap()
+        });
+
+        let policies =
+            std::env::temp_dir().join(format!("rustirc-sts-upgrade-{}", std::process::id()));
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port: plain_port,
+            use_tls: false,
+            nickname: "stsuser".to_string(),
+            reconnect_attempts: 1,
+            sts_policies: Some(policies.clone()),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        tokio::spawn(async move { connection.connect().await });
+
+        // A TLS handshake starts with a handshake record
+        let first_byte = tokio::time::timeout(Duration::from_secs(10), secure_server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first_byte, 0x16);
+
+        let received = tokio::time::timeout(Duration::from_secs(10), plain_server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            received.iter().any(|line| line == "CAP LS 302"),
+            "{received:?}"
+        );
+        assert!(
+            !received.iter().any(|line| line.starts_with("CAP REQ")),
+            "{received:?}"
+        );
+
+        let _ = std::fs::remove_file(&policies);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_policies_do_not_block_connecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // A directory cannot be read as a policy file
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port,
+            use_tls: false,
+            nickname: "stsuser".to_string(),
+            reconnect_attempts: 1,
+            sts_policies: Some(std::env::temp_dir()),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        tokio::spawn(async move { connection.connect().await });
+
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(10), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let registering = async {
+            while let Some(line) = lines.next_line().await.unwrap() {
+                if line == "CAP LS 302" {
+                    return;
+                }
+            }
+            panic!("connection closed before CAP LS");
+        };
+        tokio::time::timeout(Duration::from_secs(10), registering)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unverified_link_does_not_store_policy() {
+        let dir =
+            std::env::temp_dir().join(format!("rustirc-sts-unverified-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("server.pem");
+        let key = dir.join("server.key");
+        std::fs::write(&cert, TEST_CERT).unwrap();
+        std::fs::write(&key, TEST_KEY).unwrap();
+        let identity = ClientIdentity::load(&cert, Some(&key)).unwrap();
+        let server_config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(identity.certificates, identity.key)
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Advertise a persistent policy and wait for negotiation to finish
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = acceptor.accept(stream).await.unwrap();
+            let (reader, mut writer) = tokio::io::split(stream);
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.starts_with("CAP LS") {
+                    let ls = ":irc.test CAP * LS :sts=duration=3600\r\n";
+                    writer.write_all(ls.as_bytes()).await.unwrap();
+                } else if line == "CAP END" {
+                    return;
+                }
+            }
+            panic!("connection closed before CAP END");
+        });
+
+        let policies = dir.join("sts");
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port,
+            use_tls: true,
+            tls_verification: TlsVerification::AcceptInvalid,
+            nickname: "stsuser".to_string(),
+            reconnect_attempts: 1,
+            sts_policies: Some(policies.clone()),
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        tokio::spawn(async move { connection.connect().await });
+
+        tokio::time::timeout(Duration::from_secs(10), server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(StsPolicies::open(&policies).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,252 @@
+//! IRC over WebSocket
+//!
+//! Carries one IRC line per WebSocket message, without the `\r\n`. The
+//! `binary.ircv3.net` subprotocol is preferred since it allows non-UTF-8
+//! text, which is then handled by the connection's [`Charset`];
+//! `text.ircv3.net` is used when the server picks it or picks neither.
+//!
+//! See: <https://ircv3.net/specs/extensions/websocket>
+
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use futures_util::future::ready;
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use rustirc_protocol::Message;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message as Frame;
+use tokio_tungstenite::{client_async_with_config, WebSocketStream};
+use tracing::debug;
+
+use crate::codec::{Charset, MAX_LINE_LENGTH};
+use crate::error::{Error, Result};
+
+/// Subprotocol carrying lines as binary messages in any encoding
+pub const BINARY_PROTOCOL: &str = "binary.ircv3.net";
+/// Subprotocol carrying lines as UTF-8 text messages
+pub const TEXT_PROTOCOL: &str = "text.ircv3.net";
+
+const PROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+
+/// The subprotocol chosen by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketProtocol {
+    Binary,
+    Text,
+}
+
+/// Build the `ws://` or `wss://` URL for a server
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_core::websocket::websocket_url;
+///
+/// assert_eq!(websocket_url("irc.example.com", 443, true, "/webirc"), "wss://irc.example.com:443/webirc");
+/// assert_eq!(websocket_url("::1", 8080, false, "irc"), "ws://[::1]:8080/irc");
+/// ```
+pub fn websocket_url(server: &str, port: u16, use_tls: bool, path: &str) -> String {
+    let scheme = if use_tls { "wss" } else { "ws" };
+    let host = if server.contains(':') {
+        format!("[{server}]")
+    } else {
+        server.to_string()
+    };
+    let path = path.trim_start_matches('/');
+    format!("{scheme}://{host}:{port}/{path}")
+}
+
+/// Perform the WebSocket handshake over an established stream
+///
+/// The stream is already connected to the server, through a proxy and TLS
+/// if configured, so `url` only supplies the request's host and path.
+pub async fn handshake<S>(stream: S, url: &str) -> Result<(WebSocketStream<S>, WebSocketProtocol)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| Error::InvalidAddress(format!("{url}: {e}")))?;
+    request.headers_mut().insert(
+        PROTOCOL_HEADER,
+        HeaderValue::from_static("binary.ircv3.net, text.ircv3.net"),
+    );
+
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_LINE_LENGTH));
+    let (socket, response) = client_async_with_config(request, stream, Some(config))
+        .await
+        .map_err(|e| Error::ConnectionFailed(format!("WebSocket handshake failed: {e}")))?;
+
+    let protocol = match response
+        .headers()
+        .get(PROTOCOL_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(BINARY_PROTOCOL) => WebSocketProtocol::Binary,
+        Some(TEXT_PROTOCOL) | None => WebSocketProtocol::Text,
+        Some(other) => {
+            return Err(Error::Protocol(format!(
+                "Server chose unsupported WebSocket subprotocol {other}"
+            )))
+        }
+    };
+    debug!("WebSocket connected to {} using {:?}", url, protocol);
+    Ok((socket, protocol))
+}
+
+/// Split a WebSocket into a stream of received lines and a sink of messages
+///
+/// Binary lines are decoded and encoded with `charset`; text lines are
+/// always UTF-8.
+pub fn split<S>(
+    socket: WebSocketStream<S>,
+    protocol: WebSocketProtocol,
+    charset: Arc<RwLock<Charset>>,
+) -> (
+    impl Stream<Item = io::Result<String>> + Unpin + Send + 'static,
+    impl Sink<Message, Error = io::Error> + Unpin + Send + 'static,
+)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (sink, stream) = socket.split();
+
+    let decoder = charset.clone();
+    let lines = stream.filter_map(move |frame| {
+        let line = match frame {
+            Ok(Frame::Text(text)) => Some(Ok(trim_line(text.as_str()).to_string())),
+            Ok(Frame::Binary(bytes)) => {
+                let bytes = bytes.strip_suffix(b"\n").unwrap_or(&bytes);
+                let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+                let charset = decoder.read().unwrap_or_else(|e| e.into_inner());
+                Some(Ok(charset.decode(bytes)))
+            }
+            // Pings are answered by the WebSocket itself
+            Ok(_) => None,
+            Err(e) => Some(Err(io::Error::other(e))),
+        };
+        ready(line.filter(|line| !matches!(line, Ok(text) if text.is_empty())))
+    });
+
+    let sink = sink
+        .sink_map_err(io::Error::other)
+        .with(move |message: Message| {
+            let line = message.to_string();
+            let frame = match protocol {
+                WebSocketProtocol::Text => Frame::text(line),
+                WebSocketProtocol::Binary => {
+                    let charset = charset.read().unwrap_or_else(|e| e.into_inner());
+                    Frame::binary(charset.encode(&line))
+                }
+            };
+            ready(Ok::<_, io::Error>(frame))
+        });
+
+    (lines, sink)
+}
+
+fn trim_line(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Transport;
+    use crate::connection::{ConnectionConfig, ConnectionState, IrcConnection};
+    use crate::events::EventBus;
+    use crate::mock_server::{MockIrcServer, MockServerConfig};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    /// Accept one WebSocket client and relay its lines to the mock server
+    #[allow(clippy::result_large_err)] // The handshake callback's error is an HTTP response
+    async fn start_gateway(irc_addr: SocketAddr) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let choose_text = |request: &Request, mut response: Response| {
+                let offered = request.headers().get(PROTOCOL_HEADER).unwrap();
+                assert!(offered.to_str().unwrap().contains(TEXT_PROTOCOL));
+                response
+                    .headers_mut()
+                    .insert(PROTOCOL_HEADER, HeaderValue::from_static(TEXT_PROTOCOL));
+                Ok(response)
+            };
+            let socket = tokio_tungstenite::accept_hdr_async(stream, choose_text)
+                .await
+                .unwrap();
+            let (mut ws_sink, mut ws_stream) = socket.split();
+
+            let (irc_reader, mut irc_writer) =
+                TcpStream::connect(irc_addr).await.unwrap().into_split();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(irc_reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if ws_sink.send(Frame::text(line)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            while let Some(Ok(frame)) = ws_stream.next().await {
+                if let Frame::Text(text) = frame {
+                    // Lines arrive without a terminator
+                    assert!(!text.as_str().ends_with('\n'));
+                    let line = format!("{}\r\n", text.as_str());
+                    if irc_writer.write_all(line.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_register_over_websocket() {
+        let mut server = MockIrcServer::new(MockServerConfig::default());
+        server.start("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let gateway = start_gateway(server.local_addr().unwrap()).await;
+
+        let config = ConnectionConfig {
+            server: "127.0.0.1".to_string(),
+            port: gateway.port(),
+            use_tls: false,
+            nickname: "wsuser".to_string(),
+            transport: Transport::WebSocket {
+                path: "/webirc".to_string(),
+            },
+            reconnect_attempts: 1,
+            ..Default::default()
+        };
+        let connection = IrcConnection::new(config, Arc::new(EventBus::new()));
+        let mut states = connection.subscribe_state_changes();
+        tokio::spawn({
+            let connection = connection.clone();
+            async move { connection.connect().await }
+        });
+
+        let registered = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Ok(state) = states.recv().await {
+                if state == ConnectionState::Registered {
+                    return true;
+                }
+            }
+            false
+        })
+        .await;
+        assert_eq!(registered, Ok(true));
+        assert_eq!(connection.nickname().await, "wsuser");
+
+        server.stop().await.unwrap();
+    }
+}
@@ -960,9 +960,11 @@ impl RustIrcGui {
                         }
                     }
                     InputAreaMessage::ToggleMultiline => {
-                        // Toggle multiline input mode
+                        // The input area toggles itself in its update below
                         info!("Toggling multiline input mode");
-                        self.input_area.toggle_multiline();
+                    }
+                    InputAreaMessage::EditorAction(_) => {
+                        // Editing is handled by the input area itself
                     }
                     InputAreaMessage::TabCompletion => {
                         // Handle tab completion - this is already implemented in InputArea
@@ -1278,7 +1280,7 @@ impl RustIrcGui {
                                 // Update channel user list
                                 if let Some(server) = self.app_state.servers.get_mut(&connection_id)
                                 {
-                                    if let Some(channel_info) = server.channels.get_mut(channel) {
+                                    if let Some(channel_info) = server.channel_mut(channel) {
                                         channel_info.users = users.clone();
                                         channel_info.user_count = channel_info.users.len();
                                     }
@@ -1330,7 +1332,7 @@ impl RustIrcGui {
                                 // Add user to channel
                                 if let Some(server) = self.app_state.servers.get_mut(&connection_id)
                                 {
-                                    if let Some(channel_info) = server.channels.get_mut(channel) {
+                                    if let Some(channel_info) = server.channel_mut(channel) {
                                         if !channel_info.users.contains(&nick.to_string()) {
                                             channel_info.users.push(nick.to_string());
                                             channel_info.user_count = channel_info.users.len();
@@ -1359,7 +1361,7 @@ impl RustIrcGui {
                                 // Remove user from channel
                                 if let Some(server) = self.app_state.servers.get_mut(&connection_id)
                                 {
-                                    if let Some(channel_info) = server.channels.get_mut(channel) {
+                                    if let Some(channel_info) = server.channel_mut(channel) {
                                         channel_info.users.retain(|u| u != nick);
                                         channel_info.user_count = channel_info.users.len();
                                     }
@@ -1673,6 +1675,17 @@ impl RustIrcGui {
                                 }
                             }
                             "002" | "003" | "004" | "005" => {
+                                if message.command == "005" {
+                                    // RPL_ISUPPORT: match names with the server's CASEMAPPING
+                                    let casemapping = message
+                                        .params
+                                        .iter()
+                                        .find_map(|param| param.strip_prefix("CASEMAPPING="))
+                                        .and_then(rustirc_protocol::CaseMapping::parse);
+                                    if let Some(casemapping) = casemapping {
+                                        self.app_state.set_casemapping(&connection_id, casemapping);
+                                    }
+                                }
                                 // Server info messages
                                 if message.params.len() >= 2 {
                                     let info_text = &message.params[1];
@@ -1913,6 +1926,49 @@ impl RustIrcGui {
                             server.connection_state = state;
                         }
                     }
+                    CoreEventMessage::EndpointChanged {
+                        connection_id,
+                        endpoint,
+                    } => {
+                        info!("Core event: {} connecting via {}", connection_id, endpoint);
+                        if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
+                            server.endpoint = Some(endpoint);
+                        }
+                    }
+                    CoreEventMessage::SendQueueChanged {
+                        connection_id,
+                        pending,
+                    } => {
+                        if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
+                            server.pending_lines = pending;
+                        }
+                    }
+                    CoreEventMessage::HistoryGap {
+                        connection_id,
+                        target,
+                        since,
+                    } => {
+                        self.app_state
+                            .add_history_gap(&connection_id, &target, since.as_deref());
+                    }
+                    CoreEventMessage::Registered {
+                        connection_id,
+                        nickname,
+                    } => {
+                        info!(
+                            "Core event: Registered on {} as {}",
+                            connection_id, nickname
+                        );
+                        if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
+                            server.nickname = nickname.clone();
+                        }
+                        self.app_state.add_message(
+                            &connection_id,
+                            &connection_id,
+                            &format!("Registered as {nickname}"),
+                            "system",
+                        );
+                    }
                     CoreEventMessage::Error {
                         connection_id,
                         error,
@@ -1948,7 +2004,7 @@ impl RustIrcGui {
                         );
                         // Add channel to server if not already there
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
-                            if !server.channels.contains_key(&channel) {
+                            if server.channel(&channel).is_none() {
                                 self.app_state
                                     .add_channel_tab(connection_id.clone(), channel.clone());
                             }
@@ -1967,7 +2023,7 @@ impl RustIrcGui {
                         info!("Core event: Left channel {} on {}", channel, connection_id);
                         // Remove channel from server
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
-                            server.channels.remove(&channel);
+                            server.remove_channel(&channel);
                         }
                         self.app_state.add_message(
                             &connection_id,
@@ -1987,7 +2043,7 @@ impl RustIrcGui {
                         );
                         // Add user to channel
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
-                            if let Some(channel_info) = server.channels.get_mut(&channel) {
+                            if let Some(channel_info) = server.channel_mut(&channel) {
                                 if !channel_info.users.contains(&user) {
                                     channel_info.users.push(user.clone());
                                     channel_info.user_count = channel_info.users.len();
@@ -2012,7 +2068,7 @@ impl RustIrcGui {
                         );
                         // Remove user from channel
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
-                            if let Some(channel_info) = server.channels.get_mut(&channel) {
+                            if let Some(channel_info) = server.channel_mut(&channel) {
                                 channel_info.users.retain(|u| u != &user);
                                 channel_info.user_count = channel_info.users.len();
                             }
@@ -2036,9 +2092,15 @@ impl RustIrcGui {
                         // Update nick in all channels and collect affected channels
                         let mut affected_channels = Vec::new();
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
+                            if server.is_own_nick(&old_nick) {
+                                server.nickname = new_nick.clone();
+                            }
+                            let casemapping = server.casemapping;
                             for (channel_name, channel_info) in server.channels.iter_mut() {
-                                if let Some(pos) =
-                                    channel_info.users.iter().position(|u| u == &old_nick)
+                                if let Some(pos) = channel_info
+                                    .users
+                                    .iter()
+                                    .position(|u| casemapping.equals(u, &old_nick))
                                 {
                                     channel_info.users[pos] = new_nick.clone();
                                     affected_channels.push(channel_name.clone());
@@ -2066,7 +2128,7 @@ impl RustIrcGui {
                         );
                         // Update channel topic
                         if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
-                            if let Some(channel_info) = server.channels.get_mut(&channel) {
+                            if let Some(channel_info) = server.channel_mut(&channel) {
                                 channel_info.topic = Some(topic.clone());
                             }
                         }
@@ -2077,6 +2139,55 @@ impl RustIrcGui {
                             "system",
                         );
                     }
+                    CoreEventMessage::ServerCertificate {
+                        connection_id,
+                        host,
+                        certificate,
+                    } => {
+                        use rustirc_core::tls::{CertificateStatus, KnownHosts};
+
+                        info!(
+                            "Core event: Certificate for {} is {:?}",
+                            host, certificate.status
+                        );
+                        if let Some(server) = self.app_state.servers.get_mut(&connection_id) {
+                            server.tls_unverified =
+                                certificate.status == CertificateStatus::Unverified;
+                        }
+
+                        let notice = match &certificate.status {
+                            CertificateStatus::Changed { expected } => Some((
+                                format!(
+                                    "WARNING: certificate for {host} has CHANGED \
+                                     (expected {expected}, got {}). Connection refused; if the \
+                                     change is legitimate, remove {host} from {}",
+                                    certificate.fingerprint,
+                                    KnownHosts::default_path().display()
+                                ),
+                                "error",
+                            )),
+                            CertificateStatus::TrustedOnFirstUse => Some((
+                                format!(
+                                    "Trusting certificate for {host} on first use: {}",
+                                    certificate.fingerprint
+                                ),
+                                "system",
+                            )),
+                            CertificateStatus::Unverified => Some((
+                                format!("Certificate verification is disabled for {host}"),
+                                "error",
+                            )),
+                            CertificateStatus::Verified | CertificateStatus::Pinned => None,
+                        };
+                        if let Some((notice, sender)) = notice {
+                            self.app_state.add_message(
+                                &connection_id,
+                                &connection_id,
+                                &notice,
+                                sender,
+                            );
+                        }
+                    }
                 }
             }
             // Menu dropdown handlers
@@ -2824,7 +2935,7 @@ impl RustIrcGui {
                 if let Some(tab) = self.app_state.tabs.get(current_tab_id) {
                     if let Some(server_id) = &tab.server_id {
                         if let Some(server_info) = self.app_state.servers.get(server_id) {
-                            if let Some(channel_info) = server_info.channels.get(&tab.name) {
+                            if let Some(channel_info) = server_info.channel(&tab.name) {
                                 // Display actual users from the channel
                                 for user in &channel_info.users {
                                     // Use user as-is (with or without mode prefix)
@@ -3064,6 +3175,26 @@ impl RustIrcGui {
                     self.app_state.add_server(server_id, server_addr);
                 }
             }
+            "/cancelqueue" => {
+                // Drop lines held back by flood protection on the current server
+                if let Some(server_id) = self
+                    .app_state
+                    .current_tab()
+                    .and_then(|tab| tab.server_id.clone())
+                {
+                    let client_clone = self.irc_client.clone();
+                    tokio::spawn(async move {
+                        let client_guard = client_clone.read().await;
+                        if let Some(client) = client_guard.as_ref() {
+                            let manager = client.connection_manager();
+                            if let Some(connection) = manager.get_connection(&server_id).await {
+                                let cancelled = connection.cancel_queued_messages().await;
+                                info!("Cancelled {} queued lines on {}", cancelled, server_id);
+                            }
+                        }
+                    });
+                }
+            }
             _ => {
                 warn!("Unknown command: {}", command);
             }
@@ -3150,7 +3281,7 @@ impl RustIrcGui {
                     if let Some(server_info) = self.app_state.servers.get_mut(server_id) {
                         // Update users for the current channel
                         let channel_name = &tab.name;
-                        if let Some(channel_info) = server_info.channels.get_mut(channel_name) {
+                        if let Some(channel_info) = server_info.channel_mut(channel_name) {
                             channel_info.users.clear();
                             channel_info.users.extend(users);
                             channel_info.user_count = channel_info.users.len();
@@ -3,6 +3,8 @@ use iced::{
     Background, Border, Color, Element, Length, Renderer, Theme,
 };
 
+use rustirc_protocol::formatting::{IrcColor, Style};
+
 use crate::components::atoms::button::{ButtonVariant, MaterialButton};
 use crate::components::atoms::typography::{MaterialText, TypographyVariant};
 use crate::themes::material_design_3::MaterialTheme;
@@ -14,7 +16,7 @@ pub struct RichTextEditor {
     cursor_position: usize,
     selection_start: Option<usize>,
     selection_end: Option<usize>,
-    formatting_stack: Vec<FormatType>,
+    style: Style,
     show_toolbar: bool,
     show_emoji_picker: bool,
     recent_emojis: Vec<String>,
@@ -30,8 +32,8 @@ pub enum FormatType {
     Italic,
     Underline,
     Strikethrough,
-    Color(u8),      // IRC color code 0-15
-    Background(u8), // IRC background color
+    Color(u8),      // IRC color code 0-98
+    Background(u8), // IRC background color 0-98
     Monospace,
     Reset,
 }
@@ -96,7 +98,7 @@ impl RichTextEditor {
             cursor_position: 0,
             selection_start: None,
             selection_end: None,
-            formatting_stack: Vec::new(),
+            style: Style::default(),
             show_toolbar: true,
             show_emoji_picker: false,
             recent_emojis: vec!["😀".to_string(), "👍".to_string(), "❤️".to_string()],
@@ -175,7 +177,7 @@ impl RichTextEditor {
                 self.cursor_position = 0;
                 self.selection_start = None;
                 self.selection_end = None;
-                self.formatting_stack.clear();
+                self.style = Style::default();
             }
             RichTextMessage::Submit => {
                 // This would typically be handled by the parent component
@@ -250,7 +252,7 @@ impl RichTextEditor {
         let toolbar_content = row![
             // Text formatting buttons
             MaterialButton::new("B")
-                .variant(if self.formatting_stack.contains(&FormatType::Bold) {
+                .variant(if self.style.bold {
                     ButtonVariant::Filled
                 } else {
                     ButtonVariant::Text
@@ -258,7 +260,7 @@ impl RichTextEditor {
                 .on_press(RichTextMessage::FormatToggled(FormatType::Bold))
                 .build(),
             MaterialButton::new("I")
-                .variant(if self.formatting_stack.contains(&FormatType::Italic) {
+                .variant(if self.style.italic {
                     ButtonVariant::Filled
                 } else {
                     ButtonVariant::Text
@@ -266,7 +268,7 @@ impl RichTextEditor {
                 .on_press(RichTextMessage::FormatToggled(FormatType::Italic))
                 .build(),
             MaterialButton::new("U")
-                .variant(if self.formatting_stack.contains(&FormatType::Underline) {
+                .variant(if self.style.underline {
                     ButtonVariant::Filled
                 } else {
                     ButtonVariant::Text
@@ -274,13 +276,11 @@ impl RichTextEditor {
                 .on_press(RichTextMessage::FormatToggled(FormatType::Underline))
                 .build(),
             MaterialButton::new("S")
-                .variant(
-                    if self.formatting_stack.contains(&FormatType::Strikethrough) {
-                        ButtonVariant::Filled
-                    } else {
-                        ButtonVariant::Text
-                    }
-                )
+                .variant(if self.style.strikethrough {
+                    ButtonVariant::Filled
+                } else {
+                    ButtonVariant::Text
+                })
                 .on_press(RichTextMessage::FormatToggled(FormatType::Strikethrough))
                 .build(),
             // Color palette button
@@ -290,7 +290,7 @@ impl RichTextEditor {
                 .build(),
             // Monospace toggle
             MaterialButton::new("</>")
-                .variant(if self.formatting_stack.contains(&FormatType::Monospace) {
+                .variant(if self.style.monospace {
                     ButtonVariant::Filled
                 } else {
                     ButtonVariant::Text
@@ -490,53 +490,42 @@ impl RichTextEditor {
 
     // Helper methods
     fn apply_formatting(&mut self, format_type: FormatType) {
+        let mut next = self.style;
         match format_type {
-            FormatType::Reset => {
-                self.formatting_stack.clear();
-                self.insert_text_at_cursor("\u{000F}"); // IRC reset character
+            FormatType::Bold => next.bold = !next.bold,
+            FormatType::Italic => next.italic = !next.italic,
+            FormatType::Underline => next.underline = !next.underline,
+            FormatType::Strikethrough => next.strikethrough = !next.strikethrough,
+            FormatType::Monospace => next.monospace = !next.monospace,
+            FormatType::Color(code) => {
+                let color = IrcColor::Palette(code);
+                next.foreground = (next.foreground != Some(color)).then_some(color);
             }
-            _ => {
-                if let Some(pos) = self
-                    .formatting_stack
-                    .iter()
-                    .position(|f| std::mem::discriminant(f) == std::mem::discriminant(&format_type))
-                {
-                    self.formatting_stack.remove(pos);
-                } else {
-                    self.formatting_stack.push(format_type.clone());
-                }
-
-                match format_type {
-                    FormatType::Bold => self.insert_text_at_cursor("\u{0002}"),
-                    FormatType::Italic => self.insert_text_at_cursor("\u{001D}"),
-                    FormatType::Underline => self.insert_text_at_cursor("\u{001F}"),
-                    FormatType::Strikethrough => self.insert_text_at_cursor("\u{001E}"),
-                    FormatType::Color(code) => {
-                        // Insert proper IRC color code
-                        let color_code = format!("\u{0003}{code:02}");
-                        self.insert_text_at_cursor(&color_code);
-                    }
-                    FormatType::Background(code) => {
-                        // Insert proper IRC background color code
-                        let bg_code = format!("\u{0003},{code:02}");
-                        self.insert_text_at_cursor(&bg_code);
-                    }
-                    FormatType::Monospace => self.insert_text_at_cursor("\u{0011}"),
-                    FormatType::Reset => self.insert_text_at_cursor("\u{000F}"),
-                };
+            FormatType::Background(code) => {
+                let color = IrcColor::Palette(code);
+                next.background = (next.background != Some(color)).then_some(color);
             }
+            FormatType::Reset => next = Style::default(),
+        }
+
+        // Let the serializer pick the codes, so e.g. a background alone
+        // becomes a valid `\x0399,NN` rather than `\x03,NN`
+        let codes = self.style.codes_to(&next);
+        if self.insert_text_at_cursor(&codes) {
+            self.style = next;
         }
     }
 
-    fn insert_text_at_cursor(&mut self, text: &str) {
+    fn insert_text_at_cursor(&mut self, text: &str) -> bool {
         if let Some(max_len) = self.max_length {
             if self.content.len() + text.len() > max_len {
-                return;
+                return false;
             }
         }
 
         self.content.insert_str(self.cursor_position, text);
         self.cursor_position += text.len();
+        true
     }
 
     fn add_to_recent_emojis(&mut self, emoji: String) {
@@ -584,7 +573,7 @@ impl RichTextEditor {
         self.cursor_position = 0;
         self.selection_start = None;
         self.selection_end = None;
-        self.formatting_stack.clear();
+        self.style = Style::default();
     }
 }
 
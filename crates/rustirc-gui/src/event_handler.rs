@@ -164,6 +164,52 @@ impl EventHandler for GuiEventHandler {
                 }));
             }
 
+            Event::EndpointChanged {
+                connection_id,
+                endpoint,
+            } => {
+                debug!("{} connecting via {}", connection_id, endpoint);
+                self.send_message(Message::CoreEvent(CoreEventMessage::EndpointChanged {
+                    connection_id: connection_id.clone(),
+                    endpoint: endpoint.clone(),
+                }));
+            }
+
+            Event::HistoryGap {
+                connection_id,
+                target,
+                since,
+            } => {
+                debug!("History gap in {} on {}", target, connection_id);
+                self.send_message(Message::CoreEvent(CoreEventMessage::HistoryGap {
+                    connection_id: connection_id.clone(),
+                    target: target.clone(),
+                    since: since.clone(),
+                }));
+            }
+
+            Event::SendQueueChanged {
+                connection_id,
+                pending,
+            } => {
+                debug!("{} has {} lines pending", connection_id, pending);
+                self.send_message(Message::CoreEvent(CoreEventMessage::SendQueueChanged {
+                    connection_id: connection_id.clone(),
+                    pending: *pending,
+                }));
+            }
+
+            Event::Registered {
+                connection_id,
+                nickname,
+            } => {
+                info!("Registered on {} as {}", connection_id, nickname);
+                self.send_message(Message::CoreEvent(CoreEventMessage::Registered {
+                    connection_id: connection_id.clone(),
+                    nickname: nickname.clone(),
+                }));
+            }
+
             Event::MessageSent {
                 connection_id,
                 message,
@@ -182,6 +228,33 @@ impl EventHandler for GuiEventHandler {
                 debug!("Pong required for {} to server {}", connection_id, server);
                 // Pong is automatically handled by the IRC client, just log it
             }
+
+            Event::CapabilitiesChanged {
+                connection_id,
+                capabilities,
+            } => {
+                debug!(
+                    "Capabilities enabled on {}: {}",
+                    connection_id,
+                    capabilities.join(" ")
+                );
+            }
+
+            Event::ServerCertificate {
+                connection_id,
+                host,
+                certificate,
+            } => {
+                info!(
+                    "Server certificate for {} on {}: {:?}",
+                    host, connection_id, certificate.status
+                );
+                self.send_message(Message::CoreEvent(CoreEventMessage::ServerCertificate {
+                    connection_id: connection_id.clone(),
+                    host: host.clone(),
+                    certificate: certificate.clone(),
+                }));
+            }
         }
     }
 
@@ -244,4 +317,26 @@ pub enum CoreEventMessage {
         connection_id: String,
         state: rustirc_core::connection::ConnectionState,
     },
+    EndpointChanged {
+        connection_id: String,
+        endpoint: String,
+    },
+    SendQueueChanged {
+        connection_id: String,
+        pending: usize,
+    },
+    HistoryGap {
+        connection_id: String,
+        target: String,
+        since: Option<String>,
+    },
+    Registered {
+        connection_id: String,
+        nickname: String,
+    },
+    ServerCertificate {
+        connection_id: String,
+        host: String,
+        certificate: rustirc_core::tls::ServerCertificate,
+    },
 }
@@ -4,6 +4,7 @@
 //! private messages, tabs, and user interface state.
 
 use rustirc_core::connection::ConnectionState as CoreConnectionState;
+use rustirc_protocol::{CaseMapping, IrcString};
 use std::collections::{HashMap, VecDeque};
 use std::time::SystemTime;
 
@@ -73,21 +74,82 @@ impl AppState {
         }
     }
 
+    /// CASEMAPPING of a server, `rfc1459` until the server announces one
+    fn casemapping(&self, server_id: &str) -> CaseMapping {
+        self.servers
+            .get(server_id)
+            .map(|server| server.casemapping)
+            .unwrap_or_default()
+    }
+
+    /// Tab id of a channel; channel names differing only in case share a tab
+    pub fn channel_tab_id(&self, server_id: &str, channel: &str) -> String {
+        format!("{server_id}:{}", self.casemapping(server_id).fold(channel))
+    }
+
+    /// Tab id of a private conversation with a nickname
+    pub fn private_tab_id(&self, server_id: &str, nick: &str) -> String {
+        format!("{server_id}:pm:{}", self.casemapping(server_id).fold(nick))
+    }
+
+    /// Apply a CASEMAPPING announced by a server, re-keying its channels and tabs
+    pub fn set_casemapping(&mut self, server_id: &str, casemapping: CaseMapping) {
+        let Some(server) = self.servers.get_mut(server_id) else {
+            return;
+        };
+        if server.casemapping == casemapping {
+            return;
+        }
+        server.set_casemapping(casemapping);
+
+        let renamed: Vec<(String, String)> = self
+            .tabs
+            .iter()
+            .filter(|(_, tab)| tab.server_id.as_deref() == Some(server_id))
+            .filter_map(|(id, tab)| {
+                let new_id = match &tab.tab_type {
+                    TabType::Channel { channel } => self.channel_tab_id(server_id, channel),
+                    TabType::PrivateMessage { nick } => self.private_tab_id(server_id, nick),
+                    _ => return None,
+                };
+                (*id != new_id).then(|| (id.clone(), new_id))
+            })
+            .collect();
+
+        for (old_id, new_id) in renamed {
+            if let Some(tab) = self.tabs.remove(&old_id) {
+                self.tabs.insert(new_id.clone(), tab);
+            }
+            for id in &mut self.tab_order {
+                if *id == old_id {
+                    *id = new_id.clone();
+                }
+            }
+            if self.current_tab_id.as_deref() == Some(old_id.as_str()) {
+                self.current_tab_id = Some(new_id);
+            }
+        }
+    }
+
     /// Add a channel tab
     pub fn add_channel_tab(&mut self, server_id: String, channel: String) {
-        let tab = Tab::channel(server_id.clone(), channel.clone());
-        let tab_id = format!("{server_id}:{channel}");
-        self.tabs.insert(tab_id.clone(), tab);
-        self.tab_order.push(tab_id.clone());
+        let tab_id = self.channel_tab_id(&server_id, &channel);
+        if !self.tabs.contains_key(&tab_id) {
+            let tab = Tab::channel(server_id.clone(), channel.clone());
+            self.tabs.insert(tab_id.clone(), tab);
+            self.tab_order.push(tab_id.clone());
+        }
 
         // Set as current tab
         self.current_tab_id = Some(tab_id);
 
         // Add channel to server if server exists
         if let Some(server) = self.servers.get_mut(&server_id) {
+            let key = server.key(&channel);
             server
                 .channels
-                .insert(channel.clone(), ChannelInfo::new(channel));
+                .entry(key)
+                .or_insert_with(|| ChannelInfo::new(channel));
         }
     }
 
@@ -146,30 +208,58 @@ impl AppState {
 
     /// Add a private message tab
     pub fn add_private_tab(&mut self, server_id: &str, nick: String) {
-        let tab = Tab::private_message(server_id.to_string(), nick.clone());
-        let tab_id = format!("{server_id}:pm:{nick}");
-        self.tabs.insert(tab_id.clone(), tab);
-        self.tab_order.push(tab_id.clone());
+        let tab_id = self.private_tab_id(server_id, &nick);
+        if !self.tabs.contains_key(&tab_id) {
+            let tab = Tab::private_message(server_id.to_string(), nick);
+            self.tabs.insert(tab_id.clone(), tab);
+            self.tab_order.push(tab_id.clone());
+        }
 
         // Set as current tab
         self.current_tab_id = Some(tab_id);
     }
 
+    /// Mark where a tab may be missing messages from while disconnected
+    pub fn add_history_gap(&mut self, server_id: &str, target: &str, since: Option<&str>) {
+        let tab_id = if target.starts_with('#') || target.starts_with('&') {
+            self.channel_tab_id(server_id, target)
+        } else {
+            self.private_tab_id(server_id, target)
+        };
+        let content = match since {
+            Some(since) => format!("--- Reconnected; messages since {since} may be missing ---"),
+            None => "--- Reconnected; messages may be missing ---".to_string(),
+        };
+
+        let message_id = self.next_message_id();
+        if let Some(tab) = self.tabs.get_mut(&tab_id) {
+            tab.messages.push_back(DisplayMessage {
+                id: message_id,
+                timestamp: SystemTime::now(),
+                sender: "system".to_string(),
+                content,
+                message_type: MessageType::System,
+                formatted_spans: Vec::new(),
+                is_highlight: false,
+                is_own_message: false,
+            });
+            if tab.messages.len() > 1000 {
+                tab.messages.pop_front();
+            }
+        }
+    }
+
     /// Add a message to a tab
     pub fn add_message(&mut self, server_id: &str, target: &str, message: &str, sender: &str) {
         let tab_id = if target.starts_with('#') || target.starts_with('&') {
             // Channel message - use format server_id:channel_name
-            format!("{server_id}:{target}")
+            self.channel_tab_id(server_id, target)
         } else if target == server_id {
             // Server message - use format server:server_id
             format!("server:{server_id}")
         } else {
             // Private message - use format server_id:pm:nick
-            format!(
-                "{}:pm:{}",
-                server_id,
-                if sender == "self" { target } else { sender }
-            )
+            self.private_tab_id(server_id, if sender == "self" { target } else { sender })
         };
 
         // Get message ID before mutable borrow
@@ -237,7 +327,7 @@ impl AppState {
     /// Add a user to a channel
     pub fn add_user_to_channel(&mut self, server_id: &str, channel: &str, nick: &str) {
         if let Some(server) = self.servers.get_mut(server_id) {
-            if let Some(channel_info) = server.channels.get_mut(channel) {
+            if let Some(channel_info) = server.channel_mut(channel) {
                 if !channel_info.users.contains(&nick.to_string()) {
                     channel_info.users.push(nick.to_string());
                     channel_info.user_count = channel_info.users.len();
@@ -246,7 +336,7 @@ impl AppState {
         }
 
         // Also add to tab's user list if it exists
-        let tab_id = format!("{server_id}:{channel}");
+        let tab_id = self.channel_tab_id(server_id, channel);
         if let Some(tab) = self.tabs.get_mut(&tab_id) {
             tab.users
                 .insert(nick.to_string(), UserInfo::new(nick.to_string()));
@@ -256,14 +346,14 @@ impl AppState {
     /// Remove a user from a channel
     pub fn remove_user_from_channel(&mut self, server_id: &str, channel: &str, nick: &str) {
         if let Some(server) = self.servers.get_mut(server_id) {
-            if let Some(channel_info) = server.channels.get_mut(channel) {
+            if let Some(channel_info) = server.channel_mut(channel) {
                 channel_info.users.retain(|user| user != nick);
                 channel_info.user_count = channel_info.users.len();
             }
         }
 
         // Also remove from tab's user list if it exists
-        let tab_id = format!("{server_id}:{channel}");
+        let tab_id = self.channel_tab_id(server_id, channel);
         if let Some(tab) = self.tabs.get_mut(&tab_id) {
             tab.users.remove(nick);
         }
@@ -393,12 +483,21 @@ pub struct ServerInfo {
     pub name: String,
     pub connection_state: CoreConnectionState,
     pub nickname: String,
-    pub channels: HashMap<String, ChannelInfo>,
+    /// Channels keyed by the server's CASEMAPPING
+    pub channels: HashMap<IrcString, ChannelInfo>,
     pub users: HashMap<String, UserInfo>,
     /// Server modes
     pub modes: Vec<String>,
     /// Last ping time
     pub last_ping: Option<SystemTime>,
+    /// Connected with server certificate verification disabled
+    pub tls_unverified: bool,
+    /// Endpoint of the network in use, as `host:port`
+    pub endpoint: Option<String>,
+    /// Outgoing lines held back by flood protection
+    pub pending_lines: usize,
+    /// Server CASEMAPPING used to match channel names and nicknames
+    pub casemapping: CaseMapping,
 }
 
 impl ServerInfo {
@@ -411,8 +510,46 @@ impl ServerInfo {
             users: HashMap::new(),
             modes: Vec::new(),
             last_ping: None,
+            tls_unverified: false,
+            endpoint: None,
+            pending_lines: 0,
+            casemapping: CaseMapping::default(),
         }
     }
+
+    /// Build a lookup key for a channel or nickname
+    pub fn key(&self, name: &str) -> IrcString {
+        IrcString::new(name, self.casemapping)
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&ChannelInfo> {
+        self.channels.get(&self.key(name))
+    }
+
+    pub fn channel_mut(&mut self, name: &str) -> Option<&mut ChannelInfo> {
+        let key = self.key(name);
+        self.channels.get_mut(&key)
+    }
+
+    pub fn remove_channel(&mut self, name: &str) -> Option<ChannelInfo> {
+        let key = self.key(name);
+        self.channels.remove(&key)
+    }
+
+    /// Check whether a nickname is ours
+    pub fn is_own_nick(&self, nick: &str) -> bool {
+        self.casemapping.equals(&self.nickname, nick)
+    }
+
+    /// Re-key channels with a new CASEMAPPING
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+        self.channels = self
+            .channels
+            .drain()
+            .map(|(name, channel)| (name.with_casemapping(casemapping), channel))
+            .collect();
+    }
 }
 
 /// Channel information
@@ -7,7 +7,7 @@ use crate::state::{AppState, TabType};
 use crate::theme::Theme;
 use iced::{
     keyboard::{Key, Modifiers},
-    widget::{button, column, container, row, text, text_input, Space},
+    widget::{button, column, container, row, text, text_editor, text_input, Space},
     Alignment, Color, Element, Length, Task,
 };
 use std::collections::VecDeque;
@@ -21,6 +21,7 @@ pub enum InputAreaMessage {
     InputSubmitted(String),
     TabCompleted(String),
     ToggleMultiline,
+    EditorAction(text_editor::Action),
     HistoryUp,
     HistoryDown,
     TabCompletion,
@@ -39,6 +40,8 @@ pub struct InputArea {
     completion_index: Option<usize>,
     completion_prefix: String,
     multiline_mode: bool,
+    /// Editor contents in multiline mode, mirrored into `current_input`
+    editor: text_editor::Content,
     max_history_size: usize,
 }
 
@@ -52,6 +55,7 @@ impl InputArea {
             completion_index: None,
             completion_prefix: String::new(),
             multiline_mode: false,
+            editor: text_editor::Content::new(),
             max_history_size: 100,
         }
     }
@@ -72,9 +76,7 @@ impl InputArea {
             InputAreaMessage::SendMessage(text) => {
                 if !text.trim().is_empty() {
                     self.add_to_history(text.clone());
-                    self.current_input.clear();
-                    self.reset_completion();
-                    self.history_index = None;
+                    self.clear();
                 }
                 Task::none()
             }
@@ -82,15 +84,14 @@ impl InputArea {
                 // Handle multiline input submission
                 if !text.trim().is_empty() {
                     self.add_to_history(text.clone());
-                    self.current_input.clear();
-                    self.reset_completion();
-                    self.history_index = None;
+                    self.clear();
                 }
                 Task::none()
             }
             InputAreaMessage::TabCompleted(text) => {
                 // Handle tab completion result
                 self.current_input = text;
+                self.sync_editor();
                 self.reset_completion();
                 Task::none()
             }
@@ -98,6 +99,13 @@ impl InputArea {
                 self.toggle_multiline();
                 Task::none()
             }
+            InputAreaMessage::EditorAction(action) => {
+                self.editor.perform(action);
+                self.current_input = self.editor.text();
+                self.reset_completion();
+                self.history_index = None;
+                Task::none()
+            }
             InputAreaMessage::HistoryUp => {
                 if !self.input_history.is_empty() {
                     match self.history_index {
@@ -115,6 +123,7 @@ impl InputArea {
                         }
                         _ => {} // Already at the beginning
                     }
+                    self.sync_editor();
                 }
                 Task::none()
             }
@@ -132,6 +141,7 @@ impl InputArea {
                     }
                     None => {} // Not in history mode
                 }
+                self.sync_editor();
                 Task::none()
             }
             InputAreaMessage::TabCompletion => {
@@ -139,13 +149,12 @@ impl InputArea {
                 Task::none()
             }
             InputAreaMessage::ClearInput => {
-                self.current_input.clear();
-                self.reset_completion();
-                self.history_index = None;
+                self.clear();
                 Task::none()
             }
             InputAreaMessage::PasteText(text) => {
                 self.current_input.push_str(&text);
+                self.sync_editor();
                 self.reset_completion();
                 Task::none()
             }
@@ -235,15 +244,24 @@ impl InputArea {
         .align_y(Alignment::Center);
 
         let content = if self.multiline_mode {
-            // Implement multiline input mode
-            let multiline_input = text_input(
-                "Type multiple lines... (Ctrl+Enter to send)",
-                &self.current_input,
-            )
-            .on_input(InputAreaMessage::InputChanged)
-            .on_submit(InputAreaMessage::InputSubmitted(self.current_input.clone()))
-            .padding(8)
-            .width(Length::Fill);
+            // Enter breaks the line; Ctrl+Enter sends all lines as one message
+            let submitted = self.current_input.clone();
+            let multiline_input = text_editor(&self.editor)
+                .placeholder("Type multiple lines... (Ctrl+Enter to send)")
+                .on_action(InputAreaMessage::EditorAction)
+                .key_binding(move |key_press| {
+                    let enter =
+                        matches!(key_press.key, Key::Named(iced::keyboard::key::Named::Enter));
+                    if enter && key_press.modifiers.control() {
+                        Some(text_editor::Binding::Custom(
+                            InputAreaMessage::InputSubmitted(submitted.clone()),
+                        ))
+                    } else {
+                        text_editor::Binding::from_key_press(key_press)
+                    }
+                })
+                .height(Length::Fixed(96.0))
+                .padding(8);
 
             let send_button = button("Send")
                 .on_press(InputAreaMessage::InputSubmitted(self.current_input.clone()))
@@ -408,6 +426,7 @@ impl InputArea {
                 "/disconnect",
                 "/server",
                 "/clear",
+                "/cancelqueue",
                 "/help",
             ];
 
@@ -440,7 +459,7 @@ impl InputArea {
                         if let Some(server_state) = app_state.servers.get(current_server_id) {
                             for channel_name in server_state.channels.keys() {
                                 if channel_name.to_lowercase().starts_with(&lower_prefix) {
-                                    candidates.push(channel_name.clone());
+                                    candidates.push(channel_name.to_string());
                                 }
                             }
                         }
@@ -451,7 +470,7 @@ impl InputArea {
                         info!("Searching channels in server: {}", server_id);
                         for channel_name in server_state.channels.keys() {
                             if channel_name.to_lowercase().starts_with(&lower_prefix) {
-                                candidates.push(channel_name.clone());
+                                candidates.push(channel_name.to_string());
                             }
                         }
                     }
@@ -528,6 +547,7 @@ impl InputArea {
 
     /// Set input text
     pub fn set_input(&mut self, text: String) {
+        self.editor = text_editor::Content::with_text(&text);
         self.current_input = text;
         self.reset_completion();
     }
@@ -535,13 +555,22 @@ impl InputArea {
     /// Clear input
     pub fn clear(&mut self) {
         self.current_input.clear();
+        self.editor = text_editor::Content::new();
         self.reset_completion();
         self.history_index = None;
     }
 
-    /// Toggle multiline mode
+    /// Show `current_input` in the multiline editor after it changed elsewhere
+    fn sync_editor(&mut self) {
+        if self.multiline_mode {
+            self.editor = text_editor::Content::with_text(&self.current_input);
+        }
+    }
+
+    /// Toggle multiline mode, carrying the current input over
     pub fn toggle_multiline(&mut self) {
         self.multiline_mode = !self.multiline_mode;
+        self.sync_editor();
     }
 
     /// Get input history
@@ -132,12 +132,15 @@ impl ServerTree {
 
             // Channel list (if expanded)
             if is_expanded {
-                for channel_name in server_state.channels.keys() {
-                    let tab_id = format!("{server_id}:{channel_name}");
+                for channel_info in server_state.channels.values() {
+                    let channel_name = &channel_info.name;
+                    let tab_id = app_state.channel_tab_id(server_id, channel_name);
                     let is_active = app_state
                         .current_tab()
                         .map(|tab| match &tab.tab_type {
-                            TabType::Channel { channel } => channel == channel_name,
+                            TabType::Channel { channel } => {
+                                server_state.casemapping.equals(channel, channel_name)
+                            }
                             _ => false,
                         })
                         .unwrap_or(false);
@@ -102,6 +102,37 @@ impl StatusBar {
                 .color(theme.get_text_color()),
         );
 
+        // Server of the network in use
+        if let Some(endpoint) = self.current_endpoint(app_state) {
+            status_content = status_content.push(Space::new().width(Length::Fixed(8.0)));
+            status_content = status_content.push(
+                text(format!("via {endpoint}"))
+                    .size(11.0)
+                    .color(Color::from_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+
+        // Lines held back by flood protection
+        let pending = self.pending_lines(app_state);
+        if pending > 0 {
+            status_content = status_content.push(Space::new().width(Length::Fixed(8.0)));
+            status_content = status_content.push(
+                text(format!("{pending} lines pending"))
+                    .size(11.0)
+                    .color(Color::from_rgb(0.9, 0.7, 0.3)),
+            );
+        }
+
+        // Flag connections whose server certificate was not verified
+        if self.is_tls_unverified(app_state) {
+            status_content = status_content.push(Space::new().width(Length::Fixed(8.0)));
+            status_content = status_content.push(
+                text("TLS UNVERIFIED")
+                    .size(11.0)
+                    .color(Color::from_rgb(0.9, 0.2, 0.2)),
+            );
+        }
+
         status_content = status_content.push(Space::new().width(Length::Fixed(8.0)));
         status_content =
             status_content.push(text("|").size(11.0).color(Color::from_rgb(0.5, 0.5, 0.5)));
@@ -220,6 +251,33 @@ impl StatusBar {
         }
     }
 
+    /// Whether the current server was connected without certificate verification
+    fn is_tls_unverified(&self, app_state: &AppState) -> bool {
+        app_state
+            .current_tab()
+            .and_then(|tab| tab.server_id.as_ref())
+            .and_then(|server_id| app_state.servers.get(server_id))
+            .is_some_and(|server_state| server_state.tls_unverified)
+    }
+
+    /// Endpoint the current server is connected through
+    fn current_endpoint<'a>(&self, app_state: &'a AppState) -> Option<&'a str> {
+        app_state
+            .current_tab()
+            .and_then(|tab| tab.server_id.as_ref())
+            .and_then(|server_id| app_state.servers.get(server_id))
+            .and_then(|server_state| server_state.endpoint.as_deref())
+    }
+
+    /// Outgoing lines the current server holds back for flood protection
+    fn pending_lines(&self, app_state: &AppState) -> usize {
+        app_state
+            .current_tab()
+            .and_then(|tab| tab.server_id.as_ref())
+            .and_then(|server_id| app_state.servers.get(server_id))
+            .map_or(0, |server_state| server_state.pending_lines)
+    }
+
     /// Get tab-specific information
     fn get_tab_info(&self, tab: &crate::state::Tab, _app_state: &AppState) -> String {
         match &tab.tab_type {
@@ -292,7 +350,7 @@ impl StatusBar {
         if let TabType::Channel { channel } = &current_tab.tab_type {
             if let Some(server_id) = &current_tab.server_id {
                 if let Some(server_state) = app_state.servers.get(server_id) {
-                    if let Some(channel_state) = server_state.channels.get(channel) {
+                    if let Some(channel_state) = server_state.channel(channel) {
                         if let Some(ref topic) = channel_state.topic {
                             let topic_text = if topic.len() > 100 {
                                 format!("{}...", &topic[..97])
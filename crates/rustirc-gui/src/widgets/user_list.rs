@@ -85,7 +85,7 @@ impl UserList {
                 {
                     let server_id = server_id.clone();
                     app_state.add_private_tab(&server_id, nick.clone());
-                    let tab_id = format!("{server_id}:pm:{nick}");
+                    let tab_id = app_state.private_tab_id(&server_id, &nick);
                     app_state.switch_to_tab(&tab_id);
                 }
                 Task::none()
@@ -45,6 +45,69 @@ fn benchmark_malformed_message(c: &mut Criterion) {
     });
 }
 
+fn benchmark_borrowed_message(c: &mut Criterion) {
+    let parser = Parser::new();
+    let line = "@time=2021-01-01T00:00:00.000Z;msgid=12345 :nick!user@host.example.com PRIVMSG #channel :Hello with tags!";
+
+    c.bench_function("parse IRCv3 message borrowed", |b| {
+        b.iter(|| {
+            parser
+                .parse_ref(black_box(line))
+                .map(|message| message.params.len())
+        })
+    });
+}
+
+/// Bouncer playback on connect: tagged history lines and large NAMES replies
+fn replay_lines() -> Vec<String> {
+    let names: Vec<String> = (0..40)
+        .map(|i| format!("@op{i} +voice{i} user{i}"))
+        .collect();
+    let mut lines = Vec::new();
+    for i in 0..1000 {
+        lines.push(format!(
+            "@batch=abc;time=2024-01-01T00:00:{:02}.000Z;msgid=m{i} :nick{i}!user@host.example.com PRIVMSG #channel :Replayed message number {i}",
+            i % 60
+        ));
+        if i % 10 == 0 {
+            lines.push(format!(
+                ":server.example.com 353 me = #channel :{}",
+                names[i % 40]
+            ));
+        }
+    }
+    lines
+}
+
+fn benchmark_replay(c: &mut Criterion) {
+    let parser = Parser::new();
+    let lines = replay_lines();
+    let mut group = c.benchmark_group("replay 1100 lines");
+
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let _ = black_box(parser.parse(black_box(line)));
+            }
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let _ = black_box(parser.parse_ref(black_box(line)));
+            }
+        })
+    });
+    group.bench_function("borrowed then owned", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let _ = black_box(parser.parse_ref(black_box(line)).map(|m| m.to_message()));
+            }
+        })
+    });
+    group.finish();
+}
+
 fn benchmark_batch_parsing(c: &mut Criterion) {
     let messages = vec![
         "PING :server1.example.com",
@@ -75,6 +138,8 @@ criterion_group!(
     benchmark_ircv3_message,
     benchmark_long_message,
     benchmark_malformed_message,
-    benchmark_batch_parsing
+    benchmark_batch_parsing,
+    benchmark_borrowed_message,
+    benchmark_replay
 );
 criterion_main!(benches);
@@ -1,7 +1,7 @@
 //! IRCv3 Capabilities
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -23,9 +23,11 @@ pub enum Capability {
     Sasl,
     ServerTime,
     SetName,
+    Sts,
     UserhostInNames,
 
     // Draft capabilities
+    ChatHistory,
     Multiline,
     NoImplicitNames,
     StandardReplies,
@@ -53,7 +55,9 @@ impl Capability {
             Capability::Sasl => "sasl",
             Capability::ServerTime => "server-time",
             Capability::SetName => "setname",
+            Capability::Sts => "sts",
             Capability::UserhostInNames => "userhost-in-names",
+            Capability::ChatHistory => "draft/chathistory",
             Capability::Multiline => "draft/multiline",
             Capability::NoImplicitNames => "draft/no-implicit-names",
             Capability::StandardReplies => "draft/standard-replies",
@@ -83,7 +87,9 @@ impl FromStr for Capability {
             "sasl" => Capability::Sasl,
             "server-time" => Capability::ServerTime,
             "setname" => Capability::SetName,
+            "sts" => Capability::Sts,
             "userhost-in-names" => Capability::UserhostInNames,
+            "draft/chathistory" => Capability::ChatHistory,
             "draft/multiline" => Capability::Multiline,
             "draft/no-implicit-names" => Capability::NoImplicitNames,
             "draft/standard-replies" => Capability::StandardReplies,
@@ -95,6 +101,8 @@ impl FromStr for Capability {
 #[derive(Debug, Clone, Default)]
 pub struct CapabilitySet {
     capabilities: HashSet<Capability>,
+    /// Values advertised alongside capabilities (e.g. `sasl=PLAIN,EXTERNAL`)
+    values: HashMap<Capability, String>,
 }
 
 impl CapabilitySet {
@@ -102,11 +110,56 @@ impl CapabilitySet {
         Self::default()
     }
 
+    /// Parse a space-separated capability list as sent in `CAP LS`, `ACK`,
+    /// `NEW` and `DEL` replies. Values (`name=value`) are preserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::{Capability, CapabilitySet};
+    ///
+    /// let caps = CapabilitySet::parse("multi-prefix sasl=PLAIN,EXTERNAL server-time");
+    /// assert_eq!(caps.len(), 3);
+    /// assert_eq!(caps.value(&Capability::Sasl), Some("PLAIN,EXTERNAL"));
+    /// ```
+    pub fn parse(list: &str) -> Self {
+        let mut set = Self::new();
+        set.extend_from_list(list);
+        set
+    }
+
+    /// Add every capability in a space-separated list to this set
+    pub fn extend_from_list(&mut self, list: &str) {
+        for token in list.split_whitespace() {
+            let (name, value) = match token.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (token, None),
+            };
+            if let Ok(cap) = name.parse::<Capability>() {
+                self.add_with_value(cap, value);
+            }
+        }
+    }
+
     pub fn add(&mut self, cap: Capability) {
         self.capabilities.insert(cap);
     }
 
+    /// Add a capability together with its advertised value
+    pub fn add_with_value(&mut self, cap: Capability, value: Option<String>) {
+        match value {
+            Some(value) => {
+                self.values.insert(cap.clone(), value);
+            }
+            None => {
+                self.values.remove(&cap);
+            }
+        }
+        self.capabilities.insert(cap);
+    }
+
     pub fn remove(&mut self, cap: &Capability) -> bool {
+        self.values.remove(cap);
         self.capabilities.remove(cap)
     }
 
@@ -114,6 +167,11 @@ impl CapabilitySet {
         self.capabilities.contains(cap)
     }
 
+    /// Get the value advertised for a capability, if any
+    pub fn value(&self, cap: &Capability) -> Option<&str> {
+        self.values.get(cap).map(String::as_str)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.capabilities.is_empty()
     }
@@ -122,6 +180,11 @@ impl CapabilitySet {
         self.capabilities.len()
     }
 
+    pub fn clear(&mut self) {
+        self.capabilities.clear();
+        self.values.clear();
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Capability> {
         self.capabilities.iter()
     }
@@ -133,3 +196,29 @@ impl CapabilitySet {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_capability_list() {
+        let caps = CapabilitySet::parse(
+            "away-notify sasl=PLAIN,EXTERNAL draft/multiline=max-bytes=4096 vendor/foo",
+        );
+        assert_eq!(caps.len(), 4);
+        assert!(caps.contains(&Capability::AwayNotify));
+        assert_eq!(caps.value(&Capability::Sasl), Some("PLAIN,EXTERNAL"));
+        assert_eq!(caps.value(&Capability::Multiline), Some("max-bytes=4096"));
+        assert!(caps.contains(&Capability::Custom("vendor/foo".to_string())));
+        assert_eq!(caps.value(&Capability::AwayNotify), None);
+    }
+
+    #[test]
+    fn test_remove_clears_value() {
+        let mut caps = CapabilitySet::parse("sasl=PLAIN");
+        assert!(caps.remove(&Capability::Sasl));
+        assert_eq!(caps.value(&Capability::Sasl), None);
+        assert!(caps.is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! Casemapping-aware IRC names
+//!
+//! IRC servers compare nicknames and channel names case-insensitively using
+//! the rules advertised with `CASEMAPPING` in `005`. Under the default
+//! `rfc1459` mapping `[]\~` are the uppercase forms of `{}|^`, so `Nick[away]`
+//! and `nick{AWAY}` name the same user.
+//!
+//! [`IrcString`] pairs a name as the server sent it with its folded form. It
+//! hashes and compares by the folded form, which makes it usable as a map key,
+//! and displays the original casing.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::CaseMapping;
+
+impl CaseMapping {
+    /// Fold a name to its canonical lowercase form under this mapping
+    ///
+    /// `rfc7613` is approximated with Unicode lowercasing. The full PRECIS
+    /// profile also maps fullwidth and halfwidth characters and applies NFC
+    /// normalization, so names differing only in those respects are not
+    /// treated as equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::CaseMapping;
+    ///
+    /// assert_eq!(CaseMapping::Rfc1459.fold("Nick[A]~"), "nick{a}^");
+    /// assert_eq!(CaseMapping::StrictRfc1459.fold("Nick[A]~"), "nick{a}~");
+    /// assert_eq!(CaseMapping::Ascii.fold("Nick[A]~"), "nick[a]~");
+    /// ```
+    pub fn fold(&self, name: &str) -> String {
+        match self {
+            Self::Ascii => name.to_ascii_lowercase(),
+            Self::Rfc1459 | Self::StrictRfc1459 => {
+                let strict = *self == Self::StrictRfc1459;
+                name.chars()
+                    .map(|c| match c {
+                        '[' => '{',
+                        ']' => '}',
+                        '\\' => '|',
+                        '~' if !strict => '^',
+                        c => c.to_ascii_lowercase(),
+                    })
+                    .collect()
+            }
+            Self::Rfc7613 => name.to_lowercase(),
+        }
+    }
+
+    /// Compare two names under this mapping
+    pub fn equals(&self, a: &str, b: &str) -> bool {
+        self.fold(a) == self.fold(b)
+    }
+}
+
+/// A nickname or channel name that compares by the server's casemapping
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::{CaseMapping, IrcString};
+///
+/// let a = IrcString::new("#Rust[Dev]", CaseMapping::Rfc1459);
+/// let b = IrcString::new("#rust{dev}", CaseMapping::Rfc1459);
+/// assert_eq!(a, b);
+/// assert_eq!(a.to_string(), "#Rust[Dev]");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IrcString {
+    /// The name as the server or user wrote it
+    original: String,
+    /// The name folded with `casemapping`
+    folded: String,
+    casemapping: CaseMapping,
+}
+
+impl IrcString {
+    /// Create a name folded with the given mapping
+    pub fn new(name: impl Into<String>, casemapping: CaseMapping) -> Self {
+        let original = name.into();
+        Self {
+            folded: casemapping.fold(&original),
+            original,
+            casemapping,
+        }
+    }
+
+    /// The name with its original casing
+    pub fn as_str(&self) -> &str {
+        &self.original
+    }
+
+    /// The folded form used for comparisons
+    pub fn folded(&self) -> &str {
+        &self.folded
+    }
+
+    /// The mapping this name was folded with
+    pub fn casemapping(&self) -> CaseMapping {
+        self.casemapping
+    }
+
+    /// Check whether another name refers to the same nickname or channel
+    pub fn matches(&self, name: &str) -> bool {
+        self.casemapping.fold(name) == self.folded
+    }
+
+    /// Fold the name again with a different mapping
+    pub fn with_casemapping(self, casemapping: CaseMapping) -> Self {
+        Self::new(self.original, casemapping)
+    }
+
+    /// Update the displayed casing, e.g. after `NICK Foo` from `foo`
+    ///
+    /// The folded form is unchanged when the new name is equivalent.
+    pub fn set_display(&mut self, name: impl Into<String>) {
+        *self = Self::new(name, self.casemapping);
+    }
+
+    /// Take the name with its original casing
+    pub fn into_string(self) -> String {
+        self.original
+    }
+}
+
+impl PartialEq for IrcString {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded == other.folded
+    }
+}
+
+impl Eq for IrcString {}
+
+impl Hash for IrcString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.folded.hash(state);
+    }
+}
+
+impl PartialOrd for IrcString {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IrcString {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.folded.cmp(&other.folded)
+    }
+}
+
+impl Deref for IrcString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.original
+    }
+}
+
+impl AsRef<str> for IrcString {
+    fn as_ref(&self) -> &str {
+        &self.original
+    }
+}
+
+impl fmt::Display for IrcString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.original)
+    }
+}
+
+/// Serialized as the original name only
+impl Serialize for IrcString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.original)
+    }
+}
+
+/// Deserialized names use the default `rfc1459` mapping; re-fold them with
+/// [`IrcString::with_casemapping`] once the server's mapping is known.
+impl<'de> Deserialize<'de> for IrcString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::new(name, CaseMapping::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_fold_mappings() {
+        assert_eq!(CaseMapping::Ascii.fold("ABC[]\\~"), "abc[]\\~");
+        assert_eq!(CaseMapping::Rfc1459.fold("ABC[]\\~"), "abc{}|^");
+        assert_eq!(CaseMapping::StrictRfc1459.fold("ABC[]\\~"), "abc{}|~");
+        assert_eq!(CaseMapping::Rfc7613.fold("ÉCOLE"), "école");
+        // Non-ASCII letters only fold under rfc7613
+        assert_eq!(CaseMapping::Rfc1459.fold("ÉCOLE"), "École");
+        assert!(CaseMapping::Rfc1459.equals("Nick\\", "nick|"));
+    }
+
+    #[test]
+    fn test_map_lookup_keeps_display_casing() {
+        let mapping = CaseMapping::Rfc1459;
+        let mut users = HashMap::new();
+        users.insert(IrcString::new("Nick[m]", mapping), 1);
+
+        assert_eq!(users.get(&IrcString::new("NICK{M}", mapping)), Some(&1));
+
+        let key = users.keys().next().unwrap();
+        assert_eq!(key.as_str(), "Nick[m]");
+        assert!(key.matches("nick{M}"));
+        assert!(!key.matches("nick[m]_"));
+    }
+
+    #[test]
+    fn test_refold_with_new_mapping() {
+        let name = IrcString::new("Foo~", CaseMapping::Rfc1459);
+        assert!(name.matches("foo^"));
+
+        let name = name.with_casemapping(CaseMapping::Ascii);
+        assert!(!name.matches("foo^"));
+        assert_eq!(name.folded(), "foo~");
+    }
+}
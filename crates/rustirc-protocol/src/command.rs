@@ -20,8 +20,15 @@
 //! };
 //! ```
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::validation::{IrcValidator, ValidationError};
+
+/// Maximum nicknames in one `USERHOST` request
+pub const MAX_USERHOST_NICKNAMES: usize = 5;
+
 /// Strongly-typed IRC commands
 ///
 /// # Examples
@@ -144,6 +151,86 @@ pub enum Command {
         params: Vec<String>,
     },
 
+    // Presence and user queries
+    Away {
+        message: Option<String>,
+    },
+    Ison {
+        nicknames: Vec<String>,
+    },
+    Userhost {
+        nicknames: Vec<String>,
+    },
+    Monitor {
+        subcommand: MonitorSubcommand,
+    },
+    SetName {
+        realname: String,
+    },
+
+    // Channel requests
+    Invite {
+        nickname: String,
+        channel: String,
+    },
+    Knock {
+        channel: String,
+        message: Option<String>,
+    },
+
+    // Message-less and history commands (IRCv3)
+    TagMsg {
+        target: String,
+    },
+    ChatHistory {
+        subcommand: ChatHistorySubcommand,
+    },
+
+    // Server queries
+    Motd {
+        target: Option<String>,
+    },
+    Lusers,
+    Version {
+        target: Option<String>,
+    },
+    Time {
+        target: Option<String>,
+    },
+    Info {
+        target: Option<String>,
+    },
+    Admin {
+        target: Option<String>,
+    },
+
+    // Operator commands
+    Oper {
+        name: String,
+        password: String,
+    },
+    Wallops {
+        text: String,
+    },
+    Kill {
+        nickname: String,
+        comment: String,
+    },
+
+    /// SILENCE command - list the server-side ignore list, or add (`+mask`)
+    /// or remove (`-mask`) an entry
+    Silence {
+        mask: Option<String>,
+    },
+
+    /// REGISTER command (draft/account-registration); `account` may be `*`
+    /// to register the current nickname
+    Register {
+        account: String,
+        email: Option<String>,
+        password: String,
+    },
+
     // Other
     Raw {
         command: String,
@@ -161,6 +248,90 @@ pub enum CapSubcommand {
     End,
 }
 
+/// `MONITOR` subcommands for the IRCv3 presence watch list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonitorSubcommand {
+    Add { targets: Vec<String> },
+    Remove { targets: Vec<String> },
+    Clear,
+    List,
+    Status,
+}
+
+/// `CHATHISTORY` subcommands (IRCv3 chathistory)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatHistorySubcommand {
+    Latest {
+        target: String,
+        reference: HistoryReference,
+        limit: u32,
+    },
+    Before {
+        target: String,
+        reference: HistoryReference,
+        limit: u32,
+    },
+    After {
+        target: String,
+        reference: HistoryReference,
+        limit: u32,
+    },
+    Around {
+        target: String,
+        reference: HistoryReference,
+        limit: u32,
+    },
+    Between {
+        target: String,
+        start: HistoryReference,
+        end: HistoryReference,
+        limit: u32,
+    },
+    Targets {
+        start: HistoryReference,
+        end: HistoryReference,
+        limit: u32,
+    },
+}
+
+/// A point in a `CHATHISTORY` request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryReference {
+    /// `timestamp=2019-01-04T14:33:26.123Z`
+    Timestamp(String),
+    /// `msgid=...`
+    MsgId(String),
+    /// `*`, only valid for `LATEST`
+    Any,
+}
+
+impl HistoryReference {
+    /// Parse a reference parameter
+    pub fn parse(value: &str) -> Result<Self, ValidationError> {
+        if value == "*" {
+            Ok(Self::Any)
+        } else if let Some(timestamp) = value.strip_prefix("timestamp=") {
+            Ok(Self::Timestamp(timestamp.to_string()))
+        } else if let Some(msgid) = value.strip_prefix("msgid=") {
+            Ok(Self::MsgId(msgid.to_string()))
+        } else {
+            Err(ValidationError::InvalidParameter(format!(
+                "Invalid CHATHISTORY reference: {value}"
+            )))
+        }
+    }
+}
+
+impl fmt::Display for HistoryReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timestamp(timestamp) => write!(f, "timestamp={timestamp}"),
+            Self::MsgId(msgid) => write!(f, "msgid={msgid}"),
+            Self::Any => f.write_str("*"),
+        }
+    }
+}
+
 impl Command {
     pub fn to_message(&self) -> crate::Message {
         match self {
@@ -213,6 +384,7 @@ impl Command {
                 }
                 msg
             }
+            Command::Names { channels } if channels.is_empty() => crate::Message::new("NAMES"),
             Command::Names { channels } => {
                 crate::Message::new("NAMES").add_param(channels.join(","))
             }
@@ -292,6 +464,102 @@ impl Command {
                 }
                 msg
             }
+            Command::Away { message } => optional(crate::Message::new("AWAY"), message),
+            Command::Ison { nicknames } => {
+                crate::Message::new("ISON").with_params(nicknames.clone())
+            }
+            Command::Userhost { nicknames } => {
+                crate::Message::new("USERHOST").with_params(nicknames.clone())
+            }
+            Command::Monitor { subcommand } => {
+                let msg = crate::Message::new("MONITOR");
+                match subcommand {
+                    MonitorSubcommand::Add { targets } => {
+                        msg.add_param("+").add_param(targets.join(","))
+                    }
+                    MonitorSubcommand::Remove { targets } => {
+                        msg.add_param("-").add_param(targets.join(","))
+                    }
+                    MonitorSubcommand::Clear => msg.add_param("C"),
+                    MonitorSubcommand::List => msg.add_param("L"),
+                    MonitorSubcommand::Status => msg.add_param("S"),
+                }
+            }
+            Command::SetName { realname } => {
+                crate::Message::new("SETNAME").add_param(realname.clone())
+            }
+            Command::Invite { nickname, channel } => crate::Message::new("INVITE")
+                .add_param(nickname.clone())
+                .add_param(channel.clone()),
+            Command::Knock { channel, message } => optional(
+                crate::Message::new("KNOCK").add_param(channel.clone()),
+                message,
+            ),
+            Command::TagMsg { target } => crate::Message::new("TAGMSG").add_param(target.clone()),
+            Command::ChatHistory { subcommand } => {
+                let msg = crate::Message::new("CHATHISTORY");
+                let (name, target, references, limit) = match subcommand {
+                    ChatHistorySubcommand::Latest {
+                        target,
+                        reference,
+                        limit,
+                    } => ("LATEST", Some(target), vec![reference], limit),
+                    ChatHistorySubcommand::Before {
+                        target,
+                        reference,
+                        limit,
+                    } => ("BEFORE", Some(target), vec![reference], limit),
+                    ChatHistorySubcommand::After {
+                        target,
+                        reference,
+                        limit,
+                    } => ("AFTER", Some(target), vec![reference], limit),
+                    ChatHistorySubcommand::Around {
+                        target,
+                        reference,
+                        limit,
+                    } => ("AROUND", Some(target), vec![reference], limit),
+                    ChatHistorySubcommand::Between {
+                        target,
+                        start,
+                        end,
+                        limit,
+                    } => ("BETWEEN", Some(target), vec![start, end], limit),
+                    ChatHistorySubcommand::Targets { start, end, limit } => {
+                        ("TARGETS", None, vec![start, end], limit)
+                    }
+                };
+                let mut msg = msg.add_param(name);
+                if let Some(target) = target {
+                    msg = msg.add_param(target.clone());
+                }
+                for reference in references {
+                    msg = msg.add_param(reference.to_string());
+                }
+                msg.add_param(limit.to_string())
+            }
+            Command::Motd { target } => optional(crate::Message::new("MOTD"), target),
+            Command::Lusers => crate::Message::new("LUSERS"),
+            Command::Version { target } => optional(crate::Message::new("VERSION"), target),
+            Command::Time { target } => optional(crate::Message::new("TIME"), target),
+            Command::Info { target } => optional(crate::Message::new("INFO"), target),
+            Command::Admin { target } => optional(crate::Message::new("ADMIN"), target),
+            Command::Oper { name, password } => crate::Message::new("OPER")
+                .add_param(name.clone())
+                .add_param(password.clone()),
+            Command::Wallops { text } => crate::Message::new("WALLOPS").add_param(text.clone()),
+            Command::Kill { nickname, comment } => crate::Message::new("KILL")
+                .add_param(nickname.clone())
+                .add_param(comment.clone()),
+            Command::Silence { mask } => optional(crate::Message::new("SILENCE"), mask),
+            Command::Register {
+                account,
+                email,
+                password,
+            } => crate::Message::new("REGISTER")
+                .add_param(account.clone())
+                .add_param(email.clone().unwrap_or_else(|| "*".to_string()))
+                .add_param(password.clone()),
             Command::Raw { command, params } => {
                 let mut msg = crate::Message::new(command.clone());
                 for param in params {
@@ -301,4 +569,695 @@ impl Command {
             }
         }
     }
+
+    /// Parse a command as a client sends it, e.g. from `/quote` or a script
+    ///
+    /// Unknown commands become [`Command::Raw`]; known commands with missing
+    /// or malformed parameters are rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::command::{Command, MonitorSubcommand};
+    /// use rustirc_protocol::Parser;
+    ///
+    /// let message = Parser::parse_message("MONITOR + alice,bob").unwrap();
+    /// let command = Command::from_message(&message).unwrap();
+    /// assert_eq!(
+    ///     command,
+    ///     Command::Monitor {
+    ///         subcommand: MonitorSubcommand::Add {
+    ///             targets: vec!["alice".to_string(), "bob".to_string()],
+    ///         },
+    ///     }
+    /// );
+    /// assert_eq!(command.to_message(), message);
+    /// ```
+    pub fn from_message(message: &crate::Message) -> Result<Self, ValidationError> {
+        let name = message.command.to_ascii_uppercase();
+        let params = &message.params;
+        let arg = |index: usize, what: &str| {
+            params
+                .get(index)
+                .cloned()
+                .ok_or_else(|| ValidationError::MissingParameter(format!("{name} {what}")))
+        };
+        let opt = |index: usize| params.get(index).cloned();
+        // ISON and USERHOST nicknames may be sent as separate or space-joined parameters
+        let words = || -> Vec<String> {
+            params
+                .iter()
+                .flat_map(|param| param.split_whitespace())
+                .map(str::to_string)
+                .collect()
+        };
+
+        let command = match name.as_str() {
+            "NICK" => Command::Nick {
+                nickname: arg(0, "<nickname>")?,
+            },
+            "USER" => Command::User {
+                username: arg(0, "<username>")?,
+                mode: arg(1, "<mode>")?,
+                realname: arg(3, "<realname>")?,
+            },
+            "PASS" => Command::Pass {
+                password: arg(0, "<password>")?,
+            },
+            "QUIT" => Command::Quit { message: opt(0) },
+            "JOIN" => Command::Join {
+                channels: split_list(&arg(0, "<channels>")?),
+                keys: opt(1).map(|keys| split_list(&keys)).unwrap_or_default(),
+            },
+            "PART" => Command::Part {
+                channels: split_list(&arg(0, "<channels>")?),
+                message: opt(1),
+            },
+            "TOPIC" => Command::Topic {
+                channel: arg(0, "<channel>")?,
+                topic: opt(1),
+            },
+            "NAMES" => Command::Names {
+                channels: opt(0)
+                    .map(|channels| split_list(&channels))
+                    .unwrap_or_default(),
+            },
+            "LIST" => Command::List {
+                channels: opt(0).map(|channels| split_list(&channels)),
+            },
+            "PRIVMSG" => Command::PrivMsg {
+                target: arg(0, "<target>")?,
+                text: arg(1, "<text>")?,
+            },
+            "NOTICE" => Command::Notice {
+                target: arg(0, "<target>")?,
+                text: arg(1, "<text>")?,
+            },
+            "WHO" => Command::Who {
+                mask: arg(0, "<mask>")?,
+            },
+            // WHOIS [<server>] <nicknames>
+            "WHOIS" => Command::Whois {
+                targets: split_list(&arg(params.len().max(1) - 1, "<nicknames>")?),
+            },
+            "WHOWAS" => Command::Whowas {
+                nicknames: split_list(&arg(0, "<nicknames>")?),
+                count: opt(1).map(|count| parse_number(&count)).transpose()?,
+            },
+            "KICK" => Command::Kick {
+                channel: arg(0, "<channel>")?,
+                nick: arg(1, "<nick>")?,
+                comment: opt(2),
+            },
+            "PING" => Command::Ping {
+                server1: arg(0, "<token>")?,
+                server2: opt(1),
+            },
+            "PONG" => Command::Pong {
+                server1: arg(0, "<token>")?,
+                server2: opt(1),
+            },
+            "CAP" => {
+                let capabilities = || {
+                    opt(1)
+                        .map(|caps| caps.split_whitespace().map(str::to_string).collect())
+                        .unwrap_or_default()
+                };
+                let subcommand = match arg(0, "<subcommand>")?.to_ascii_uppercase().as_str() {
+                    "LS" => CapSubcommand::Ls { version: opt(1) },
+                    "LIST" => CapSubcommand::List,
+                    "REQ" => CapSubcommand::Req {
+                        capabilities: capabilities(),
+                    },
+                    "ACK" => CapSubcommand::Ack {
+                        capabilities: capabilities(),
+                    },
+                    "NAK" => CapSubcommand::Nak {
+                        capabilities: capabilities(),
+                    },
+                    "END" => CapSubcommand::End,
+                    other => {
+                        return Err(ValidationError::InvalidParameter(format!(
+                            "Unknown CAP subcommand: {other}"
+                        )))
+                    }
+                };
+                Command::Cap { subcommand }
+            }
+            "AUTHENTICATE" => Command::Authenticate {
+                data: arg(0, "<data>")?,
+            },
+            "MODE" => Command::Mode {
+                target: arg(0, "<target>")?,
+                modes: opt(1),
+                params: params.iter().skip(2).cloned().collect(),
+            },
+            // An empty message marks us as back
+            "AWAY" => Command::Away {
+                message: opt(0).filter(|message| !message.is_empty()),
+            },
+            "ISON" => Command::Ison { nicknames: words() },
+            "USERHOST" => Command::Userhost { nicknames: words() },
+            "MONITOR" => {
+                let subcommand = match arg(0, "<subcommand>")?.to_ascii_uppercase().as_str() {
+                    "+" => MonitorSubcommand::Add {
+                        targets: split_list(&arg(1, "<targets>")?),
+                    },
+                    "-" => MonitorSubcommand::Remove {
+                        targets: split_list(&arg(1, "<targets>")?),
+                    },
+                    "C" => MonitorSubcommand::Clear,
+                    "L" => MonitorSubcommand::List,
+                    "S" => MonitorSubcommand::Status,
+                    other => {
+                        return Err(ValidationError::InvalidParameter(format!(
+                            "Unknown MONITOR subcommand: {other}"
+                        )))
+                    }
+                };
+                Command::Monitor { subcommand }
+            }
+            "SETNAME" => Command::SetName {
+                realname: arg(0, "<realname>")?,
+            },
+            "INVITE" => Command::Invite {
+                nickname: arg(0, "<nickname>")?,
+                channel: arg(1, "<channel>")?,
+            },
+            "KNOCK" => Command::Knock {
+                channel: arg(0, "<channel>")?,
+                message: opt(1),
+            },
+            "TAGMSG" => Command::TagMsg {
+                target: arg(0, "<target>")?,
+            },
+            "CHATHISTORY" => {
+                let limit = |index| parse_number(&arg(index, "<limit>")?);
+                let reference = |index, what: &str| HistoryReference::parse(&arg(index, what)?);
+                let subcommand = match arg(0, "<subcommand>")?.to_ascii_uppercase().as_str() {
+                    "LATEST" => ChatHistorySubcommand::Latest {
+                        target: arg(1, "<target>")?,
+                        reference: reference(2, "<reference>")?,
+                        limit: limit(3)?,
+                    },
+                    "BEFORE" => ChatHistorySubcommand::Before {
+                        target: arg(1, "<target>")?,
+                        reference: reference(2, "<reference>")?,
+                        limit: limit(3)?,
+                    },
+                    "AFTER" => ChatHistorySubcommand::After {
+                        target: arg(1, "<target>")?,
+                        reference: reference(2, "<reference>")?,
+                        limit: limit(3)?,
+                    },
+                    "AROUND" => ChatHistorySubcommand::Around {
+                        target: arg(1, "<target>")?,
+                        reference: reference(2, "<reference>")?,
+                        limit: limit(3)?,
+                    },
+                    "BETWEEN" => ChatHistorySubcommand::Between {
+                        target: arg(1, "<target>")?,
+                        start: reference(2, "<start>")?,
+                        end: reference(3, "<end>")?,
+                        limit: limit(4)?,
+                    },
+                    "TARGETS" => ChatHistorySubcommand::Targets {
+                        start: reference(1, "<start>")?,
+                        end: reference(2, "<end>")?,
+                        limit: limit(3)?,
+                    },
+                    other => {
+                        return Err(ValidationError::InvalidParameter(format!(
+                            "Unknown CHATHISTORY subcommand: {other}"
+                        )))
+                    }
+                };
+                Command::ChatHistory { subcommand }
+            }
+            "MOTD" => Command::Motd { target: opt(0) },
+            "LUSERS" => Command::Lusers,
+            "VERSION" => Command::Version { target: opt(0) },
+            "TIME" => Command::Time { target: opt(0) },
+            "INFO" => Command::Info { target: opt(0) },
+            "ADMIN" => Command::Admin { target: opt(0) },
+            "OPER" => Command::Oper {
+                name: arg(0, "<name>")?,
+                password: arg(1, "<password>")?,
+            },
+            "WALLOPS" => Command::Wallops {
+                text: arg(0, "<text>")?,
+            },
+            "KILL" => Command::Kill {
+                nickname: arg(0, "<nickname>")?,
+                comment: arg(1, "<comment>")?,
+            },
+            "SILENCE" => Command::Silence { mask: opt(0) },
+            "REGISTER" => Command::Register {
+                account: arg(0, "<account>")?,
+                email: Some(arg(1, "<email>")?).filter(|email| email != "*"),
+                password: arg(2, "<password>")?,
+            },
+            _ => Command::Raw {
+                command: message.command.clone(),
+                params: params.clone(),
+            },
+        };
+
+        Ok(command)
+    }
+
+    /// Check that the command is well-formed before sending it
+    ///
+    /// Rejects parameters that would break the line (CR, LF, NUL), middle
+    /// parameters that are empty or contain spaces, and invalid nicknames or
+    /// channel names according to the validator's (server) limits.
+    ///
+    /// The text of a `PRIVMSG` or `NOTICE` may be of any length and contain
+    /// line breaks, as it is split into lines before sending; see
+    /// [`split`](crate::split).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::command::Command;
+    /// use rustirc_protocol::validation::IrcValidator;
+    ///
+    /// let validator = IrcValidator::new();
+    /// let invite = Command::Invite {
+    ///     nickname: "alice".to_string(),
+    ///     channel: "#rust".to_string(),
+    /// };
+    /// assert!(invite.validate(&validator).is_ok());
+    ///
+    /// let invite = Command::Invite {
+    ///     nickname: "alice".to_string(),
+    ///     channel: "rust".to_string(),
+    /// };
+    /// assert!(invite.validate(&validator).is_err());
+    /// ```
+    pub fn validate(&self, validator: &IrcValidator) -> Result<(), ValidationError> {
+        let message = self.to_message();
+        validator.validate_command(&message.command)?;
+
+        let last = message.params.len().saturating_sub(1);
+        let splittable = matches!(self, Command::PrivMsg { .. } | Command::Notice { .. });
+        for (index, param) in message.params.iter().enumerate() {
+            if splittable && index == last {
+                let bare_cr = param
+                    .split('\n')
+                    .any(|line| line.strip_suffix('\r').unwrap_or(line).contains('\r'));
+                if bare_cr || param.contains('\0') {
+                    return Err(ValidationError::InvalidCharacters(
+                        "Null and bare CR characters not allowed".to_string(),
+                    ));
+                }
+                continue;
+            }
+            validator.validate_parameter(param)?;
+            if index < last && (param.is_empty() || param.contains(' ') || param.starts_with(':')) {
+                return Err(ValidationError::InvalidParameter(format!(
+                    "{} parameter {} must be a single non-empty word",
+                    message.command,
+                    index + 1
+                )));
+            }
+        }
+
+        let non_empty = |value: &str, what: &str| {
+            if value.is_empty() {
+                Err(ValidationError::MissingParameter(format!(
+                    "{} {what}",
+                    message.command
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        let nicknames = |nicks: &[String]| {
+            if nicks.is_empty() {
+                return non_empty("", "<nicknames>");
+            }
+            nicks
+                .iter()
+                .try_for_each(|nick| validator.validate_nickname(nick))
+        };
+
+        match self {
+            Command::Nick { nickname } | Command::Kill { nickname, .. } => {
+                validator.validate_nickname(nickname)?
+            }
+            Command::Join { channels, .. } | Command::Part { channels, .. } => {
+                if channels.is_empty() {
+                    non_empty("", "<channels>")?;
+                }
+                for channel in channels {
+                    validator.validate_channel_name(channel)?;
+                }
+            }
+            Command::Topic { channel, .. }
+            | Command::Knock { channel, .. }
+            | Command::Kick { channel, .. } => validator.validate_channel_name(channel)?,
+            Command::Invite { nickname, channel } => {
+                validator.validate_nickname(nickname)?;
+                validator.validate_channel_name(channel)?;
+            }
+            Command::PrivMsg { target, text } | Command::Notice { target, text } => {
+                non_empty(target, "<target>")?;
+                non_empty(text, "<text>")?;
+            }
+            Command::TagMsg { target } => non_empty(target, "<target>")?,
+            Command::Ison { nicknames: nicks } => nicknames(nicks)?,
+            Command::Userhost { nicknames: nicks } => {
+                nicknames(nicks)?;
+                if nicks.len() > MAX_USERHOST_NICKNAMES {
+                    return Err(ValidationError::InvalidParameter(format!(
+                        "USERHOST accepts at most {MAX_USERHOST_NICKNAMES} nicknames"
+                    )));
+                }
+            }
+            Command::Monitor {
+                subcommand:
+                    MonitorSubcommand::Add { targets } | MonitorSubcommand::Remove { targets },
+            } => nicknames(targets)?,
+            Command::SetName { realname } => non_empty(realname, "<realname>")?,
+            Command::Oper { name, password } => {
+                non_empty(name, "<name>")?;
+                non_empty(password, "<password>")?;
+            }
+            Command::Wallops { text } => non_empty(text, "<text>")?,
+            Command::Silence { mask: Some(mask) } => {
+                if !mask.starts_with(['+', '-']) || mask.len() < 2 {
+                    return Err(ValidationError::InvalidParameter(format!(
+                        "SILENCE mask must start with + or -: {mask}"
+                    )));
+                }
+            }
+            Command::Register {
+                account, password, ..
+            } => {
+                if account != "*" {
+                    validator.validate_nickname(account)?;
+                }
+                non_empty(password, "<password>")?;
+            }
+            Command::ChatHistory { subcommand } => {
+                let (references, limit) = match subcommand {
+                    ChatHistorySubcommand::Latest { limit, .. } => (Vec::new(), limit),
+                    ChatHistorySubcommand::Before {
+                        reference, limit, ..
+                    }
+                    | ChatHistorySubcommand::After {
+                        reference, limit, ..
+                    }
+                    | ChatHistorySubcommand::Around {
+                        reference, limit, ..
+                    } => (vec![reference], limit),
+                    ChatHistorySubcommand::Between {
+                        start, end, limit, ..
+                    }
+                    | ChatHistorySubcommand::Targets { start, end, limit } => {
+                        (vec![start, end], limit)
+                    }
+                };
+                if *limit == 0 {
+                    return Err(ValidationError::InvalidParameter(
+                        "CHATHISTORY limit must be positive".to_string(),
+                    ));
+                }
+                if references.contains(&&HistoryReference::Any) {
+                    return Err(ValidationError::InvalidParameter(
+                        "CHATHISTORY * reference is only valid with LATEST".to_string(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Append an optional last parameter
+fn optional(msg: crate::Message, param: &Option<String>) -> crate::Message {
+    match param {
+        Some(param) => msg.add_param(param.clone()),
+        None => msg,
+    }
+}
+
+/// Split a comma-separated target list
+fn split_list(param: &str) -> Vec<String> {
+    param
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_number(value: &str) -> Result<u32, ValidationError> {
+    value
+        .parse()
+        .map_err(|_| ValidationError::InvalidParameter(format!("Expected a number, got {value}")))
+}
+
+impl TryFrom<&crate::Message> for Command {
+    type Error = ValidationError;
+
+    fn try_from(message: &crate::Message) -> Result<Self, Self::Error> {
+        Self::from_message(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn round_trip(command: Command, line: &str) {
+        assert_eq!(command.to_message().to_string(), line);
+        let parsed = Command::from_message(&Parser::parse_message(line).unwrap()).unwrap();
+        assert_eq!(parsed, command);
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_round_trip_new_commands() {
+        round_trip(
+            Command::Away {
+                message: Some("gone fishing".into()),
+            },
+            "AWAY :gone fishing",
+        );
+        round_trip(Command::Away { message: None }, "AWAY");
+        round_trip(
+            Command::Invite {
+                nickname: "alice".into(),
+                channel: "#rust".into(),
+            },
+            "INVITE alice #rust",
+        );
+        round_trip(
+            Command::Oper {
+                name: "admin".into(),
+                password: "hunter2".into(),
+            },
+            "OPER admin hunter2",
+        );
+        round_trip(
+            Command::Ison {
+                nicknames: strings(&["alice", "bob"]),
+            },
+            "ISON alice bob",
+        );
+        round_trip(
+            Command::Userhost {
+                nicknames: strings(&["alice"]),
+            },
+            "USERHOST alice",
+        );
+        round_trip(
+            Command::Monitor {
+                subcommand: MonitorSubcommand::Remove {
+                    targets: strings(&["alice", "bob"]),
+                },
+            },
+            "MONITOR - alice,bob",
+        );
+        round_trip(
+            Command::Monitor {
+                subcommand: MonitorSubcommand::Status,
+            },
+            "MONITOR S",
+        );
+        round_trip(
+            Command::SetName {
+                realname: "Rusty Crab".into(),
+            },
+            "SETNAME :Rusty Crab",
+        );
+        round_trip(
+            Command::TagMsg {
+                target: "#rust".into(),
+            },
+            "TAGMSG #rust",
+        );
+        round_trip(
+            Command::Knock {
+                channel: "#secret".into(),
+                message: Some("let me in".into()),
+            },
+            "KNOCK #secret :let me in",
+        );
+        round_trip(Command::Motd { target: None }, "MOTD");
+        round_trip(Command::Lusers, "LUSERS");
+        round_trip(
+            Command::Version {
+                target: Some("irc.example.com".into()),
+            },
+            "VERSION irc.example.com",
+        );
+        round_trip(Command::Time { target: None }, "TIME");
+        round_trip(Command::Info { target: None }, "INFO");
+        round_trip(Command::Admin { target: None }, "ADMIN");
+        round_trip(
+            Command::Wallops {
+                text: "maintenance soon".into(),
+            },
+            "WALLOPS :maintenance soon",
+        );
+        round_trip(
+            Command::Kill {
+                nickname: "spammer".into(),
+                comment: "go away".into(),
+            },
+            "KILL spammer :go away",
+        );
+        round_trip(
+            Command::Silence {
+                mask: Some("+*!*@spam".into()),
+            },
+            "SILENCE +*!*@spam",
+        );
+        round_trip(
+            Command::Register {
+                account: "*".into(),
+                email: None,
+                password: "secret".into(),
+            },
+            "REGISTER * * secret",
+        );
+    }
+
+    #[test]
+    fn test_round_trip_chathistory() {
+        round_trip(
+            Command::ChatHistory {
+                subcommand: ChatHistorySubcommand::Latest {
+                    target: "#rust".into(),
+                    reference: HistoryReference::Any,
+                    limit: 50,
+                },
+            },
+            "CHATHISTORY LATEST #rust * 50",
+        );
+        round_trip(
+            Command::ChatHistory {
+                subcommand: ChatHistorySubcommand::Between {
+                    target: "#rust".into(),
+                    start: HistoryReference::MsgId("abc".into()),
+                    end: HistoryReference::Timestamp("2024-01-01T00:00:00.000Z".into()),
+                    limit: 10,
+                },
+            },
+            "CHATHISTORY BETWEEN #rust msgid=abc timestamp=2024-01-01T00:00:00.000Z 10",
+        );
+        round_trip(
+            Command::ChatHistory {
+                subcommand: ChatHistorySubcommand::Targets {
+                    start: HistoryReference::Timestamp("2024-01-01T00:00:00.000Z".into()),
+                    end: HistoryReference::Timestamp("2024-01-02T00:00:00.000Z".into()),
+                    limit: 5,
+                },
+            },
+            "CHATHISTORY TARGETS timestamp=2024-01-01T00:00:00.000Z \
+             timestamp=2024-01-02T00:00:00.000Z 5",
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        let parse = |line: &str| Command::from_message(&Parser::parse_message(line).unwrap());
+
+        assert!(matches!(
+            parse("INVITE alice"),
+            Err(ValidationError::MissingParameter(_))
+        ));
+        assert!(matches!(
+            parse("MONITOR X alice"),
+            Err(ValidationError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            parse("CHATHISTORY LATEST #rust * many"),
+            Err(ValidationError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            parse("CHATHISTORY BEFORE #rust id=1 10"),
+            Err(ValidationError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            parse("FOO bar"),
+            Ok(Command::Raw { command, .. }) if command == "FOO"
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let validator = IrcValidator::new();
+        let valid = |command: Command| command.validate(&validator).is_ok();
+
+        assert!(valid(Command::Userhost {
+            nicknames: strings(&["a", "b", "c", "d", "e"]),
+        }));
+        assert!(!valid(Command::Userhost {
+            nicknames: strings(&["a", "b", "c", "d", "e", "f"]),
+        }));
+        assert!(!valid(Command::Ison { nicknames: vec![] }));
+        assert!(!valid(Command::Kill {
+            nickname: "bad nick".into(),
+            comment: "bye".into(),
+        }));
+        assert!(!valid(Command::Away {
+            message: Some("line\r\nQUIT".into()),
+        }));
+        assert!(valid(Command::PrivMsg {
+            target: "#rust".into(),
+            text: format!("{}\r\nsecond line", "long ".repeat(100)),
+        }));
+        assert!(!valid(Command::Notice {
+            target: "#rust".into(),
+            text: "bare\rcarriage return".into(),
+        }));
+        assert!(!valid(Command::Oper {
+            name: "two words".into(),
+            password: "secret".into(),
+        }));
+        assert!(!valid(Command::Silence {
+            mask: Some("*!*@spam".into()),
+        }));
+        assert!(!valid(Command::ChatHistory {
+            subcommand: ChatHistorySubcommand::Before {
+                target: "#rust".into(),
+                reference: HistoryReference::Any,
+                limit: 10,
+            },
+        }));
+        assert!(valid(Command::Register {
+            account: "rusty".into(),
+            email: Some("rusty@example.com".into()),
+            password: "secret".into(),
+        }));
+    }
 }
@@ -0,0 +1,463 @@
+//! IRC text formatting
+//!
+//! Parses the control codes of formatted IRC text into [`StyledSpan`]s, and
+//! serializes spans back into control codes. Covers bold, italic, underline,
+//! strikethrough, monospace, reverse, the 99 numbered colors (`\x03`) and
+//! hex colors (`\x04`). Front ends map [`Style`] onto their own rendering.
+//!
+//! See: <https://modern.ircdocs.horse/formatting>
+//!
+//! # Examples
+//!
+//! ```
+//! use rustirc_protocol::formatting::{parse, serialize, IrcColor};
+//!
+//! let spans = parse("plain \x02bold \x0304,12red on blue");
+//! assert_eq!(spans[1].text, "bold ");
+//! assert!(spans[1].style.bold);
+//! assert_eq!(spans[2].style.foreground, Some(IrcColor::Palette(4)));
+//! assert_eq!(serialize(&spans), "plain \x02bold \x0304,12red on blue");
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// IRC formatting control codes
+pub mod codes {
+    pub const BOLD: char = '\x02';
+    pub const ITALIC: char = '\x1D';
+    pub const UNDERLINE: char = '\x1F';
+    pub const STRIKETHROUGH: char = '\x1E';
+    pub const MONOSPACE: char = '\x11';
+    pub const REVERSE: char = '\x16';
+    pub const COLOR: char = '\x03';
+    pub const HEX_COLOR: char = '\x04';
+    pub const RESET: char = '\x0F';
+}
+
+/// Color number meaning "the default color"
+const DEFAULT_COLOR: u8 = 99;
+
+/// RGB values of colors 0 to 98
+const PALETTE: [u32; 99] = [
+    // 0-15: the original mIRC colors
+    0xffffff, 0x000000, 0x00007f, 0x009300, 0xff0000, 0x7f0000, 0x9c009c, 0xfc7f00, 0xffff00,
+    0x00fc00, 0x009393, 0x00ffff, 0x0000fc, 0xff00ff, 0x7f7f7f, 0xd2d2d2,
+    // 16-98: the extended colors
+    0x470000, 0x472100, 0x474700, 0x324700, 0x004700, 0x00472c, 0x004747, 0x002747, 0x000047,
+    0x2e0047, 0x470047, 0x47002a, 0x740000, 0x743a00, 0x747400, 0x517400, 0x007400, 0x007449,
+    0x007474, 0x004074, 0x000074, 0x4b0074, 0x740074, 0x740045, 0xb50000, 0xb56300, 0xb5b500,
+    0x7db500, 0x00b500, 0x00b571, 0x00b5b5, 0x0063b5, 0x0000b5, 0x7500b5, 0xb500b5, 0xb5006b,
+    0xff0000, 0xff8c00, 0xffff00, 0xb2ff00, 0x00ff00, 0x00ffa0, 0x00ffff, 0x008cff, 0x0000ff,
+    0xa500ff, 0xff00ff, 0xff0098, 0xff5959, 0xffb459, 0xffff71, 0xcfff60, 0x6fff6f, 0x65ffc9,
+    0x6dffff, 0x59b4ff, 0x5959ff, 0xc459ff, 0xff66ff, 0xff59bc, 0xff9c9c, 0xffd39c, 0xffff9c,
+    0xe2ff9c, 0x9cff9c, 0x9cffdb, 0x9cffff, 0x9cd3ff, 0x9c9cff, 0xdc9cff, 0xff9cff, 0xff94d3,
+    0x000000, 0x131313, 0x282828, 0x363636, 0x4d4d4d, 0x656565, 0x818181, 0x9f9f9f, 0xbcbcbc,
+    0xe2e2e2, 0xffffff,
+];
+
+/// A text or background color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IrcColor {
+    /// A numbered color from 0 to 98, set with `\x03`
+    Palette(u8),
+    /// A hex color, set with `\x04`
+    Rgb(u8, u8, u8),
+}
+
+impl IrcColor {
+    /// The RGB value of the color
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::formatting::IrcColor;
+    ///
+    /// assert_eq!(IrcColor::Palette(4).rgb(), (0xff, 0, 0));
+    /// assert_eq!(IrcColor::Rgb(1, 2, 3).rgb(), (1, 2, 3));
+    /// ```
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            IrcColor::Palette(index) => {
+                let [_, r, g, b] = PALETTE[usize::from(index).min(PALETTE.len() - 1)].to_be_bytes();
+                (r, g, b)
+            }
+            IrcColor::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    fn hex(&self) -> String {
+        let (r, g, b) = self.rgb();
+        format!("{r:02X}{g:02X}{b:02X}")
+    }
+}
+
+/// The formatting of a run of text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub monospace: bool,
+    pub reverse: bool,
+    /// Text color; `None` is the client's default
+    pub foreground: Option<IrcColor>,
+    /// Background color; `None` is the client's default
+    pub background: Option<IrcColor>,
+}
+
+impl Style {
+    /// Whether this is plain, unformatted text
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+    /// Control codes that change formatting from `self` to `next`
+    ///
+    /// A hex background needs a hex foreground; it is dropped when the
+    /// foreground is the default color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::formatting::{IrcColor, Style};
+    ///
+    /// let bold = Style { bold: true, ..Style::default() };
+    /// assert_eq!(Style::default().codes_to(&bold), "\x02");
+    ///
+    /// let on_red = Style { background: Some(IrcColor::Palette(4)), ..bold };
+    /// assert_eq!(bold.codes_to(&on_red), "\x0399,04");
+    /// assert_eq!(on_red.codes_to(&Style::default()), "\x0f");
+    /// ```
+    pub fn codes_to(&self, next: &Style) -> String {
+        if next.is_plain() {
+            return if self.is_plain() {
+                String::new()
+            } else {
+                codes::RESET.to_string()
+            };
+        }
+
+        let mut codes = String::new();
+        if (self.foreground, self.background) != (next.foreground, next.background) {
+            self.push_color_codes(next, &mut codes);
+        }
+        for (from, to, code) in [
+            (self.bold, next.bold, codes::BOLD),
+            (self.italic, next.italic, codes::ITALIC),
+            (self.underline, next.underline, codes::UNDERLINE),
+            (self.strikethrough, next.strikethrough, codes::STRIKETHROUGH),
+            (self.monospace, next.monospace, codes::MONOSPACE),
+            (self.reverse, next.reverse, codes::REVERSE),
+        ] {
+            if from != to {
+                codes.push(code);
+            }
+        }
+        codes
+    }
+
+    fn push_color_codes(&self, next: &Style, codes: &mut String) {
+        // Color codes cannot unset just the background, and a default
+        // foreground is `99`, which needs a numbered background
+        let (mut foreground, mut background) = (self.foreground, self.background);
+        if next.background.is_none() && background.is_some()
+            || next.foreground.is_none() && !matches!(next.background, Some(IrcColor::Palette(_)))
+        {
+            codes.push(codes::COLOR);
+            (foreground, background) = (None, None);
+        }
+
+        let new_background = next.background.filter(|bg| Some(*bg) != background);
+        match (next.foreground, new_background) {
+            (Some(fg @ IrcColor::Rgb(..)), bg) | (Some(fg), bg @ Some(IrcColor::Rgb(..))) => {
+                codes.push(codes::HEX_COLOR);
+                codes.push_str(&fg.hex());
+                if let Some(bg) = bg {
+                    codes.push(',');
+                    codes.push_str(&bg.hex());
+                }
+            }
+            (fg, bg) => {
+                let fg = match fg {
+                    Some(IrcColor::Palette(index)) => index,
+                    _ => DEFAULT_COLOR,
+                };
+                match bg {
+                    Some(IrcColor::Palette(bg)) => {
+                        codes.push_str(&format!("{}{fg:02},{bg:02}", codes::COLOR))
+                    }
+                    // A hex background without a foreground cannot be written
+                    _ if next.foreground != foreground => {
+                        codes.push_str(&format!("{}{fg:02}", codes::COLOR))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// A run of text with a single style
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyledSpan {
+    pub text: String,
+    pub style: Style,
+}
+
+impl StyledSpan {
+    /// Create a span of text with the given style
+    pub fn new(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+/// Take `min` to `max` leading bytes matching `accept` from `text`
+///
+/// Returns an empty string when fewer than `min` bytes match.
+fn take_digits(text: &str, min: usize, max: usize, accept: fn(&u8) -> bool) -> &str {
+    let len = text.bytes().take(max).take_while(accept).count();
+    if len < min {
+        return "";
+    }
+    &text[..len]
+}
+
+/// Parse `fg[,bg]` color digits after a color code
+///
+/// Returns the colors and the number of bytes consumed. The comma is only
+/// part of the code when a background follows it. Each color needs at least
+/// `min` digits; with fewer, the code has no digits at all.
+fn parse_color_digits(
+    text: &str,
+    min: usize,
+    max: usize,
+    accept: fn(&u8) -> bool,
+) -> (Option<&str>, Option<&str>, usize) {
+    let fg = take_digits(text, min, max, accept);
+    if fg.is_empty() {
+        return (None, None, 0);
+    }
+
+    let rest = &text[fg.len()..];
+    if let Some(after_comma) = rest.strip_prefix(',') {
+        let bg = take_digits(after_comma, min, max, accept);
+        if !bg.is_empty() {
+            return (Some(fg), Some(bg), fg.len() + 1 + bg.len());
+        }
+    }
+    (Some(fg), None, fg.len())
+}
+
+fn palette_color(digits: &str) -> Option<IrcColor> {
+    match digits.parse::<u8>() {
+        Ok(DEFAULT_COLOR) | Err(_) => None,
+        Ok(index) => Some(IrcColor::Palette(index)),
+    }
+}
+
+fn hex_color(digits: &str) -> Option<IrcColor> {
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Some(IrcColor::Rgb(r, g, b))
+}
+
+/// Parse formatted text into styled spans
+///
+/// Adjacent text with the same style is merged, and empty spans are omitted.
+/// A color code without digits resets both colors.
+pub fn parse(text: &str) -> Vec<StyledSpan> {
+    let mut spans: Vec<StyledSpan> = Vec::new();
+    let mut style = Style::default();
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        rest = &rest[ch.len_utf8()..];
+        match ch {
+            codes::BOLD => style.bold = !style.bold,
+            codes::ITALIC => style.italic = !style.italic,
+            codes::UNDERLINE => style.underline = !style.underline,
+            codes::STRIKETHROUGH => style.strikethrough = !style.strikethrough,
+            codes::MONOSPACE => style.monospace = !style.monospace,
+            codes::REVERSE => style.reverse = !style.reverse,
+            codes::RESET => style = Style::default(),
+            codes::COLOR | codes::HEX_COLOR => {
+                let (fg, bg, consumed) = if ch == codes::COLOR {
+                    parse_color_digits(rest, 1, 2, u8::is_ascii_digit)
+                } else {
+                    // Hex colors are always six digits
+                    parse_color_digits(rest, 6, 6, u8::is_ascii_hexdigit)
+                };
+                rest = &rest[consumed..];
+
+                let to_color = if ch == codes::COLOR {
+                    palette_color
+                } else {
+                    hex_color
+                };
+                match fg {
+                    // A foreground alone keeps the background
+                    Some(fg) => {
+                        style.foreground = to_color(fg);
+                        if let Some(bg) = bg {
+                            style.background = to_color(bg);
+                        }
+                    }
+                    None => {
+                        style.foreground = None;
+                        style.background = None;
+                    }
+                }
+            }
+            _ => match spans.last_mut() {
+                Some(span) if span.style == style => span.text.push(ch),
+                _ => spans.push(StyledSpan::new(ch, style)),
+            },
+        }
+    }
+
+    spans
+}
+
+/// Serialize styled spans into formatted text
+///
+/// Only the codes needed to change from one span's style to the next are
+/// written, and color numbers are always two digits so text starting with
+/// a digit is not read as part of the code.
+pub fn serialize(spans: &[StyledSpan]) -> String {
+    let mut text = String::new();
+    let mut style = Style::default();
+
+    for span in spans.iter().filter(|span| !span.text.is_empty()) {
+        let codes = style.codes_to(&span.style);
+        text.push_str(&codes);
+        if codes.ends_with(codes::COLOR) && span.text.starts_with(|c: char| c.is_ascii_digit()) {
+            // Keep leading digits out of a bare color reset
+            text.push_str("99,99");
+        }
+        text.push_str(&span.text);
+        style = span.style;
+    }
+    text
+}
+
+/// Remove all formatting codes from text
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::formatting::strip;
+///
+/// assert_eq!(strip("\x02bold\x02 \x0304,12red\x03 \x04FF8800hex"), "bold red hex");
+/// ```
+pub fn strip(text: &str) -> String {
+    parse(text).into_iter().map(|span| span.text).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(text: &str) -> Vec<(String, Style)> {
+        parse(text)
+            .into_iter()
+            .map(|span| (span.text, span.style))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_toggles() {
+        let spans = styled("a\x02b\x1dc\x1fd\x1ee\x11f\x16g\x0fh");
+        let texts: Vec<&str> = spans.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, ["a", "b", "c", "d", "e", "f", "g", "h"]);
+
+        let g = spans[6].1;
+        assert!(g.bold && g.italic && g.underline && g.strikethrough && g.monospace && g.reverse);
+        assert!(spans[7].1.is_plain());
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        let spans = parse("\x0304,12a\x037b\x03c\x0399,1d\x031,e");
+        assert_eq!(spans[0].style.foreground, Some(IrcColor::Palette(4)));
+        assert_eq!(spans[0].style.background, Some(IrcColor::Palette(12)));
+        // A foreground alone keeps the background
+        assert_eq!(spans[1].style.foreground, Some(IrcColor::Palette(7)));
+        assert_eq!(spans[1].style.background, Some(IrcColor::Palette(12)));
+        assert!(spans[2].style.is_plain());
+        // 99 is the default color
+        assert_eq!(spans[3].style.foreground, None);
+        assert_eq!(spans[3].style.background, Some(IrcColor::Palette(1)));
+        // A comma without digits is text
+        assert_eq!(spans[4].text, ",e");
+
+        let spans = parse("\x04FF8800,000000hex\x04 plain");
+        assert_eq!(
+            spans[0].style.foreground,
+            Some(IrcColor::Rgb(0xff, 0x88, 0))
+        );
+        assert_eq!(spans[0].style.background, Some(IrcColor::Rgb(0, 0, 0)));
+        assert!(spans[1].style.is_plain());
+
+        // Fewer than six hex digits is a bare reset, and the digits are text
+        let spans = parse("FF8800redABCtext FF8800,FFFsame");
+        assert_eq!(spans[1].text, "ABCtext ");
+        assert!(spans[1].style.is_plain());
+        assert_eq!(spans[2].text, ",FFFsame");
+        assert_eq!(spans[2].style.background, None);
+
+        assert_eq!(IrcColor::Palette(52).rgb(), (0xff, 0, 0));
+        assert_eq!(IrcColor::Palette(98).rgb(), (0xff, 0xff, 0xff));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        for text in [
+            "plain",
+            "\x02bold\x02 and \x1ditalic\x1d",
+            "\x0304red \x0303,01green on black\x03 none",
+            "\x0399,04default on red\x0f plain",
+            "\x04FF8800,000000hex\x0304 palette",
+            "\x1e\x11struck code\x0f",
+        ] {
+            let spans = parse(text);
+            assert_eq!(parse(&serialize(&spans)), spans, "{text:?}");
+        }
+
+        // Digits in the text are not read as part of a color code
+        let spans = vec![StyledSpan::new(
+            "1st",
+            Style {
+                foreground: Some(IrcColor::Palette(4)),
+                ..Style::default()
+            },
+        )];
+        assert_eq!(serialize(&spans), "\x03041st");
+
+        let mut spans = parse("\x02\x0304red");
+        let bold = Style {
+            bold: true,
+            ..Style::default()
+        };
+        spans.push(StyledSpan::new("1st", bold));
+        assert_eq!(serialize(&spans), "\x0304\x02red\x0399,991st");
+    }
+
+    #[test]
+    fn test_serialize_unsets_background() {
+        let red_on_blue = Style {
+            foreground: Some(IrcColor::Palette(4)),
+            background: Some(IrcColor::Palette(2)),
+            ..Style::default()
+        };
+        let red = Style {
+            background: None,
+            ..red_on_blue
+        };
+        assert_eq!(red_on_blue.codes_to(&red), "\x03\x0304");
+        assert_eq!(red.codes_to(&red_on_blue), "\x0304,02");
+    }
+}
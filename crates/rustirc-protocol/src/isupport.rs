@@ -0,0 +1,566 @@
+//! RPL_ISUPPORT (005) server feature registry
+//!
+//! Servers advertise their limits and protocol behaviour through `005`
+//! tokens after registration. [`Isupport`] parses the tokens the client acts
+//! on into typed fields, applies later `005` lines incrementally and resets a
+//! feature to its default when the server withdraws it with `-TOKEN`.
+//!
+//! Unadvertised features fall back to the RFC 1459 behaviour.
+//!
+//! See: <https://modern.ircdocs.horse/#rplisupport-parameters>
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::Message;
+
+/// Nickname/channel case mapping advertised with `CASEMAPPING`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CaseMapping {
+    /// Only `A-Z` and `a-z` are equivalent
+    Ascii,
+    /// ASCII plus `[]\~` equivalent to `{}|^`
+    #[default]
+    Rfc1459,
+    /// ASCII plus `[]\` equivalent to `{}|`
+    StrictRfc1459,
+    /// Unicode case folding per RFC 7613 (PRECIS), approximated; see [`CaseMapping::fold`]
+    Rfc7613,
+}
+
+impl CaseMapping {
+    /// Parse a `CASEMAPPING` value, returning `None` for unknown mappings
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "ascii" => Some(Self::Ascii),
+            "rfc1459" => Some(Self::Rfc1459),
+            "strict-rfc1459" => Some(Self::StrictRfc1459),
+            "rfc7613" => Some(Self::Rfc7613),
+            _ => None,
+        }
+    }
+
+    /// Token value as sent by the server
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Rfc1459 => "rfc1459",
+            Self::StrictRfc1459 => "strict-rfc1459",
+            Self::Rfc7613 => "rfc7613",
+        }
+    }
+}
+
+impl fmt::Display for CaseMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A channel membership prefix from `PREFIX`, e.g. mode `o` shown as `@`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixMode {
+    pub mode: char,
+    pub prefix: char,
+}
+
+/// How a channel mode takes its parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModeKind {
+    /// Membership prefix mode from `PREFIX`; always takes a nickname
+    Prefix,
+    /// `CHANMODES` type A: list mode, parameter on set and unset
+    List,
+    /// `CHANMODES` type B: parameter on set and unset
+    AlwaysParam,
+    /// `CHANMODES` type C: parameter only when set
+    SetParam,
+    /// `CHANMODES` type D: no parameter
+    Flag,
+}
+
+/// Channel mode groups from `CHANMODES=A,B,C,D`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChanModes {
+    pub list: String,
+    pub always_param: String,
+    pub set_param: String,
+    pub flag: String,
+}
+
+impl Default for ChanModes {
+    fn default() -> Self {
+        Self {
+            list: "beI".to_string(),
+            always_param: "k".to_string(),
+            set_param: "l".to_string(),
+            flag: "imnpst".to_string(),
+        }
+    }
+}
+
+impl ChanModes {
+    fn parse(value: &str) -> Self {
+        let mut groups = value.split(',').map(str::to_string);
+        Self {
+            list: groups.next().unwrap_or_default(),
+            always_param: groups.next().unwrap_or_default(),
+            set_param: groups.next().unwrap_or_default(),
+            flag: groups.next().unwrap_or_default(),
+        }
+    }
+
+    /// Kind of a (non-prefix) channel mode
+    pub fn kind(&self, mode: char) -> Option<ModeKind> {
+        if self.list.contains(mode) {
+            Some(ModeKind::List)
+        } else if self.always_param.contains(mode) {
+            Some(ModeKind::AlwaysParam)
+        } else if self.set_param.contains(mode) {
+            Some(ModeKind::SetParam)
+        } else if self.flag.contains(mode) {
+            Some(ModeKind::Flag)
+        } else {
+            None
+        }
+    }
+}
+
+/// Typed view of the server's RPL_ISUPPORT tokens
+///
+/// # Examples
+///
+/// ```rust
+/// use rustirc_protocol::isupport::{CaseMapping, Isupport};
+///
+/// let mut isupport = Isupport::default();
+/// isupport.apply_tokens(["PREFIX=(qaohv)~&@%+", "CASEMAPPING=ascii", "NICKLEN=30"]);
+///
+/// assert_eq!(isupport.prefix_for_mode('h'), Some('%'));
+/// assert_eq!(isupport.casemapping, CaseMapping::Ascii);
+/// assert_eq!(isupport.nicklen, Some(30));
+///
+/// isupport.apply_tokens(["-NICKLEN"]);
+/// assert_eq!(isupport.nicklen, None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Isupport {
+    /// Membership prefixes, highest rank first (`PREFIX`)
+    pub prefix: Vec<PrefixMode>,
+    /// Channel mode groups (`CHANMODES`)
+    pub chanmodes: ChanModes,
+    /// Characters that start a channel name (`CHANTYPES`)
+    pub chantypes: String,
+    /// Case mapping for nicknames and channels (`CASEMAPPING`)
+    pub casemapping: CaseMapping,
+    /// Maximum nickname length (`NICKLEN`)
+    pub nicklen: Option<usize>,
+    /// Maximum channel name length (`CHANNELLEN`)
+    pub channellen: Option<usize>,
+    /// Maximum topic length (`TOPICLEN`)
+    pub topiclen: Option<usize>,
+    /// Maximum line length in bytes, excluding tags (`LINELEN`)
+    pub linelen: usize,
+    /// Per-command target limits; `None` means unlimited (`TARGMAX`)
+    pub targmax: HashMap<String, Option<usize>>,
+    /// Maximum parameterised modes per `MODE` command; `None` means
+    /// unlimited (`MODES`)
+    pub modes: Option<usize>,
+    /// Whether `MONITOR` is supported
+    pub monitor: bool,
+    /// Maximum `MONITOR` list size, if limited
+    pub monitor_limit: Option<usize>,
+    /// Whether `WHO` accepts WHOX field selection (`WHOX`)
+    pub whox: bool,
+    /// Network name (`NETWORK`)
+    pub network: Option<String>,
+    /// Whether the server only accepts UTF-8 (`UTF8ONLY`)
+    pub utf8only: bool,
+    /// Supported `LIST` search extensions (`ELIST`)
+    pub elist: String,
+    /// Every advertised token with its raw value, including unrecognised ones
+    pub tokens: BTreeMap<String, Option<String>>,
+}
+
+impl Default for Isupport {
+    fn default() -> Self {
+        Self {
+            prefix: vec![
+                PrefixMode {
+                    mode: 'o',
+                    prefix: '@',
+                },
+                PrefixMode {
+                    mode: 'v',
+                    prefix: '+',
+                },
+            ],
+            chanmodes: ChanModes::default(),
+            chantypes: "#&".to_string(),
+            casemapping: CaseMapping::default(),
+            nicklen: None,
+            channellen: None,
+            topiclen: None,
+            linelen: crate::MAX_MESSAGE_LENGTH,
+            targmax: HashMap::new(),
+            modes: Some(3),
+            monitor: false,
+            monitor_limit: None,
+            whox: false,
+            network: None,
+            utf8only: false,
+            elist: String::new(),
+            tokens: BTreeMap::new(),
+        }
+    }
+}
+
+/// The token parameters of an `005` message
+///
+/// `005 <client> <token>... :are supported by this server`. The free-text
+/// suffix is optional; it is recognised by containing a space, which tokens
+/// never do.
+pub fn isupport_tokens<S: AsRef<str>>(params: &[S]) -> &[S] {
+    let Some((_client, rest)) = params.split_first() else {
+        return &[];
+    };
+    match rest.split_last() {
+        Some((last, tokens)) if last.as_ref().is_empty() || last.as_ref().contains(' ') => tokens,
+        _ => rest,
+    }
+}
+
+impl Isupport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the tokens of an `005 RPL_ISUPPORT` message
+    ///
+    /// Other messages are ignored.
+    pub fn apply_message(&mut self, message: &Message) {
+        if message.command != "005" {
+            return;
+        }
+
+        self.apply_tokens(isupport_tokens(&message.params).iter().map(String::as_str));
+    }
+
+    /// Apply `TOKEN`, `TOKEN=value` and `-TOKEN` entries
+    pub fn apply_tokens<'a>(&mut self, tokens: impl IntoIterator<Item = &'a str>) {
+        for token in tokens {
+            if let Some(name) = token.strip_prefix('-') {
+                self.remove(name);
+            } else {
+                let (name, value) = match token.split_once('=') {
+                    Some((name, value)) => (name, Some(unescape_value(value))),
+                    None => (token, None),
+                };
+                self.set(name, value);
+            }
+        }
+    }
+
+    fn set(&mut self, name: &str, value: Option<String>) {
+        let name = name.to_ascii_uppercase();
+        let raw = value.as_deref().unwrap_or("");
+        let number = raw.parse::<usize>().ok();
+
+        match name.as_str() {
+            "PREFIX" => self.prefix = parse_prefix(raw),
+            "CHANMODES" => self.chanmodes = ChanModes::parse(raw),
+            "CHANTYPES" => self.chantypes = raw.to_string(),
+            "CASEMAPPING" => match CaseMapping::parse(raw) {
+                Some(casemapping) => self.casemapping = casemapping,
+                None => debug!("Unknown CASEMAPPING {}, keeping {}", raw, self.casemapping),
+            },
+            "NICKLEN" => self.nicklen = number,
+            "CHANNELLEN" => self.channellen = number,
+            "TOPICLEN" => self.topiclen = number,
+            "LINELEN" => self.linelen = number.unwrap_or(crate::MAX_MESSAGE_LENGTH),
+            "TARGMAX" => self.targmax = parse_targmax(raw),
+            "MODES" => self.modes = number,
+            "MONITOR" => {
+                self.monitor = true;
+                self.monitor_limit = number;
+            }
+            "WHOX" => self.whox = true,
+            "NETWORK" => self.network = value.clone(),
+            "UTF8ONLY" => self.utf8only = true,
+            "ELIST" => self.elist = raw.to_ascii_uppercase(),
+            _ => {}
+        }
+
+        self.tokens.insert(name, value);
+    }
+
+    fn remove(&mut self, name: &str) {
+        let name = name.to_ascii_uppercase();
+        let defaults = Self::default();
+
+        match name.as_str() {
+            "PREFIX" => self.prefix = defaults.prefix,
+            "CHANMODES" => self.chanmodes = defaults.chanmodes,
+            "CHANTYPES" => self.chantypes = defaults.chantypes,
+            "CASEMAPPING" => self.casemapping = defaults.casemapping,
+            "NICKLEN" => self.nicklen = None,
+            "CHANNELLEN" => self.channellen = None,
+            "TOPICLEN" => self.topiclen = None,
+            "LINELEN" => self.linelen = defaults.linelen,
+            "TARGMAX" => self.targmax.clear(),
+            "MODES" => self.modes = defaults.modes,
+            "MONITOR" => {
+                self.monitor = false;
+                self.monitor_limit = None;
+            }
+            "WHOX" => self.whox = false,
+            "NETWORK" => self.network = None,
+            "UTF8ONLY" => self.utf8only = false,
+            "ELIST" => self.elist.clear(),
+            _ => {}
+        }
+
+        self.tokens.remove(&name);
+    }
+
+    /// Raw value of any advertised token; `Some(None)` for tokens without a value
+    pub fn get(&self, name: &str) -> Option<Option<&str>> {
+        self.tokens
+            .get(&name.to_ascii_uppercase())
+            .map(Option::as_deref)
+    }
+
+    /// Check whether a name is a channel according to `CHANTYPES`
+    pub fn is_channel(&self, name: &str) -> bool {
+        name.chars()
+            .next()
+            .is_some_and(|first| self.chantypes.contains(first))
+    }
+
+    /// Membership prefix symbol for a prefix mode, e.g. `o` -> `@`
+    pub fn prefix_for_mode(&self, mode: char) -> Option<char> {
+        self.prefix
+            .iter()
+            .find(|p| p.mode == mode)
+            .map(|p| p.prefix)
+    }
+
+    /// Prefix mode for a membership prefix symbol, e.g. `@` -> `o`
+    pub fn mode_for_prefix(&self, prefix: char) -> Option<char> {
+        self.prefix
+            .iter()
+            .find(|p| p.prefix == prefix)
+            .map(|p| p.mode)
+    }
+
+    /// Rank of a prefix mode; lower is more powerful
+    pub fn prefix_rank(&self, mode: char) -> Option<usize> {
+        self.prefix.iter().position(|p| p.mode == mode)
+    }
+
+    /// Split leading membership prefixes from a `NAMES` entry
+    ///
+    /// Returns the prefix modes (highest first, as sent with `multi-prefix`)
+    /// and the remaining nickname.
+    pub fn split_prefixes<'a>(&self, entry: &'a str) -> (Vec<char>, &'a str) {
+        let mut modes = Vec::new();
+        let mut rest = entry;
+        while let Some(first) = rest.chars().next() {
+            match self.mode_for_prefix(first) {
+                Some(mode) => {
+                    modes.push(mode);
+                    rest = &rest[first.len_utf8()..];
+                }
+                None => break,
+            }
+        }
+        (modes, rest)
+    }
+
+    /// How a channel mode takes its parameter, using `PREFIX` and `CHANMODES`
+    pub fn mode_kind(&self, mode: char) -> Option<ModeKind> {
+        if self.prefix_for_mode(mode).is_some() {
+            Some(ModeKind::Prefix)
+        } else {
+            self.chanmodes.kind(mode)
+        }
+    }
+
+    /// Maximum targets per command; `None` means unlimited
+    ///
+    /// Without a `TARGMAX` entry only `JOIN` and `PART` are assumed to take
+    /// multiple targets.
+    pub fn max_targets(&self, command: &str) -> Option<usize> {
+        let command = command.to_ascii_uppercase();
+        match self.targmax.get(&command) {
+            Some(limit) => *limit,
+            None if matches!(command.as_str(), "JOIN" | "PART") => None,
+            None => Some(1),
+        }
+    }
+
+    /// Whether `LIST` supports the given `ELIST` extension (e.g. `U` for user count)
+    pub fn supports_elist(&self, extension: char) -> bool {
+        self.elist.contains(extension.to_ascii_uppercase())
+    }
+}
+
+/// Parse `PREFIX=(modes)prefixes`
+fn parse_prefix(value: &str) -> Vec<PrefixMode> {
+    let Some((modes, prefixes)) = value
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+    else {
+        // An empty value means the server has no membership prefixes
+        return Vec::new();
+    };
+
+    modes
+        .chars()
+        .zip(prefixes.chars())
+        .map(|(mode, prefix)| PrefixMode { mode, prefix })
+        .collect()
+}
+
+/// Parse `TARGMAX=PRIVMSG:4,NOTICE:4,JOIN:`
+fn parse_targmax(value: &str) -> HashMap<String, Option<usize>> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (command, limit) = entry.split_once(':')?;
+            Some((command.to_ascii_uppercase(), limit.parse().ok()))
+        })
+        .collect()
+}
+
+/// Decode `\xHH` escapes in a token value
+fn unescape_value(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let raw = value.as_bytes();
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'\\' && raw.get(i + 1) == Some(&b'x') {
+            if let Some(byte) = value
+                .get(i + 2..i + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_defaults() {
+        let isupport = Isupport::default();
+        assert_eq!(isupport.prefix_for_mode('o'), Some('@'));
+        assert!(isupport.is_channel("#rust"));
+        assert!(isupport.is_channel("&local"));
+        assert!(!isupport.is_channel("nick"));
+        assert_eq!(isupport.casemapping, CaseMapping::Rfc1459);
+        assert_eq!(isupport.linelen, 512);
+        assert_eq!(isupport.mode_kind('b'), Some(ModeKind::List));
+        assert_eq!(isupport.max_targets("PRIVMSG"), Some(1));
+        assert_eq!(isupport.max_targets("JOIN"), None);
+    }
+
+    #[test]
+    fn test_apply_message() {
+        let mut isupport = Isupport::new();
+        let message = Parser::parse_message(
+            ":irc.example.com 005 nick PREFIX=(qaohv)~&@%+ CHANMODES=beIq,k,flj,CMnst \
+             CHANTYPES=# CASEMAPPING=ascii NICKLEN=30 CHANNELLEN=64 TOPICLEN=390 \
+             :are supported by this server",
+        )
+        .unwrap();
+        isupport.apply_message(&message);
+
+        let message = Parser::parse_message(
+            ":irc.example.com 005 nick LINELEN=2048 TARGMAX=PRIVMSG:4,JOIN:,WHOIS:1 MODES=4 \
+             MONITOR=100 WHOX NETWORK=Example\\x20Net UTF8ONLY ELIST=cmntu \
+             :are supported by this server",
+        )
+        .unwrap();
+        isupport.apply_message(&message);
+
+        assert_eq!(isupport.prefix.len(), 5);
+        assert_eq!(isupport.prefix_for_mode('q'), Some('~'));
+        assert_eq!(isupport.mode_for_prefix('%'), Some('h'));
+        assert_eq!(isupport.prefix_rank('a'), Some(1));
+        assert_eq!(isupport.mode_kind('q'), Some(ModeKind::Prefix));
+        assert_eq!(isupport.mode_kind('f'), Some(ModeKind::SetParam));
+        assert_eq!(isupport.mode_kind('k'), Some(ModeKind::AlwaysParam));
+        assert_eq!(isupport.mode_kind('C'), Some(ModeKind::Flag));
+        assert_eq!(isupport.mode_kind('z'), None);
+        assert!(!isupport.is_channel("&local"));
+        assert_eq!(isupport.casemapping, CaseMapping::Ascii);
+        assert_eq!(isupport.nicklen, Some(30));
+        assert_eq!(isupport.channellen, Some(64));
+        assert_eq!(isupport.topiclen, Some(390));
+        assert_eq!(isupport.linelen, 2048);
+        assert_eq!(isupport.max_targets("privmsg"), Some(4));
+        assert_eq!(isupport.max_targets("JOIN"), None);
+        assert_eq!(isupport.max_targets("NOTICE"), Some(1));
+        assert_eq!(isupport.modes, Some(4));
+        assert!(isupport.monitor);
+        assert_eq!(isupport.monitor_limit, Some(100));
+        assert!(isupport.whox);
+        assert_eq!(isupport.network.as_deref(), Some("Example Net"));
+        assert!(isupport.utf8only);
+        assert!(isupport.supports_elist('u'));
+        assert!(!isupport.supports_elist('x'));
+        assert_eq!(isupport.get("WHOX"), Some(None));
+        assert_eq!(isupport.get("nicklen"), Some(Some("30")));
+
+        // Servers may omit the free-text suffix
+        let message =
+            Parser::parse_message(":irc.example.com 005 nick -WHOX TOPICLEN=300").unwrap();
+        isupport.apply_message(&message);
+        assert_eq!(isupport.topiclen, Some(300));
+        assert!(!isupport.whox);
+    }
+
+    #[test]
+    fn test_token_removal_restores_defaults() {
+        let mut isupport = Isupport::new();
+        isupport.apply_tokens(["PREFIX=(ov)@+", "MONITOR", "MODES", "NICKLEN=16", "EXCEPTS"]);
+        assert!(isupport.monitor);
+        assert_eq!(isupport.modes, None);
+        assert_eq!(isupport.get("EXCEPTS"), Some(None));
+
+        isupport.apply_tokens(["-MONITOR", "-MODES", "-NICKLEN", "-EXCEPTS", "-PREFIX"]);
+        assert!(!isupport.monitor);
+        assert_eq!(isupport.modes, Some(3));
+        assert_eq!(isupport.nicklen, None);
+        assert_eq!(isupport.get("EXCEPTS"), None);
+        assert_eq!(isupport.prefix, Isupport::default().prefix);
+    }
+
+    #[test]
+    fn test_split_prefixes() {
+        let mut isupport = Isupport::new();
+        isupport.apply_tokens(["PREFIX=(qaohv)~&@%+"]);
+        assert_eq!(
+            isupport.split_prefixes("~@alice"),
+            (vec!['q', 'o'], "alice")
+        );
+        assert_eq!(isupport.split_prefixes("bob"), (vec![], "bob"));
+
+        isupport.apply_tokens(["PREFIX="]);
+        assert!(isupport.prefix.is_empty());
+        assert_eq!(isupport.split_prefixes("@carol"), (vec![], "@carol"));
+    }
+}
@@ -5,20 +5,32 @@
 
 pub mod builder;
 pub mod caps;
+pub mod casemap;
 pub mod command;
 pub mod ctcp;
+pub mod formatting;
+pub mod isupport;
 pub mod message;
+pub mod message_ref;
+pub mod mode;
 pub mod numeric;
 pub mod parser;
+pub mod reply;
+pub mod split;
 pub mod validation;
 
 pub use builder::MessageBuilder;
 pub use caps::{Capability, CapabilitySet};
+pub use casemap::IrcString;
 pub use command::Command;
 pub use ctcp::{escape_ctcp, unescape_ctcp, CtcpHandler, CtcpMessage};
+pub use isupport::{CaseMapping, Isupport, ModeKind, PrefixMode};
 pub use message::{escape_tag_value, unescape_tag_value, Message, Prefix, Tag};
+pub use message_ref::{MessageRef, PrefixRef, TagRef};
+pub use mode::ModeChange;
 pub use numeric::Numeric;
 pub use parser::Parser;
+pub use reply::{Reply, ReplyError};
 pub use validation::{
     is_valid_channel, is_valid_command, is_valid_nickname, IrcValidator, ValidationError,
 };
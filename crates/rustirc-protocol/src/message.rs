@@ -268,7 +268,9 @@ impl fmt::Display for Message {
         // Parameters
         for (i, param) in self.params.iter().enumerate() {
             write!(f, " ")?;
-            if i == self.params.len() - 1 && (param.contains(' ') || param.starts_with(':')) {
+            if i == self.params.len() - 1
+                && (param.is_empty() || param.contains(' ') || param.starts_with(':'))
+            {
                 write!(f, ":{param}")?;
             } else {
                 write!(f, "{param}")?;
@@ -0,0 +1,219 @@
+//! Borrowed IRC messages
+//!
+//! [`MessageRef`] is a view of a message that borrows the command, parameters,
+//! prefix and tags from the line it was parsed from. Parsing one allocates at
+//! most the parameter and tag lists, which keeps bulk input such as NAMES
+//! bursts and bouncer playback cheap. Convert to an owned [`Message`] with
+//! [`MessageRef::to_message`] only where the message has to outlive the line.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustirc_protocol::{Message, Parser};
+//!
+//! let line = String::from("@msgid=abc :alice!a@host PRIVMSG #rust :hi there");
+//! let message = Parser::new().parse_ref(&line).unwrap();
+//! assert_eq!(message.command, "PRIVMSG");
+//! assert_eq!(message.params, ["#rust", "hi there"]);
+//! assert_eq!(message.nick(), Some("alice"));
+//! assert_eq!(message.get_tag("msgid").as_deref(), Some("abc"));
+//!
+//! let owned: Message = message.to_message();
+//! assert_eq!(owned.to_string(), line);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::{unescape_tag_value, Message, Prefix, Tag};
+
+/// An IRC message borrowing from its input line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRef<'a> {
+    /// Optional IRCv3 message tags
+    pub tags: Option<Vec<TagRef<'a>>>,
+    /// Optional message prefix (server or user)
+    pub prefix: Option<PrefixRef<'a>>,
+    /// IRC command (e.g., "PRIVMSG", "JOIN", "001")
+    pub command: &'a str,
+    /// Command parameters
+    pub params: Vec<&'a str>,
+}
+
+/// A borrowed IRCv3 message tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagRef<'a> {
+    /// The tag key
+    pub key: &'a str,
+    /// The optional tag value, still escaped
+    pub value: Option<&'a str>,
+}
+
+/// A borrowed message prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixRef<'a> {
+    Server(&'a str),
+    User {
+        nick: &'a str,
+        user: Option<&'a str>,
+        host: Option<&'a str>,
+    },
+}
+
+impl<'a> TagRef<'a> {
+    /// Get the unescaped value, borrowing when nothing is escaped
+    pub fn unescaped_value(&self) -> Option<Cow<'a, str>> {
+        self.value.map(|value| {
+            if value.contains('\\') {
+                Cow::Owned(unescape_tag_value(value))
+            } else {
+                Cow::Borrowed(value)
+            }
+        })
+    }
+
+    /// Convert to an owned tag
+    pub fn to_tag(&self) -> Tag {
+        Tag::from_raw(self.key, self.value)
+    }
+}
+
+impl<'a> PrefixRef<'a> {
+    /// The nickname of a user prefix
+    pub fn nick(&self) -> Option<&'a str> {
+        match self {
+            PrefixRef::User { nick, .. } => Some(nick),
+            PrefixRef::Server(_) => None,
+        }
+    }
+
+    /// Convert to an owned prefix
+    pub fn to_prefix(&self) -> Prefix {
+        match *self {
+            PrefixRef::Server(server) => Prefix::Server(server.to_string()),
+            PrefixRef::User { nick, user, host } => Prefix::User {
+                nick: nick.to_string(),
+                user: user.map(str::to_string),
+                host: host.map(str::to_string),
+            },
+        }
+    }
+}
+
+impl<'a> MessageRef<'a> {
+    /// Get a tag value by key (unescaped)
+    pub fn get_tag(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.tags
+            .as_ref()?
+            .iter()
+            .find(|tag| tag.key == key)
+            .and_then(TagRef::unescaped_value)
+    }
+
+    /// Check if a tag exists
+    pub fn has_tag(&self, key: &str) -> bool {
+        self.tags
+            .as_ref()
+            .is_some_and(|tags| tags.iter().any(|tag| tag.key == key))
+    }
+
+    /// The nickname of the sender, if it is a user
+    pub fn nick(&self) -> Option<&'a str> {
+        self.prefix.as_ref().and_then(PrefixRef::nick)
+    }
+
+    /// Copy the message into an owned [`Message`]
+    pub fn to_message(&self) -> Message {
+        Message {
+            tags: self
+                .tags
+                .as_ref()
+                .map(|tags| tags.iter().map(TagRef::to_tag).collect()),
+            prefix: self.prefix.as_ref().map(PrefixRef::to_prefix),
+            command: self.command.to_string(),
+            params: self.params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a Message> for MessageRef<'a> {
+    fn from(message: &'a Message) -> Self {
+        Self {
+            tags: message.tags.as_ref().map(|tags| {
+                tags.iter()
+                    .map(|tag| TagRef {
+                        key: &tag.key,
+                        value: tag.value.as_deref(),
+                    })
+                    .collect()
+            }),
+            prefix: message.prefix.as_ref().map(|prefix| match prefix {
+                Prefix::Server(server) => PrefixRef::Server(server),
+                Prefix::User { nick, user, host } => PrefixRef::User {
+                    nick,
+                    user: user.as_deref(),
+                    host: host.as_deref(),
+                },
+            }),
+            command: &message.command,
+            params: message.params.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl From<MessageRef<'_>> for Message {
+    fn from(message: MessageRef<'_>) -> Self {
+        message.to_message()
+    }
+}
+
+impl fmt::Display for MessageRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_message(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_matches_owned_parser() {
+        let lines = [
+            "PING :server",
+            ":nick!user@host PRIVMSG #channel :Hello, world!",
+            "@time=2021-01-01T00:00:00.000Z;draft/label=a\\sb :srv NOTICE * :hi",
+            ":nick@host JOIN #rust",
+            ":srv 353 me = #rust :@alice +bob carol",
+            "CAP * LS :",
+        ];
+        let parser = Parser::new();
+
+        for line in lines {
+            let borrowed = parser.parse_ref(line).unwrap();
+            let owned = parser.parse(line).unwrap();
+            assert_eq!(borrowed.to_message(), owned, "{line}");
+            assert_eq!(MessageRef::from(&owned), borrowed, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_tag_unescaping_borrows_when_possible() {
+        let line = "@a=plain;b=with\\sspace;c :srv TAGMSG #rust";
+        let message = Parser::new().parse_ref(line).unwrap();
+
+        assert!(matches!(message.get_tag("a"), Some(Cow::Borrowed("plain"))));
+        assert_eq!(message.get_tag("b").as_deref(), Some("with space"));
+        assert!(message.has_tag("c") && message.get_tag("c").is_none());
+    }
+
+    #[test]
+    fn test_rejects_like_owned_parser() {
+        let parser = Parser::new();
+        for line in ["", ": PING", "@a=b", ":srv"] {
+            assert!(parser.parse_ref(line).is_err(), "{line:?}");
+            assert!(parser.parse(line).is_err(), "{line:?}");
+        }
+    }
+}
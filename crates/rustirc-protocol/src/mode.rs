@@ -0,0 +1,193 @@
+//! Channel and user mode change parsing
+//!
+//! A `MODE` line packs several changes into one mode string followed by their
+//! parameters, e.g. `MODE #chan +ovk-l alice bob secret`. Which modes consume
+//! a parameter depends on the server's `PREFIX` and `CHANMODES` tokens, so
+//! channel modes are parsed against an [`Isupport`] registry.
+//!
+//! See: <https://modern.ircdocs.horse/#channel-mode>
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{Isupport, ModeKind};
+
+/// A single mode being set or unset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModeChange {
+    /// `true` for `+`, `false` for `-`
+    pub adding: bool,
+    pub mode: char,
+    pub kind: ModeKind,
+    /// Nickname, mask, key or limit, when the mode takes one
+    pub param: Option<String>,
+}
+
+impl ModeChange {
+    fn new(adding: bool, mode: char, kind: ModeKind, param: Option<String>) -> Self {
+        Self {
+            adding,
+            mode,
+            kind,
+            param,
+        }
+    }
+}
+
+impl fmt::Display for ModeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.adding { '+' } else { '-' };
+        match &self.param {
+            Some(param) => write!(f, "{sign}{} {param}", self.mode),
+            None => write!(f, "{sign}{}", self.mode),
+        }
+    }
+}
+
+/// Whether a mode of this kind consumes a parameter
+fn takes_param(kind: ModeKind, adding: bool) -> bool {
+    match kind {
+        ModeKind::Prefix | ModeKind::List | ModeKind::AlwaysParam => true,
+        ModeKind::SetParam => adding,
+        ModeKind::Flag => false,
+    }
+}
+
+/// Parse a channel mode string and its parameters
+///
+/// Modes missing from `CHANMODES` and `PREFIX` are treated as parameterless
+/// flags. A list mode without a parameter (`MODE #chan +b`) is a list query
+/// and is returned with `param: None`; other modes missing their parameter
+/// are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::{mode::parse_channel_modes, Isupport, ModeKind};
+///
+/// let params = ["alice", "bob", "secret"].map(String::from);
+/// let changes = parse_channel_modes("+ovk-l", &params, &Isupport::default());
+///
+/// assert_eq!(changes.len(), 4);
+/// assert_eq!(changes[0].kind, ModeKind::Prefix);
+/// assert_eq!(changes[2].param.as_deref(), Some("secret"));
+/// assert!(!changes[3].adding && changes[3].param.is_none());
+/// ```
+pub fn parse_channel_modes<S: AsRef<str>>(
+    modestring: &str,
+    params: &[S],
+    isupport: &Isupport,
+) -> Vec<ModeChange> {
+    let mut params = params.iter();
+    let mut adding = true;
+    let mut changes = Vec::new();
+
+    for mode in modestring.chars() {
+        match mode {
+            '+' => adding = true,
+            '-' => adding = false,
+            _ => {
+                let kind = isupport.mode_kind(mode).unwrap_or_else(|| {
+                    debug!("Unknown channel mode {}, assuming no parameter", mode);
+                    ModeKind::Flag
+                });
+
+                if !takes_param(kind, adding) {
+                    changes.push(ModeChange::new(adding, mode, kind, None));
+                    continue;
+                }
+
+                match params.next() {
+                    Some(param) => {
+                        let param = Some(param.as_ref().to_string());
+                        changes.push(ModeChange::new(adding, mode, kind, param))
+                    }
+                    None if kind == ModeKind::List => {
+                        changes.push(ModeChange::new(adding, mode, kind, None))
+                    }
+                    None => debug!("Mode {} is missing its parameter", mode),
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+/// Parse a user mode string such as `+iw-x`
+///
+/// User modes never take parameters.
+pub fn parse_user_modes(modestring: &str) -> Vec<ModeChange> {
+    let mut adding = true;
+    modestring
+        .chars()
+        .filter_map(|mode| match mode {
+            '+' => {
+                adding = true;
+                None
+            }
+            '-' => {
+                adding = false;
+                None
+            }
+            _ => Some(ModeChange::new(adding, mode, ModeKind::Flag, None)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_with_server_chanmodes() {
+        let mut isupport = Isupport::default();
+        isupport.apply_tokens(["PREFIX=(qaohv)~&@%+", "CHANMODES=beIq,k,fl,imnpst"]);
+
+        let changes = parse_channel_modes(
+            "+qf-k+b",
+            &params(&["alice", "10:5", "oldkey", "*!*@spam"]),
+            &isupport,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                ModeChange::new(true, 'q', ModeKind::Prefix, Some("alice".into())),
+                ModeChange::new(true, 'f', ModeKind::SetParam, Some("10:5".into())),
+                ModeChange::new(false, 'k', ModeKind::AlwaysParam, Some("oldkey".into())),
+                ModeChange::new(true, 'b', ModeKind::List, Some("*!*@spam".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unset_limit_and_list_query() {
+        let isupport = Isupport::default();
+
+        let changes = parse_channel_modes("-l+nb", &params(&[]), &isupport);
+        assert_eq!(
+            changes,
+            vec![
+                ModeChange::new(false, 'l', ModeKind::SetParam, None),
+                ModeChange::new(true, 'n', ModeKind::Flag, None),
+                ModeChange::new(true, 'b', ModeKind::List, None),
+            ]
+        );
+
+        // A missing key is dropped rather than shifting later parameters
+        assert!(parse_channel_modes("+k", &params(&[]), &isupport).is_empty());
+    }
+
+    #[test]
+    fn test_user_modes() {
+        let changes = parse_user_modes("+iw-x");
+        let rendered: Vec<String> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(rendered, ["+i", "+w", "-x"]);
+    }
+}
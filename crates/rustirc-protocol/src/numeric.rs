@@ -28,6 +28,17 @@ pub enum Numeric {
     RplWhoisIdle = 317,
     RplEndOfWhois = 318,
     RplWhoisChannels = 319,
+    RplWhoisAccount = 330,
+    RplWhoisSecure = 671,
+
+    // WHO replies
+    RplEndOfWho = 315,
+    RplWhoReply = 352,
+    RplWhoSpcRpl = 354,
+
+    // Ban list
+    RplBanList = 367,
+    RplEndOfBanList = 368,
 
     // List replies
     RplListStart = 321,
@@ -43,6 +54,13 @@ pub enum Numeric {
     RplMotd = 372,
     RplEndOfMotd = 376,
 
+    // MONITOR
+    RplMonOnline = 730,
+    RplMonOffline = 731,
+    RplMonList = 732,
+    RplEndOfMonList = 733,
+    ErrMonListFull = 734,
+
     // Error replies
     ErrNoSuchNick = 401,
     ErrNoSuchServer = 402,
@@ -130,8 +148,23 @@ impl Numeric {
             331 => Some(Numeric::RplNoTopic),
             332 => Some(Numeric::RplTopic),
             333 => Some(Numeric::RplTopicWhoTime),
+            311 => Some(Numeric::RplWhoisUser),
+            312 => Some(Numeric::RplWhoisServer),
+            313 => Some(Numeric::RplWhoisOperator),
+            315 => Some(Numeric::RplEndOfWho),
+            317 => Some(Numeric::RplWhoisIdle),
+            318 => Some(Numeric::RplEndOfWhois),
+            319 => Some(Numeric::RplWhoisChannels),
+            321 => Some(Numeric::RplListStart),
+            322 => Some(Numeric::RplList),
+            323 => Some(Numeric::RplListEnd),
+            330 => Some(Numeric::RplWhoisAccount),
+            352 => Some(Numeric::RplWhoReply),
             353 => Some(Numeric::RplNameReply),
+            354 => Some(Numeric::RplWhoSpcRpl),
             366 => Some(Numeric::RplEndOfNames),
+            367 => Some(Numeric::RplBanList),
+            368 => Some(Numeric::RplEndOfBanList),
             372 => Some(Numeric::RplMotd),
             375 => Some(Numeric::RplMotdStart),
             376 => Some(Numeric::RplEndOfMotd),
@@ -141,6 +174,12 @@ impl Numeric {
             421 => Some(Numeric::ErrUnknownCommand),
             433 => Some(Numeric::ErrNicknameInUse),
             461 => Some(Numeric::ErrNeedMoreParams),
+            671 => Some(Numeric::RplWhoisSecure),
+            730 => Some(Numeric::RplMonOnline),
+            731 => Some(Numeric::RplMonOffline),
+            732 => Some(Numeric::RplMonList),
+            733 => Some(Numeric::RplEndOfMonList),
+            734 => Some(Numeric::ErrMonListFull),
             _ => None,
         }
     }
@@ -28,7 +28,7 @@
 //! assert!(message.tags.is_some());
 //! ```
 
-use crate::{IrcValidator, Message, Prefix, Tag, ValidationError};
+use crate::{IrcValidator, Message, MessageRef, PrefixRef, TagRef, ValidationError};
 use thiserror::Error;
 
 /// Errors that can occur during IRC message parsing
@@ -183,6 +183,30 @@ impl Parser {
     /// - `InvalidFormat` - Malformed message structure
     /// - `ValidationError` - Protocol compliance violation
     pub fn parse(&self, input: &str) -> Result<Message, ParseError> {
+        self.parse_ref(input).map(|message| message.to_message())
+    }
+
+    /// Parse an IRC message without copying it
+    ///
+    /// Applies the same validation as [`Parser::parse`], but the returned
+    /// [`MessageRef`] borrows its command, parameters, prefix and tags from
+    /// `input`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rustirc_protocol::parser::Parser;
+    ///
+    /// let line = String::from(":srv 353 me = #rust :@alice bob");
+    /// let msg = Parser::new().parse_ref(&line).unwrap();
+    /// assert_eq!(msg.command, "353");
+    /// assert_eq!(msg.params[3], "@alice bob");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as [`Parser::parse`].
+    pub fn parse_ref<'a>(&self, input: &'a str) -> Result<MessageRef<'a>, ParseError> {
         if input.is_empty() {
             return Err(ParseError::EmptyMessage);
         }
@@ -194,76 +218,46 @@ impl Parser {
             return Err(ParseError::MessageTooLong(input.len()));
         }
 
-        let mut chars = input.chars().peekable();
+        let mut rest = input;
         let mut tags = None;
         let mut prefix = None;
-        let mut command = String::new();
-        let mut params = Vec::new();
 
         // Parse tags (IRCv3)
-        if chars.peek() == Some(&'@') {
-            chars.next(); // Skip '@'
-            tags = Some(self.parse_tags(&mut chars)?);
-            Self::skip_whitespace(&mut chars);
+        if let Some(stripped) = rest.strip_prefix('@') {
+            let (raw_tags, remainder) = Self::split_word(stripped);
+            tags = Some(self.parse_tags(raw_tags)?);
+            rest = remainder.trim_start();
         }
 
         // Parse prefix
-        if chars.peek() == Some(&':') {
-            chars.next(); // Skip ':'
-            prefix = Some(Self::parse_prefix(&mut chars)?);
-            Self::skip_whitespace(&mut chars);
+        if let Some(stripped) = rest.strip_prefix(':') {
+            let (raw_prefix, remainder) = Self::split_word(stripped);
+            prefix = Some(Self::parse_prefix(raw_prefix)?);
+            rest = remainder.trim_start();
         }
 
         // Parse command
-        while let Some(ch) = chars.peek() {
-            if ch.is_whitespace() {
-                break;
-            }
-            if let Some(c) = chars.next() {
-                command.push(c);
-            } else {
-                return Err(ParseError::InvalidFormat(
-                    "Unexpected end of input while parsing command".to_string(),
-                ));
-            }
-        }
-
+        let (command, remainder) = Self::split_word(rest);
         if command.is_empty() {
             return Err(ParseError::InvalidFormat("Missing command".to_string()));
         }
 
         // Validate command
-        self.validator.validate_command(&command)?;
-
-        Self::skip_whitespace(&mut chars);
+        self.validator.validate_command(command)?;
 
         // Parse parameters
-        while chars.peek().is_some() {
-            if chars.peek() == Some(&':') {
+        let mut params = Vec::new();
+        rest = remainder.trim_start();
+        while !rest.is_empty() {
+            if let Some(trailing) = rest.strip_prefix(':') {
                 // Trailing parameter
-                chars.next(); // Skip ':'
-                params.push(chars.collect());
+                params.push(trailing);
                 break;
-            } else {
-                // Regular parameter
-                let mut param = String::new();
-                while let Some(ch) = chars.peek() {
-                    if ch.is_whitespace() {
-                        break;
-                    }
-                    if let Some(c) = chars.next() {
-                        param.push(c);
-                    } else {
-                        return Err(ParseError::InvalidFormat(
-                            "Unexpected end of input while parsing parameter".to_string(),
-                        ));
-                    }
-                }
-                if !param.is_empty() {
-                    params.push(param);
-                }
-                Self::skip_whitespace(&mut chars);
             }
+            // Regular parameter
+            let (param, remainder) = Self::split_word(rest);
+            params.push(param);
+            rest = remainder.trim_start();
         }
 
         // Validate parameters
@@ -271,7 +265,7 @@ impl Parser {
             self.validator.validate_parameter(param)?;
         }
 
-        Ok(Message {
+        Ok(MessageRef {
             tags,
             prefix,
             command,
@@ -279,56 +273,36 @@ impl Parser {
         })
     }
 
-    /// Parse IRCv3 message tags from the character stream
+    /// Parse IRCv3 message tags
     ///
-    /// Handles the `@key=value;key2=value2` format at the start of IRCv3 messages.
+    /// Handles the `key=value;key2=value2` section after the leading `@`.
     /// Tags are separated by semicolons and may have optional values.
-    fn parse_tags(
-        &self,
-        chars: &mut std::iter::Peekable<std::str::Chars>,
-    ) -> Result<Vec<Tag>, ParseError> {
-        let mut tags = Vec::new();
-        let mut current_tag = String::new();
-
-        while let Some(ch) = chars.peek() {
-            if ch.is_whitespace() {
-                break;
-            }
-            if *ch == ';' {
-                chars.next();
-                if !current_tag.is_empty() {
-                    tags.push(self.parse_single_tag(&current_tag)?);
-                    current_tag.clear();
-                }
-            } else if let Some(c) = chars.next() {
-                current_tag.push(c);
-            } else {
-                return Err(ParseError::InvalidFormat(
-                    "Unexpected end of input while parsing tag".to_string(),
-                ));
-            }
-        }
-
-        if !current_tag.is_empty() {
-            tags.push(self.parse_single_tag(&current_tag)?);
-        }
-
-        Ok(tags)
+    fn parse_tags<'a>(&self, raw: &'a str) -> Result<Vec<TagRef<'a>>, ParseError> {
+        raw.split(';')
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| self.parse_single_tag(tag))
+            .collect()
     }
 
     /// Parse a single tag from key=value or key format
     ///
     /// Validates both the key and optional value according to IRCv3 specifications.
-    fn parse_single_tag(&self, tag_str: &str) -> Result<Tag, ParseError> {
+    fn parse_single_tag<'a>(&self, tag_str: &'a str) -> Result<TagRef<'a>, ParseError> {
         if let Some((key, value)) = tag_str.split_once('=') {
             // Validate tag key and value
             self.validator.validate_tag_key(key)?;
             self.validator.validate_tag_value(value)?;
-            Ok(Tag::from_raw(key, Some(value)))
+            Ok(TagRef {
+                key,
+                value: Some(value),
+            })
         } else {
             // Validate tag key (no value)
             self.validator.validate_tag_key(tag_str)?;
-            Ok(Tag::from_raw(tag_str, None::<String>))
+            Ok(TagRef {
+                key: tag_str,
+                value: None,
+            })
         }
     }
 
@@ -336,74 +310,41 @@ impl Parser {
     ///
     /// Determines whether the prefix is a server name or user information
     /// and extracts the appropriate components.
-    fn parse_prefix(
-        chars: &mut std::iter::Peekable<std::str::Chars>,
-    ) -> Result<Prefix, ParseError> {
-        let mut prefix_str = String::new();
-
-        while let Some(ch) = chars.peek() {
-            if ch.is_whitespace() {
-                break;
-            }
-            if let Some(c) = chars.next() {
-                prefix_str.push(c);
-            } else {
-                return Err(ParseError::InvalidFormat(
-                    "Unexpected end of input while parsing prefix".to_string(),
-                ));
-            }
-        }
-
+    fn parse_prefix(prefix_str: &str) -> Result<PrefixRef<'_>, ParseError> {
         if prefix_str.is_empty() {
             return Err(ParseError::InvalidFormat("Empty prefix".to_string()));
         }
 
         // Check if it's a user prefix (contains ! or @)
-        if prefix_str.contains('!') || prefix_str.contains('@') {
-            let mut parts = prefix_str.splitn(2, '!');
-            let nick = parts
-                .next()
-                .ok_or_else(|| ParseError::InvalidFormat("Empty nick in prefix".to_string()))?
-                .to_string();
-
-            if let Some(rest) = parts.next() {
-                let mut parts = rest.splitn(2, '@');
-                let user = Some(
-                    parts
-                        .next()
-                        .ok_or_else(|| {
-                            ParseError::InvalidFormat("Empty user in prefix".to_string())
-                        })?
-                        .to_string(),
-                );
-                let host = parts.next().map(String::from);
-
-                Ok(Prefix::User { nick, user, host })
-            } else if let Some((nick, host)) = prefix_str.split_once('@') {
-                Ok(Prefix::User {
-                    nick: nick.to_string(),
-                    user: None,
-                    host: Some(host.to_string()),
-                })
-            } else {
-                Ok(Prefix::User {
-                    nick,
-                    user: None,
-                    host: None,
-                })
-            }
+        if let Some((nick, rest)) = prefix_str.split_once('!') {
+            let (user, host) = match rest.split_once('@') {
+                Some((user, host)) => (user, Some(host)),
+                None => (rest, None),
+            };
+            Ok(PrefixRef::User {
+                nick,
+                user: Some(user),
+                host,
+            })
+        } else if let Some((nick, host)) = prefix_str.split_once('@') {
+            Ok(PrefixRef::User {
+                nick,
+                user: None,
+                host: Some(host),
+            })
         } else {
-            Ok(Prefix::Server(prefix_str))
+            Ok(PrefixRef::Server(prefix_str))
         }
     }
 
-    /// Skip whitespace characters in the input stream
+    /// Split off the next space-separated word
     ///
-    /// IRC messages use spaces to separate components, this helper advances
-    /// past any whitespace to the next significant character.
-    fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
-        while chars.peek().is_some_and(|ch| ch.is_whitespace()) {
-            chars.next();
+    /// IRC messages use spaces to separate components; returns the word and
+    /// the remainder starting at the separator.
+    fn split_word(input: &str) -> (&str, &str) {
+        match input.find(char::is_whitespace) {
+            Some(end) => input.split_at(end),
+            None => (input, ""),
         }
     }
 }
@@ -411,6 +352,7 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Prefix;
 
     #[test]
     fn test_parse_simple_command() {
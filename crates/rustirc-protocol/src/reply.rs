@@ -0,0 +1,578 @@
+//! Structured numeric replies
+//!
+//! [`Numeric`](crate::Numeric) only names reply codes. [`Reply`] decodes the
+//! parameters of the common replies into named fields so consumers no longer
+//! index into `message.params` themselves.
+//!
+//! Every reply starts with the client's own nickname, which is dropped.
+//!
+//! See: <https://modern.ircdocs.horse/#numerics>
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::isupport::isupport_tokens;
+use crate::{Message, MessageRef, Prefix};
+
+/// Errors decoding a numeric reply
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReplyError {
+    #[error("{0} is not a supported numeric reply")]
+    Unsupported(String),
+
+    #[error("{numeric} is missing <{name}>")]
+    MissingParameter { numeric: String, name: &'static str },
+
+    #[error("{numeric} has invalid <{name}>: {value:?}")]
+    InvalidParameter {
+        numeric: String,
+        name: &'static str,
+        value: String,
+    },
+}
+
+/// A decoded numeric reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reply {
+    /// 005 RPL_ISUPPORT
+    ISupport { tokens: Vec<String> },
+
+    /// 311 RPL_WHOISUSER
+    WhoisUser {
+        nick: String,
+        username: String,
+        host: String,
+        realname: String,
+    },
+    /// 312 RPL_WHOISSERVER
+    WhoisServer {
+        nick: String,
+        server: String,
+        server_info: String,
+    },
+    /// 313 RPL_WHOISOPERATOR
+    WhoisOperator { nick: String, text: String },
+    /// 317 RPL_WHOISIDLE
+    WhoisIdle {
+        nick: String,
+        idle_secs: u64,
+        /// Signon time, when the server sends it
+        signon: Option<u64>,
+    },
+    /// 318 RPL_ENDOFWHOIS
+    EndOfWhois { nick: String },
+    /// 319 RPL_WHOISCHANNELS; entries keep their membership prefixes
+    WhoisChannels { nick: String, channels: Vec<String> },
+    /// 330 RPL_WHOISACCOUNT
+    WhoisAccount { nick: String, account: String },
+    /// 671 RPL_WHOISSECURE
+    WhoisSecure { nick: String },
+
+    /// 352 RPL_WHOREPLY
+    WhoReply {
+        channel: String,
+        username: String,
+        host: String,
+        server: String,
+        nick: String,
+        /// `H`/`G` followed by `*` for operators and membership prefixes
+        flags: String,
+        hopcount: u32,
+        realname: String,
+    },
+    /// 354 RPL_WHOSPCRPL; the fields depend on the WHOX request
+    WhoxReply { fields: Vec<String> },
+
+    /// 353 RPL_NAMREPLY; entries keep their membership prefixes
+    NameReply {
+        /// `=` public, `*` private or `@` secret
+        symbol: char,
+        channel: String,
+        names: Vec<String>,
+    },
+    /// 366 RPL_ENDOFNAMES
+    EndOfNames { channel: String },
+
+    /// 332 RPL_TOPIC
+    Topic { channel: String, topic: String },
+    /// 333 RPL_TOPICWHOTIME
+    TopicWhoTime {
+        channel: String,
+        setter: String,
+        set_at: u64,
+    },
+
+    /// 367 RPL_BANLIST
+    BanList {
+        channel: String,
+        mask: String,
+        setter: Option<String>,
+        set_at: Option<u64>,
+    },
+    /// 368 RPL_ENDOFBANLIST
+    EndOfBanList { channel: String },
+
+    /// 321 RPL_LISTSTART
+    ListStart,
+    /// 322 RPL_LIST
+    List {
+        channel: String,
+        visible: u32,
+        topic: String,
+    },
+    /// 323 RPL_LISTEND
+    ListEnd,
+
+    /// 730 RPL_MONONLINE
+    MonOnline { targets: Vec<Prefix> },
+    /// 731 RPL_MONOFFLINE
+    MonOffline { targets: Vec<Prefix> },
+}
+
+/// Positional access to a reply's parameters with descriptive errors
+struct Params<'a> {
+    numeric: &'a str,
+    params: &'a [&'a str],
+}
+
+impl<'a> Params<'a> {
+    fn get(&self, index: usize, name: &'static str) -> Result<&'a str, ReplyError> {
+        self.params
+            .get(index)
+            .copied()
+            .ok_or_else(|| ReplyError::MissingParameter {
+                numeric: self.numeric.to_string(),
+                name,
+            })
+    }
+
+    fn string(&self, index: usize, name: &'static str) -> Result<String, ReplyError> {
+        self.get(index, name).map(str::to_string)
+    }
+
+    fn number<T: std::str::FromStr>(
+        &self,
+        index: usize,
+        name: &'static str,
+    ) -> Result<T, ReplyError> {
+        let value = self.get(index, name)?;
+        value.parse().map_err(|_| self.invalid(name, value))
+    }
+
+    fn invalid(&self, name: &'static str, value: &str) -> ReplyError {
+        ReplyError::InvalidParameter {
+            numeric: self.numeric.to_string(),
+            name,
+            value: value.to_string(),
+        }
+    }
+}
+
+impl Reply {
+    /// Decode a numeric reply
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustirc_protocol::{Parser, Reply};
+    ///
+    /// let message = Parser::parse_message(":srv 322 me #rust 42 :All things Rust").unwrap();
+    /// assert_eq!(
+    ///     Reply::parse(&message).unwrap(),
+    ///     Reply::List {
+    ///         channel: "#rust".to_string(),
+    ///         visible: 42,
+    ///         topic: "All things Rust".to_string(),
+    ///     }
+    /// );
+    ///
+    /// let message = Parser::parse_message(":srv 322 me #rust lots :topic").unwrap();
+    /// assert!(Reply::parse(&message).is_err());
+    /// ```
+    pub fn parse(message: &Message) -> Result<Self, ReplyError> {
+        Self::parse_ref(&MessageRef::from(message))
+    }
+
+    /// Decode a numeric reply from a borrowed message
+    pub fn parse_ref(message: &MessageRef<'_>) -> Result<Self, ReplyError> {
+        let p = Params {
+            numeric: message.command,
+            params: &message.params,
+        };
+
+        let reply = match message.command {
+            // 005 <client> <token>... :are supported by this server
+            "005" => {
+                p.get(1, "token")?;
+                Reply::ISupport {
+                    tokens: strings(isupport_tokens(&message.params)),
+                }
+            }
+            // 311 <client> <nick> <username> <host> * :<realname>
+            "311" => Reply::WhoisUser {
+                nick: p.string(1, "nick")?,
+                username: p.string(2, "username")?,
+                host: p.string(3, "host")?,
+                realname: p.string(5, "realname")?,
+            },
+            // 312 <client> <nick> <server> :<server info>
+            "312" => Reply::WhoisServer {
+                nick: p.string(1, "nick")?,
+                server: p.string(2, "server")?,
+                server_info: p.string(3, "server info")?,
+            },
+            // 313 <client> <nick> :is an IRC operator
+            "313" => Reply::WhoisOperator {
+                nick: p.string(1, "nick")?,
+                text: p.string(2, "text")?,
+            },
+            // 317 <client> <nick> <secs> [<signon>] :seconds idle, signon time
+            "317" => Reply::WhoisIdle {
+                nick: p.string(1, "nick")?,
+                idle_secs: p.number(2, "secs")?,
+                signon: if message.params.len() > 4 {
+                    Some(p.number(3, "signon")?)
+                } else {
+                    None
+                },
+            },
+            // 318 <client> <nick> :End of /WHOIS list
+            "318" => Reply::EndOfWhois {
+                nick: p.string(1, "nick")?,
+            },
+            // 319 <client> <nick> :[prefix]<channel>{ [prefix]<channel>}
+            "319" => Reply::WhoisChannels {
+                nick: p.string(1, "nick")?,
+                channels: words(p.get(2, "channels")?),
+            },
+            // 330 <client> <nick> <account> :is logged in as
+            "330" => Reply::WhoisAccount {
+                nick: p.string(1, "nick")?,
+                account: p.string(2, "account")?,
+            },
+            // 671 <client> <nick> :is using a secure connection
+            "671" => Reply::WhoisSecure {
+                nick: p.string(1, "nick")?,
+            },
+            // 352 <client> <channel> <username> <host> <server> <nick> <flags> :<hopcount> <realname>
+            "352" => {
+                let trailing = p.get(7, "hopcount")?;
+                let (hopcount, realname) = trailing.split_once(' ').unwrap_or((trailing, ""));
+                Reply::WhoReply {
+                    channel: p.string(1, "channel")?,
+                    username: p.string(2, "username")?,
+                    host: p.string(3, "host")?,
+                    server: p.string(4, "server")?,
+                    nick: p.string(5, "nick")?,
+                    flags: p.string(6, "flags")?,
+                    hopcount: hopcount
+                        .parse()
+                        .map_err(|_| p.invalid("hopcount", hopcount))?,
+                    realname: realname.to_string(),
+                }
+            }
+            // 354 <client> [token] [channel] [user] [ip] [host] [server] [nick] [flags] [hop] [idle] [account] [oplevel] [:realname]
+            "354" => {
+                p.get(1, "field")?;
+                Reply::WhoxReply {
+                    fields: strings(&message.params[1..]),
+                }
+            }
+            // 353 <client> <symbol> <channel> :[prefix]<nick>{ [prefix]<nick>}
+            "353" => {
+                let symbol = p.get(1, "symbol")?;
+                let symbol = match symbol {
+                    "=" | "*" | "@" => symbol.chars().next().unwrap_or('='),
+                    _ => return Err(p.invalid("symbol", symbol)),
+                };
+                Reply::NameReply {
+                    symbol,
+                    channel: p.string(2, "channel")?,
+                    names: words(p.get(3, "nicknames")?),
+                }
+            }
+            // 366 <client> <channel> :End of /NAMES list
+            "366" => Reply::EndOfNames {
+                channel: p.string(1, "channel")?,
+            },
+            // 332 <client> <channel> :<topic>
+            "332" => Reply::Topic {
+                channel: p.string(1, "channel")?,
+                topic: p.string(2, "topic")?,
+            },
+            // 333 <client> <channel> <nick> <setat>
+            "333" => Reply::TopicWhoTime {
+                channel: p.string(1, "channel")?,
+                setter: p.string(2, "nick")?,
+                set_at: p.number(3, "setat")?,
+            },
+            // 367 <client> <channel> <mask> [<who> <set-ts>]
+            "367" => Reply::BanList {
+                channel: p.string(1, "channel")?,
+                mask: p.string(2, "mask")?,
+                setter: message.params.get(3).map(|setter| setter.to_string()),
+                set_at: match message.params.get(4) {
+                    Some(_) => Some(p.number(4, "set-ts")?),
+                    None => None,
+                },
+            },
+            // 368 <client> <channel> :End of channel ban list
+            "368" => Reply::EndOfBanList {
+                channel: p.string(1, "channel")?,
+            },
+            // 321 <client> Channel :Users  Name
+            "321" => Reply::ListStart,
+            // 322 <client> <channel> <client count> :<topic>
+            "322" => Reply::List {
+                channel: p.string(1, "channel")?,
+                visible: p.number(2, "client count")?,
+                topic: message
+                    .params
+                    .get(3)
+                    .map(|topic| topic.to_string())
+                    .unwrap_or_default(),
+            },
+            // 323 <client> :End of /LIST
+            "323" => Reply::ListEnd,
+            // 730 <client> :target[!user@host][,target[!user@host]]*
+            "730" => Reply::MonOnline {
+                targets: monitor_targets(&p)?,
+            },
+            // 731 <client> :target[,target2]*
+            "731" => Reply::MonOffline {
+                targets: monitor_targets(&p)?,
+            },
+            other => return Err(ReplyError::Unsupported(other.to_string())),
+        };
+
+        Ok(reply)
+    }
+}
+
+impl TryFrom<&Message> for Reply {
+    type Error = ReplyError;
+
+    fn try_from(message: &Message) -> Result<Self, Self::Error> {
+        Self::parse(message)
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn words(value: &str) -> Vec<String> {
+    value.split_whitespace().map(str::to_string).collect()
+}
+
+/// Parse the comma-separated `nick[!user@host]` targets of 730/731
+fn monitor_targets(p: &Params<'_>) -> Result<Vec<Prefix>, ReplyError> {
+    let targets = p.get(1, "targets")?;
+    targets
+        .split(',')
+        .filter(|target| !target.is_empty())
+        .map(|target| {
+            let (nick, rest) = match target.split_once('!') {
+                Some((nick, rest)) => (nick, Some(rest)),
+                None => (target, None),
+            };
+            if nick.is_empty() {
+                return Err(p.invalid("targets", targets));
+            }
+            let (user, host) = match rest.map(|rest| rest.split_once('@')) {
+                Some(Some((user, host))) => (Some(user.to_string()), Some(host.to_string())),
+                Some(None) => return Err(p.invalid("targets", targets)),
+                None => (None, None),
+            };
+            Ok(Prefix::User {
+                nick: nick.to_string(),
+                user,
+                host,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn parse(line: &str) -> Result<Reply, ReplyError> {
+        Reply::parse(&Parser::parse_message(line).unwrap())
+    }
+
+    #[test]
+    fn test_isupport() {
+        assert_eq!(
+            parse(":srv 005 me NICKLEN=30 WHOX :are supported by this server").unwrap(),
+            Reply::ISupport {
+                tokens: vec!["NICKLEN=30".into(), "WHOX".into()],
+            }
+        );
+        // No free-text suffix: the last parameter is a token
+        assert_eq!(
+            parse(":srv 005 me NICKLEN=30 WHOX").unwrap(),
+            Reply::ISupport {
+                tokens: vec!["NICKLEN=30".into(), "WHOX".into()],
+            }
+        );
+        assert_eq!(
+            parse(":srv 005 me :UTF8ONLY").unwrap(),
+            Reply::ISupport {
+                tokens: vec!["UTF8ONLY".into()],
+            }
+        );
+        assert!(parse(":srv 005 me").is_err());
+    }
+
+    #[test]
+    fn test_whois_family() {
+        assert_eq!(
+            parse(":srv 311 me alice ~alice host.example * :Alice Liddell").unwrap(),
+            Reply::WhoisUser {
+                nick: "alice".into(),
+                username: "~alice".into(),
+                host: "host.example".into(),
+                realname: "Alice Liddell".into(),
+            }
+        );
+        assert_eq!(
+            parse(":srv 317 me alice 120 1700000000 :seconds idle, signon time").unwrap(),
+            Reply::WhoisIdle {
+                nick: "alice".into(),
+                idle_secs: 120,
+                signon: Some(1700000000),
+            }
+        );
+        assert_eq!(
+            parse(":srv 317 me alice 120 :seconds idle").unwrap(),
+            Reply::WhoisIdle {
+                nick: "alice".into(),
+                idle_secs: 120,
+                signon: None,
+            }
+        );
+        assert_eq!(
+            parse(":srv 319 me alice :@#rust +#crabs").unwrap(),
+            Reply::WhoisChannels {
+                nick: "alice".into(),
+                channels: vec!["@#rust".into(), "+#crabs".into()],
+            }
+        );
+        assert_eq!(
+            parse(":srv 330 me alice alice_acct :is logged in as").unwrap(),
+            Reply::WhoisAccount {
+                nick: "alice".into(),
+                account: "alice_acct".into(),
+            }
+        );
+        assert_eq!(
+            parse(":srv 671 me alice :is using a secure connection").unwrap(),
+            Reply::WhoisSecure {
+                nick: "alice".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_who_names_and_topic() {
+        assert_eq!(
+            parse(":srv 352 me #rust ~bob host srv.example bob H@ :2 Bob Builder").unwrap(),
+            Reply::WhoReply {
+                channel: "#rust".into(),
+                username: "~bob".into(),
+                host: "host".into(),
+                server: "srv.example".into(),
+                nick: "bob".into(),
+                flags: "H@".into(),
+                hopcount: 2,
+                realname: "Bob Builder".into(),
+            }
+        );
+        assert_eq!(
+            parse(":srv 353 me = #rust :@alice +bob carol").unwrap(),
+            Reply::NameReply {
+                symbol: '=',
+                channel: "#rust".into(),
+                names: vec!["@alice".into(), "+bob".into(), "carol".into()],
+            }
+        );
+        assert_eq!(
+            parse(":srv 333 me #rust alice!a@host 1700000000").unwrap(),
+            Reply::TopicWhoTime {
+                channel: "#rust".into(),
+                setter: "alice!a@host".into(),
+                set_at: 1700000000,
+            }
+        );
+        assert_eq!(
+            parse(":srv 367 me #rust *!*@spam").unwrap(),
+            Reply::BanList {
+                channel: "#rust".into(),
+                mask: "*!*@spam".into(),
+                setter: None,
+                set_at: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_monitor_targets() {
+        assert_eq!(
+            parse(":srv 730 me :alice!a@host,bob").unwrap(),
+            Reply::MonOnline {
+                targets: vec![
+                    Prefix::User {
+                        nick: "alice".into(),
+                        user: Some("a".into()),
+                        host: Some("host".into()),
+                    },
+                    Prefix::User {
+                        nick: "bob".into(),
+                        user: None,
+                        host: None,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_replies() {
+        assert_eq!(
+            parse(":srv 311 me alice ~alice"),
+            Err(ReplyError::MissingParameter {
+                numeric: "311".into(),
+                name: "host",
+            })
+        );
+        assert_eq!(
+            parse(":srv 333 me #rust alice yesterday"),
+            Err(ReplyError::InvalidParameter {
+                numeric: "333".into(),
+                name: "setat",
+                value: "yesterday".into(),
+            })
+        );
+        assert!(matches!(
+            parse(":srv 353 me ? #rust :alice"),
+            Err(ReplyError::InvalidParameter { name: "symbol", .. })
+        ));
+        assert!(matches!(
+            parse(":srv 352 me #rust u h s bob H :two Bob"),
+            Err(ReplyError::InvalidParameter {
+                name: "hopcount",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(":srv 731 me :!user@host"),
+            Err(ReplyError::InvalidParameter { .. })
+        ));
+        assert_eq!(
+            parse(":srv 372 me :- motd"),
+            Err(ReplyError::Unsupported("372".into()))
+        );
+    }
+}
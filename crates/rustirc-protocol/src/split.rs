@@ -0,0 +1,497 @@
+//! Splitting long outgoing messages
+//!
+//! A relayed `PRIVMSG` or `NOTICE` may be at most 512 bytes (or the server's
+//! `LINELEN`), counting the CRLF and the `:nick!user@host` prefix the server
+//! adds. [`split_text`] breaks text into chunks that fit. It breaks on spaces
+//! where possible and never cuts a UTF-8 character or separates a formatting
+//! code from its colour digits. Formatting that is active at a break is
+//! applied again at the start of the next chunk.
+//!
+//! When `draft/multiline` is enabled, [`multiline_batch`] sends the text as a
+//! single batch instead.
+//!
+//! See: <https://ircv3.net/specs/extensions/multiline>
+
+use crate::{Command, Message, Tag};
+
+/// Longest hostname to assume in our prefix when `HOSTLEN` is not advertised
+pub const DEFAULT_HOSTLEN: usize = 63;
+
+/// Tag marking a multiline line that continues the previous one
+pub const MULTILINE_CONCAT_TAG: &str = "draft/multiline-concat";
+
+/// Batch type of a multiline message
+pub const MULTILINE_BATCH_TYPE: &str = "draft/multiline";
+
+const ACTION_START: &str = "\x01ACTION ";
+
+/// Bytes available for the text of `<command> <target> :<text>`
+///
+/// `source_len` is the length of our `nick!user@host` as the server relays it.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::split::text_budget;
+///
+/// // ":nick!user@host PRIVMSG #rust :" plus CRLF
+/// assert_eq!(text_budget(512, "nick!user@host".len(), "PRIVMSG", "#rust"), 512 - 33);
+/// ```
+pub fn text_budget(linelen: usize, source_len: usize, command: &str, target: &str) -> usize {
+    // :<source> <command> <target> :<text>\r\n
+    let overhead = 1 + source_len + 1 + command.len() + 1 + target.len() + 2 + 2;
+    linelen.saturating_sub(overhead)
+}
+
+/// Formatting active at a point in the text
+#[derive(Debug, Clone, Default)]
+struct Formatting {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    monospace: bool,
+    reverse: bool,
+    /// Foreground and background digits of `\x03`
+    color: Option<(String, Option<String>)>,
+    /// Foreground and background of `\x04`
+    hex_color: Option<(String, Option<String>)>,
+}
+
+impl Formatting {
+    /// Update the state with a formatting code or character
+    fn apply(&mut self, atom: &str) {
+        let Some(code) = atom.chars().next() else {
+            return;
+        };
+        match code {
+            '\x02' => self.bold = !self.bold,
+            '\x1d' => self.italic = !self.italic,
+            '\x1f' => self.underline = !self.underline,
+            '\x1e' => self.strikethrough = !self.strikethrough,
+            '\x11' => self.monospace = !self.monospace,
+            '\x16' => self.reverse = !self.reverse,
+            '\x0f' => *self = Self::default(),
+            '\x03' => Self::apply_color(&mut self.color, &atom[1..]),
+            '\x04' => Self::apply_color(&mut self.hex_color, &atom[1..]),
+            _ => {}
+        }
+    }
+
+    fn apply_color(color: &mut Option<(String, Option<String>)>, digits: &str) {
+        if digits.is_empty() {
+            *color = None;
+            return;
+        }
+        let (fg, bg) = match digits.split_once(',') {
+            Some((fg, bg)) => (fg, Some(bg.to_string())),
+            // A foreground alone keeps the current background
+            None => (digits, color.take().and_then(|(_, bg)| bg)),
+        };
+        *color = Some((fg.to_string(), bg));
+    }
+
+    /// Codes that turn this formatting on at the start of a line
+    fn restore(&self) -> String {
+        let mut codes = String::new();
+        for (on, code) in [
+            (self.bold, '\x02'),
+            (self.italic, '\x1d'),
+            (self.underline, '\x1f'),
+            (self.strikethrough, '\x1e'),
+            (self.monospace, '\x11'),
+            (self.reverse, '\x16'),
+        ] {
+            if on {
+                codes.push(code);
+            }
+        }
+        for (code, color) in [('\x03', &self.color), ('\x04', &self.hex_color)] {
+            if let Some((fg, bg)) = color {
+                codes.push(code);
+                codes.push_str(fg);
+                if let Some(bg) = bg {
+                    codes.push(',');
+                    codes.push_str(bg);
+                }
+            }
+        }
+        codes
+    }
+}
+
+/// Length of the formatting code at the start of `text`, or 0 if there is none
+fn format_code_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    match bytes.first() {
+        Some(0x02 | 0x0f | 0x11 | 0x16 | 0x1d | 0x1e | 0x1f) => 1,
+        Some(0x03) => 1 + color_len(&bytes[1..], |b| b.is_ascii_digit(), 1, 2),
+        Some(0x04) => 1 + color_len(&bytes[1..], |b| b.is_ascii_hexdigit(), 6, 6),
+        _ => 0,
+    }
+}
+
+/// Length of `fg[,bg]` colour digits, each `min` to `max` digits long
+fn color_len(bytes: &[u8], is_digit: fn(&u8) -> bool, min: usize, max: usize) -> usize {
+    let digits = |bytes: &[u8]| match bytes.iter().take(max).take_while(|b| is_digit(b)).count() {
+        len if len < min => 0,
+        len => len,
+    };
+    let fg = digits(bytes);
+    if fg == 0 {
+        return 0;
+    }
+    // The comma belongs to the code only when a background follows
+    match bytes.get(fg) {
+        Some(b',') => match digits(&bytes[fg + 1..]) {
+            0 => fg,
+            bg => fg + 1 + bg,
+        },
+        _ => fg,
+    }
+}
+
+/// Split text into formatting codes and single characters
+fn atoms(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let len = match format_code_len(rest) {
+            0 => first.len_utf8(),
+            len => len,
+        };
+        let (atom, tail) = rest.split_at(len);
+        rest = tail;
+        Some(atom)
+    })
+}
+
+/// Builds the chunks of one line of text
+struct LineSplitter {
+    max_bytes: usize,
+    concat: bool,
+    chunks: Vec<String>,
+    current: String,
+    formatting: Formatting,
+    /// Length of `current` up to its last visible character, or `None` when
+    /// it holds nothing but formatting
+    visible_end: Option<usize>,
+}
+
+impl LineSplitter {
+    fn fits(&self, len: usize) -> bool {
+        self.current.len() + len <= self.max_bytes
+    }
+
+    fn push(&mut self, atom: &str) {
+        self.current.push_str(atom);
+        if format_code_len(atom) == 0 {
+            self.visible_end = Some(self.current.len());
+        } else {
+            self.formatting.apply(atom);
+        }
+    }
+
+    fn break_line(&mut self) {
+        let mut chunk = std::mem::take(&mut self.current);
+        if !self.concat {
+            // Trailing codes are re-applied at the start of the next chunk
+            chunk.truncate(self.visible_end.unwrap_or(chunk.len()));
+            self.current = self.formatting.restore();
+        }
+        self.chunks.push(chunk);
+        self.visible_end = None;
+    }
+}
+
+/// Split one line of text into chunks of at most `max_bytes`
+///
+/// With `concat`, the chunks join back into the original text: the space at
+/// a break is kept and no formatting is re-applied, as lines tagged
+/// `draft/multiline-concat` continue the previous one.
+fn split_line(text: &str, max_bytes: usize, concat: bool) -> Vec<String> {
+    let mut splitter = LineSplitter {
+        max_bytes,
+        concat,
+        chunks: Vec::new(),
+        current: String::new(),
+        formatting: Formatting::default(),
+        visible_end: None,
+    };
+
+    for (index, word) in text.split(' ').enumerate() {
+        let mut space = index > 0;
+
+        if !splitter.fits(usize::from(space) + word.len()) && splitter.visible_end.is_some() {
+            if space && (!concat || splitter.fits(1)) {
+                // The space at the break ends this chunk, or is dropped
+                if concat {
+                    splitter.push(" ");
+                }
+                space = false;
+            }
+            splitter.break_line();
+        }
+        if space {
+            splitter.push(" ");
+        }
+
+        if splitter.fits(word.len()) {
+            atoms(word).for_each(|atom| splitter.push(atom));
+            continue;
+        }
+
+        // The word does not fit on a line of its own: break it between characters
+        for atom in atoms(word) {
+            if !splitter.fits(atom.len()) && splitter.visible_end.is_some() {
+                splitter.break_line();
+            }
+            splitter.push(atom);
+        }
+    }
+
+    if splitter.visible_end.is_some() || splitter.chunks.is_empty() {
+        splitter.break_line();
+    }
+    splitter.chunks
+}
+
+/// Split message text into chunks of at most `max_bytes`
+///
+/// Line breaks always start a new chunk, and empty lines are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::split::split_text;
+///
+/// let chunks = split_text("\x02bold words here", 11);
+/// assert_eq!(chunks, ["\x02bold words", "\x02here"]);
+/// ```
+pub fn split_text(text: &str, max_bytes: usize) -> Vec<String> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .flat_map(|line| split_line(line, max_bytes, false))
+        .collect()
+}
+
+/// Split a `PRIVMSG` or `NOTICE` into commands that each fit in one line
+///
+/// CTCP `ACTION`s are split inside the CTCP framing. Other commands, and
+/// other CTCP messages, are returned unchanged.
+pub fn split_command(command: &Command, source_len: usize, linelen: usize) -> Vec<Command> {
+    let (name, target, text) = match command {
+        Command::PrivMsg { target, text } => ("PRIVMSG", target, text),
+        Command::Notice { target, text } => ("NOTICE", target, text),
+        _ => return vec![command.clone()],
+    };
+    let budget = text_budget(linelen, source_len, name, target);
+
+    let action = text
+        .strip_prefix(ACTION_START)
+        .map(|action| action.strip_suffix('\x01').unwrap_or(action));
+    let chunks = match action {
+        Some(action) => split_text(action, budget.saturating_sub(ACTION_START.len() + 1))
+            .into_iter()
+            .map(|chunk| format!("{ACTION_START}{chunk}\x01"))
+            .collect(),
+        None if text.starts_with('\x01') => vec![text.clone()],
+        None => split_text(text, budget),
+    };
+
+    chunks
+        .into_iter()
+        .map(|text| match command {
+            Command::Notice { .. } => Command::Notice {
+                target: target.clone(),
+                text,
+            },
+            _ => Command::PrivMsg {
+                target: target.clone(),
+                text,
+            },
+        })
+        .collect()
+}
+
+/// Limits advertised with the `draft/multiline` capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultilineLimits {
+    /// Maximum bytes of message text in one batch
+    pub max_bytes: usize,
+    /// Maximum lines in one batch
+    pub max_lines: Option<usize>,
+}
+
+impl MultilineLimits {
+    /// Parse a capability value such as `max-bytes=4096,max-lines=24`
+    ///
+    /// `max-bytes` is mandatory.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut max_bytes = None;
+        let mut max_lines = None;
+        for (key, value) in value.split(',').filter_map(|pair| pair.split_once('=')) {
+            match key {
+                "max-bytes" => max_bytes = value.parse().ok(),
+                "max-lines" => max_lines = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            max_bytes: max_bytes?,
+            max_lines,
+        })
+    }
+}
+
+/// Build a `draft/multiline` batch sending `text` to `target`
+///
+/// Each line of the text becomes one message; lines longer than
+/// `line_budget` continue on messages tagged `draft/multiline-concat`.
+/// Returns `None` when the text exceeds `limits`; split it with
+/// [`split_text`] instead.
+///
+/// # Examples
+///
+/// ```
+/// use rustirc_protocol::split::{multiline_batch, MultilineLimits};
+///
+/// let limits = MultilineLimits::parse("max-bytes=4096").unwrap();
+/// let batch = multiline_batch("PRIVMSG", "#rust", "hello\nworld", "ml1", 400, &limits).unwrap();
+/// let lines: Vec<String> = batch.iter().map(ToString::to_string).collect();
+/// assert_eq!(
+///     lines,
+///     [
+///         "BATCH +ml1 draft/multiline #rust",
+///         "@batch=ml1 PRIVMSG #rust hello",
+///         "@batch=ml1 PRIVMSG #rust world",
+///         "BATCH -ml1",
+///     ]
+/// );
+/// ```
+pub fn multiline_batch(
+    command: &str,
+    target: &str,
+    text: &str,
+    reference: &str,
+    line_budget: usize,
+    limits: &MultilineLimits,
+) -> Option<Vec<Message>> {
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+
+    // Line breaks count towards the byte limit
+    let total_bytes = lines.iter().map(|line| line.len()).sum::<usize>() + lines.len() - 1;
+    if total_bytes > limits.max_bytes || line_budget == 0 {
+        return None;
+    }
+
+    let mut messages = vec![Message::new("BATCH")
+        .add_param(format!("+{reference}"))
+        .add_param(MULTILINE_BATCH_TYPE)
+        .add_param(target)];
+
+    for line in lines {
+        for (index, chunk) in split_line(line, line_budget, true).into_iter().enumerate() {
+            let mut tags = vec![Tag::new("batch", Some(reference))];
+            if index > 0 {
+                tags.push(Tag::new(MULTILINE_CONCAT_TAG, None::<&str>));
+            }
+            messages.push(
+                Message::new(command)
+                    .with_tags(tags)
+                    .add_param(target)
+                    .add_param(chunk),
+            );
+        }
+    }
+
+    // BATCH +, the lines and BATCH -
+    if limits.max_lines.is_some_and(|max| messages.len() - 1 > max) {
+        return None;
+    }
+
+    messages.push(Message::new("BATCH").add_param(format!("-{reference}")));
+    Some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_on_word_boundaries() {
+        let chunks = split_text("the quick brown fox jumps", 10);
+        assert_eq!(chunks, ["the quick", "brown fox", "jumps"]);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 10));
+
+        // Long words are broken between characters, never inside one
+        let chunks = split_text("ééééé", 4);
+        assert_eq!(chunks, ["éé", "éé", "é"]);
+
+        assert_eq!(split_text("one\r\n\ntwo", 100), ["one", "two"]);
+    }
+
+    #[test]
+    fn test_formatting_is_kept_whole_and_restored() {
+        // The colour code and its digits stay together
+        let chunks = split_text("ab\x0304,12cd", 8);
+        assert_eq!(chunks, ["ab", "\x0304,12cd"]);
+
+        let chunks = split_text("\x02\x0304bold red\x0f plain", 12);
+        // Codes at a break are dropped, as formatting ends with the line anyway
+        assert_eq!(chunks, ["\x02\x0304bold", "\x02\x0304red", "plain"]);
+
+        // A foreground change keeps the background
+        let chunks = split_text("\x033,5a \x037b c", 8);
+        assert_eq!(chunks, ["\x033,5a", "\x033,5\x037b", "\x037,5c"]);
+
+        // A hex colour needs all six digits, so short ones are not restored
+        let chunks = split_text("\x04ABCD wxyz", 7);
+        assert_eq!(chunks, ["\x04ABCD", "wxyz"]);
+    }
+
+    #[test]
+    fn test_split_command_fits_line() {
+        let source = "nick!~user@very.long.hostname.example.com";
+        let text = "word ".repeat(200);
+        let command = Command::PrivMsg {
+            target: "#rust".to_string(),
+            text: format!("{ACTION_START}{text}\x01"),
+        };
+
+        let commands = split_command(&command, source.len(), 512);
+        assert!(commands.len() > 1);
+        for command in &commands {
+            let line = format!(":{source} {}\r\n", command.to_message());
+            assert!(line.len() <= 512, "{} bytes", line.len());
+            let Command::PrivMsg { text, .. } = command else {
+                panic!("expected PRIVMSG");
+            };
+            assert!(text.starts_with(ACTION_START) && text.ends_with('\x01'));
+        }
+    }
+
+    #[test]
+    fn test_multiline_concat_and_limits() {
+        let limits = MultilineLimits::parse("max-bytes=100,max-lines=3").unwrap();
+        let batch = multiline_batch("PRIVMSG", "#rust", "aaaa bbbb", "x", 6, &limits).unwrap();
+        let lines: Vec<String> = batch.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "BATCH +x draft/multiline #rust",
+                "@batch=x PRIVMSG #rust :aaaa ",
+                "@batch=x;draft/multiline-concat PRIVMSG #rust bbbb",
+                "BATCH -x",
+            ]
+        );
+
+        assert!(multiline_batch("PRIVMSG", "#rust", "a\nb\nc\nd", "x", 6, &limits).is_none());
+        assert!(multiline_batch("PRIVMSG", "#rust", &"a".repeat(101), "x", 400, &limits).is_none());
+        assert_eq!(MultilineLimits::parse("max-lines=5"), None);
+    }
+}
@@ -5,6 +5,8 @@
 
 use thiserror::Error;
 
+use crate::isupport::Isupport;
+
 /// Validation error types
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum ValidationError {
@@ -32,6 +34,12 @@ pub enum ValidationError {
     #[error("Empty parameter not allowed")]
     EmptyParameter,
 
+    #[error("Missing parameter: {0}")]
+    MissingParameter(String),
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
     #[error("Invalid tag key: {0}")]
     InvalidTagKey(String),
 
@@ -50,6 +58,8 @@ pub struct IrcValidator {
     pub max_channel_length: usize,
     /// Maximum parameter length
     pub max_parameter_length: usize,
+    /// Characters that may start a channel name
+    pub channel_types: String,
     /// Strict mode (RFC compliance)
     pub strict_mode: bool,
 }
@@ -77,10 +87,45 @@ impl IrcValidator {
             max_nickname_length: 30,   // Most servers use 30
             max_channel_length: 50,    // Reasonable default
             max_parameter_length: 400, // Leave room for protocol overhead
-            strict_mode: false,        // Be lenient by default
+            channel_types: "#&".to_string(),
+            strict_mode: false, // Be lenient by default
         }
     }
 
+    /// Create a validator using the limits advertised by the server
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rustirc_protocol::isupport::Isupport;
+    /// use rustirc_protocol::validation::IrcValidator;
+    ///
+    /// let mut isupport = Isupport::default();
+    /// isupport.apply_tokens(["NICKLEN=9", "CHANTYPES=#"]);
+    ///
+    /// let validator = IrcValidator::from_isupport(&isupport);
+    /// assert!(validator.validate_nickname("verylongname").is_err());
+    /// assert!(validator.validate_channel_name("&local").is_err());
+    /// ```
+    pub fn from_isupport(isupport: &Isupport) -> Self {
+        let mut validator = Self::new();
+        validator.apply_isupport(isupport);
+        validator
+    }
+
+    /// Update the limits from the server's RPL_ISUPPORT tokens
+    ///
+    /// Limits the server does not advertise keep their current values.
+    pub fn apply_isupport(&mut self, isupport: &Isupport) {
+        if let Some(nicklen) = isupport.nicklen {
+            self.max_nickname_length = nicklen;
+        }
+        if let Some(channellen) = isupport.channellen {
+            self.max_channel_length = channellen;
+        }
+        self.channel_types = isupport.chantypes.clone();
+    }
+
     /// Create a strict RFC-compliant validator
     ///
     /// Uses RFC 1459 limits and strict validation rules.
@@ -99,6 +144,7 @@ impl IrcValidator {
             max_nickname_length: 9, // RFC 1459 limit
             max_channel_length: 50,
             max_parameter_length: 400,
+            channel_types: "#&".to_string(),
             strict_mode: true,
         }
     }
@@ -178,7 +224,8 @@ impl IrcValidator {
 
     /// Validate an IRC channel name
     ///
-    /// Channel names must start with # or & and contain only valid characters.
+    /// Channel names must start with one of the channel types (`#` or `&` unless
+    /// the server advertises other `CHANTYPES`) and contain only valid characters.
     ///
     /// # Arguments
     ///
@@ -214,11 +261,12 @@ impl IrcValidator {
             )));
         }
 
-        // Must start with # or &
-        if !channel.starts_with('#') && !channel.starts_with('&') {
-            return Err(ValidationError::InvalidChannelName(
-                "Must start with # or &".to_string(),
-            ));
+        // Must start with a channel type character
+        if !channel.starts_with(|c| self.channel_types.contains(c)) {
+            return Err(ValidationError::InvalidChannelName(format!(
+                "Must start with one of: {}",
+                self.channel_types
+            )));
         }
 
         // Check for forbidden characters
@@ -650,6 +698,19 @@ mod tests {
         assert!(strict_validator.validate_nickname("tencharsss").is_err());
     }
 
+    #[test]
+    fn test_isupport_limits() {
+        let mut isupport = Isupport::default();
+        isupport.apply_tokens(["NICKLEN=12", "CHANNELLEN=10", "CHANTYPES=#!"]);
+        let validator = IrcValidator::from_isupport(&isupport);
+
+        assert!(validator.validate_nickname("twelve_chars").is_ok());
+        assert!(validator.validate_nickname("thirteen_char").is_err());
+        assert!(validator.validate_channel_name("!ABCDEchan").is_ok());
+        assert!(validator.validate_channel_name("#elevenchars").is_err());
+        assert!(validator.validate_channel_name("&local").is_err());
+    }
+
     #[test]
     fn test_quick_validation_functions() {
         assert!(is_valid_nickname("alice"));
@@ -136,7 +136,7 @@ impl ScriptApi {
             state
                 .servers
                 .values()
-                .flat_map(|s| s.channels.keys().cloned())
+                .flat_map(|s| s.channels.keys().map(|name| name.to_string()))
                 .collect()
         } else {
             vec![]
@@ -148,7 +148,7 @@ impl ScriptApi {
         if let Some(state) = &self.state {
             let state = state.read().await;
             for server in state.servers.values() {
-                if let Some(ch) = server.channels.get(channel) {
+                if let Some(ch) = server.channel(channel) {
                     return ch.users.values().map(|u| u.nick.clone()).collect();
                 }
             }
@@ -667,11 +667,10 @@ impl TuiApp {
                 Ok(rustirc_protocol::Command::Quit { message })
             }
             _ => {
-                // For unknown commands, use Raw command with full implementation
-                Ok(rustirc_protocol::Command::Raw {
-                    command: cmd_name,
-                    params: parts[1..].iter().map(|s| s.to_string()).collect(),
-                })
+                // Other commands are typed when known and sent raw otherwise
+                let mut message = rustirc_protocol::Message::new(cmd_name);
+                message.params = parts[1..].iter().map(|s| s.to_string()).collect();
+                Ok(rustirc_protocol::Command::from_message(&message)?)
             }
         }
     }
@@ -6,6 +6,8 @@
 use crate::state::TuiState;
 use async_trait::async_trait;
 use rustirc_core::events::{Event, EventHandler};
+use rustirc_core::tls::{CertificateStatus, KnownHosts};
+use rustirc_protocol::CaseMapping;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
@@ -31,7 +33,9 @@ impl EventHandler for TuiEventHandler {
         match event {
             Event::Connected { connection_id } => {
                 info!("TUI: Connection established: {}", connection_id);
-                state.add_server(connection_id.clone());
+                if !state.servers.contains_key(connection_id) {
+                    state.add_server(connection_id.clone());
+                }
             }
 
             Event::Disconnected {
@@ -160,6 +164,18 @@ impl EventHandler for TuiEventHandler {
                             }
                         }
                     }
+                    "005" => {
+                        let casemapping = message
+                            .params
+                            .iter()
+                            .find_map(|param| param.strip_prefix("CASEMAPPING="))
+                            .and_then(CaseMapping::parse);
+                        if let (Some(casemapping), Some(server)) =
+                            (casemapping, state.servers.get_mut(connection_id))
+                        {
+                            server.set_casemapping(casemapping);
+                        }
+                    }
                     _ => {
                         debug!("TUI: Unhandled IRC message command: {}", message.command);
                         warn!(
@@ -213,7 +229,11 @@ impl EventHandler for TuiEventHandler {
                     "TUI: Nick changed from {} to {} on {}",
                     old, new, connection_id
                 );
-                // Nick change handling could be added here
+                if let Some(server) = state.servers.get_mut(connection_id) {
+                    if server.casemapping.equals(&server.nickname, old) {
+                        server.nickname = new.clone();
+                    }
+                }
             }
 
             Event::TopicChanged {
@@ -243,6 +263,19 @@ impl EventHandler for TuiEventHandler {
                 }
             }
 
+            Event::Registered {
+                connection_id,
+                nickname,
+            } => {
+                info!("TUI: Registered on {} as {}", connection_id, nickname);
+                if !state.servers.contains_key(connection_id) {
+                    state.add_server(connection_id.clone());
+                }
+                if let Some(server) = state.servers.get_mut(connection_id) {
+                    server.nickname = nickname.clone();
+                }
+            }
+
             Event::StateChanged {
                 connection_id,
                 state: _state,
@@ -251,6 +284,37 @@ impl EventHandler for TuiEventHandler {
                 // Connection state changes could be reflected in the UI
             }
 
+            Event::EndpointChanged {
+                connection_id,
+                endpoint,
+            } => {
+                debug!("TUI: {} connecting via {}", connection_id, endpoint);
+                state
+                    .endpoints
+                    .insert(connection_id.clone(), endpoint.clone());
+            }
+
+            Event::HistoryGap {
+                connection_id,
+                target,
+                since,
+            } => {
+                debug!("TUI: History gap in {} on {}", target, connection_id);
+                state.add_history_gap(connection_id, target, since.as_deref());
+            }
+
+            Event::SendQueueChanged {
+                connection_id,
+                pending,
+            } => {
+                debug!("TUI: {} has {} lines pending", connection_id, pending);
+                if *pending > 0 {
+                    state.pending_lines.insert(connection_id.clone(), *pending);
+                } else {
+                    state.pending_lines.remove(connection_id);
+                }
+            }
+
             Event::MessageSent {
                 connection_id,
                 message,
@@ -269,6 +333,59 @@ impl EventHandler for TuiEventHandler {
                 );
                 // PONG responses are handled by the core connection manager
             }
+
+            Event::CapabilitiesChanged {
+                connection_id,
+                capabilities,
+            } => {
+                debug!(
+                    "TUI: Capabilities enabled on {}: {}",
+                    connection_id,
+                    capabilities.join(" ")
+                );
+            }
+
+            Event::ServerCertificate {
+                connection_id,
+                host,
+                certificate,
+            } => {
+                if certificate.status == CertificateStatus::Unverified {
+                    state.unverified_tls.insert(connection_id.clone());
+                } else {
+                    state.unverified_tls.remove(connection_id);
+                }
+
+                let notice = match &certificate.status {
+                    CertificateStatus::Changed { expected } => Some(format!(
+                        "WARNING: certificate for {host} has CHANGED (expected {expected}, \
+                         got {}). Connection refused; if the change is legitimate, remove \
+                         {host} from {}",
+                        certificate.fingerprint,
+                        KnownHosts::default_path().display()
+                    )),
+                    CertificateStatus::TrustedOnFirstUse => Some(format!(
+                        "Trusting certificate for {host} on first use: {}",
+                        certificate.fingerprint
+                    )),
+                    CertificateStatus::Unverified => {
+                        Some(format!("Certificate verification is disabled for {host}"))
+                    }
+                    CertificateStatus::Verified | CertificateStatus::Pinned => None,
+                };
+
+                if let Some(notice) = notice {
+                    warn!("TUI: {}", notice);
+                    if let Some(current_channel) = state.current_channel().cloned() {
+                        state.add_message(
+                            connection_id.clone(),
+                            current_channel,
+                            "*".to_string(),
+                            notice,
+                        );
+                    }
+                }
+            }
         }
     }
 
@@ -1,7 +1,7 @@
 //! IRC message formatting for TUI
 //!
 //! Provides IRC text formatting support for the terminal interface including:
-//! - mIRC color codes parsing
+//! - mIRC and hex color codes, parsed by `rustirc_protocol::formatting`
 //! - Text formatting (bold, italic, underline)
 //! - URL detection
 //! - Conversion to ratatui Spans
@@ -11,19 +11,11 @@ use ratatui::{
     text::{Line, Span},
 };
 use regex::Regex;
+use rustirc_protocol::formatting::{parse, strip, IrcColor};
 use std::sync::OnceLock;
 
 /// IRC formatting codes
-pub mod codes {
-    pub const BOLD: char = '\x02';
-    pub const ITALIC: char = '\x1D';
-    pub const UNDERLINE: char = '\x1F';
-    pub const STRIKETHROUGH: char = '\x1E';
-    pub const MONOSPACE: char = '\x11';
-    pub const REVERSE: char = '\x16';
-    pub const COLOR: char = '\x03';
-    pub const RESET: char = '\x0F';
-}
+pub use rustirc_protocol::formatting::codes;
 
 /// IRC color palette (mIRC colors 0-15)
 pub const IRC_COLORS: [Color; 16] = [
@@ -68,88 +60,38 @@ fn get_url_regex() -> &'static Regex {
         .get_or_init(|| Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>"]*[^\s<>".,;:!?]"#).unwrap())
 }
 
-/// Parse IRC formatted text into styled spans
-pub fn parse_irc_text(text: &str) -> Vec<FormattedSpan> {
-    let mut spans = Vec::new();
-    let mut current_span = FormattedSpan::default();
-    let mut chars = text.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            codes::BOLD => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.bold = !current_span.bold;
-            }
-            codes::ITALIC => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.italic = !current_span.italic;
-            }
-            codes::UNDERLINE => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.underline = !current_span.underline;
-            }
-            codes::STRIKETHROUGH => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.strikethrough = !current_span.strikethrough;
-            }
-            codes::MONOSPACE => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.monospace = !current_span.monospace;
-            }
-            codes::REVERSE => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-                current_span.reverse = !current_span.reverse;
-            }
-            codes::COLOR => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                    current_span.text.clear();
-                }
-
-                // Parse color codes
-                let (fg, bg) = parse_color_codes(&mut chars);
-                if let Some(fg_color) = fg {
-                    current_span.foreground =
-                        Some(IRC_COLORS.get(fg_color).copied().unwrap_or(Color::White));
-                }
-                if let Some(bg_color) = bg {
-                    current_span.background =
-                        Some(IRC_COLORS.get(bg_color).copied().unwrap_or(Color::Black));
-                }
-            }
-            codes::RESET => {
-                if !current_span.text.is_empty() {
-                    spans.push(current_span.clone());
-                }
-                current_span = FormattedSpan::default();
-            }
-            _ => {
-                current_span.text.push(ch);
-            }
+/// Map an IRC color onto the terminal palette
+///
+/// Colors 0-15 use [`IRC_COLORS`]; extended and hex colors use their RGB value.
+pub fn irc_color(color: IrcColor) -> Color {
+    match color {
+        IrcColor::Palette(index) if usize::from(index) < IRC_COLORS.len() => {
+            IRC_COLORS[usize::from(index)]
+        }
+        color => {
+            let (r, g, b) = color.rgb();
+            Color::Rgb(r, g, b)
         }
     }
+}
 
-    if !current_span.text.is_empty() {
-        spans.push(current_span);
-    }
+/// Parse IRC formatted text into styled spans
+pub fn parse_irc_text(text: &str) -> Vec<FormattedSpan> {
+    let mut spans = parse(text)
+        .into_iter()
+        .map(|span| FormattedSpan {
+            text: span.text,
+            foreground: span.style.foreground.map(irc_color),
+            background: span.style.background.map(irc_color),
+            bold: span.style.bold,
+            italic: span.style.italic,
+            underline: span.style.underline,
+            strikethrough: span.style.strikethrough,
+            monospace: span.style.monospace,
+            reverse: span.style.reverse,
+            is_url: false,
+        })
+        .collect();
 
     // Post-process for URL detection
     detect_urls(&mut spans);
@@ -157,59 +99,6 @@ pub fn parse_irc_text(text: &str) -> Vec<FormattedSpan> {
     spans
 }
 
-/// Parse color codes following the IRC color format
-fn parse_color_codes(
-    chars: &mut std::iter::Peekable<std::str::Chars>,
-) -> (Option<usize>, Option<usize>) {
-    let mut fg_str = String::new();
-    let mut bg_str = String::new();
-
-    // Parse foreground color (up to 2 digits)
-    for _ in 0..2 {
-        if let Some(&ch) = chars.peek() {
-            if ch.is_ascii_digit() {
-                fg_str.push(chars.next().unwrap());
-            } else {
-                break;
-            }
-        } else {
-            break;
-        }
-    }
-
-    // Check for comma (background color separator)
-    if let Some(&',') = chars.peek() {
-        chars.next(); // consume comma
-
-        // Parse background color (up to 2 digits)
-        for _ in 0..2 {
-            if let Some(&ch) = chars.peek() {
-                if ch.is_ascii_digit() {
-                    bg_str.push(chars.next().unwrap());
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
-    }
-
-    let fg = if fg_str.is_empty() {
-        None
-    } else {
-        fg_str.parse().ok().filter(|&n| n < 16)
-    };
-
-    let bg = if bg_str.is_empty() {
-        None
-    } else {
-        bg_str.parse().ok().filter(|&n| n < 16)
-    };
-
-    (fg, bg)
-}
-
 /// Detect URLs in text spans and mark them
 fn detect_urls(spans: &mut Vec<FormattedSpan>) {
     let regex = get_url_regex();
@@ -307,31 +196,7 @@ pub fn spans_to_line(spans: &[FormattedSpan]) -> Line<'_> {
 
 /// Strip all IRC formatting from text
 pub fn strip_formatting(text: &str) -> String {
-    let mut result = String::new();
-    let mut chars = text.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            codes::COLOR => {
-                // Skip color codes
-                parse_color_codes(&mut chars);
-            }
-            codes::BOLD
-            | codes::ITALIC
-            | codes::UNDERLINE
-            | codes::STRIKETHROUGH
-            | codes::MONOSPACE
-            | codes::REVERSE
-            | codes::RESET => {
-                // Skip formatting codes
-            }
-            _ => {
-                result.push(ch);
-            }
-        }
-    }
-
-    result
+    strip(text)
 }
 
 /// Get plain text from formatted spans
@@ -313,6 +313,11 @@ impl InputHandler {
                 state.set_focus(FocusArea::MessageArea);
             }
 
+            // Alt+Enter starts a new line of a multi-line message
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => {
+                state.insert_char('\n');
+            }
+
             // Submit input
             KeyCode::Enter => {
                 let command = state.submit_input();
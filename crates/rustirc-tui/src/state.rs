@@ -6,7 +6,8 @@
 //! - Message history and scrolling
 //! - Input buffer and command history
 
-use std::collections::{HashMap, VecDeque};
+use rustirc_protocol::{CaseMapping, IrcString};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Maximum number of messages to keep per channel
@@ -97,10 +98,12 @@ impl ChannelState {
 #[derive(Debug, Clone)]
 pub struct ServerState {
     pub name: String,
-    pub channels: HashMap<String, ChannelState>,
+    pub channels: HashMap<IrcString, ChannelState>,
     pub nickname: String,
     pub connected: bool,
     pub current_channel: Option<String>,
+    /// Server CASEMAPPING used to match channel names and nicknames
+    pub casemapping: CaseMapping,
 }
 
 impl ServerState {
@@ -111,12 +114,46 @@ impl ServerState {
             nickname: "RustIRC".to_string(),
             connected: false,
             current_channel: None,
+            casemapping: CaseMapping::default(),
         }
     }
 
+    /// Build a lookup key for a channel or nickname
+    pub fn key(&self, name: &str) -> IrcString {
+        IrcString::new(name, self.casemapping)
+    }
+
+    pub fn channel(&self, channel_name: &str) -> Option<&ChannelState> {
+        self.channels.get(&self.key(channel_name))
+    }
+
+    pub fn channel_mut(&mut self, channel_name: &str) -> Option<&mut ChannelState> {
+        let key = self.key(channel_name);
+        self.channels.get_mut(&key)
+    }
+
+    /// Check whether a name refers to the current channel
+    pub fn is_current_channel(&self, channel_name: &str) -> bool {
+        self.current_channel
+            .as_deref()
+            .is_some_and(|current| self.casemapping.equals(current, channel_name))
+    }
+
+    /// Re-key channels after the server announces its CASEMAPPING
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+        self.channels = self
+            .channels
+            .drain()
+            .map(|(name, channel)| (name.with_casemapping(casemapping), channel))
+            .collect();
+    }
+
     pub fn add_channel(&mut self, channel_name: String) {
-        let channel = ChannelState::new(channel_name.clone());
-        self.channels.insert(channel_name.clone(), channel);
+        let key = self.key(&channel_name);
+        self.channels
+            .entry(key)
+            .or_insert_with(|| ChannelState::new(channel_name.clone()));
 
         // Switch to this channel if it's the first one
         if self.current_channel.is_none() {
@@ -125,11 +162,12 @@ impl ServerState {
     }
 
     pub fn remove_channel(&mut self, channel_name: &str) {
-        self.channels.remove(channel_name);
+        let key = self.key(channel_name);
+        self.channels.remove(&key);
 
         // Switch to another channel if current was removed
-        if self.current_channel.as_ref() == Some(&channel_name.to_string()) {
-            self.current_channel = self.channels.keys().next().cloned();
+        if self.is_current_channel(channel_name) {
+            self.current_channel = self.channels.values().next().map(|c| c.name.clone());
         }
     }
 }
@@ -246,10 +284,10 @@ impl TuiTab {
         }
     }
 
-    pub fn channel(server_id: String, channel: String) -> Self {
+    pub fn channel(server_id: String, channel: IrcString) -> Self {
         Self {
-            id: format!("{server_id}:{channel}"),
-            name: channel,
+            id: format!("{server_id}:{}", channel.folded()),
+            name: channel.into_string(),
             tab_type: TuiTabType::Channel,
             server_id: Some(server_id),
             messages: VecDeque::new(),
@@ -259,10 +297,10 @@ impl TuiTab {
         }
     }
 
-    pub fn private_message(server_id: String, nick: String) -> Self {
+    pub fn private_message(server_id: String, nick: IrcString) -> Self {
         Self {
-            id: format!("{server_id}:pm:{nick}"),
-            name: nick,
+            id: format!("{server_id}:pm:{}", nick.folded()),
+            name: nick.into_string(),
             tab_type: TuiTabType::PrivateMessage,
             server_id: Some(server_id),
             messages: VecDeque::new(),
@@ -320,6 +358,15 @@ pub struct TuiState {
 
     /// UI state
     pub ui_state: TuiUiState,
+
+    /// Servers connected with certificate verification disabled
+    pub unverified_tls: HashSet<String>,
+
+    /// Endpoint each server is connected through, as `host:port`
+    pub endpoints: HashMap<String, String>,
+
+    /// Outgoing lines each server holds back for flood protection
+    pub pending_lines: HashMap<String, usize>,
 }
 
 impl TuiState {
@@ -340,6 +387,9 @@ impl TuiState {
             start_time: SystemTime::now(),
             settings: TuiSettings::default(),
             ui_state: TuiUiState::default(),
+            unverified_tls: HashSet::new(),
+            endpoints: HashMap::new(),
+            pending_lines: HashMap::new(),
         }
     }
 
@@ -379,12 +429,40 @@ impl TuiState {
         }
     }
 
+    /// Build a channel or nickname key with the server's CASEMAPPING
+    fn name_key(&self, server_name: &str, name: &str) -> IrcString {
+        let casemapping = self
+            .servers
+            .get(server_name)
+            .map(|server| server.casemapping)
+            .unwrap_or_default();
+        IrcString::new(name, casemapping)
+    }
+
+    /// Tab id of a channel, matching the channel name case-insensitively
+    pub fn channel_tab_id(&self, server_name: &str, channel: &str) -> String {
+        format!(
+            "{server_name}:{}",
+            self.name_key(server_name, channel).folded()
+        )
+    }
+
+    /// Tab id of a private conversation, matching the nickname case-insensitively
+    pub fn private_message_tab_id(&self, server_name: &str, nick: &str) -> String {
+        format!(
+            "{server_name}:pm:{}",
+            self.name_key(server_name, nick).folded()
+        )
+    }
+
     /// Add a channel tab
     pub fn add_channel_tab(&mut self, server_name: String, channel: String) {
-        let tab = TuiTab::channel(server_name.clone(), channel.clone());
-        let tab_id = format!("{server_name}:{channel}");
-        self.tabs.insert(tab_id.clone(), tab);
-        self.tab_order.push(tab_id.clone());
+        let tab = TuiTab::channel(server_name.clone(), self.name_key(&server_name, &channel));
+        let tab_id = tab.id.clone();
+        if !self.tabs.contains_key(&tab_id) {
+            self.tabs.insert(tab_id.clone(), tab);
+            self.tab_order.push(tab_id.clone());
+        }
 
         // Set as current tab
         self.current_tab_id = Some(tab_id);
@@ -397,10 +475,12 @@ impl TuiState {
 
     /// Add a private message tab
     pub fn add_private_message_tab(&mut self, server_name: String, nick: String) {
-        let tab = TuiTab::private_message(server_name.clone(), nick.clone());
-        let tab_id = format!("{server_name}:pm:{nick}");
-        self.tabs.insert(tab_id.clone(), tab);
-        self.tab_order.push(tab_id.clone());
+        let tab = TuiTab::private_message(server_name.clone(), self.name_key(&server_name, &nick));
+        let tab_id = tab.id.clone();
+        if !self.tabs.contains_key(&tab_id) {
+            self.tabs.insert(tab_id.clone(), tab);
+            self.tab_order.push(tab_id.clone());
+        }
 
         // Set as current tab
         self.current_tab_id = Some(tab_id);
@@ -446,6 +526,9 @@ impl TuiState {
     /// Remove a server
     pub fn remove_server(&mut self, server_name: &str) {
         self.servers.remove(server_name);
+        self.unverified_tls.remove(server_name);
+        self.endpoints.remove(server_name);
+        self.pending_lines.remove(server_name);
 
         // Switch to another server if current was removed
         if self.current_server.as_ref() == Some(&server_name.to_string()) {
@@ -476,7 +559,7 @@ impl TuiState {
         content: String,
     ) {
         if let Some(server) = self.servers.get_mut(&server_name) {
-            if let Some(channel) = server.channels.get_mut(&channel_name) {
+            if let Some(channel) = server.channel_mut(&channel_name) {
                 let message = TuiMessage {
                     nick,
                     content,
@@ -490,6 +573,29 @@ impl TuiState {
         }
     }
 
+    /// Mark where a buffer may be missing messages from while disconnected
+    pub fn add_history_gap(&mut self, server_name: &str, target: &str, since: Option<&str>) {
+        let Some(channel) = self
+            .servers
+            .get_mut(server_name)
+            .and_then(|server| server.channel_mut(target))
+        else {
+            return;
+        };
+        let content = match since {
+            Some(since) => format!("--- Reconnected; messages since {since} may be missing ---"),
+            None => "--- Reconnected; messages may be missing ---".to_string(),
+        };
+        channel.add_message(TuiMessage {
+            nick: "*".to_string(),
+            content,
+            timestamp: SystemTime::now(),
+            is_own_message: false,
+            is_highlight: false,
+            message_type: MessageType::System,
+        });
+    }
+
     /// Get current server
     pub fn current_server(&self) -> Option<&String> {
         self.current_server.as_ref()
@@ -511,7 +617,7 @@ impl TuiState {
             (&self.current_server, self.current_channel())
         {
             if let Some(server) = self.servers.get(server_name) {
-                return server.channels.get(channel_name);
+                return server.channel(channel_name);
             }
         }
         None
@@ -522,7 +628,7 @@ impl TuiState {
         if let Some(server_name) = self.current_server.clone() {
             if let Some(server) = self.servers.get_mut(&server_name) {
                 if let Some(channel_name) = server.current_channel.clone() {
-                    return server.channels.get_mut(&channel_name);
+                    return server.channel_mut(&channel_name);
                 }
             }
         }
@@ -532,14 +638,13 @@ impl TuiState {
     /// Switch to a different channel
     pub fn switch_to_channel(&mut self, server_name: &str, channel_name: &str) {
         if let Some(server) = self.servers.get_mut(server_name) {
-            if server.channels.contains_key(channel_name) {
-                server.current_channel = Some(channel_name.to_string());
-                self.current_server = Some(server_name.to_string());
-
+            if let Some(channel) = server.channel_mut(channel_name) {
                 // Mark channel as read
-                if let Some(channel) = server.channels.get_mut(channel_name) {
-                    channel.mark_as_read();
-                }
+                channel.mark_as_read();
+                let name = channel.name.clone();
+
+                server.current_channel = Some(name);
+                self.current_server = Some(server_name.to_string());
             }
         }
     }
@@ -548,7 +653,11 @@ impl TuiState {
     pub fn current_server_channels(&self) -> Vec<&String> {
         if let Some(server_name) = &self.current_server {
             if let Some(server) = self.servers.get(server_name) {
-                return server.channels.keys().collect();
+                return server
+                    .channels
+                    .values()
+                    .map(|channel| &channel.name)
+                    .collect();
             }
         }
         Vec::new()
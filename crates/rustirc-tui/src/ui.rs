@@ -22,6 +22,9 @@ use ratatui::{
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
+/// Lines of a multi-line message shown in the input area before it scrolls
+const MAX_INPUT_LINES: usize = 5;
+
 /// TUI renderer
 pub struct TuiRenderer {
     theme_manager: ThemeManager,
@@ -242,12 +245,13 @@ impl TuiRenderer {
             return;
         }
 
+        let input_lines = state.input_buffer.split('\n').count().min(MAX_INPUT_LINES);
         let main_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(1),    // Main content
-                Constraint::Length(3), // Input area
-                Constraint::Length(1), // Status line
+                Constraint::Min(1),                         // Main content
+                Constraint::Length(2 + input_lines as u16), // Input area
+                Constraint::Length(1),                      // Status line
             ])
             .split(frame.area());
 
@@ -312,7 +316,7 @@ impl TuiRenderer {
             // Channels
             for (channel_name, channel) in &server.channels {
                 let is_current = Some(server_name) == state.current_server.as_ref()
-                    && Some(channel_name) == server.current_channel.as_ref();
+                    && server.is_current_channel(channel_name);
 
                 let mut style = Style::default().fg(self.colors().text);
                 let mut prefix = "  # "; // Channel symbol (same for Unicode and ASCII)
@@ -340,9 +344,9 @@ impl TuiRenderer {
                 }
 
                 let channel_text = if channel.unread_count > 0 {
-                    format!("{} ({})", channel_name, channel.unread_count)
+                    format!("{} ({})", channel.name, channel.unread_count)
                 } else {
-                    channel_name.clone()
+                    channel.name.clone()
                 };
 
                 let channel_item = ListItem::new(Line::from(vec![
@@ -420,8 +424,7 @@ impl TuiRenderer {
                     break;
                 }
 
-                let formatted_line = self.format_message(message);
-                lines.push(formatted_line);
+                lines.extend(self.format_message(message));
             }
 
             let text = Text::from(lines);
@@ -539,14 +542,39 @@ impl TuiRenderer {
             InputMode::Command => ("COMMAND", Style::default().fg(self.colors().activity)),
         };
 
-        // Create styled input text with proper mode indicator styling
+        // Line and column of the cursor, scrolling long input to keep it visible
+        let before_cursor = state
+            .input_buffer
+            .get(..state.input_cursor)
+            .unwrap_or(&state.input_buffer);
+        let cursor_row = before_cursor.matches('\n').count();
+        let cursor_column = before_cursor
+            .rsplit('\n')
+            .next()
+            .map_or(0, |line| line.chars().count());
+        let first_row = cursor_row.saturating_sub(MAX_INPUT_LINES - 1);
+
+        // Create styled input text with proper mode indicator styling; continuation
+        // lines of a multi-line message line up under the first
         let mode_indicator = Span::styled(format!("[{mode_text}]"), mode_style);
-        let input_content = Span::raw(format!(" {}", state.input_buffer));
-
-        let styled_input_line = Line::from(vec![mode_indicator, input_content]);
+        let indent = " ".repeat(mode_text.len() + 2);
+        let styled_input_lines: Vec<Line> = state
+            .input_buffer
+            .split('\n')
+            .enumerate()
+            .skip(first_row)
+            .take(MAX_INPUT_LINES)
+            .map(|(row, text)| {
+                if row == 0 {
+                    Line::from(vec![mode_indicator.clone(), Span::raw(format!(" {text}"))])
+                } else {
+                    Line::from(format!("{indent} {text}"))
+                }
+            })
+            .collect();
         debug!("Input mode style applied: {:?}", mode_style);
 
-        let input_paragraph = Paragraph::new(styled_input_line).block(
+        let input_paragraph = Paragraph::new(styled_input_lines).block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(if focused {
@@ -561,8 +589,8 @@ impl TuiRenderer {
 
         // Show cursor if focused
         if focused {
-            let cursor_x = area.x + 1 + mode_text.len() as u16 + 3 + state.input_cursor as u16;
-            let cursor_y = area.y + 1;
+            let cursor_x = area.x + 1 + mode_text.len() as u16 + 3 + cursor_column as u16;
+            let cursor_y = area.y + 1 + (cursor_row - first_row) as u16;
             frame.set_cursor_position((cursor_x, cursor_y));
         }
     }
@@ -571,8 +599,18 @@ impl TuiRenderer {
     fn render_status_line(&mut self, frame: &mut Frame, area: Rect, state: &TuiState) {
         let server_status = if let Some(server_name) = &state.current_server {
             if let Some(server) = state.servers.get(server_name) {
+                let via = state
+                    .endpoints
+                    .get(server_name)
+                    .map(|endpoint| format!(" via {endpoint}"))
+                    .unwrap_or_default();
+                let pending = state
+                    .pending_lines
+                    .get(server_name)
+                    .map(|count| format!(" | {count} lines pending"))
+                    .unwrap_or_default();
                 if server.connected {
-                    format!("Connected to {server_name}")
+                    format!("Connected to {server_name}{via}{pending}")
                 } else {
                     format!("Disconnected from {server_name}")
                 }
@@ -601,8 +639,23 @@ impl TuiRenderer {
 
         let status_text = format!("{server_status}{unread_text}  | Ctrl+C to quit | ? for help");
 
-        let status_paragraph =
-            Paragraph::new(status_text).style(Style::default().fg(self.colors().text_muted));
+        let mut status_spans = Vec::new();
+        if state
+            .current_server
+            .as_ref()
+            .is_some_and(|server| state.unverified_tls.contains(server))
+        {
+            status_spans.push(Span::styled(
+                "[TLS UNVERIFIED] ",
+                Style::default()
+                    .fg(self.colors().error)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        status_spans.push(Span::raw(status_text));
+
+        let status_paragraph = Paragraph::new(Line::from(status_spans))
+            .style(Style::default().fg(self.colors().text_muted));
 
         frame.render_widget(status_paragraph, status_layout[0]);
 
@@ -686,6 +739,7 @@ impl TuiRenderer {
             Line::from("  i - Enter insert mode"),
             Line::from("  : - Enter command mode"),
             Line::from("  Esc - Return to normal mode"),
+            Line::from("  Alt+Enter - New line in message (insert mode)"),
             Line::from(""),
             Line::from(Span::styled(
                 "Commands:",
@@ -720,8 +774,24 @@ impl TuiRenderer {
         frame.render_widget(help_paragraph, popup_area);
     }
 
-    /// Format a message for display  
-    fn format_message<'a>(&self, message: &'a TuiMessage) -> Line<'a> {
+    /// Format a message for display, one line per line of a multi-line message
+    fn format_message<'a>(&self, message: &'a TuiMessage) -> Vec<Line<'a>> {
+        message
+            .content
+            .split('\n')
+            .enumerate()
+            .map(|(index, content)| self.format_message_line(message, content, index > 0))
+            .collect()
+    }
+
+    /// Format one line of a message; continuation lines are indented instead
+    /// of repeating the timestamp and nickname
+    fn format_message_line<'a>(
+        &self,
+        message: &'a TuiMessage,
+        content: &str,
+        continuation: bool,
+    ) -> Line<'a> {
         use crate::formatting::{parse_irc_text, replace_emoticons, spans_to_line};
 
         let timestamp = self.format_timestamp(&message.timestamp);
@@ -757,7 +827,7 @@ impl TuiRenderer {
         };
 
         // Parse IRC formatting in message content
-        let content_with_emotes = replace_emoticons(&message.content);
+        let content_with_emotes = replace_emoticons(content);
         let formatted_spans = parse_irc_text(&content_with_emotes);
 
         // Build the complete message line
@@ -769,6 +839,10 @@ impl TuiRenderer {
             Span::styled(suffix, nick_style),
             Span::raw(" "),
         ];
+        if continuation {
+            let width = line_spans.iter().map(Span::width).sum();
+            line_spans = vec![Span::raw(" ".repeat(width))];
+        }
 
         // Use spans_to_line for complex formatting, fallback to manual for simple cases
         if formatted_spans.len() > 1